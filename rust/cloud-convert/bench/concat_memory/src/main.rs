@@ -0,0 +1,105 @@
+//! Peak memory of building the batch QAQC table, all at once or in chunks of files
+//!
+//! Usage: `concat_memory <files> [chunksize]`
+
+use polars::prelude::*;
+use std::time::Instant;
+
+const BANDS: usize = 4;
+
+/// Peak resident memory of this process in MB
+fn peak_mb() -> f64 {
+    let status = std::fs::read_to_string("/proc/self/status").expect("needs /proc (Linux)");
+    let line = status
+        .lines()
+        .find(|line| line.starts_with("VmHWM"))
+        .expect("no VmHWM in /proc/self/status");
+    let kb: f64 = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|v| v.parse().ok())
+        .expect("unreadable VmHWM");
+    kb / 1024.0
+}
+
+/// The table of one file, with the columns `raster_stats_to_df` writes by default
+fn file_frame(index: usize) -> LazyFrame {
+    let value = index as f64;
+    let mut columns = vec![
+        Column::new("file".into(), vec![format!("file_{:06}.tif", index); BANDS]),
+        Column::new("band".into(), (1..=BANDS as u32).collect::<Vec<_>>()),
+        Column::new("name".into(), vec!["band name".to_string(); BANDS]),
+        Column::new("dtype".into(), vec!["Float32".to_string(); BANDS]),
+    ];
+    for name in ["mean", "sum", "min", "max", "variance", "stdev", "cv"] {
+        columns.push(Column::new(name.into(), vec![value; BANDS]));
+    }
+    for name in ["valid_count", "nodata_count", "nan_count", "zero_count"] {
+        columns.push(Column::new(name.into(), vec![index as u64; BANDS]));
+    }
+    columns.push(Column::new(
+        "distinct_count".into(),
+        vec![None::<u64>; BANDS],
+    ));
+    columns.push(Column::new(
+        "effective_nodata".into(),
+        vec!["declared -9999".to_string(); BANDS],
+    ));
+    columns.push(Column::new("percent_valid".into(), vec![value; BANDS]));
+    for name in ["q1", "median", "q3"] {
+        columns.push(Column::new(name.into(), vec![value as f32; BANDS]));
+    }
+    columns.push(Column::new(
+        "stats_source".into(),
+        vec!["computed".to_string(); BANDS],
+    ));
+    DataFrame::new(columns).unwrap().lazy()
+}
+
+/// Mirror of `concat_frames`: every frame at once, or `chunksize` files at a time
+fn concat_frames(files: usize, chunksize: Option<usize>) -> DataFrame {
+    let Some(chunksize) = chunksize.filter(|&n| n > 0 && n < files) else {
+        let frames: Vec<LazyFrame> = (0..files).map(file_frame).collect();
+        return concat(frames, UnionArgs::default())
+            .unwrap()
+            .collect()
+            .unwrap();
+    };
+
+    let mut parts = Vec::with_capacity(files.div_ceil(chunksize));
+    for start in (0..files).step_by(chunksize) {
+        let frames: Vec<LazyFrame> = (start..(start + chunksize).min(files))
+            .map(file_frame)
+            .collect();
+        let mut part = concat(frames, UnionArgs::default())
+            .unwrap()
+            .collect()
+            .unwrap();
+        part.rechunk_mut();
+        parts.push(part.lazy());
+    }
+    concat(parts, UnionArgs::default())
+        .unwrap()
+        .collect()
+        .unwrap()
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let files: usize = args
+        .first()
+        .and_then(|v| v.parse().ok())
+        .expect("usage: concat_memory <files> [chunksize]");
+    let chunksize: Option<usize> = args.get(1).and_then(|v| v.parse().ok());
+
+    let start = Instant::now();
+    let table = concat_frames(files, chunksize);
+    println!(
+        "files={} chunksize={} rows={} time={:.2}s peak={:.0}MB",
+        files,
+        chunksize.map_or("none".to_string(), |n| n.to_string()),
+        table.height(),
+        start.elapsed().as_secs_f64(),
+        peak_mb()
+    );
+}
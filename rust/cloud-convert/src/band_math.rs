@@ -0,0 +1,306 @@
+use crate::open_limit;
+use crate::progress::status;
+use crate::tempdir::{MATH_TIF, PARTIAL_TIF, TempFile, move_into_place};
+use crate::tif2cog::create_with_type;
+use gdal::raster::{Buffer, GdalDataType, RasterCreationOptions};
+use gdal::{DriverManager, Metadata};
+use std::path::Path;
+
+/// NoData value written where the expression has no valid result
+pub const BAND_MATH_NODATA: f64 = -9999.0;
+
+/// A parsed band math expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Num(f64),
+    /// 1-based band index, written `b1`, `b2`, ...
+    Band(usize),
+    Neg(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Evaluate for one pixel, `None` on division by zero or a non-finite result
+    pub fn eval(&self, bands: &[f64]) -> Option<f64> {
+        let value = match self {
+            Expr::Num(v) => *v,
+            Expr::Band(b) => bands[b - 1],
+            Expr::Neg(e) => -e.eval(bands)?,
+            Expr::Add(a, b) => a.eval(bands)? + b.eval(bands)?,
+            Expr::Sub(a, b) => a.eval(bands)? - b.eval(bands)?,
+            Expr::Mul(a, b) => a.eval(bands)? * b.eval(bands)?,
+            Expr::Div(a, b) => {
+                let divisor = b.eval(bands)?;
+                if divisor == 0.0 {
+                    return None;
+                }
+                a.eval(bands)? / divisor
+            }
+        };
+        value.is_finite().then_some(value)
+    }
+
+    /// Highest band index referenced by the expression
+    pub fn max_band(&self) -> usize {
+        match self {
+            Expr::Num(_) => 0,
+            Expr::Band(b) => *b,
+            Expr::Neg(e) => e.max_band(),
+            Expr::Add(a, b) | Expr::Sub(a, b) | Expr::Mul(a, b) | Expr::Div(a, b) => {
+                a.max_band().max(b.max_band())
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    input: &'a str,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.peek().map(|&(_, c)| c)
+    }
+
+    fn position(&mut self) -> usize {
+        self.chars
+            .peek()
+            .map(|&(i, _)| i)
+            .unwrap_or(self.input.len())
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.term()?;
+        while let Some(op @ ('+' | '-')) = self.peek() {
+            self.chars.next();
+            let rhs = self.term()?;
+            lhs = match op {
+                '+' => Expr::Add(Box::new(lhs), Box::new(rhs)),
+                _ => Expr::Sub(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(lhs)
+    }
+
+    // term := factor (('*' | '/') factor)*
+    fn term(&mut self) -> Result<Expr, String> {
+        let mut lhs = self.factor()?;
+        while let Some(op @ ('*' | '/')) = self.peek() {
+            self.chars.next();
+            let rhs = self.factor()?;
+            lhs = match op {
+                '*' => Expr::Mul(Box::new(lhs), Box::new(rhs)),
+                _ => Expr::Div(Box::new(lhs), Box::new(rhs)),
+            };
+        }
+        Ok(lhs)
+    }
+
+    // factor := '-' factor | '(' expr ')' | 'b' digits | number
+    fn factor(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            Some('-') => {
+                self.chars.next();
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.chars.next();
+                let inner = self.expr()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.chars.next();
+                        Ok(inner)
+                    }
+                    _ => Err(format!("Expected ')' at position {}", self.position())),
+                }
+            }
+            Some('b' | 'B') => {
+                let start = self.position();
+                self.chars.next();
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                match digits.parse::<usize>() {
+                    Ok(band) if band >= 1 => Ok(Expr::Band(band)),
+                    _ => Err(format!("Invalid band reference at position {}", start)),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.position();
+                let number = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                number
+                    .parse()
+                    .map(Expr::Num)
+                    .map_err(|_| format!("Invalid number '{}' at position {}", number, start))
+            }
+            Some(c) => Err(format!(
+                "Unexpected '{}' at position {}",
+                c,
+                self.position()
+            )),
+            None => Err("Unexpected end of expression".to_string()),
+        }
+    }
+
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let mut out = String::new();
+        while let Some((_, c)) = self.chars.next_if(|&(_, c)| pred(c)) {
+            out.push(c);
+        }
+        out
+    }
+}
+
+/// Parse an expression such as `(b4 - b3) / (b4 + b3)`
+///
+/// Supports `+ - * /`, unary minus, parentheses, numbers and 1-based band references.
+pub fn parse_expression(input: &str) -> Result<Expr, String> {
+    let mut parser = Parser {
+        chars: input.char_indices().peekable(),
+        input,
+    };
+    let expr = parser.expr()?;
+    if parser.peek().is_some() {
+        return Err(format!(
+            "Unexpected '{}' at position {}",
+            parser.peek().unwrap_or_default(),
+            parser.position()
+        ));
+    }
+    Ok(expr)
+}
+
+/// Evaluate `expression` over the bands of a raster and write the result as a Float32 COG
+///
+/// Pixels where a referenced band is NoData/NaN, or the expression divides by zero, are
+/// written as [`BAND_MATH_NODATA`].
+///
+/// # Arguments
+/// * `input_path` - Source raster whose bands the expression references
+/// * `output_path` - Path of the COG to write
+/// * `expression` - Band math expression, e.g. `(b4-b3)/(b4+b3)`
+/// * `overwrite` - Replace `output_path` if it already exists
+pub fn band_math_to_cog(
+    input_path: &Path,
+    output_path: &Path,
+    expression: &str,
+    overwrite: bool,
+) -> Result<String, String> {
+    let expr = parse_expression(expression)?;
+    if output_path.exists() && !overwrite {
+        return Err(format!(
+            "Error: The file {:?} already exists and overwrite is false.",
+            output_path
+        ));
+    }
+
+    let (_permit, dataset) = open_limit::open(input_path)
+        .map_err(|e| format!("Failed to open dataset {}: {:?}", input_path.display(), e))?;
+    let band_count = expr.max_band();
+    if band_count > dataset.raster_count() {
+        return Err(format!(
+            "Expression references b{} but {} has {} bands",
+            band_count,
+            input_path.display(),
+            dataset.raster_count()
+        ));
+    }
+
+    let (cols, rows) = dataset.raster_size();
+    let gtiff = DriverManager::get_driver_by_name("GTiff")
+        .map_err(|e| format!("Failed to get GTiff driver: {:?}", e))?;
+    let temp_path = TempFile::new(output_path, MATH_TIF);
+    let mut result = create_with_type(
+        &gtiff,
+        temp_path.path(),
+        (cols, rows),
+        1,
+        GdalDataType::Float32,
+    )
+    .map_err(|e| format!("Failed to create band math output: {:?}", e))?;
+    if let Ok(transform) = dataset.geo_transform() {
+        result
+            .set_geo_transform(&transform)
+            .map_err(|e| format!("Failed to set geotransform: {:?}", e))?;
+    }
+    if let Ok(srs) = dataset.spatial_ref() {
+        result
+            .set_spatial_ref(&srs)
+            .map_err(|e| format!("Failed to set CRS: {:?}", e))?;
+    }
+    let mut out_band = result
+        .rasterband(1)
+        .map_err(|e| format!("Failed to read output band: {:?}", e))?;
+    out_band
+        .set_no_data_value(Some(BAND_MATH_NODATA))
+        .map_err(|e| format!("Failed to set NoData: {:?}", e))?;
+    out_band
+        .set_description(expression)
+        .map_err(|e| format!("Failed to set band description: {:?}", e))?;
+
+    let nodata: Vec<Option<f64>> = (1..=band_count)
+        .map(|i| dataset.rasterband(i).map(|b| b.no_data_value()))
+        .collect::<gdal::errors::Result<_>>()
+        .map_err(|e| format!("Failed to read bands: {:?}", e))?;
+
+    let chunk_rows = 256.min(rows.max(1));
+    let mut pixel = vec![0.0; band_count];
+    for y in (0..rows).step_by(chunk_rows) {
+        let height = chunk_rows.min(rows - y);
+        let inputs = (1..=band_count)
+            .map(|i| {
+                dataset
+                    .rasterband(i)
+                    .and_then(|b| {
+                        b.read_as::<f64>((0, y as isize), (cols, height), (cols, height), None)
+                    })
+                    .map_err(|e| format!("Failed to read band {}: {:?}", i, e))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        let data: Vec<f32> = (0..cols * height)
+            .map(|p| {
+                for (b, buf) in inputs.iter().enumerate() {
+                    let value = buf.data()[p];
+                    if value.is_nan() || nodata[b] == Some(value) {
+                        return BAND_MATH_NODATA as f32;
+                    }
+                    pixel[b] = value;
+                }
+                expr.eval(&pixel)
+                    .map_or(BAND_MATH_NODATA as f32, |v| v as f32)
+            })
+            .collect();
+        let mut buf = Buffer::new((cols, height), data);
+        out_band
+            .write((0, y as isize), (cols, height), &mut buf)
+            .map_err(|e| format!("Failed to write band math output: {:?}", e))?;
+    }
+
+    let cog = DriverManager::get_driver_by_name("COG")
+        .map_err(|e| format!("Failed to get COG driver: {:?}", e))?;
+    let partial_path = TempFile::new(output_path, PARTIAL_TIF);
+    let copied = result
+        .create_copy(
+            &cog,
+            partial_path.path(),
+            &RasterCreationOptions::from_iter(["COMPRESS=LZW"]),
+        )
+        .map(|_| ())
+        .map_err(|e| format!("Failed to create COG: {:?}", e));
+    drop(result);
+    drop(temp_path);
+    copied?;
+    move_into_place(partial_path.path(), output_path)?;
+
+    status!("Wrote {} to: {:?}", expression, output_path);
+    Ok(output_path.display().to_string())
+}
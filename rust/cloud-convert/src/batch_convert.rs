@@ -1,24 +1,585 @@
-use crate::tif2cog::tif_to_cog;
-use crate::vect2gpq::vector_to_geoparquet;
+use crate::datainfo::get_datainfo;
+use crate::progress;
+use crate::stats_cache::file_hash;
+use crate::tif2cog::{
+    CogOptions, Compression, check_blocksize, planned_cog_output, tif_to_cog, validate_cog,
+};
+use crate::vect2gpq::{GpqOptions, is_stdout, planned_vector_output, vector_convert_to_files};
+use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
+use std::io::IsTerminal;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use walkdir::WalkDir;
 
+/// Outcome of a batch run; written by `--report` and read back by `--resume-from`
+///
+/// Entries are `[input path, output name, error message or skip reason]` pairs.
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct BatchSummary {
     pub successful: Vec<(PathBuf, String)>,
     pub failed: Vec<(PathBuf, String)>,
+    /// Inputs left alone because they needed no conversion; absent from older reports
+    #[serde(default)]
+    pub skipped: Vec<(PathBuf, String)>,
+    /// `(input, output path)` of every file written, several per input with `--all-layers`
+    #[serde(skip)]
+    pub outputs: Vec<(PathBuf, PathBuf)>,
 }
 
-fn batch_convert<F>(
+/// Options shared by all batch conversions
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /// Group failures by error category instead of listing every file
+    pub summarize_errors: bool,
+    /// Retry a failing per-file conversion this many times before recording it as failed
+    pub max_retries: u32,
+    /// Pause between retries, in milliseconds
+    pub retry_delay_ms: u64,
+    /// Emit a JSON line on stderr as each file finishes (see [`ProgressReporter`])
+    pub progress_json: bool,
+    /// Write the [`BatchSummary`] as JSON to this path when the batch finishes
+    pub report: Option<PathBuf>,
+    /// Only convert the files listed as failed in this earlier report
+    pub resume_from: Option<PathBuf>,
+    /// List the inputs and the outputs they would be written to without converting anything
+    pub dry_run: bool,
+    /// Hide the progress bar and per-file progress lines
+    pub quiet: bool,
+    /// Also convert files in subdirectories, mirroring them under the output directory
+    pub recursive: bool,
+    /// Reconvert and replace outputs that are already COGs instead of skipping them (`to-cog` only)
+    pub force: bool,
+    /// Stop at the first file that fails instead of finishing the batch (`run-qaqc` only)
+    pub fail_fast: bool,
+    /// Write the size and SHA-256 of every output here, as JSON for a `.json` path and CSV otherwise
+    pub checksums: Option<PathBuf>,
+}
+
+/// Resolve `--out` and `--output-dir` into the output path of one command-line conversion
+///
+/// `--output-dir` is passed on as-is when the input is a directory or `several_files` is set
+/// (e.g. `--explode`), and is otherwise joined with the input's file name as in batch mode; the
+/// converter then gives it the extension of its format. The directory is created if missing.
+pub fn resolve_output(
+    input_path: &Path,
+    out: Option<PathBuf>,
+    output_dir: Option<PathBuf>,
+    several_files: bool,
+) -> Result<Option<PathBuf>, String> {
+    let Some(dir) = output_dir else {
+        return Ok(out);
+    };
+    if out.is_some() {
+        return Err("--out and --output-dir cannot be combined".to_string());
+    }
+    if input_path.is_dir() || several_files {
+        return Ok(Some(dir));
+    }
+    let file_name = input_path
+        .file_name()
+        .ok_or_else(|| format!("{} has no file name", input_path.display()))?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create output directory {}: {}", dir.display(), e))?;
+    Ok(Some(dir.join(file_name)))
+}
+
+/// Write `summary` as pretty JSON to `path`
+pub fn write_batch_report(summary: &BatchSummary, path: &Path) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(summary)
+        .map_err(|e| format!("Failed to serialize batch report: {}", e))?;
+    fs::write(path, json)
+        .map_err(|e| format!("Failed to write batch report {}: {}", path.display(), e))?;
+    eprintln!("Wrote batch report to: {}", path.display());
+    Ok(())
+}
+
+/// One output of a batch with its size and SHA-256, as written by [`write_checksums`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChecksumRow {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub bytes: u64,
+    pub sha256: String,
+}
+
+/// Hash each output, streaming it from disk, and write the rows sorted by input to `path`
+///
+/// Rows are sorted so the checksums of two runs can be diffed.
+pub fn write_checksums(
+    outputs: &[(PathBuf, PathBuf)],
+    path: &Path,
+) -> Result<Vec<ChecksumRow>, String> {
+    let mut rows = outputs
+        .par_iter()
+        .map(|(input, output)| {
+            let bytes = fs::metadata(output)
+                .map_err(|e| format!("Failed to read {}: {}", output.display(), e))?
+                .len();
+            let sha256 = file_hash(output)
+                .map_err(|e| format!("Failed to hash {}: {}", output.display(), e))?;
+            Ok(ChecksumRow {
+                input: input.clone(),
+                output: output.clone(),
+                bytes,
+                sha256,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    rows.sort_by(|a, b| (&a.input, &a.output).cmp(&(&b.input, &b.output)));
+
+    let is_json = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+    let text = if is_json {
+        serde_json::to_string_pretty(&rows)
+            .map_err(|e| format!("Failed to serialize checksums: {}", e))?
+    } else {
+        let mut text = String::from("input,output,bytes,sha256\n");
+        for row in &rows {
+            text.push_str(&format!(
+                "{},{},{},{}\n",
+                csv_field(&row.input.display().to_string()),
+                csv_field(&row.output.display().to_string()),
+                row.bytes,
+                row.sha256
+            ));
+        }
+        text
+    };
+    fs::write(path, text)
+        .map_err(|e| format!("Failed to write checksums {}: {}", path.display(), e))?;
+    eprintln!(
+        "Wrote checksums of {} outputs to: {}",
+        rows.len(),
+        path.display()
+    );
+    Ok(rows)
+}
+
+/// Read the failed inputs of a report written by [`write_batch_report`]
+///
+/// Every failed path must lie inside `input_dir`, so a report from another batch is not
+/// picked up by mistake.
+pub fn read_failed_from_report(path: &Path, input_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read batch report {}: {}", path.display(), e))?;
+    let report: BatchSummary = serde_json::from_str(&text).map_err(|e| {
+        format!(
+            "{} is not a batch report (expected \"successful\" and \"failed\" lists of [path, message] pairs): {}",
+            path.display(),
+            e
+        )
+    })?;
+
+    let mut failed: Vec<PathBuf> = report.failed.into_iter().map(|(p, _)| p).collect();
+    failed.sort();
+    failed.dedup();
+    if let Some(outside) = failed.iter().find(|p| !p.starts_with(input_dir)) {
+        return Err(format!(
+            "Batch report {} lists {}, which is not in {}",
+            path.display(),
+            outside.display(),
+            input_dir.display()
+        ));
+    }
+    eprintln!(
+        "Retrying {} failed files from {} ({} succeeded previously)",
+        failed.len(),
+        path.display(),
+        report.successful.len()
+    );
+    Ok(failed)
+}
+
+/// One `--progress-json` line, written when a file finishes
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgressEvent {
+    /// 1-based completion order
+    pub index: usize,
+    pub total: usize,
+    pub path: String,
+    /// "ok" or "failed"
+    pub status: &'static str,
+    /// Milliseconds since the batch started
+    pub elapsed_ms: u64,
+}
+
+/// Numbers finished files and, when enabled, prints each as a JSON line on stderr
+///
+/// The index is assigned and the line written under one lock, so events arrive in index
+/// order even when rayon workers finish at the same time. With [`with_bar`](Self::with_bar)
+/// it also advances an [`indicatif`] progress bar, above which `status!` lines from the
+/// conversions are printed while the batch runs.
+pub struct ProgressReporter {
+    enabled: bool,
+    bar: Option<ProgressBar>,
+    quiet: bool,
+    total: usize,
+    start: Instant,
+    /// Finished and failed file counts
+    done: Mutex<(usize, usize)>,
+}
+
+impl ProgressReporter {
+    pub fn new(total: usize, enabled: bool) -> Self {
+        ProgressReporter {
+            enabled,
+            bar: None,
+            quiet: false,
+            total,
+            start: Instant::now(),
+            done: Mutex::new((0, 0)),
+        }
+    }
+
+    /// Draw a progress bar unless `quiet`
+    ///
+    /// The bar is only drawn when stderr is a terminal and JSON events are off, as it would
+    /// clutter logs and break JSON parsing.
+    pub fn with_bar(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        if !quiet && !self.enabled && std::io::stderr().is_terminal() {
+            let style = ProgressStyle::with_template("[{bar:30}] {pos}/{len}{msg} ETA {eta}")
+                .expect("progress bar template is valid")
+                .progress_chars("= ");
+            let bar = ProgressBar::new(self.total as u64).with_style(style);
+            progress::set_active(Some(bar.clone()));
+            self.bar = Some(bar);
+        }
+        self
+    }
+
+    /// Whether callers should print their own line per finished file
+    pub fn shows_lines(&self) -> bool {
+        !self.quiet && self.bar.is_none() && !self.enabled
+    }
+
+    /// Record that `path` finished and return the emitted event
+    pub fn finish(&self, path: &Path, ok: bool) -> ProgressEvent {
+        let mut done = self.done.lock().unwrap_or_else(|e| e.into_inner());
+        done.0 += 1;
+        if !ok {
+            done.1 += 1;
+        }
+        let event = ProgressEvent {
+            index: done.0,
+            total: self.total,
+            path: path.display().to_string(),
+            status: if ok { "ok" } else { "failed" },
+            elapsed_ms: self.start.elapsed().as_millis() as u64,
+        };
+        if self.enabled
+            && let Ok(line) = serde_json::to_string(&event)
+        {
+            eprintln!("{}", line);
+        }
+        if let Some(bar) = &self.bar {
+            if !ok {
+                bar.set_message(format!(", {} failed", done.1));
+            }
+            bar.inc(1);
+            if done.0 == self.total {
+                bar.finish();
+            }
+        }
+        event
+    }
+
+    /// Print `text` on stderr without breaking the progress bar
+    pub fn message(&self, text: &str) {
+        match &self.bar {
+            Some(bar) => bar.println(text),
+            None => eprintln!("{}", text),
+        }
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        if self.bar.is_some() {
+            progress::set_active(None);
+        }
+    }
+}
+
+/// Run `converter` on one file, retrying up to `max_retries` times after a failure
+///
+/// Each retry is reported through `log`, so batches can print it without breaking the
+/// progress bar.
+pub fn convert_with_retries<F, R>(
+    path: &Path,
+    output_path: Option<&Path>,
+    max_retries: u32,
+    retry_delay: Duration,
+    converter: F,
+    log: impl Fn(&str),
+) -> Result<R, String>
+where
+    F: Fn(&Path, Option<&Path>) -> Result<R, String>,
+{
+    let mut attempt = 0;
+    loop {
+        match converter(path, output_path) {
+            Ok(output) => return Ok(output),
+            Err(e) if attempt < max_retries => {
+                attempt += 1;
+                log(&format!(
+                    "Retry {}/{} for {}: {}",
+                    attempt,
+                    max_retries,
+                    path.display(),
+                    e
+                ));
+                if !retry_delay.is_zero() {
+                    thread::sleep(retry_delay);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Broad category of a per-file failure, used to summarise large batches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ErrorCategory {
+    InputMissing,
+    OutputExists,
+    UnsupportedDriver,
+    CrsMissing,
+    EmptyInput,
+    Io,
+    Other,
+}
+
+/// Whole phrases that mark an I/O failure; bare "read" or "write" would also match words
+/// like "thread" or "overwrite"
+const IO_PHRASES: &[&str] = &[
+    "i/o",
+    "permission denied",
+    "no space",
+    "failed to read",
+    "failed to write",
+    "cannot read",
+    "cannot write",
+    "read error",
+    "write error",
+];
+
+/// Phrases that mark a dataset without a CRS. A bare "crs" would also match "CRS has no
+/// authority code", which `--require-epsg` reports for a CRS that is present
+const CRS_MISSING_PHRASES: &[&str] = &["no crs", "no spatial ref", "no projection", "missing crs"];
+
+const EMPTY_INPUT_PHRASES: &[&str] = &["no layers", "no bands", "no features", "is empty"];
+
+/// `msg` without the paths and file names it mentions, so that an input under a directory
+/// such as `crs_tiles/` or `empty/` does not decide its category
+fn without_paths(msg: &str) -> String {
+    msg.split_whitespace()
+        .filter(|word| {
+            let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+            word == "i/o" || !word.contains(['/', '\\', '.'])
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl ErrorCategory {
+    /// Classify an error message into a category based on its wording, ignoring any paths in it
+    pub fn classify(msg: &str) -> Self {
+        let msg = without_paths(&msg.to_lowercase());
+        let has = |phrases: &[&str]| phrases.iter().any(|phrase| msg.contains(phrase));
+        if msg.contains("already exists") {
+            ErrorCategory::OutputExists
+        } else if msg.contains("does not exist") || msg.contains("no such file") {
+            ErrorCategory::InputMissing
+        } else if msg.contains("driver") || msg.contains("not recognized as") {
+            ErrorCategory::UnsupportedDriver
+        } else if has(CRS_MISSING_PHRASES) {
+            ErrorCategory::CrsMissing
+        } else if has(EMPTY_INPUT_PHRASES) {
+            ErrorCategory::EmptyInput
+        } else if has(IO_PHRASES) {
+            ErrorCategory::Io
+        } else {
+            ErrorCategory::Other
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ErrorCategory::InputMissing => "input missing",
+            ErrorCategory::OutputExists => "output exists",
+            ErrorCategory::UnsupportedDriver => "unsupported driver",
+            ErrorCategory::CrsMissing => "CRS missing",
+            ErrorCategory::EmptyInput => "empty input",
+            ErrorCategory::Io => "I/O error",
+            ErrorCategory::Other => "other",
+        }
+    }
+}
+
+/// Group failures by category, returning (category, count, first example) sorted by count
+pub fn summarize_errors(
+    failed: &[(PathBuf, String)],
+) -> Vec<(ErrorCategory, usize, &(PathBuf, String))> {
+    let mut groups: BTreeMap<ErrorCategory, (usize, &(PathBuf, String))> = BTreeMap::new();
+    for entry in failed {
+        groups
+            .entry(ErrorCategory::classify(&entry.1))
+            .and_modify(|(count, _)| *count += 1)
+            .or_insert((1, entry));
+    }
+
+    let mut summary: Vec<_> = groups
+        .into_iter()
+        .map(|(category, (count, example))| (category, count, example))
+        .collect();
+    summary.sort_by_key(|entry| std::cmp::Reverse(entry.1));
+    summary
+}
+
+/// Print the grouped failures with one example per category
+pub fn print_error_summary(failed: &[(PathBuf, String)]) {
+    eprintln!("Error summary ({} files failed):", failed.len());
+    for (category, count, (path, err)) in summarize_errors(failed) {
+        eprintln!("  {:>6}  {}", count, category.label());
+        eprintln!("          e.g. {}: {}", path.display(), err);
+    }
+}
+
+const RASTER_EXTENSIONS: [&str; 5] = ["tif", "tiff", "tff", "asc", "img"];
+
+/// List the files in `input_path` with one of `extensions`
+///
+/// Only the top level is listed unless `recursive` is set, in which case files under
+/// `skip_dir` (the output directory when it lies inside the input) are left out.
+fn list_files(
+    input_path: &Path,
+    extensions: &[&str],
+    file_type: &str,
+    recursive: bool,
+    skip_dir: Option<&Path>,
+) -> Result<Vec<PathBuf>, String> {
+    let has_extension = |path: &Path| {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+            .unwrap_or(false)
+    };
+    let files: Vec<PathBuf> = if recursive {
+        WalkDir::new(input_path)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| skip_dir.is_none_or(|dir| entry.path() != dir))
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| entry.into_path())
+            .filter(|path| has_extension(path))
+            .collect()
+    } else {
+        input_path
+            .read_dir()
+            .map_err(|e| format!("Failed to read directory: {}", e))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| has_extension(path))
+            .collect()
+    };
+
+    if files.is_empty() {
+        return Err(format!(
+            "No supported {} files found in '{}'",
+            file_type,
+            input_path.display()
+        ));
+    }
+
+    Ok(files)
+}
+
+/// Data types, band count and CRS shared by a group of input rasters
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RasterProfile {
+    pub dtypes: String,
+    pub band_count: usize,
+    pub crs: String,
+}
+
+/// Group the rasters in a directory by data type, band count and CRS, most common first
+///
+/// Files that GDAL cannot open are grouped under an "unreadable" profile.
+pub fn report_dtypes(input_path: &Path) -> Result<Vec<(RasterProfile, usize)>, String> {
+    let files = list_files(input_path, &RASTER_EXTENSIONS, "raster", false, None)?;
+
+    let profiles: Vec<RasterProfile> = files
+        .par_iter()
+        .map(|path| match get_datainfo(path) {
+            Ok(info) => RasterProfile {
+                dtypes: info.band_types.unwrap_or_default().join(","),
+                band_count: info.band_count.unwrap_or(0),
+                crs: info.crs.unwrap_or_else(|| "Unknown".to_string()),
+            },
+            Err(_) => RasterProfile {
+                dtypes: "unreadable".to_string(),
+                band_count: 0,
+                crs: "Unknown".to_string(),
+            },
+        })
+        .collect();
+
+    let mut groups: BTreeMap<RasterProfile, usize> = BTreeMap::new();
+    for profile in profiles {
+        *groups.entry(profile).or_insert(0) += 1;
+    }
+    let mut report: Vec<_> = groups.into_iter().collect();
+    report.sort_by_key(|entry| std::cmp::Reverse(entry.1));
+    Ok(report)
+}
+
+/// Print the output of [`report_dtypes`] as a small table
+pub fn print_dtype_report(report: &[(RasterProfile, usize)]) {
+    let total: usize = report.iter().map(|(_, count)| count).sum();
+    println!("{:>6}  {:>5}  {:<24}  CRS", "Files", "Bands", "Data types");
+    for (profile, count) in report {
+        println!(
+            "{:>6}  {:>5}  {:<24}  {}",
+            count, profile.band_count, profile.dtypes, profile.crs
+        );
+    }
+    if report.len() > 1 {
+        println!(
+            "{} distinct profiles across {} files; consider homogenizing before converting",
+            report.len(),
+            total
+        );
+    }
+}
+
+/// Convert every matching file in `input_path`, skipping those `skip` gives a reason for
+///
+/// `skip` is called with each input and its planned output.
+#[allow(clippy::too_many_arguments)]
+fn batch_convert<F, P, S>(
     input_path: &Path,
     output_dir: Option<&Path>,
     extensions: &[&str],
     file_type: &str,
+    options: &BatchOptions,
+    planned_output: P,
+    skip: S,
     converter: F,
 ) -> Result<BatchSummary, String>
 where
-    F: Fn(&Path, Option<&Path>) -> Result<String, String> + Send + Sync,
+    F: Fn(&Path, Option<&Path>) -> Result<Vec<PathBuf>, String> + Send + Sync,
+    P: Fn(&Path, Option<&Path>) -> PathBuf + Sync,
+    S: Fn(&Path, &Path) -> Option<String> + Sync,
 {
     if !input_path.is_dir() {
         return Err(format!(
@@ -28,69 +589,209 @@ where
     }
 
     // Create output directory if specified and doesn't exist
-    if let Some(out_dir) = output_dir {
-        if !out_dir.exists() {
-            fs::create_dir_all(out_dir)
-                .map_err(|e| format!("Failed to create output directory: {}", e))?;
-        }
+    if let Some(out_dir) = output_dir
+        && !options.dry_run
+        && !out_dir.exists()
+    {
+        fs::create_dir_all(out_dir)
+            .map_err(|e| format!("Failed to create output directory: {}", e))?;
     }
 
-    let files: Vec<PathBuf> = input_path
-        .read_dir()
-        .map_err(|e| format!("Failed to read directory: {}", e))?
-        .filter_map(Result::ok)
-        .filter(|entry| {
-            entry
-                .path()
-                .extension()
-                .and_then(|e| e.to_str())
-                .map(|ext| extensions.contains(&ext.to_lowercase().as_str()))
-                .unwrap_or(false)
+    let files = match &options.resume_from {
+        Some(report) => read_failed_from_report(report, input_path)?,
+        None => list_files(
+            input_path,
+            extensions,
+            file_type,
+            options.recursive,
+            output_dir,
+        )?,
+    };
+    let jobs: Vec<BatchJob> = files
+        .into_iter()
+        .map(|input| {
+            let output = output_dir.map(|out_dir| {
+                // Keep the subdirectory a recursive listing found the file in
+                match input.strip_prefix(input_path) {
+                    Ok(relative) if options.recursive => out_dir.join(relative),
+                    _ => out_dir.join(input.file_name().unwrap_or_default()),
+                }
+            });
+            BatchJob {
+                input,
+                output,
+                settings: (),
+            }
         })
-        .map(|entry| entry.path())
         .collect();
 
-    if files.is_empty() {
-        return Err(format!(
-            "No supported {} files found in '{}'",
-            file_type,
-            input_path.display()
-        ));
+    // Checking an input can mean reading its headers, so do it in parallel too
+    let reasons: Vec<Option<String>> = jobs
+        .par_iter()
+        .map(|job| {
+            skip(
+                &job.input,
+                &planned_output(&job.input, job.output.as_deref()),
+            )
+        })
+        .collect();
+    let mut skipped = Vec::new();
+    let jobs: Vec<BatchJob> = jobs
+        .into_iter()
+        .zip(reasons)
+        .filter_map(|(job, reason)| match reason {
+            Some(reason) => {
+                skipped.push((job.input, reason));
+                None
+            }
+            None => Some(job),
+        })
+        .collect();
+    if !options.quiet {
+        for (path, reason) in &skipped {
+            eprintln!("Skipping {}: {}", path.display(), reason);
+        }
     }
 
-    let results: Vec<Result<(PathBuf, String), (PathBuf, String)>> = files
-        .par_iter()
-        .map(|path| {
-            let file_output_path = output_dir.map(|out_dir| {
-                let file_name = path.file_name().unwrap_or_default();
-                out_dir.join(file_name)
-            });
+    if options.dry_run {
+        let planned: Vec<(PathBuf, PathBuf)> = jobs
+            .iter()
+            .map(|job| {
+                (
+                    job.input.clone(),
+                    planned_output(&job.input, job.output.as_deref()),
+                )
+            })
+            .collect();
+        return Ok(BatchSummary {
+            skipped,
+            ..dry_run_summary(planned)
+        });
+    }
+
+    for parent in jobs
+        .iter()
+        .filter_map(|job| job.output.as_deref()?.parent())
+        .collect::<BTreeSet<_>>()
+    {
+        fs::create_dir_all(parent).map_err(|e| {
+            format!(
+                "Failed to create output directory {}: {}",
+                parent.display(),
+                e
+            )
+        })?;
+    }
+
+    let summary = BatchSummary {
+        skipped,
+        ..run_batch(&jobs, options, |path, out_path, _| {
+            converter(path, out_path)
+        })
+    };
+    if let Some(report) = &options.report {
+        write_batch_report(&summary, report)?;
+    }
+    if let Some(checksums) = &options.checksums {
+        write_checksums(&summary.outputs, checksums)?;
+    }
+    Ok(summary)
+}
+
+/// Print the `(input, output)` pairs a dry run would convert, marking outputs that already exist
+///
+/// The returned summary lists each planned output as successful and has no failures.
+fn dry_run_summary(planned: Vec<(PathBuf, PathBuf)>) -> BatchSummary {
+    println!("Dry run: {} files would be converted", planned.len());
+    let mut summary = BatchSummary::default();
+    for (input, output) in planned {
+        let note = if output.exists() { " (exists)" } else { "" };
+        println!("{} -> {}{}", input.display(), output.display(), note);
+        summary
+            .successful
+            .push((input, output.display().to_string()));
+    }
+    summary
+}
+
+/// One conversion of a batch
+#[derive(Debug)]
+struct BatchJob<T = ()> {
+    input: PathBuf,
+    /// `None` lets the converter pick its default output next to the input
+    output: Option<PathBuf>,
+    /// Settings for this job alone, such as a manifest row's COG options
+    settings: T,
+}
 
-            match converter(path, file_output_path.as_deref()) {
-                Ok(output) => Ok((path.clone(), output)),
-                Err(e) => Err((path.clone(), e)),
+/// Convert every job in parallel, with retries and progress
+///
+/// `converter` returns the paths it wrote; the summary lists their file names per input.
+fn run_batch<T, F>(jobs: &[BatchJob<T>], options: &BatchOptions, converter: F) -> BatchSummary
+where
+    T: Sync,
+    F: Fn(&Path, Option<&Path>, &T) -> Result<Vec<PathBuf>, String> + Send + Sync,
+{
+    let progress = ProgressReporter::new(jobs.len(), options.progress_json).with_bar(options.quiet);
+
+    let results: Vec<Result<(PathBuf, Vec<PathBuf>), (PathBuf, String)>> = jobs
+        .par_iter()
+        .map(|job| {
+            let result = convert_with_retries(
+                &job.input,
+                job.output.as_deref(),
+                options.max_retries,
+                Duration::from_millis(options.retry_delay_ms),
+                |path, out_path| converter(path, out_path, &job.settings),
+                |text| progress.message(text),
+            );
+            progress.finish(&job.input, result.is_ok());
+            match result {
+                Ok(output) => Ok((job.input.clone(), output)),
+                Err(e) => Err((job.input.clone(), e)),
             }
         })
         .collect();
 
     let (successful, failed): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
 
-    let successful = successful
+    let written = successful
         .into_iter()
         .map(Result::unwrap)
         .collect::<Vec<_>>();
+    let successful = written
+        .iter()
+        .map(|(input, paths)| {
+            let names: Vec<String> = paths
+                .iter()
+                .map(|path| {
+                    path.file_name()
+                        .unwrap_or_default()
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect();
+            (input.clone(), names.join(", "))
+        })
+        .collect::<Vec<_>>();
+    let outputs = written
+        .into_iter()
+        .flat_map(|(input, paths)| paths.into_iter().map(move |path| (input.clone(), path)))
+        .collect();
     let failed = failed
         .into_iter()
         .map(Result::unwrap_err)
         .collect::<Vec<_>>();
 
-    println!("{} files failed", failed.len());
+    eprintln!("{} files failed", failed.len());
 
-    if !failed.is_empty() {
+    if options.summarize_errors && !failed.is_empty() {
+        print_error_summary(&failed);
+    } else if !failed.is_empty() {
         let mut error_msg = format!(
             "Converted {}/{} files. Errors occurred:\n",
             successful.len(),
-            files.len()
+            jobs.len()
         );
         for (path, err) in &failed {
             error_msg.push_str(&format!("- {}: {}\n", path.display(), err));
@@ -98,34 +799,327 @@ where
         eprintln!("{}", error_msg);
     }
 
-    Ok(BatchSummary { successful, failed })
+    BatchSummary {
+        successful,
+        failed,
+        outputs,
+        ..Default::default()
+    }
 }
 
 pub fn batch_convert_cog(
     input_path: &Path,
     output_dir: Option<&Path>,
     overwrite: bool,
+    cog_options: &CogOptions,
+    options: &BatchOptions,
 ) -> Result<BatchSummary, String> {
-    let raster_exts = ["tif", "tiff", "tff", "asc", "img"];
+    // Fail once up front rather than once per file
+    if let Some(blocksize) = cog_options.blocksize {
+        check_blocksize(blocksize)?;
+    }
+    // Re-running a batch should not spend hours rebuilding COGs it already wrote. The planned
+    // output is checked, not the input, so options that transform an input COG still apply.
+    let skip_cogs = !overwrite && !options.force;
+    let planned_output =
+        |path: &Path, out_path: Option<&Path>| planned_cog_output(path, out_path, overwrite);
     batch_convert(
         input_path,
         output_dir,
-        &raster_exts,
+        &RASTER_EXTENSIONS,
         "raster",
-        |path, out_path| tif_to_cog(path, out_path, overwrite),
+        options,
+        planned_output,
+        |_, planned| {
+            let blocksize = cog_options.blocksize.unwrap_or(512);
+            let is_cog = || validate_cog(planned, blocksize, cog_options.min_overview_size).is_ok();
+            (skip_cogs && planned.exists() && is_cog()).then(|| {
+                format!(
+                    "{} is already a COG (pass --force to reconvert)",
+                    planned.display()
+                )
+            })
+        },
+        |path, out_path| {
+            let planned = planned_output(path, out_path);
+            let name = if options.force && !overwrite {
+                // --force replaces the output, but unlike --overwrite keeps the `_cog` name
+                tif_to_cog(path, Some(&planned), true, cog_options)?
+            } else {
+                tif_to_cog(path, out_path, overwrite, cog_options)?
+            };
+            Ok(vec![planned.with_file_name(name)])
+        },
     )
 }
 
 pub fn batch_convert_gpq(
     input_path: &Path,
     output_dir: Option<&Path>,
+    gpq_options: &GpqOptions,
+    options: &BatchOptions,
 ) -> Result<BatchSummary, String> {
+    if output_dir.is_some_and(is_stdout) {
+        return Err("A batch writes one file per input and cannot write to stdout".to_string());
+    }
     let vector_exts = ["gpkg", "json", "geojson", "fgb", "kml", "gpx", "shp"];
     batch_convert(
         input_path,
         output_dir,
         &vector_exts,
         "vector",
-        |path, out_path| vector_to_geoparquet(path, out_path),
+        options,
+        |path, out_path| planned_vector_output(path, out_path, gpq_options.format),
+        |_, _| None,
+        |path, out_path| vector_convert_to_files(path, out_path, gpq_options),
     )
 }
+
+/// Columns a `--manifest` CSV may have; `input` and `output` are required
+pub const MANIFEST_COLUMNS: [&str; 6] = [
+    "input",
+    "output",
+    "compression",
+    "compression_level",
+    "nodata",
+    "t_srs",
+];
+
+/// One row of a `--manifest` CSV: a conversion with its own option overrides
+///
+/// Empty override cells keep the value from the command line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ManifestRow {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub compression: Option<Compression>,
+    pub compression_level: Option<u8>,
+    pub nodata: Option<f64>,
+    pub t_srs: Option<String>,
+}
+
+impl ManifestRow {
+    /// `base` with this row's overrides applied
+    pub fn cog_options(&self, base: &CogOptions) -> CogOptions {
+        let mut options = base.clone();
+        if let Some(compression) = self.compression {
+            options.compression = compression;
+            // A level or predictor chosen for the global codec may not suit this one
+            options.compression_level = None;
+            options.predictor = None;
+        }
+        if self.compression_level.is_some() {
+            options.compression_level = self.compression_level;
+        }
+        if self.nodata.is_some() {
+            options.nodata = self.nodata;
+        }
+        if self.t_srs.is_some() {
+            options.t_srs = self.t_srs.clone();
+        }
+        options
+    }
+}
+
+/// Quote a CSV field when it holds a comma, quote or line break, as [`split_csv_line`] reads it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Split one CSV line, honouring double-quoted fields with `""` escapes
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("unterminated quoted field".to_string());
+    }
+    fields.push(field);
+    Ok(fields.into_iter().map(|f| f.trim().to_string()).collect())
+}
+
+/// Read and validate a `--manifest` CSV
+///
+/// The header must name `input` and `output` and may add any of [`MANIFEST_COLUMNS`].
+/// Relative paths are resolved against the manifest's directory. Unknown columns, bad
+/// values, missing inputs and duplicate outputs are reported with their line number.
+pub fn read_manifest(path: &Path) -> Result<Vec<ManifestRow>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read manifest {}: {}", path.display(), e))?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line))
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'));
+
+    let (header_line, header) = lines
+        .next()
+        .ok_or_else(|| format!("Manifest {} is empty", path.display()))?;
+    let header = split_csv_line(header).map_err(|e| format!("Line {}: {}", header_line, e))?;
+    for (i, column) in header.iter().enumerate() {
+        if !MANIFEST_COLUMNS.contains(&column.as_str()) {
+            return Err(format!(
+                "Unknown manifest column '{}'; expected {}",
+                column,
+                MANIFEST_COLUMNS.join(", ")
+            ));
+        }
+        if header[..i].contains(column) {
+            return Err(format!("Duplicate manifest column '{}'", column));
+        }
+    }
+    for required in ["input", "output"] {
+        if !header.iter().any(|c| c == required) {
+            return Err(format!("Manifest is missing the '{}' column", required));
+        }
+    }
+
+    let resolve = |value: &str| {
+        let path = PathBuf::from(value);
+        if path.is_absolute() {
+            path
+        } else {
+            base_dir.join(path)
+        }
+    };
+
+    let mut rows: Vec<ManifestRow> = Vec::new();
+    for (line_number, line) in lines {
+        let context = |e: String| format!("Line {}: {}", line_number, e);
+        let values = split_csv_line(line).map_err(context)?;
+        if values.len() != header.len() {
+            return Err(context(format!(
+                "expected {} fields, found {}",
+                header.len(),
+                values.len()
+            )));
+        }
+
+        let mut row = ManifestRow::default();
+        for (column, value) in header.iter().zip(&values) {
+            if value.is_empty() {
+                continue;
+            }
+            match column.as_str() {
+                "input" => row.input = resolve(value),
+                "output" => row.output = resolve(value),
+                "compression" => row.compression = Some(value.parse().map_err(context)?),
+                "compression_level" => {
+                    row.compression_level =
+                        Some(value.parse().map_err(|_| {
+                            context(format!("invalid compression_level '{}'", value))
+                        })?)
+                }
+                "nodata" => {
+                    row.nodata = Some(
+                        value
+                            .parse()
+                            .map_err(|_| context(format!("invalid nodata '{}'", value)))?,
+                    )
+                }
+                "t_srs" => row.t_srs = Some(value.clone()),
+                _ => unreachable!("columns are validated against MANIFEST_COLUMNS"),
+            }
+        }
+
+        if row.input.as_os_str().is_empty() || row.output.as_os_str().is_empty() {
+            return Err(context("input and output are required".to_string()));
+        }
+        if !row.input.exists() {
+            return Err(context(format!(
+                "input {} does not exist",
+                row.input.display()
+            )));
+        }
+        if rows.iter().any(|r| r.output == row.output) {
+            return Err(context(format!(
+                "output {} is listed more than once",
+                row.output.display()
+            )));
+        }
+        rows.push(row);
+    }
+
+    if rows.is_empty() {
+        return Err(format!("Manifest {} has no rows", path.display()));
+    }
+    Ok(rows)
+}
+
+/// Convert each row of a `--manifest` CSV to a COG, applying its overrides on top of
+/// `cog_options`
+pub fn batch_convert_manifest(
+    manifest_path: &Path,
+    overwrite: bool,
+    cog_options: &CogOptions,
+    options: &BatchOptions,
+) -> Result<BatchSummary, String> {
+    let rows = read_manifest(manifest_path)?;
+
+    let mut jobs = Vec::with_capacity(rows.len());
+    for row in &rows {
+        let row_options = row.cog_options(cog_options);
+        row_options
+            .compression
+            .creation_options(row_options.compression_level)
+            .map_err(|e| format!("{}: {}", row.input.display(), e))?;
+        if let Some(parent) = row.output.parent()
+            && !parent.as_os_str().is_empty()
+            && !options.dry_run
+        {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create output directory: {}", e))?;
+        }
+        jobs.push(BatchJob {
+            input: row.input.clone(),
+            output: Some(row.output.clone()),
+            settings: row_options,
+        });
+    }
+    eprintln!(
+        "Manifest {}: {} conversions",
+        manifest_path.display(),
+        jobs.len()
+    );
+
+    if options.dry_run {
+        let planned = rows
+            .into_iter()
+            .map(|row| {
+                let output = planned_cog_output(&row.input, Some(&row.output), overwrite);
+                (row.input, output)
+            })
+            .collect();
+        return Ok(dry_run_summary(planned));
+    }
+
+    let summary = run_batch(&jobs, options, |path, out_path, row_options| {
+        let name = tif_to_cog(path, out_path, overwrite, row_options)?;
+        let planned = planned_cog_output(path, out_path, overwrite);
+        Ok(vec![planned.with_file_name(name)])
+    });
+    if let Some(report) = &options.report {
+        write_batch_report(&summary, report)?;
+    }
+    if let Some(checksums) = &options.checksums {
+        write_checksums(&summary.outputs, checksums)?;
+    }
+    Ok(summary)
+}
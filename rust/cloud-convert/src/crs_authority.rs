@@ -0,0 +1,88 @@
+use crate::open_limit;
+use crate::progress::status;
+use gdal::spatial_ref::SpatialRef;
+use gdal::vector::LayerAccess;
+use std::path::Path;
+
+/// Checks that output CRSs carry an authority code, for catalogs that reject bare WKT
+#[derive(Debug, Clone, Default)]
+pub struct AuthorityCheck {
+    /// Reopen the output and fail when a CRS has no code from `authority`
+    pub require: bool,
+    /// Authority the code must come from, EPSG when unset
+    pub authority: Option<String>,
+    /// Write the EPSG code found by `auto_identify_epsg` into CRSs that lack one
+    pub stamp: bool,
+}
+
+impl AuthorityCheck {
+    pub fn authority(&self) -> &str {
+        self.authority.as_deref().unwrap_or("EPSG")
+    }
+}
+
+/// `srs` with the EPSG code GDAL confidently identifies for it, or `None` when there is no match
+///
+/// Only CRSs without an authority code are identified; GDAL recognises common cases such as
+/// WGS 84 and the UTM zones.
+pub fn identify_epsg(srs: &SpatialRef) -> Option<SpatialRef> {
+    if srs.authority().is_ok() {
+        return None;
+    }
+    let mut identified = srs.clone();
+    identified.auto_identify_epsg().ok()?;
+    identified
+        .authority()
+        .is_ok_and(|code| code.starts_with("EPSG:"))
+        .then_some(identified)
+}
+
+/// The `AUTH:CODE` of `srs`, or why it does not satisfy `authority`
+fn authority_code(srs: Option<&SpatialRef>, authority: &str) -> Result<String, String> {
+    let srs = srs.ok_or_else(|| "no CRS".to_string())?;
+    match srs.authority() {
+        Ok(code)
+            if code
+                .split(':')
+                .next()
+                .is_some_and(|a| a.eq_ignore_ascii_case(authority)) =>
+        {
+            Ok(code)
+        }
+        Ok(code) => Err(format!("CRS code {} is not from {}", code, authority)),
+        Err(_) => match identify_epsg(srs).and_then(|s| s.authority().ok()) {
+            Some(code) if authority.eq_ignore_ascii_case("EPSG") => Err(format!(
+                "CRS has no authority code but matches {}; pass --stamp-epsg to write it",
+                code
+            )),
+            _ => Err(format!(
+                "CRS '{}' has no authority code",
+                srs.name().unwrap_or_default()
+            )),
+        },
+    }
+}
+
+/// Reopen the written dataset at `path` and check its CRS (every layer's, for vectors)
+///
+/// Converters call this on the output under its temporary name, before moving it into place,
+/// so an output that fails never replaces an existing file.
+pub fn check_output_authority(path: &Path, check: &AuthorityCheck) -> Result<(), String> {
+    let (_permit, dataset) = open_limit::open(path)
+        .map_err(|e| format!("Cannot reopen {} to check its CRS: {}", path.display(), e))?;
+    let crss: Vec<(String, Option<SpatialRef>)> = if dataset.raster_count() > 0 {
+        vec![("raster".to_string(), dataset.spatial_ref().ok())]
+    } else {
+        dataset
+            .layers()
+            .map(|layer| (format!("layer '{}'", layer.name()), layer.spatial_ref()))
+            .collect()
+    };
+
+    for (label, srs) in crss {
+        let code = authority_code(srs.as_ref(), check.authority())
+            .map_err(|e| format!("{} {}: {}", path.display(), label, e))?;
+        status!("CRS check: {} {} is {}", path.display(), label, code);
+    }
+    Ok(())
+}
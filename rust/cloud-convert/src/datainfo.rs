@@ -1,8 +1,13 @@
-use gdal::Dataset;
-use gdal::vector::OGRFieldType;
-use std::path::Path;
-// use gdal::spatial_ref::SpatialRef;
+use crate::crs_authority::identify_epsg;
+use crate::open_limit;
+use crate::vsi::to_vsi;
+use gdal::spatial_ref::SpatialRef;
 use gdal::vector::LayerAccess;
+use gdal::vector::{FieldValue, OGRFieldType};
+use gdal::{Dataset, Driver, DriverManager, Metadata};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
 
 fn field_type_to_str(ftype: u32) -> &'static str {
     match ftype {
@@ -20,49 +25,232 @@ fn field_type_to_str(ftype: u32) -> &'static str {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub enum DatasetType {
     Raster,
     Vector,
 }
 
-#[derive(Debug)]
+/// Units of a CRS's coordinates, used to pick resolution and simplification tolerances
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CrsUnits {
+    /// Unit name as given by the CRS, e.g. "degree", "metre", "US survey foot"
+    pub name: String,
+    /// "angular" for geographic CRSs, "linear" otherwise
+    pub kind: String,
+    /// Size of one unit in radians (angular) or metres (linear)
+    pub to_base: f64,
+}
+
+impl CrsUnits {
+    pub fn from_spatial_ref(srs: &SpatialRef) -> Option<Self> {
+        if srs.is_geographic() {
+            Some(CrsUnits {
+                name: srs.angular_units_name()?,
+                kind: "angular".to_string(),
+                to_base: srs.angular_units(),
+            })
+        } else {
+            Some(CrsUnits {
+                name: srs.linear_units_name()?,
+                kind: "linear".to_string(),
+                to_base: srs.linear_units(),
+            })
+        }
+    }
+}
+
+/// Authority code (e.g. "EPSG:4326") of a CRS, or its PROJ string when it has none
+fn crs_id(srs: &SpatialRef) -> Option<String> {
+    srs.authority().or_else(|_| srs.to_proj4()).ok()
+}
+
+/// `AUTH:CODE` of a CRS (e.g. "EPSG:4326"), identified by GDAL when the CRS carries none, or
+/// its WKT when no code matches
+fn crs_epsg(srs: &SpatialRef) -> Option<String> {
+    srs.authority()
+        .ok()
+        .or_else(|| identify_epsg(srs)?.authority().ok())
+        .or_else(|| srs.to_wkt().ok())
+}
+
+/// `[xmin, ymin, xmax, ymax]` covered by a raster of `size` pixels on `transform`
+fn raster_bounds(transform: &[f64; 6], size: (usize, usize)) -> [f64; 4] {
+    let (cols, rows) = (size.0 as f64, size.1 as f64);
+    let corners = [(0.0, 0.0), (cols, 0.0), (0.0, rows), (cols, rows)].map(|(px, py)| {
+        (
+            transform[0] + px * transform[1] + py * transform[2],
+            transform[3] + px * transform[4] + py * transform[5],
+        )
+    });
+    corners.iter().fold(
+        [
+            f64::INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NEG_INFINITY,
+        ],
+        |b, &(x, y)| [b[0].min(x), b[1].min(y), b[2].max(x), b[3].max(y)],
+    )
+}
+
+fn format_bounds(bounds: &Option<[f64; 4]>) -> String {
+    match bounds {
+        Some([xmin, ymin, xmax, ymax]) => format!("[{}, {}, {}, {}]", xmin, ymin, xmax, ymax),
+        None => "Unknown".to_string(),
+    }
+}
+
+fn format_units(units: &Option<CrsUnits>) -> String {
+    match units {
+        Some(u) if u.kind == "angular" => format!("{} ({})", u.name, u.kind),
+        Some(u) => format!("{} ({}, {} m)", u.name, u.kind, u.to_base),
+        None => "Unknown".to_string(),
+    }
+}
+
+/// Per-band properties of a raster
+#[derive(Debug, Clone, Serialize)]
+pub struct BandInfo {
+    pub no_data_value: Option<f64>,
+    pub dtype: String,
+    /// Band description, empty when unset
+    pub description: String,
+}
+
+#[derive(Debug, Serialize)]
 pub struct LayerInfo {
     pub name: String,
     pub crs: Option<String>,
+    /// Authority code or PROJ string, usable with `SpatialRef::from_definition`
+    pub crs_id: Option<String>,
+    /// Authority code such as "EPSG:4326", including codes GDAL identifies, else the WKT
+    pub epsg: Option<String>,
+    pub crs_units: Option<CrsUnits>,
+    /// Layer extent as `[xmin, ymin, xmax, ymax]` in the layer CRS
+    pub bounds: Option<[f64; 4]>,
     pub fields: Vec<(String, String)>,
     pub feature_count: u64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct DatasetInfo {
     pub dataset_type: DatasetType,
     pub driver: String,
     pub crs: Option<String>,
+    /// Authority code or PROJ string, usable with `SpatialRef::from_definition`
+    pub crs_id: Option<String>,
+    /// Authority code such as "EPSG:4326", including codes GDAL identifies, else the WKT
+    pub epsg: Option<String>,
+    pub crs_units: Option<CrsUnits>,
     pub size: Option<(usize, usize)>,
+    /// Pixel width and height in CRS units
+    pub resolution: Option<(f64, f64)>,
+    /// Raster extent as `[xmin, ymin, xmax, ymax]` in the raster CRS
+    pub bounds: Option<[f64; 4]>,
+    /// Full affine geotransform, set only when it is rotated or sheared
+    ///
+    /// `resolution` and `bounds` stay meaningful for such rasters, but the pixel grid is not
+    /// aligned with the CRS axes, so they cannot be compared with north-up rasters.
+    pub rotated_transform: Option<[f64; 6]>,
     pub band_count: Option<usize>,
+    pub band_types: Option<Vec<String>>,
+    pub bands: Option<Vec<BandInfo>>,
+    /// Number of overviews of band 1, 0 when it has none
+    pub overview_count: Option<usize>,
+    /// Internal block width and height of band 1; blocks as wide as the raster are strips
+    pub block_size: Option<(usize, usize)>,
     pub layers: Option<Vec<LayerInfo>>,
     pub layer_count: Option<usize>,
+    /// `(name, description)` of each subdataset, for containers such as NetCDF and HDF5
+    ///
+    /// Pass a name back to `info` (or any other command) to open that subdataset.
+    pub subdatasets: Option<Vec<(String, String)>>,
 }
 
+/// The `(name, description)` pairs in a dataset's SUBDATASETS metadata domain, in order
+pub fn subdatasets(ds: &Dataset) -> Vec<(String, String)> {
+    let mut entries: BTreeMap<usize, (String, String)> = BTreeMap::new();
+    for item in ds.metadata_domain("SUBDATASETS").unwrap_or_default() {
+        let Some((key, value)) = item.split_once('=') else {
+            continue;
+        };
+        let Some((index, field)) = key
+            .strip_prefix("SUBDATASET_")
+            .and_then(|rest| rest.split_once('_'))
+        else {
+            continue;
+        };
+        let Ok(index) = index.parse::<usize>() else {
+            continue;
+        };
+        let entry = entries.entry(index).or_default();
+        match field {
+            "NAME" => entry.0 = value.to_string(),
+            "DESC" => entry.1 = value.to_string(),
+            _ => {}
+        }
+    }
+    entries.into_values().collect()
+}
+
+/// Read the metadata of a local file, or of a cloud object given as a `/vsi` path or URL
 pub fn get_datainfo(path: &Path) -> gdal::errors::Result<DatasetInfo> {
-    let ds = Dataset::open(path)?;
+    let (_permit, ds) = open_limit::open(to_vsi(path))?;
     let driver = ds.driver().short_name().to_string();
     let band_count = ds.raster_count();
     let layer_count = ds.layer_count();
+    let subdatasets = Some(subdatasets(&ds)).filter(|s| !s.is_empty());
 
-    if band_count > 0 {
+    // Containers such as NetCDF expose their rasters as subdatasets, with no top-level bands
+    if band_count > 0 || (subdatasets.is_some() && layer_count == 0) {
         // Raster dataset
-        let crs = ds.spatial_ref().ok().and_then(|r| r.name());
+        let srs = ds.spatial_ref().ok();
+        let crs = srs.as_ref().and_then(|r| r.name());
+        let crs_id = srs.as_ref().and_then(crs_id);
+        let epsg = srs.as_ref().and_then(crs_epsg);
+        let crs_units = srs.as_ref().and_then(CrsUnits::from_spatial_ref);
+        // A container's own grid is a placeholder; its subdatasets carry the real ones
+        let transform = ds.geo_transform().ok().filter(|_| band_count > 0);
+        let resolution = transform.map(|t| (t[1].hypot(t[4]), t[2].hypot(t[5])));
+        let bounds = transform.map(|t| raster_bounds(&t, ds.raster_size()));
+        let rotated_transform = transform.filter(|t| t[2] != 0.0 || t[4] != 0.0);
+        let bands = ds
+            .rasterbands()
+            .map(|band| {
+                band.map(|b| BandInfo {
+                    no_data_value: b.no_data_value(),
+                    dtype: b.band_type().name(),
+                    description: b.description().unwrap_or_default(),
+                })
+            })
+            .collect::<gdal::errors::Result<Vec<_>>>()?;
+        let band_types = bands.iter().map(|b| b.dtype.clone()).collect();
+        let first_band = ds.rasterband(1).ok();
+        let overview_count = first_band
+            .as_ref()
+            .map(|b| b.overview_count().unwrap_or(0).max(0) as usize);
+        let block_size = first_band.as_ref().map(|b| b.block_size());
 
         Ok(DatasetInfo {
             dataset_type: DatasetType::Raster,
             driver,
             crs,
+            crs_id,
+            epsg,
+            crs_units,
             size: Some(ds.raster_size()),
+            resolution,
+            bounds,
+            rotated_transform,
             band_count: Some(band_count),
+            band_types: Some(band_types),
+            bands: Some(bands),
+            overview_count,
+            block_size,
             layer_count: None,
             layers: None,
+            subdatasets,
         })
     } else {
         // Vector dataset
@@ -70,7 +258,15 @@ pub fn get_datainfo(path: &Path) -> gdal::errors::Result<DatasetInfo> {
 
         for idx in 0..layer_count {
             let layer = ds.layer(idx)?;
-            let crs = layer.spatial_ref().and_then(|r| r.name());
+            let srs = layer.spatial_ref();
+            let crs = srs.as_ref().and_then(|r| r.name());
+            let crs_id = srs.as_ref().and_then(crs_id);
+            let epsg = srs.as_ref().and_then(crs_epsg);
+            let crs_units = srs.as_ref().and_then(CrsUnits::from_spatial_ref);
+            let bounds = layer
+                .get_extent()
+                .ok()
+                .map(|e| [e.MinX, e.MinY, e.MaxX, e.MaxY]);
             // .unwrap_or("Unknown CRS".to_string());
             let name = layer.name();
             let feature_count = layer.feature_count();
@@ -84,6 +280,10 @@ pub fn get_datainfo(path: &Path) -> gdal::errors::Result<DatasetInfo> {
             layers_info.push(LayerInfo {
                 name,
                 crs,
+                crs_id,
+                epsg,
+                crs_units,
+                bounds,
                 fields,
                 feature_count,
             });
@@ -93,14 +293,34 @@ pub fn get_datainfo(path: &Path) -> gdal::errors::Result<DatasetInfo> {
             dataset_type: DatasetType::Vector,
             driver,
             crs: None,
+            crs_id: None,
+            epsg: None,
+            crs_units: None,
             size: None,
+            resolution: None,
+            bounds: None,
+            rotated_transform: None,
             band_count: None,
+            band_types: None,
+            bands: None,
+            overview_count: None,
+            block_size: None,
             layer_count: Some(layer_count),
             layers: Some(layers_info),
+            subdatasets,
         })
     }
 }
 
+/// Print `info` as pretty JSON on stdout, for scripts
+///
+/// Every [`DatasetInfo`] field is present, with `null` for values that do not apply to the
+/// dataset type; layer fields are `[name, type]` pairs.
+pub fn print_datainfo_json(info: &DatasetInfo) -> serde_json::Result<()> {
+    println!("{}", serde_json::to_string_pretty(info)?);
+    Ok(())
+}
+
 pub fn print_datainfo(info: &DatasetInfo) {
     match info.dataset_type {
         DatasetType::Raster => {
@@ -112,7 +332,46 @@ pub fn print_datainfo(info: &DatasetInfo) {
                 info.size.unwrap().1
             );
             println!("Band count: {}", info.band_count.unwrap());
+            if let Some(types) = &info.band_types {
+                println!("Band types: {}", types.join(", "));
+            }
+            for (idx, band) in info.bands.iter().flatten().enumerate() {
+                let no_data = band
+                    .no_data_value
+                    .map_or("none".to_string(), |v| v.to_string());
+                print!("  Band {}: {}, NoData {}", idx + 1, band.dtype, no_data);
+                if band.description.is_empty() {
+                    println!();
+                } else {
+                    println!(", '{}'", band.description);
+                }
+            }
+            if let Some(count) = info.overview_count {
+                println!("Overviews: {}", count);
+            }
+            if let Some((x, y)) = info.block_size {
+                println!("Block size: {} x {}", x, y);
+            }
             println!("CRS: {}", info.crs.clone().unwrap_or("Unknown".to_string()));
+            println!(
+                "CRS code: {}",
+                info.epsg.clone().unwrap_or("Unknown".to_string())
+            );
+            println!("CRS units: {}", format_units(&info.crs_units));
+            if let Some((x, y)) = info.resolution {
+                println!("Resolution: {} x {}", x, y);
+            }
+            println!("Bounds: {}", format_bounds(&info.bounds));
+            if let Some(t) = info.rotated_transform {
+                println!("Rotated geotransform: {:?}", t);
+            }
+            if let Some(subdatasets) = &info.subdatasets {
+                println!("Subdatasets: {}", subdatasets.len());
+                for (name, description) in subdatasets {
+                    println!("  {}", name);
+                    println!("    {}", description);
+                }
+            }
         }
         DatasetType::Vector => {
             println!("Vector dataset:");
@@ -129,7 +388,308 @@ pub fn print_datainfo(info: &DatasetInfo) {
                     "CRS: {}",
                     layer.crs.clone().unwrap_or("Unknown".to_string())
                 );
+                println!(
+                    "CRS code: {}",
+                    layer.epsg.clone().unwrap_or("Unknown".to_string())
+                );
+                println!("CRS units: {}", format_units(&layer.crs_units));
+                println!("Bounds: {}", format_bounds(&layer.bounds));
+            }
+        }
+    }
+}
+
+/// Raw GDAL details that `info --verbose` prints after the summary
+#[derive(Debug)]
+pub struct DatasetDetails {
+    /// Affine geotransform coefficients, for rasters that have one
+    pub geo_transform: Option<[f64; 6]>,
+    /// Every dataset metadata item as `(domain, key, value)`; the default domain is ""
+    pub metadata: Vec<(String, String, String)>,
+    /// Ground control points as `(id, pixel, line, x, y, z)`
+    pub gcps: Vec<(String, f64, f64, f64, f64, f64)>,
+    /// `(label, pretty WKT)` of the dataset CRS, the GCP CRS and each layer CRS that is set
+    pub wkt: Vec<(String, String)>,
+}
+
+/// Read the geotransform, metadata, GCPs and CRS WKT that `gdalinfo` would show
+pub fn get_details(path: &Path) -> gdal::errors::Result<DatasetDetails> {
+    let (_permit, ds) = open_limit::open(to_vsi(path))?;
+    let geo_transform = ds.geo_transform().ok().filter(|_| ds.raster_count() > 0);
+    let metadata = ds
+        .metadata()
+        .map(|entry| (entry.domain, entry.key, entry.value))
+        .collect();
+    let gcps = ds
+        .gcps()
+        .iter()
+        .map(|gcp| (gcp.id(), gcp.pixel(), gcp.line(), gcp.x(), gcp.y(), gcp.z()))
+        .collect();
+
+    let mut wkt = Vec::new();
+    let mut push_wkt = |label: String, srs: Option<SpatialRef>| {
+        if let Some(text) = srs.and_then(|srs| srs.to_pretty_wkt().ok()) {
+            wkt.push((label, text));
+        }
+    };
+    push_wkt("CRS".to_string(), ds.spatial_ref().ok());
+    push_wkt("GCP CRS".to_string(), ds.gcp_spatial_ref());
+    for layer in ds.layers() {
+        push_wkt(format!("Layer '{}' CRS", layer.name()), layer.spatial_ref());
+    }
+
+    Ok(DatasetDetails {
+        geo_transform,
+        metadata,
+        gcps,
+        wkt,
+    })
+}
+
+pub fn print_details(details: &DatasetDetails) {
+    if let Some(t) = details.geo_transform {
+        println!("Geotransform:");
+        let labels = [
+            "x origin",
+            "pixel width",
+            "row rotation",
+            "y origin",
+            "column rotation",
+            "pixel height",
+        ];
+        for (idx, (label, value)) in labels.iter().zip(t).enumerate() {
+            println!("  [{}] {}: {}", idx, label, value);
+        }
+    }
+
+    let mut domain = None;
+    for (entry_domain, key, value) in &details.metadata {
+        if domain != Some(entry_domain) {
+            let name = if entry_domain.is_empty() {
+                "default"
+            } else {
+                entry_domain.as_str()
+            };
+            println!("Metadata ({}):", name);
+            domain = Some(entry_domain);
+        }
+        println!("  {}={}", key, value);
+    }
+
+    if !details.gcps.is_empty() {
+        println!("GCPs: {}", details.gcps.len());
+        for (id, pixel, line, x, y, z) in &details.gcps {
+            println!("  {}: ({}, {}) -> ({}, {}, {})", id, pixel, line, x, y, z);
+        }
+    }
+
+    for (label, text) in &details.wkt {
+        println!("{} WKT:", label);
+        for line in text.lines() {
+            println!("  {}", line);
+        }
+    }
+}
+
+/// JSON form of an OGR field value; dates become ISO 8601 strings
+fn field_value_to_json(value: FieldValue) -> serde_json::Value {
+    match value {
+        FieldValue::IntegerValue(v) => v.into(),
+        FieldValue::IntegerListValue(v) => v.into(),
+        FieldValue::Integer64Value(v) => v.into(),
+        FieldValue::Integer64ListValue(v) => v.into(),
+        FieldValue::StringValue(v) => v.into(),
+        FieldValue::StringListValue(v) => v.into(),
+        FieldValue::RealValue(v) => v.into(),
+        FieldValue::RealListValue(v) => v.into(),
+        FieldValue::DateValue(v) => v.to_string().into(),
+        FieldValue::DateTimeValue(v) => v.to_rfc3339().into(),
+    }
+}
+
+/// Find one feature by FID or OGR SQL attribute filter and return it as a GeoJSON Feature
+///
+/// Reads `layer_name`, or the first layer when it is `None`. When a filter matches several
+/// features the first is returned and the match count is reported on stderr.
+pub fn extract_feature(
+    path: &Path,
+    layer_name: Option<&str>,
+    fid: Option<u64>,
+    filter: Option<&str>,
+) -> Result<serde_json::Value, String> {
+    let (_permit, ds) = open_limit::open(path)
+        .map_err(|e| format!("Failed to open dataset {}: {}", path.display(), e))?;
+    let mut layer = match layer_name {
+        Some(name) => ds.layer_by_name(name).map_err(|_| {
+            let names: Vec<String> = ds.layers().map(|l| l.name()).collect();
+            format!(
+                "Layer '{}' not found in {} (layers: {})",
+                name,
+                path.display(),
+                names.join(", ")
+            )
+        })?,
+        None => ds
+            .layer(0)
+            .map_err(|e| format!("{} has no vector layers: {}", path.display(), e))?,
+    };
+    let layer_label = layer.name();
+
+    let feature = match (fid, filter) {
+        (Some(fid), _) => layer.feature(fid).ok_or_else(|| {
+            format!(
+                "No feature with FID {} in layer '{}' of {}",
+                fid,
+                layer_label,
+                path.display()
+            )
+        })?,
+        (None, Some(filter)) => {
+            layer
+                .set_attribute_filter(filter)
+                .map_err(|e| format!("Invalid filter '{}': {}", filter, e))?;
+            let matches = layer.feature_count();
+            if matches > 1 {
+                eprintln!("{} features match '{}', showing the first", matches, filter);
             }
+            layer.features().next().ok_or_else(|| {
+                format!(
+                    "No feature matches '{}' in layer '{}' of {}",
+                    filter,
+                    layer_label,
+                    path.display()
+                )
+            })?
+        }
+        (None, None) => return Err("Pass an FID or an attribute filter".to_string()),
+    };
+
+    let properties: serde_json::Map<String, serde_json::Value> = feature
+        .fields()
+        .map(|(name, value)| {
+            let value = value.map_or(serde_json::Value::Null, field_value_to_json);
+            (name, value)
+        })
+        .collect();
+    let geometry = match feature.geometry() {
+        Some(geom) => {
+            let json = geom
+                .json()
+                .map_err(|e| format!("Failed to convert geometry to GeoJSON: {}", e))?;
+            serde_json::from_str(&json)
+                .map_err(|e| format!("Failed to parse geometry GeoJSON: {}", e))?
+        }
+        None => serde_json::Value::Null,
+    };
+
+    Ok(serde_json::json!({
+        "type": "Feature",
+        "id": feature.fid(),
+        "properties": properties,
+        "geometry": geometry,
+    }))
+}
+
+/// Short names of the GDAL drivers this tool reads or writes
+pub const TOOL_DRIVERS: [&str; 14] = [
+    "GTiff",
+    "COG",
+    "Zarr",
+    "VRT",
+    "MEM",
+    "PNG",
+    "AAIGrid",
+    "HFA",
+    "Parquet",
+    "GPKG",
+    "GeoJSON",
+    "FlatGeobuf",
+    "KML",
+    "ESRI Shapefile",
+];
+
+#[derive(Debug, Serialize)]
+pub struct DriverInfo {
+    pub short_name: String,
+    pub long_name: String,
+    /// False when a driver this tool uses is missing from the GDAL build
+    pub available: bool,
+    pub raster: bool,
+    pub vector: bool,
+    pub create: bool,
+    pub create_copy: bool,
+}
+
+impl DriverInfo {
+    fn from_driver(driver: &Driver) -> Self {
+        let capability = |key: &str| {
+            driver
+                .metadata_item(key, "")
+                .is_some_and(|v| v.eq_ignore_ascii_case("YES"))
+        };
+        DriverInfo {
+            short_name: driver.short_name(),
+            long_name: driver.long_name(),
+            available: true,
+            raster: capability("DCAP_RASTER"),
+            vector: capability("DCAP_VECTOR"),
+            create: capability("DCAP_CREATE"),
+            create_copy: capability("DCAP_CREATECOPY"),
+        }
+    }
+
+    fn missing(short_name: &str) -> Self {
+        DriverInfo {
+            short_name: short_name.to_string(),
+            long_name: String::new(),
+            available: false,
+            raster: false,
+            vector: false,
+            create: false,
+            create_copy: false,
+        }
+    }
+}
+
+/// List the GDAL drivers registered at runtime
+///
+/// With `tool_only`, only the drivers in [`TOOL_DRIVERS`] are listed, including missing ones.
+pub fn list_drivers(tool_only: bool) -> Vec<DriverInfo> {
+    if tool_only {
+        TOOL_DRIVERS
+            .iter()
+            .map(|name| match DriverManager::get_driver_by_name(name) {
+                Ok(driver) => DriverInfo::from_driver(&driver),
+                Err(_) => DriverInfo::missing(name),
+            })
+            .collect()
+    } else {
+        DriverManager::all()
+            .map(|driver| DriverInfo::from_driver(&driver))
+            .collect()
+    }
+}
+
+pub fn print_drivers(drivers: &[DriverInfo]) {
+    let flag = |set: bool, c: char| if set { c } else { '-' };
+    println!("{:<16} {:<6} Long name", "Driver", "Caps");
+    for d in drivers {
+        if !d.available {
+            println!(
+                "{:<16} {:<6} (not available in this GDAL build)",
+                d.short_name, ""
+            );
+            continue;
         }
+        let caps: String = [
+            flag(d.raster, 'r'),
+            flag(d.vector, 'v'),
+            flag(d.create, 'c'),
+            flag(d.create_copy, 'C'),
+        ]
+        .iter()
+        .collect();
+        println!("{:<16} {:<6} {}", d.short_name, caps, d.long_name);
     }
+    println!("Caps: r=raster v=vector c=create C=createcopy");
 }
@@ -0,0 +1,38 @@
+use gdal::config;
+
+/// Parse a `KEY=VALUE` pair given to `--co` or `--config`
+pub fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!(
+            "Invalid option '{}'. Use KEY=VALUE, e.g. NUM_THREADS=ALL_CPUS.",
+            s
+        )),
+    }
+}
+
+/// Set each `(key, value)` as a GDAL configuration option for the rest of the process
+pub fn set_config_options(options: &[(String, String)]) -> Result<(), String> {
+    for (key, value) in options {
+        config::set_config_option(key, value)
+            .map_err(|e| format!("Failed to set GDAL config {}={}: {}", key, value, e))?;
+    }
+    Ok(())
+}
+
+/// Add user `--co` options to the `KEY=VALUE` creation options the tool chose
+///
+/// A user option replaces a chosen one with the same key (keys are compared case-insensitively,
+/// as GDAL does), so flags such as `--compression` can be overridden without a rebuild.
+pub fn merge_creation_options(options: &mut Vec<String>, extra: &[(String, String)]) {
+    for (key, value) in extra {
+        options.retain(|option| {
+            option
+                .split_once('=')
+                .is_none_or(|(existing, _)| !existing.eq_ignore_ascii_case(key))
+        });
+        options.push(format!("{}={}", key, value));
+    }
+}
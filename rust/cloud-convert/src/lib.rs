@@ -1,5 +1,20 @@
+pub mod band_math;
 pub mod batch_convert;
+pub mod crs_authority;
 pub mod datainfo;
+pub mod gdal_options;
+pub mod mosaic;
+pub mod open_limit;
+pub mod progress;
+pub mod qaqc_rules;
 pub mod rast_qaqc;
+pub mod stats_cache;
+pub mod survey;
+pub mod tdigest;
+pub mod tempdir;
 pub mod tif2cog;
+pub mod tif2zarr;
+pub mod units;
 pub mod vect2gpq;
+pub mod vsi;
+pub mod zonal;
@@ -1,17 +1,40 @@
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use walkdir::WalkDir;
 
+mod band_math;
 mod batch_convert;
+mod crs_authority;
 mod datainfo;
+mod gdal_options;
+mod mosaic;
+mod open_limit;
+mod progress;
+mod qaqc_rules;
 mod rast_qaqc;
+mod stats_cache;
+mod survey;
+mod tdigest;
+mod tempdir;
 mod tif2cog;
+mod tif2zarr;
+mod units;
 mod vect2gpq;
+mod vsi;
+mod zonal;
 
+use band_math::*;
 use batch_convert::*;
+use crs_authority::AuthorityCheck;
 use datainfo::*;
+use gdal_options::{parse_key_value, set_config_options};
+use mosaic::*;
 use rast_qaqc::*;
+use survey::*;
 use tif2cog::*;
+use tif2zarr::*;
 use vect2gpq::*;
+use zonal::*;
 
 #[derive(Parser)]
 #[command(name = "cloud_convert")]
@@ -19,27 +42,398 @@ use vect2gpq::*;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Directory for intermediate files (defaults to $TMPDIR, else next to the output)
+    #[arg(long, global = true)]
+    temp_dir: Option<PathBuf>,
+    /// Maximum threads holding open datasets at once (defaults to 1/8 of the open file limit)
+    #[arg(long, global = true)]
+    max_open: Option<usize>,
+    /// Worker threads for batch and QAQC runs (0 = all cores; defaults to RAYON_NUM_THREADS, else all cores)
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+    /// GDAL configuration option as KEY=VALUE, e.g. GDAL_NUM_THREADS=ALL_CPUS; repeatable
+    #[arg(long, global = true, value_parser = parse_key_value)]
+    config: Vec<(String, String)>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Show information about a geospatial file
-    Info { path: PathBuf },
+    Info {
+        path: PathBuf,
+        /// Print the metadata as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+        /// Also print the geotransform, every metadata item, GCPs and the full CRS WKT
+        #[arg(short, long, default_value_t = false, conflicts_with = "json")]
+        verbose: bool,
+    },
+
+    /// Print one vector feature's attributes and geometry as GeoJSON
+    ExtractFeature {
+        path: PathBuf,
+        /// Layer name (default: the first layer)
+        #[arg(long)]
+        layer: Option<String>,
+        /// Feature ID to extract
+        #[arg(long, required_unless_present = "filter", conflicts_with = "filter")]
+        fid: Option<u64>,
+        /// OGR SQL attribute filter, e.g. "ADM0_NAME = 'Kenya'"; the first match is shown
+        #[arg(long = "where")]
+        filter: Option<String>,
+    },
+
+    /// Count the distinct CRSs, resolutions and overlapping extents across a directory tree
+    Survey {
+        path: PathBuf,
+        /// Where to write the grouped table (default: <path>/survey.<format>)
+        #[arg(long)]
+        out: Option<PathBuf>,
+        #[arg(short, long, default_value_t = OutputFormat::Csv)]
+        output_format: OutputFormat,
+    },
+
+    /// List the GDAL drivers available at runtime
+    Drivers {
+        /// Only show the drivers this tool uses, including missing ones
+        #[arg(long, default_value_t = false)]
+        used: bool,
+        /// Print the list as JSON
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
 
     /// Convert raster to Cloud-Optimized GeoTIFF
     ToCog {
-        path: PathBuf,
+        #[arg(required_unless_present = "manifest")]
+        path: Option<PathBuf>,
         #[arg(short, long)]
         out: Option<PathBuf>,
+        /// Write <dir>/<input stem>.tif for a single file; same as --out for a directory
+        #[arg(long, conflicts_with = "out")]
+        output_dir: Option<PathBuf>,
+        /// Extra COG creation option as KEY=VALUE, e.g. NUM_THREADS=ALL_CPUS; repeatable
+        #[arg(long = "co", value_parser = parse_key_value)]
+        creation_options: Vec<(String, String)>,
+        /// Write BigTIFF: yes, no, or if_needed when the output is estimated above 3.5 GB
+        #[arg(long, default_value_t = BigTiff::IfNeeded)]
+        bigtiff: BigTiff,
         #[arg(short, long, default_value_t = false)]
         overwrite: bool,
+        /// Output data type (Byte, UInt16, Int16, Float32, Float64)
+        #[arg(long)]
+        out_dtype: Option<OutDtype>,
+        /// Saturate values outside the output data type range instead of failing
+        #[arg(long, default_value_t = false)]
+        clamp: bool,
+        /// Smallest overview size in pixels; coarser overview levels are not built
+        #[arg(long)]
+        min_overview_size: Option<usize>,
+        /// Overview resampling (nearest, bilinear, cubic, average, mode, gauss); use mode for categorical data
+        #[arg(long)]
+        overview_resampling: Option<OverviewResampling>,
+        /// Internal tile size in pixels, a multiple of 16 (default 512)
+        #[arg(long)]
+        blocksize: Option<usize>,
+        /// Also write a <output>.png preview of roughly this many pixels on the longer side
+        #[arg(long)]
+        thumbnail: Option<usize>,
+        /// Use a 2-98% percentile stretch for the thumbnail
+        #[arg(long, default_value_t = false)]
+        thumbnail_stretch: bool,
+        /// Warp onto the CRS, resolution and extent of this reference raster
+        #[arg(long)]
+        reference: Option<PathBuf>,
+        /// Write an internal mask built from the source NoData instead of a NoData value
+        #[arg(long, default_value_t = false)]
+        internal_mask: bool,
+        /// Compression codec (LZW, DEFLATE, ZSTD, LERC, WEBP, NONE)
+        #[arg(long, alias = "compress", default_value_t = Compression::Lzw)]
+        compression: Compression,
+        /// ZSTD level (1-22), DEFLATE level (1-12) or WEBP quality (1-100)
+        #[arg(long, alias = "zlevel")]
+        compression_level: Option<u8>,
+        /// Predictor for LZW, DEFLATE or ZSTD: none (1), standard (2) or floating-point (3)
+        #[arg(long)]
+        predictor: Option<Predictor>,
+        /// Palette file with one "value R G B [A]" line per class, for single-band Byte/UInt16 inputs
+        #[arg(long)]
+        color_table: Option<PathBuf>,
+        /// Expand the color table into explicit RGB bands for viewers without palette support
+        #[arg(long, default_value_t = false, requires = "color_table")]
+        force_rgb: bool,
+        /// Color interpretation of each output band in order, e.g. red,green,blue,alpha
+        #[arg(long, value_delimiter = ',', conflicts_with = "color_table")]
+        color_interp: Option<Vec<ColorInterp>>,
+        /// Convert values between units, as FROM:TO (e.g. K:degC, m:ft, mm/day:mm/year)
+        #[arg(long)]
+        convert_units: Option<units::UnitConversion>,
+        /// Reproject to this CRS (e.g. EPSG:4326)
+        #[arg(long, conflicts_with = "reference")]
+        t_srs: Option<String>,
+        /// Resampling for --reference and --t-srs (nearest, bilinear, cubic, cubicspline, lanczos, average, mode); bilinear for float data and nearest otherwise by default
+        #[arg(long)]
+        resampling: Option<WarpResampling>,
+        /// Output NoData value; source NoData pixels are rewritten to it
+        #[arg(long, allow_negative_numbers = true)]
+        nodata: Option<f64>,
+        /// Remove the NoData flag from every output band, so no value is treated as missing
+        #[arg(
+            long = "a-nodata-none",
+            default_value_t = false,
+            conflicts_with = "nodata"
+        )]
+        clear_nodata: bool,
+        /// Skip writing blocks that are entirely NoData (SPARSE_OK); see the README for reader support
+        #[arg(long, default_value_t = false)]
+        sparse: bool,
+        /// Fail unless the output CRS carries an authority code (EPSG unless --crs-authority is given)
+        #[arg(long, default_value_t = false)]
+        require_epsg: bool,
+        /// Authority that --require-epsg accepts, e.g. ESRI
+        #[arg(long, requires = "require_epsg")]
+        crs_authority: Option<String>,
+        /// Write the EPSG code GDAL identifies for a CRS that has none
+        #[arg(long, default_value_t = false)]
+        stamp_epsg: bool,
+        /// Reopen each output and check its tiling, overviews and IFD layout are cloud-optimized
+        #[arg(long, default_value_t = false)]
+        verify: bool,
+        /// Only convert these 1-based bands, e.g. 1-10,50
+        #[arg(long)]
+        bands: Option<BandList>,
+        /// CSV with input,output columns and optional compression, compression_level, nodata and t_srs overrides per row
+        #[arg(long, conflicts_with_all = ["path", "out", "output_dir", "split_bands", "report_dtypes", "resume_from"])]
+        manifest: Option<PathBuf>,
+        /// Write each band to its own COG in the output directory
+        #[arg(long, default_value_t = false)]
+        split_bands: bool,
+        /// Band metadata item holding each band's date (e.g. NETCDF_DIM_time), used to name split outputs
+        #[arg(long, requires = "split_bands")]
+        date_from_metadata: Option<String>,
+        /// Only report data types, band counts and CRSs across a directory, without converting
+        #[arg(long, default_value_t = false)]
+        report_dtypes: bool,
+        /// Retry a failing file this many times in batch mode
+        #[arg(long, default_value_t = 0)]
+        max_retries: u32,
+        /// Delay between retries in milliseconds
+        #[arg(long, default_value_t = 0)]
+        retry_delay_ms: u64,
+        /// Group batch failures by error type
+        #[arg(long, default_value_t = false)]
+        summarize_errors: bool,
+        /// Print a JSON progress line on stderr as each file in a batch finishes
+        #[arg(long, default_value_t = false)]
+        progress_json: bool,
+        /// Hide the progress bar and per-file progress lines
+        #[arg(long, default_value_t = false)]
+        quiet: bool,
+        /// Write a JSON report of the converted and failed files in a batch
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// Only convert the files listed as failed in an earlier --report
+        #[arg(long)]
+        resume_from: Option<PathBuf>,
+        /// Write the size and SHA-256 of every output of a batch, as JSON for a .json path and CSV otherwise
+        #[arg(long)]
+        checksums: Option<PathBuf>,
+        /// List the files a batch would convert and their output paths, without writing anything
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Also convert files in subdirectories, keeping their layout under --out
+        #[arg(long, default_value_t = false)]
+        recursive: bool,
+        /// In batch mode, reconvert and replace outputs that are already COGs instead of skipping them
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
+
+    /// Mosaic several rasters into a single Cloud-Optimized GeoTIFF
+    Mosaic {
+        /// Input rasters, or a single directory of GeoTIFFs
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        #[arg(short, long)]
+        out: PathBuf,
+        /// How overlapping pixels are combined (first, last, min, max, mean)
+        #[arg(long, default_value_t = MergeStrategy::First)]
+        merge_strategy: MergeStrategy,
+        /// NoData of the output, filling gaps between inputs; needed for integer inputs without one
+        #[arg(long, allow_negative_numbers = true)]
+        nodata: Option<f64>,
+        #[arg(short = 'w', long, default_value_t = false)]
+        overwrite: bool,
+    },
+
+    /// Stack the bands of rasters into a multi-band Cloud-Optimized GeoTIFF
+    Stack {
+        /// Input rasters, in output band order
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        #[arg(short, long)]
+        out: PathBuf,
+        /// Text file with one band name per line, one per output band in order
+        #[arg(long)]
+        band_names_file: Option<PathBuf>,
+        #[arg(short = 'w', long, default_value_t = false)]
+        overwrite: bool,
+    },
+
+    /// Compute a new raster from a band expression such as (b4-b3)/(b4+b3)
+    BandMath {
+        path: PathBuf,
+        /// Expression over 1-based band references b1, b2, ... with + - * / and parentheses
+        #[arg(short, long)]
+        expression: String,
+        #[arg(short, long)]
+        out: PathBuf,
+        #[arg(short = 'w', long, default_value_t = false)]
+        overwrite: bool,
+    },
+
+    /// Convert raster to a Zarr store
+    ToZarr {
+        path: PathBuf,
+        /// Output store (default: <input stem>.zarr next to the input)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+        /// Chunk size in pixels, as SIZE or XSIZE,YSIZE (driver default when unset)
+        #[arg(long, value_parser = parse_chunk)]
+        chunk: Option<(usize, usize)>,
+        /// Chunk compression: zlib, gzip, zstd, lz4, blosc or none
+        #[arg(long, default_value_t = ZarrCompression::Zlib)]
+        compress: ZarrCompression,
+        /// Extra Zarr creation option as KEY=VALUE, e.g. FORMAT=ZARR_V3; repeatable
+        #[arg(long = "co", value_parser = parse_key_value)]
+        creation_options: Vec<(String, String)>,
+        /// Replace an existing store
+        #[arg(short = 'w', long, default_value_t = false)]
+        overwrite: bool,
     },
 
-    /// Convert vector to GeoParquet
+    /// Convert vector to GeoParquet (or GeoPackage, FlatGeobuf or GeoJSON with --format)
     ToGpq {
         path: PathBuf,
         #[arg(short, long)]
         out: Option<PathBuf>,
+        /// Write <dir>/<input stem>.<extension> for a single file; same as --out for a directory
+        #[arg(long, conflicts_with = "out")]
+        output_dir: Option<PathBuf>,
+        /// Extra layer creation option as KEY=VALUE, e.g. SORT_BY_BBOX=YES; repeatable
+        #[arg(long = "co", value_parser = parse_key_value)]
+        creation_options: Vec<(String, String)>,
+        /// Output format: parquet, gpkg, flatgeobuf or geojson; sets the output extension
+        #[arg(long, default_value_t = VectorFormat::Parquet)]
+        format: VectorFormat,
+        /// Replace existing outputs
+        #[arg(short = 'w', long, default_value_t = false)]
+        overwrite: bool,
+        /// Write every layer to <out>/<layer_name>.<extension> plus a manifest.json
+        #[arg(long, default_value_t = false)]
+        explode: bool,
+        /// Input layer to convert, by name or zero-based index (default: the first layer)
+        #[arg(long, conflicts_with = "explode")]
+        layer: Option<String>,
+        /// Convert every layer, writing <out stem>_<layer_name>.<extension> for each
+        #[arg(long, default_value_t = false, conflicts_with_all = ["explode", "layer"])]
+        all_layers: bool,
+        /// Reopen each output and check its feature count (doubles I/O)
+        #[arg(long, default_value_t = false)]
+        verify: bool,
+        /// Only keep features of this geometry type (point, line, polygon); multi-types match too
+        #[arg(long)]
+        geometry_type_filter: Option<GeometryKind>,
+        /// Override the source CRS (e.g. EPSG:4326) for inputs with a missing or wrong .prj
+        #[arg(long)]
+        s_srs: Option<String>,
+        /// Reproject features to this CRS (EPSG code, PROJ string or WKT)
+        #[arg(long)]
+        t_srs: Option<String>,
+        /// Add an area_m2 column with each polygon's equal-area projected area in square meters
+        #[arg(long, default_value_t = false)]
+        add_area: bool,
+        /// Add a length_m column with each line's great-circle length in meters
+        #[arg(long, default_value_t = false)]
+        add_length: bool,
+        /// Wrap longitudes into -180..180 (geographic CRS only)
+        #[arg(long, default_value_t = false)]
+        normalize_longitude: bool,
+        /// Also split geometries crossing the antimeridian into multi-part geometries
+        #[arg(long, default_value_t = false, requires = "normalize_longitude")]
+        split_antimeridian: bool,
+        /// CSV column holding longitude/x (default: lon)
+        #[arg(long)]
+        x_field: Option<String>,
+        /// CSV column holding latitude/y (default: lat)
+        #[arg(long)]
+        y_field: Option<String>,
+        /// CRS of CSV coordinates (default: EPSG:4326)
+        #[arg(long)]
+        csv_crs: Option<String>,
+        /// Only convert features with FID >= this value
+        #[arg(long)]
+        fid_start: Option<u64>,
+        /// Only convert features with FID < this value
+        #[arg(long)]
+        fid_end: Option<u64>,
+        /// Only convert features matching this OGR SQL WHERE clause, e.g. "iso3 = 'KEN'"
+        #[arg(long = "where")]
+        where_clause: Option<String>,
+        /// Only convert features intersecting MINX,MINY,MAXX,MAXY, in the source CRS
+        #[arg(long, value_parser = parse_bbox, allow_hyphen_values = true)]
+        bbox: Option<[f64; 4]>,
+        /// Fail unless the output CRS carries an authority code (EPSG unless --crs-authority is given)
+        #[arg(long, default_value_t = false)]
+        require_epsg: bool,
+        /// Authority that --require-epsg accepts, e.g. ESRI
+        #[arg(long, requires = "require_epsg")]
+        crs_authority: Option<String>,
+        /// Write the EPSG code GDAL identifies for a CRS that has none
+        #[arg(long, default_value_t = false)]
+        stamp_epsg: bool,
+        /// Geometry column encoding: wkb, or geoarrow for layers of a single geometry type
+        #[arg(long, default_value_t = GeoEncoding::Wkb)]
+        geo_encoding: GeoEncoding,
+        /// Features per Parquet row group (GDAL default: 65536)
+        #[arg(long)]
+        row_group_size: Option<usize>,
+        /// Parquet compression codec (snappy, zstd, gzip, none; GDAL default: snappy)
+        #[arg(long)]
+        parquet_compression: Option<ParquetCompression>,
+        /// Flush the Parquet writer every N features to bound memory on very large layers
+        #[arg(long)]
+        batch_size: Option<usize>,
+        /// Retry a failing file this many times in batch mode
+        #[arg(long, default_value_t = 0)]
+        max_retries: u32,
+        /// Delay between retries in milliseconds
+        #[arg(long, default_value_t = 0)]
+        retry_delay_ms: u64,
+        /// Group batch failures by error type
+        #[arg(long, default_value_t = false)]
+        summarize_errors: bool,
+        /// Print a JSON progress line on stderr as each file in a batch finishes
+        #[arg(long, default_value_t = false)]
+        progress_json: bool,
+        /// Hide the progress bar and per-file progress lines
+        #[arg(long, default_value_t = false)]
+        quiet: bool,
+        /// Write a JSON report of the converted and failed files in a batch
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// Only convert the files listed as failed in an earlier --report
+        #[arg(long)]
+        resume_from: Option<PathBuf>,
+        /// Write the size and SHA-256 of every output of a batch, as JSON for a .json path and CSV otherwise
+        #[arg(long)]
+        checksums: Option<PathBuf>,
+        /// List the files a batch would convert and their output paths, without writing anything
+        #[arg(long, default_value_t = false)]
+        dry_run: bool,
+        /// Also convert files in subdirectories, keeping their layout under --out
+        #[arg(long, default_value_t = false)]
+        recursive: bool,
     },
 
     /// Get useful stats and QAQC metrics for a GeoTIFF
@@ -49,47 +443,552 @@ enum Commands {
         /// Calculate quantiles? Takes more time and memory.
         #[arg(short, long, default_value_t = false)]
         quantiles: bool,
+        /// Estimate quartiles and percentiles while streaming blocks, for bands too large to sort in memory
+        #[arg(long, default_value_t = false)]
+        approx_quantiles: bool,
+        /// Count distinct valid values per band (memory-heavy on continuous data)
+        #[arg(long, default_value_t = false)]
+        distinct: bool,
+        /// How quartiles and percentiles are picked: nearest (rank) or linear (as numpy and R)
+        #[arg(long, default_value_t = QuantileMethod::Nearest)]
+        quantile_method: QuantileMethod,
+        /// Extra percentiles (0-100) to report as p<N> columns, e.g. 1,5,95,99
+        #[arg(long, value_delimiter = ',')]
+        percentiles: Vec<f32>,
+        /// Count valid values in this many equal-width bins between each band's min and max
+        #[arg(long)]
+        histogram: Option<usize>,
+        /// Extra values to count as NoData besides each band's declared value, e.g. -9999,255
+        #[arg(long, value_delimiter = ',', allow_negative_numbers = true)]
+        extra_nodata: Vec<f64>,
+        /// Count finite values outside MIN,MAX as NoData, e.g. 0,100
+        #[arg(long, value_parser = parse_valid_range, allow_hyphen_values = true)]
+        valid_range: Option<(f64, f64)>,
         /// Percentage of files to check in a directory
         #[arg(short, long, default_value_t = 100)]
         pct_check: u8,
+        /// Seed for the --pct-check sample, so reruns check the same files
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Only compute these 1-based bands, e.g. 1-10,50
+        #[arg(long)]
+        bands: Option<BandList>,
         /// Output directory QAQC results as CSV or Parquet
         #[arg(short, long, default_value_t = OutputFormat::Csv)]
         output_format: OutputFormat,
+        /// Write the QAQC table as CSV to stdout instead of a file
+        #[arg(long, default_value_t = false)]
+        stdout: bool,
+        /// Write the QAQC table to this path (default: qaqc.csv or qaqc.parquet in the directory)
+        #[arg(long, conflicts_with = "stdout")]
+        out: Option<PathBuf>,
+        /// Build the table of a directory run N files at a time to lower peak memory on large runs
+        #[arg(long)]
+        concat_chunksize: Option<usize>,
+        /// Write a JSON rollup of pass/fail counts for a directory run to this path
+        #[arg(long)]
+        summary_json: Option<PathBuf>,
+        /// Also write each file's band stats as JSON to <input>.qaqc.json
+        #[arg(long, default_value_t = false)]
+        per_file_report: bool,
+        /// Directory for --per-file-report output instead of next to each input
+        #[arg(long, requires = "per_file_report")]
+        report_dir: Option<PathBuf>,
+        /// TOML file of pass/fail rules (`[[rules]]` tables with column, op and value); exits 1 if any band fails
+        #[arg(long)]
+        rules: Option<PathBuf>,
+        /// Read STATISTICS_* band metadata instead of scanning pixels when available
+        #[arg(long, default_value_t = false)]
+        use_embedded_stats: bool,
+        /// Scan pixels even when embedded statistics are available
+        #[arg(long, default_value_t = false)]
+        force_scan: bool,
+        /// Stats cache file (defaults to <directory>/.qaqc_cache.json for directories)
+        #[arg(long)]
+        cache: Option<PathBuf>,
+        /// Recompute every file without reading or writing a cache
+        #[arg(long, default_value_t = false, conflicts_with = "cache")]
+        no_cache: bool,
+        /// Group failed files by error type
+        #[arg(long, default_value_t = false)]
+        summarize_errors: bool,
+        /// Print a JSON progress line on stderr as each file finishes
+        #[arg(long, default_value_t = false)]
+        progress_json: bool,
+        /// Hide the progress bar and per-file progress lines
+        #[arg(long, default_value_t = false)]
+        quiet: bool,
+        /// Also write a Byte COG marking valid (1) and NoData (0) pixels (single file only)
+        #[arg(long)]
+        write_mask: Option<PathBuf>,
+        /// Exit nonzero after writing the report if any file or band is flagged
+        #[arg(long, default_value_t = false)]
+        strict: bool,
+        /// Stop a directory run at the first file that fails, without writing a table
+        #[arg(long, default_value_t = false)]
+        fail_fast: bool,
+        /// Also write the files that failed and their errors to qaqc_errors.csv (or <out stem>_errors)
+        #[arg(long, default_value_t = false)]
+        write_errors: bool,
+    },
+    /// Compute count, mean, min and max of each band within each polygon of a vector file
+    ZonalStats {
+        /// Raster to summarize
+        raster: PathBuf,
+        /// Vector file of zones; its first layer is used
+        zones: PathBuf,
+        /// Path of the statistics table; its extension is set from --output-format
+        #[arg(long)]
+        out: PathBuf,
+        /// Zone field identifying each row (default: the feature FID)
+        #[arg(long)]
+        id_field: Option<String>,
+        /// Only compute these 1-based bands, e.g. 1-10,50
+        #[arg(long)]
+        bands: Option<BandList>,
+        /// Count every pixel a zone touches, not only those whose center is inside it
+        #[arg(long, default_value_t = false)]
+        all_touched: bool,
+        /// Write the table as CSV or Parquet
+        #[arg(short, long, default_value_t = OutputFormat::Parquet)]
+        output_format: OutputFormat,
+    },
+    /// Combine qaqc.parquet tables from several runs into one table
+    MergeQAQC {
+        /// QAQC Parquet tables to merge
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+        /// Path of the merged table; its extension is set from --output-format
+        #[arg(long)]
+        out: PathBuf,
+        /// Write the merged table as CSV or Parquet
+        #[arg(short, long, default_value_t = OutputFormat::Parquet)]
+        output_format: OutputFormat,
     },
 }
 
 fn main() {
     let cli = Cli::parse();
 
+    if let Some(dir) = &cli.temp_dir
+        && let Err(e) = tempdir::set_temp_dir(dir)
+    {
+        eprintln!("Error: {}", e);
+        return;
+    }
+    if let Some(limit) = cli.max_open
+        && let Err(e) = open_limit::set_max_open(limit)
+    {
+        eprintln!("Error: {}", e);
+        return;
+    }
+    // rayon treats 0 threads as "one per core"
+    if let Some(threads) = cli.threads
+        && let Err(e) = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+    {
+        eprintln!("Error: cannot set up {} worker threads: {}", threads, e);
+        return;
+    }
+    if let Err(e) = set_config_options(&cli.config) {
+        eprintln!("Error: {}", e);
+        return;
+    }
+
     match cli.command {
-        Commands::Info { path } => match get_datainfo(&path) {
-            Ok(info) => print_datainfo(&info),
+        Commands::Info {
+            path,
+            json,
+            verbose,
+        } => match get_datainfo(&path) {
+            Ok(info) if json => {
+                if let Err(e) = print_datainfo_json(&info) {
+                    eprintln!("Error: {}", e);
+                }
+            }
+            Ok(info) => {
+                print_datainfo(&info);
+                if verbose {
+                    match get_details(&path) {
+                        Ok(details) => print_details(&details),
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+            }
+            Err(e) => eprintln!("Error: {}", e),
+        },
+
+        Commands::ExtractFeature {
+            path,
+            layer,
+            fid,
+            filter,
+        } => match extract_feature(&path, layer.as_deref(), fid, filter.as_deref()) {
+            Ok(feature) => match serde_json::to_string_pretty(&feature) {
+                Ok(out) => println!("{}", out),
+                Err(e) => eprintln!("Error: {}", e),
+            },
             Err(e) => eprintln!("Error: {}", e),
         },
 
+        Commands::Survey {
+            path,
+            out,
+            output_format,
+        } => {
+            let out = out.unwrap_or_else(|| path.join(format!("survey.{}", output_format)));
+            if let Err(e) = survey(&path).and_then(|s| write_survey(&s, &out, output_format)) {
+                eprintln!("Survey failed: {}", e);
+            }
+        }
+
+        Commands::Drivers { used, json } => {
+            let drivers = list_drivers(used);
+            if json {
+                match serde_json::to_string_pretty(&drivers) {
+                    Ok(out) => println!("{}", out),
+                    Err(e) => eprintln!("Error: {}", e),
+                }
+            } else {
+                print_drivers(&drivers);
+            }
+        }
+
         Commands::ToCog {
             path,
             out,
+            output_dir,
+            creation_options,
+            bigtiff,
             overwrite,
+            out_dtype,
+            clamp,
+            min_overview_size,
+            overview_resampling,
+            blocksize,
+            thumbnail,
+            thumbnail_stretch,
+            reference,
+            internal_mask,
+            compression,
+            compression_level,
+            predictor,
+            color_table,
+            force_rgb,
+            color_interp,
+            convert_units,
+            t_srs,
+            resampling,
+            nodata,
+            clear_nodata,
+            sparse,
+            require_epsg,
+            crs_authority,
+            stamp_epsg,
+            verify,
+            bands,
+            manifest,
+            split_bands,
+            date_from_metadata,
+            report_dtypes,
+            max_retries,
+            retry_delay_ms,
+            summarize_errors,
+            progress_json,
+            quiet,
+            report,
+            resume_from,
+            checksums,
+            dry_run,
+            recursive,
+            force,
         } => {
-            if path.is_dir() {
-                if let Err(e) = batch_convert_cog(&path, out.as_deref(), overwrite) {
+            let cog_options = CogOptions {
+                out_dtype,
+                clamp,
+                min_overview_size,
+                overview_resampling,
+                blocksize,
+                thumbnail,
+                thumbnail_stretch,
+                reference,
+                internal_mask,
+                compression,
+                compression_level,
+                predictor,
+                color_table,
+                force_rgb,
+                convert_units,
+                t_srs,
+                resampling,
+                nodata,
+                clear_nodata,
+                creation_options,
+                bigtiff,
+                sparse,
+                crs_authority: AuthorityCheck {
+                    require: require_epsg,
+                    authority: crs_authority,
+                    stamp: stamp_epsg,
+                },
+                color_interp,
+                verify,
+                bands: bands.map(|bands| bands.0),
+            };
+            let options = BatchOptions {
+                summarize_errors,
+                progress_json,
+                quiet,
+                max_retries,
+                retry_delay_ms,
+                report,
+                resume_from,
+                checksums,
+                dry_run,
+                recursive,
+                force,
+                ..Default::default()
+            };
+            if let Some(manifest) = manifest {
+                if let Err(e) = batch_convert_manifest(&manifest, overwrite, &cog_options, &options)
+                {
+                    eprintln!("Manifest COG conversion failed: {}", e);
+                }
+                return;
+            }
+            let path = path.expect("clap requires a path without --manifest");
+            let out = match resolve_output(&path, out, output_dir, split_bands) {
+                Ok(out) => out,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            };
+            if dry_run && !path.is_dir() {
+                eprintln!("--dry-run expects a directory or --manifest");
+                return;
+            }
+            if report_dtypes {
+                match batch_convert::report_dtypes(&path) {
+                    Ok(report) => print_dtype_report(&report),
+                    Err(e) => eprintln!("Data type report failed: {}", e),
+                }
+                return;
+            }
+            if split_bands {
+                if path.is_dir() {
+                    eprintln!("--split-bands expects a single raster, not a directory");
+                } else if let Err(e) = split_bands_to_cogs(
+                    &path,
+                    out.as_deref(),
+                    overwrite,
+                    &cog_options,
+                    date_from_metadata.as_deref(),
+                ) {
+                    eprintln!("Band split failed: {}", e);
+                }
+            } else if path.is_dir() {
+                if let Err(e) =
+                    batch_convert_cog(&path, out.as_deref(), overwrite, &cog_options, &options)
+                {
                     eprintln!("Batch COG conversion failed: {}", e);
                 }
             } else {
-                if let Err(e) = tif_to_cog(&path, out.as_deref(), overwrite) {
+                if let Err(e) = tif_to_cog(&path, out.as_deref(), overwrite, &cog_options) {
                     eprintln!("Single COG conversion failed: {}", e);
                 }
             }
         }
 
-        Commands::ToGpq { path, out } => {
+        Commands::Mosaic {
+            inputs,
+            out,
+            merge_strategy,
+            nodata,
+            overwrite,
+        } => {
+            let inputs = match inputs.as_slice() {
+                [dir] if dir.is_dir() => {
+                    let mut files: Vec<PathBuf> = WalkDir::new(dir)
+                        .max_depth(1)
+                        .into_iter()
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.into_path())
+                        .filter(|p| {
+                            p.extension()
+                                .and_then(|ext| ext.to_str())
+                                .is_some_and(|ext| matches!(ext, "tif" | "tiff"))
+                        })
+                        .collect();
+                    files.sort();
+                    files
+                }
+                _ => inputs,
+            };
+            if let Err(e) = mosaic_to_cog(&inputs, &out, merge_strategy, nodata, overwrite) {
+                eprintln!("Mosaic failed: {}", e);
+            }
+        }
+
+        Commands::Stack {
+            inputs,
+            out,
+            band_names_file,
+            overwrite,
+        } => {
+            let band_names = match band_names_file.as_deref().map(read_band_names) {
+                Some(Ok(names)) => Some(names),
+                Some(Err(e)) => {
+                    eprintln!("Stack failed: {}", e);
+                    return;
+                }
+                None => None,
+            };
+            if let Err(e) = stack_to_cog(&inputs, &out, band_names.as_deref(), overwrite) {
+                eprintln!("Stack failed: {}", e);
+            }
+        }
+
+        Commands::BandMath {
+            path,
+            expression,
+            out,
+            overwrite,
+        } => {
+            if let Err(e) = band_math_to_cog(&path, &out, &expression, overwrite) {
+                eprintln!("Band math failed: {}", e);
+            }
+        }
+
+        Commands::ToZarr {
+            path,
+            out,
+            chunk,
+            compress,
+            creation_options,
+            overwrite,
+        } => {
+            let options = ZarrOptions {
+                chunk,
+                compression: compress,
+                creation_options,
+            };
             if path.is_dir() {
-                if let Err(e) = batch_convert_gpq(&path, out.as_deref()) {
+                eprintln!("to-zarr expects a single raster, not a directory");
+            } else if let Err(e) = tif_to_zarr(&path, out.as_deref(), overwrite, &options) {
+                eprintln!("Zarr conversion failed: {}", e);
+            }
+        }
+
+        Commands::ToGpq {
+            path,
+            out,
+            output_dir,
+            creation_options,
+            format,
+            overwrite,
+            explode,
+            layer,
+            all_layers,
+            verify,
+            geometry_type_filter,
+            s_srs,
+            t_srs,
+            add_area,
+            add_length,
+            normalize_longitude,
+            split_antimeridian,
+            x_field,
+            y_field,
+            csv_crs,
+            fid_start,
+            fid_end,
+            where_clause,
+            bbox,
+            require_epsg,
+            crs_authority,
+            stamp_epsg,
+            geo_encoding,
+            row_group_size,
+            parquet_compression,
+            batch_size,
+            max_retries,
+            retry_delay_ms,
+            summarize_errors,
+            progress_json,
+            quiet,
+            report,
+            resume_from,
+            checksums,
+            dry_run,
+            recursive,
+        } => {
+            let gpq_options = GpqOptions {
+                verify,
+                overwrite,
+                geometry_type: geometry_type_filter,
+                s_srs,
+                t_srs,
+                normalize_longitude,
+                split_antimeridian,
+                x_field,
+                y_field,
+                csv_crs,
+                fid_start,
+                fid_end,
+                where_clause,
+                bbox,
+                crs_authority: AuthorityCheck {
+                    require: require_epsg,
+                    authority: crs_authority,
+                    stamp: stamp_epsg,
+                },
+                geo_encoding,
+                row_group_size,
+                compression: parquet_compression,
+                batch_size,
+                layer,
+                all_layers,
+                format,
+                creation_options,
+                add_area,
+                add_length,
+            };
+            let out = match resolve_output(&path, out, output_dir, explode) {
+                Ok(out) => out,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    return;
+                }
+            };
+            if dry_run && !path.is_dir() {
+                eprintln!("--dry-run expects a directory");
+            } else if explode {
+                if path.is_dir() {
+                    eprintln!("--explode expects a single vector file, not a directory");
+                } else if let Err(e) = explode_to_geoparquet(&path, out.as_deref(), &gpq_options) {
+                    eprintln!("GPQ explode failed: {}", e);
+                }
+            } else if path.is_dir() {
+                let options = BatchOptions {
+                    summarize_errors,
+                    progress_json,
+                    quiet,
+                    max_retries,
+                    retry_delay_ms,
+                    report,
+                    resume_from,
+                    checksums,
+                    dry_run,
+                    recursive,
+                    ..Default::default()
+                };
+                if let Err(e) = batch_convert_gpq(&path, out.as_deref(), &gpq_options, &options) {
                     eprintln!("Batch GPQ conversion failed: {}", e);
                 }
             } else {
-                if let Err(e) = vector_to_geoparquet(&path, out.as_deref()) {
+                if let Err(e) = vector_convert(&path, out.as_deref(), &gpq_options) {
                     eprintln!("Single GPQ conversion failed: {}", e);
                 }
             }
@@ -97,18 +996,147 @@ fn main() {
         Commands::RunQAQC {
             path,
             pct_check,
+            seed,
+            bands,
             output_format,
             quantiles,
+            approx_quantiles,
+            distinct,
+            quantile_method,
+            percentiles,
+            histogram,
+            extra_nodata,
+            valid_range,
+            stdout,
+            out,
+            concat_chunksize,
+            summary_json,
+            per_file_report,
+            report_dir,
+            rules,
+            use_embedded_stats,
+            force_scan,
+            cache,
+            no_cache,
+            summarize_errors,
+            progress_json,
+            quiet,
+            write_mask,
+            strict,
+            fail_fast,
+            write_errors,
         } => {
-            if path.is_dir() {
-                if let Err(e) = batch_qaqc(&path, pct_check as f32, quantiles, output_format) {
+            let cache = match (no_cache, cache) {
+                (true, _) => None,
+                (false, Some(cache)) => Some(cache),
+                (false, None) if path.is_dir() => Some(path.join(".qaqc_cache.json")),
+                (false, None) => None,
+            };
+            let options = QaqcOptions {
+                quantiles,
+                approx_quantiles,
+                distinct,
+                use_embedded_stats,
+                force_scan,
+                cache,
+                quantile_method,
+                percentiles,
+                histogram,
+                extra_nodata,
+                valid_range,
+                seed,
+                bands: bands.map(|bands| bands.0),
+            };
+            let rules = match rules.as_deref().map(qaqc_rules::load_rules).transpose() {
+                Ok(rules) => rules.unwrap_or_default(),
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            // Rules and --strict gate releases, so their failures on a single file set the exit
+            // status; a failed batch always does
+            let gating = strict || !rules.is_empty();
+            let output = QaqcOutput {
+                format: output_format,
+                to_stdout: stdout,
+                out,
+                concat_chunksize,
+                summary_json,
+                strict,
+                write_mask: write_mask.clone(),
+                per_file_report,
+                report_dir,
+                rules,
+                write_errors,
+            };
+            if path.is_dir() && write_mask.is_some() {
+                eprintln!("Error: --write-mask expects a single raster, not a directory");
+                std::process::exit(1);
+            } else if path.is_dir() {
+                let batch_options = BatchOptions {
+                    summarize_errors,
+                    progress_json,
+                    quiet,
+                    fail_fast,
+                    ..Default::default()
+                };
+                if let Err(e) =
+                    batch_qaqc(&path, pct_check as f32, &output, &batch_options, &options)
+                {
                     eprintln!("Batch QAQC failed: {}", e);
+                    std::process::exit(1);
                 }
             } else {
-                if let Err(e) = single_qaqc(&path, quantiles) {
+                if let Err(e) = single_qaqc(&path, &output, &options) {
                     eprintln!("Single QAQC failed: {}", e);
+                    if gating {
+                        std::process::exit(1);
+                    }
                 }
             }
         }
+
+        Commands::ZonalStats {
+            raster,
+            zones,
+            out,
+            id_field,
+            bands,
+            all_touched,
+            output_format,
+        } => {
+            let options = ZonalOptions {
+                id_field,
+                bands: bands.map(|bands| bands.0),
+                all_touched,
+            };
+            let result = zonal_stats(&raster, &zones, &options)
+                .and_then(|stats| write_zonal_stats(&stats, &options, &out, output_format));
+            match result {
+                Ok(path) => eprintln!("Wrote zonal statistics to: {}", path.display()),
+                Err(e) => {
+                    eprintln!("Zonal statistics failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        Commands::MergeQAQC {
+            inputs,
+            out,
+            output_format,
+        } => match merge_qaqc(&inputs, &out, output_format) {
+            Ok((path, rows)) => eprintln!(
+                "Merged {} tables ({} rows) into {}",
+                inputs.len(),
+                rows,
+                path.display()
+            ),
+            Err(e) => {
+                eprintln!("Merging QAQC tables failed: {}", e);
+                std::process::exit(1);
+            }
+        },
     }
 }
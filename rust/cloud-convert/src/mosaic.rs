@@ -0,0 +1,410 @@
+use crate::open_limit;
+use crate::progress::status;
+use crate::tempdir::{PARTIAL_TIF, TempFile, move_into_place};
+use crate::tif2cog::create_with_type;
+use gdal::programs::raster::{BuildVRTOptions, build_vrt};
+use gdal::raster::{Buffer, GdalDataType, RasterCreationOptions};
+use gdal::{Dataset, DriverManager, Metadata};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How overlapping pixels are resolved when mosaicking
+///
+/// `First` and `Last` only reorder the sources of a VRT and stream straight into the
+/// COG, so they are the fastest. `Min`, `Max` and `Mean` need a reduction pass that
+/// holds one band of the full mosaic grid in memory per accumulator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    First,
+    Last,
+    Min,
+    Max,
+    Mean,
+}
+
+impl FromStr for MergeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "first" => Ok(MergeStrategy::First),
+            "last" => Ok(MergeStrategy::Last),
+            "min" => Ok(MergeStrategy::Min),
+            "max" => Ok(MergeStrategy::Max),
+            "mean" => Ok(MergeStrategy::Mean),
+            other => Err(format!(
+                "Unsupported merge strategy '{}'. Use first, last, min, max or mean.",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for MergeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            MergeStrategy::First => "first",
+            MergeStrategy::Last => "last",
+            MergeStrategy::Min => "min",
+            MergeStrategy::Max => "max",
+            MergeStrategy::Mean => "mean",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+fn vrt_options(args: Vec<String>) -> Result<Option<BuildVRTOptions>, String> {
+    if args.is_empty() {
+        return Ok(None);
+    }
+    BuildVRTOptions::new(args)
+        .map(Some)
+        .map_err(|e| format!("Invalid VRT options: {:?}", e))
+}
+
+/// Combine the overlapping pixels of every input onto the union grid of `mosaic`
+///
+/// Pixels no input covers are set to `out_nodata`.
+fn reduce_overlaps(
+    datasets: &[Dataset],
+    mosaic: &Dataset,
+    strategy: MergeStrategy,
+    out_nodata: f64,
+) -> Result<Dataset, String> {
+    let (cols, rows) = mosaic.raster_size();
+    let transform = mosaic
+        .geo_transform()
+        .map_err(|e| format!("Failed to read mosaic geotransform: {:?}", e))?;
+    let (min_x, max_y) = (transform[0], transform[3]);
+    let (max_x, min_y) = (
+        min_x + cols as f64 * transform[1],
+        max_y + rows as f64 * transform[5],
+    );
+
+    // Align each input to the mosaic grid so pixels line up one-to-one. Areas an input does
+    // not cover read as NoData rather than 0, so they are not counted as valid pixels
+    let grid_args = vec![
+        "-vrtnodata".to_string(),
+        out_nodata.to_string(),
+        "-te".to_string(),
+        min_x.to_string(),
+        min_y.to_string(),
+        max_x.to_string(),
+        max_y.to_string(),
+        "-tr".to_string(),
+        transform[1].to_string(),
+        transform[5].abs().to_string(),
+    ];
+    let aligned = datasets
+        .iter()
+        .map(|ds| {
+            build_vrt(None, &[ds], vrt_options(grid_args.clone())?)
+                .map_err(|e| format!("Failed to align input to mosaic grid: {:?}", e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    let out_type = mosaic_type(mosaic, strategy)?;
+
+    let mem_driver = DriverManager::get_driver_by_name("MEM")
+        .map_err(|e| format!("Failed to get MEM driver: {:?}", e))?;
+    let mut output = create_with_type(
+        &mem_driver,
+        Path::new(""),
+        (cols, rows),
+        mosaic.raster_count(),
+        out_type,
+    )
+    .map_err(|e| format!("Failed to create mosaic dataset: {:?}", e))?;
+    output
+        .set_geo_transform(&transform)
+        .map_err(|e| format!("Failed to set mosaic geotransform: {:?}", e))?;
+    if let Ok(srs) = mosaic.spatial_ref() {
+        output
+            .set_spatial_ref(&srs)
+            .map_err(|e| format!("Failed to set mosaic CRS: {:?}", e))?;
+    }
+
+    for band_index in 1..=mosaic.raster_count() {
+        let mut acc = vec![f64::NAN; cols * rows];
+        let mut counts = vec![0u32; cols * rows];
+
+        for ds in &aligned {
+            let band = ds
+                .rasterband(band_index)
+                .map_err(|e| format!("Failed to read band {}: {:?}", band_index, e))?;
+            let nodata = band.no_data_value();
+            let buf: Buffer<f64> = band
+                .read_band_as()
+                .map_err(|e| format!("Failed to read band {}: {:?}", band_index, e))?;
+
+            for (i, &val) in buf.data().iter().enumerate() {
+                if !val.is_finite() || nodata.is_some_and(|nd| val == nd) {
+                    continue;
+                }
+                acc[i] = if counts[i] == 0 {
+                    val
+                } else {
+                    match strategy {
+                        MergeStrategy::Min => acc[i].min(val),
+                        MergeStrategy::Max => acc[i].max(val),
+                        _ => acc[i] + val,
+                    }
+                };
+                counts[i] += 1;
+            }
+        }
+
+        let data: Vec<f64> = acc
+            .iter()
+            .zip(&counts)
+            .map(|(&val, &count)| match (count, strategy) {
+                (0, _) => out_nodata,
+                (n, MergeStrategy::Mean) => val / n as f64,
+                _ => val,
+            })
+            .collect();
+
+        let mut band = output
+            .rasterband(band_index)
+            .map_err(|e| format!("Failed to read band {}: {:?}", band_index, e))?;
+        band.set_no_data_value(Some(out_nodata))
+            .map_err(|e| format!("Failed to set NoData: {:?}", e))?;
+        let mut buf = Buffer::new((cols, rows), data);
+        band.write((0, 0), (cols, rows), &mut buf)
+            .map_err(|e| format!("Failed to write band {}: {:?}", band_index, e))?;
+    }
+
+    Ok(output)
+}
+
+/// Data type of the mosaic `strategy` writes: the input type, or a float type for `mean`
+fn mosaic_type(mosaic: &Dataset, strategy: MergeStrategy) -> Result<GdalDataType, String> {
+    let band_type = mosaic
+        .rasterband(1)
+        .map_err(|e| format!("Failed to read band 1: {:?}", e))?
+        .band_type();
+    Ok(match strategy {
+        MergeStrategy::Mean if band_type != GdalDataType::Float64 => GdalDataType::Float32,
+        _ => band_type,
+    })
+}
+
+/// NoData of the mosaic: `nodata`, else that of the first input, else NaN for float outputs
+///
+/// Integer outputs have no NaN, and without a NoData value the gaps between inputs would be
+/// written as 0 and read back as data, so they need one.
+fn mosaic_nodata(
+    nodata: Option<f64>,
+    first: &Dataset,
+    out_type: GdalDataType,
+) -> Result<f64, String> {
+    let source = first
+        .rasterband(1)
+        .map_err(|e| format!("Failed to read band 1: {:?}", e))?
+        .no_data_value();
+    match nodata.or(source) {
+        Some(nodata) => Ok(nodata),
+        None if out_type.is_floating() => Ok(f64::NAN),
+        None => Err(format!(
+            "The inputs are {} with no NoData value, so gaps between them would read as 0; pass --nodata",
+            out_type.name()
+        )),
+    }
+}
+
+/// Mosaic several rasters into a single COG
+///
+/// # Arguments
+/// * `inputs` - Rasters to merge, in priority order for the `first`/`last` strategies
+/// * `output_path` - Path of the COG to write
+/// * `strategy` - How overlapping pixels are combined
+/// * `nodata` - NoData of the output, filling the areas no input covers; see [`mosaic_nodata`]
+///   for the default
+/// * `overwrite` - Replace `output_path` if it already exists
+pub fn mosaic_to_cog(
+    inputs: &[PathBuf],
+    output_path: &Path,
+    strategy: MergeStrategy,
+    nodata: Option<f64>,
+    overwrite: bool,
+) -> Result<String, String> {
+    if inputs.len() < 2 {
+        return Err("Mosaic needs at least two input rasters".to_string());
+    }
+    if output_path.exists() && !overwrite {
+        return Err(format!(
+            "Error: The file {:?} already exists and overwrite is false.",
+            output_path
+        ));
+    }
+
+    let (_permits, mut datasets): (Vec<_>, Vec<_>) = inputs
+        .iter()
+        .map(|path| {
+            open_limit::open(path)
+                .map_err(|e| format!("Failed to open dataset {}: {:?}", path.display(), e))
+        })
+        .collect::<Result<Vec<_>, String>>()?
+        .into_iter()
+        .unzip();
+
+    let band_count = datasets[0].raster_count();
+    if let Some((path, _)) = inputs
+        .iter()
+        .zip(&datasets)
+        .find(|(_, ds)| ds.raster_count() != band_count)
+    {
+        return Err(format!(
+            "All inputs must have {} bands, {} does not",
+            band_count,
+            path.display()
+        ));
+    }
+
+    let out_type = mosaic_type(&datasets[0], strategy)?;
+    let out_nodata = mosaic_nodata(nodata, &datasets[0], out_type)?;
+
+    // VRT sources are drawn in order, so the last dataset wins where tiles overlap
+    if strategy == MergeStrategy::First {
+        datasets.reverse();
+    }
+    let mosaic = build_vrt(
+        None,
+        &datasets,
+        vrt_options(vec!["-vrtnodata".to_string(), out_nodata.to_string()])?,
+    )
+    .map_err(|e| format!("Failed to build mosaic VRT: {:?}", e))?;
+
+    let result = match strategy {
+        MergeStrategy::First | MergeStrategy::Last => mosaic,
+        _ => reduce_overlaps(&datasets, &mosaic, strategy, out_nodata)?,
+    };
+
+    let driver = DriverManager::get_driver_by_name("COG")
+        .map_err(|e| format!("Failed to get COG driver: {:?}", e))?;
+    let partial_path = TempFile::new(output_path, PARTIAL_TIF);
+    result
+        .create_copy(
+            &driver,
+            partial_path.path(),
+            &RasterCreationOptions::from_iter(["COMPRESS=LZW"]),
+        )
+        .map_err(|e| format!("Failed to create COG: {:?}", e))?;
+    move_into_place(partial_path.path(), output_path)?;
+
+    status!(
+        "Mosaicked {} rasters ({} strategy) to: {:?}",
+        inputs.len(),
+        strategy,
+        output_path
+    );
+    Ok(output_path.display().to_string())
+}
+
+/// Read one band name per line from a sidecar file, ignoring blank lines
+pub fn read_band_names(path: &Path) -> Result<Vec<String>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read band names {}: {}", path.display(), e))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Stack rasters into one multi-band COG, with every band of each input in turn
+///
+/// # Arguments
+/// * `inputs` - Rasters to stack, in output band order
+/// * `output_path` - Path of the COG to write
+/// * `band_names` - Optional band descriptions, one per output band; file stems are used
+///   otherwise, suffixed with the band number for multi-band inputs
+/// * `overwrite` - Replace `output_path` if it already exists
+pub fn stack_to_cog(
+    inputs: &[PathBuf],
+    output_path: &Path,
+    band_names: Option<&[String]>,
+    overwrite: bool,
+) -> Result<String, String> {
+    if inputs.is_empty() {
+        return Err("Stack needs at least one input raster".to_string());
+    }
+    if output_path.exists() && !overwrite {
+        return Err(format!(
+            "Error: The file {:?} already exists and overwrite is false.",
+            output_path
+        ));
+    }
+
+    let (_permits, datasets): (Vec<_>, Vec<_>) = inputs
+        .iter()
+        .map(|path| {
+            open_limit::open(path)
+                .map_err(|e| format!("Failed to open dataset {}: {:?}", path.display(), e))
+        })
+        .collect::<Result<Vec<_>, String>>()?
+        .into_iter()
+        .unzip();
+
+    // -separate takes every band of each input, so names are counted against all of them
+    let band_count: usize = datasets.iter().map(|ds| ds.raster_count()).sum();
+    let names = match band_names {
+        Some(names) if names.len() != band_count => {
+            return Err(format!(
+                "Got {} band names for {} bands in {} input rasters",
+                names.len(),
+                band_count,
+                inputs.len()
+            ));
+        }
+        Some(names) => names.to_vec(),
+        None => inputs
+            .iter()
+            .zip(&datasets)
+            .flat_map(|(path, ds)| {
+                let stem = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let count = ds.raster_count();
+                (1..=count).map(move |band| match count {
+                    1 => stem.clone(),
+                    _ => format!("{}_{}", stem, band),
+                })
+            })
+            .collect(),
+    };
+
+    let stack = build_vrt(None, &datasets, vrt_options(vec!["-separate".to_string()])?)
+        .map_err(|e| format!("Failed to build stack VRT: {:?}", e))?;
+
+    for (index, name) in names.iter().enumerate() {
+        stack
+            .rasterband(index + 1)
+            .and_then(|mut band| band.set_description(name))
+            .map_err(|e| format!("Failed to set band {} description: {:?}", index + 1, e))?;
+    }
+
+    let driver = DriverManager::get_driver_by_name("COG")
+        .map_err(|e| format!("Failed to get COG driver: {:?}", e))?;
+    let partial_path = TempFile::new(output_path, PARTIAL_TIF);
+    stack
+        .create_copy(
+            &driver,
+            partial_path.path(),
+            &RasterCreationOptions::from_iter(["COMPRESS=LZW"]),
+        )
+        .map_err(|e| format!("Failed to create COG: {:?}", e))?;
+    move_into_place(partial_path.path(), output_path)?;
+
+    status!(
+        "Stacked {} bands from {} rasters to: {:?}",
+        band_count,
+        inputs.len(),
+        output_path
+    );
+    Ok(output_path.display().to_string())
+}
@@ -0,0 +1,110 @@
+use gdal::{Dataset, DatasetOptions};
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Condvar, Mutex, OnceLock};
+
+static MAX_OPEN: OnceLock<usize> = OnceLock::new();
+static IN_USE: Mutex<usize> = Mutex::new(0);
+static RELEASED: Condvar = Condvar::new();
+
+thread_local! {
+    /// Permits held by this thread; only the first one counts against the limit
+    static HELD: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Limit how many threads may hold open datasets at once for the rest of the process
+pub fn set_max_open(limit: usize) -> Result<(), String> {
+    if limit == 0 {
+        return Err("--max-open must be at least 1".to_string());
+    }
+    MAX_OPEN
+        .set(limit)
+        .map_err(|_| "Open dataset limit is already set".to_string())
+}
+
+/// The configured limit, or one derived from the process's open file limit
+pub fn max_open() -> usize {
+    *MAX_OPEN.get_or_init(default_max_open)
+}
+
+/// A slice of the soft `RLIMIT_NOFILE`, leaving room for the several descriptors one
+/// dataset can hold (overviews, sidecars, VRT sources) and for GDAL's own caches
+fn default_max_open() -> usize {
+    soft_file_limit().map_or(64, |soft| (soft / 8).clamp(4, 1024))
+}
+
+/// The soft open file limit, read from `/proc/self/limits`
+#[cfg(target_os = "linux")]
+fn soft_file_limit() -> Option<usize> {
+    std::fs::read_to_string("/proc/self/limits")
+        .ok()?
+        .lines()
+        .find(|line| line.starts_with("Max open files"))?
+        .split_whitespace()
+        .nth(3)?
+        .parse()
+        .ok()
+}
+
+/// Other platforms have no `/proc`, so the fixed default applies
+#[cfg(not(target_os = "linux"))]
+fn soft_file_limit() -> Option<usize> {
+    None
+}
+
+/// Held while a thread has datasets open; dropping the last one on a thread frees its slot
+pub struct OpenPermit {
+    // Permits are tracked per thread, so they must be dropped where they were acquired
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Wait until fewer than [`max_open`] threads hold datasets, then take a permit
+///
+/// [`open`] and [`open_ex`] take one for each dataset; code that opens datasets some other
+/// way acquires before opening and keeps the permit alive at least as long as the dataset.
+/// A thread that already holds a permit gets another one immediately, so a conversion
+/// opening its source, reference and output cannot deadlock against itself.
+pub fn acquire() -> OpenPermit {
+    if HELD.get() == 0 {
+        let mut in_use = IN_USE.lock().unwrap_or_else(|e| e.into_inner());
+        while *in_use >= max_open() {
+            in_use = RELEASED.wait(in_use).unwrap_or_else(|e| e.into_inner());
+        }
+        *in_use += 1;
+    }
+    HELD.set(HELD.get() + 1);
+    OpenPermit {
+        _not_send: PhantomData,
+    }
+}
+
+/// [`Dataset::open`] under a permit, for every dataset the crate reads
+///
+/// Bind both parts, e.g. `let (_permit, dataset) = open_limit::open(path)?;`: the dataset is
+/// then dropped before its permit. A bare `_` would release the permit straight away.
+pub fn open<P: AsRef<Path>>(path: P) -> gdal::errors::Result<(OpenPermit, Dataset)> {
+    let permit = acquire();
+    Ok((permit, Dataset::open(path)?))
+}
+
+/// [`Dataset::open_ex`] under a permit, like [`open`]
+pub fn open_ex<P: AsRef<Path>>(
+    path: P,
+    options: DatasetOptions,
+) -> gdal::errors::Result<(OpenPermit, Dataset)> {
+    let permit = acquire();
+    Ok((permit, Dataset::open_ex(path, options)?))
+}
+
+impl Drop for OpenPermit {
+    fn drop(&mut self) {
+        let held = HELD.get() - 1;
+        HELD.set(held);
+        if held == 0 {
+            let mut in_use = IN_USE.lock().unwrap_or_else(|e| e.into_inner());
+            *in_use -= 1;
+            RELEASED.notify_one();
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use indicatif::ProgressBar;
+use std::sync::Mutex;
+
+/// Progress bar of the running batch, if one is drawn
+static ACTIVE: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Route [`status!`] lines above `bar` until it is replaced or cleared with `None`
+pub fn set_active(bar: Option<ProgressBar>) {
+    *ACTIVE.lock().unwrap_or_else(|e| e.into_inner()) = bar;
+}
+
+/// Print `text` on stderr, above the active progress bar when one is drawn
+pub fn print_line(text: &str) {
+    let bar = ACTIVE.lock().unwrap_or_else(|e| e.into_inner()).clone();
+    match bar {
+        Some(bar) if !bar.is_hidden() => bar.println(text),
+        _ => eprintln!("{}", text),
+    }
+}
+
+/// Like `eprintln!`, but printed above the progress bar of a running batch instead of
+/// through it, for messages from per-file conversions
+macro_rules! status {
+    ($($arg:tt)*) => {
+        $crate::progress::print_line(&format!($($arg)*))
+    };
+}
+pub(crate) use status;
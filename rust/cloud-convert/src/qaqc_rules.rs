@@ -0,0 +1,226 @@
+use crate::rast_qaqc::band_labels;
+use anyhow::{Context, Result, anyhow};
+use polars::prelude::*;
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Comparison operator of a [`Rule`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Comparison {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+impl Comparison {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Lt => "<",
+            Comparison::Le => "<=",
+            Comparison::Gt => ">",
+            Comparison::Ge => ">=",
+            Comparison::Eq => "==",
+            Comparison::Ne => "!=",
+        }
+    }
+
+    fn holds(&self, left: f64, right: f64) -> bool {
+        match self {
+            Comparison::Lt => left < right,
+            Comparison::Le => left <= right,
+            Comparison::Gt => left > right,
+            Comparison::Ge => left >= right,
+            Comparison::Eq => left == right,
+            Comparison::Ne => left != right,
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "<" => Ok(Comparison::Lt),
+            "<=" => Ok(Comparison::Le),
+            ">" => Ok(Comparison::Gt),
+            ">=" => Ok(Comparison::Ge),
+            "==" => Ok(Comparison::Eq),
+            "!=" => Ok(Comparison::Ne),
+            other => Err(format!(
+                "Unsupported comparison '{}'. Use <, <=, >, >=, == or !=.",
+                other
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Comparison {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl std::fmt::Display for Comparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// An acceptable range for one QAQC column, e.g. `percent_valid >= 95`
+///
+/// In a rules file each rule is a `[[rules]]` table with `column`, `op` and `value` keys.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Numeric column of the QAQC table, e.g. `min`, `mean` or `p99`
+    pub column: String,
+    #[serde(rename = "op")]
+    pub comparison: Comparison,
+    pub value: f64,
+}
+
+/// Layout of a TOML rules file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rules: Vec<Rule>,
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "Invalid rule '{}'. Use <column> <op> <value> with op one of < <= > >= == !=, e.g. percent_valid >= 95.",
+                s.trim()
+            )
+        };
+        let start = s.find(['<', '>', '=', '!']).ok_or_else(invalid)?;
+        let rest = &s[start..];
+        let (comparison, len) = match (rest.as_bytes()[0], rest.as_bytes().get(1)) {
+            (b'<', Some(b'=')) => (Comparison::Le, 2),
+            (b'>', Some(b'=')) => (Comparison::Ge, 2),
+            (b'=', Some(b'=')) => (Comparison::Eq, 2),
+            (b'!', Some(b'=')) => (Comparison::Ne, 2),
+            (b'<', _) => (Comparison::Lt, 1),
+            (b'>', _) => (Comparison::Gt, 1),
+            _ => return Err(invalid()),
+        };
+        let column = s[..start].trim();
+        let value = rest[len..].trim().parse::<f64>().map_err(|_| invalid())?;
+        if column.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Rule {
+            column: column.to_string(),
+            comparison,
+            value,
+        })
+    }
+}
+
+impl std::fmt::Display for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.column, self.comparison, self.value)
+    }
+}
+
+/// Read the `[[rules]]` tables of a TOML rules file
+///
+/// ```toml
+/// [[rules]]
+/// column = "percent_valid"
+/// op = ">="
+/// value = 95
+/// ```
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Cannot read rules file {}", path.display()))?;
+    let rules = toml::from_str::<RulesFile>(&text)
+        .map_err(|e| anyhow!("Invalid rules file {}: {}", path.display(), e))?
+        .rules;
+    if rules.is_empty() {
+        return Err(anyhow!("Rules file {} has no rules", path.display()));
+    }
+    Ok(rules)
+}
+
+/// Check every band (row) of a QAQC table against `rules`, returning the broken rules per row
+///
+/// A missing or NaN value breaks the rule. Fails if a rule names a column the table lacks.
+pub fn evaluate_rules(df: &DataFrame, rules: &[Rule]) -> Result<Vec<Vec<String>>> {
+    let mut flags = vec![Vec::new(); df.height()];
+    for rule in rules {
+        let column = df.column(&rule.column).map_err(|_| {
+            anyhow!(
+                "Rule '{}' names column '{}', which is not in the QAQC table",
+                rule,
+                rule.column
+            )
+        })?;
+        let values = column
+            .cast(&DataType::Float64)
+            .with_context(|| format!("Rule '{}' needs a numeric column", rule))?;
+        for (row, value) in values.f64()?.into_iter().enumerate() {
+            match value {
+                Some(value) if rule.comparison.holds(value, rule.value) => {}
+                Some(value) if !value.is_nan() => flags[row].push(format!(
+                    "{} is {}, needs {} {}",
+                    rule.column, value, rule.comparison, rule.value
+                )),
+                _ => flags[row].push(format!(
+                    "{} is missing, needs {} {}",
+                    rule.column, rule.comparison, rule.value
+                )),
+            }
+        }
+    }
+    Ok(flags)
+}
+
+/// Add `pass` and `flags` columns to a QAQC table, returning one message per failing band
+///
+/// `flags` joins the broken rules with "; " so the table can still be written as CSV. Nothing
+/// is added when `rules` is empty.
+pub fn apply_rules(df: &mut DataFrame, rules: &[Rule]) -> Result<Vec<String>> {
+    if rules.is_empty() {
+        return Ok(Vec::new());
+    }
+    let flags = evaluate_rules(df, rules)?;
+    let labels = band_labels(df)?;
+    let failures = labels
+        .iter()
+        .zip(&flags)
+        .filter(|(_, flags)| !flags.is_empty())
+        .map(|(label, flags)| format!("{}: {}", label, flags.join("; ")))
+        .collect();
+
+    let pass: Vec<bool> = flags.iter().map(Vec::is_empty).collect();
+    let joined: Vec<String> = flags.iter().map(|flags| flags.join("; ")).collect();
+    df.with_column(Column::new("pass".into(), pass))?;
+    df.with_column(Column::new("flags".into(), joined))?;
+    Ok(failures)
+}
+
+/// Print the bands that broke a rule and turn them into an error when there are any
+pub fn fail_on_rule_failures(failures: &[String]) -> Result<()> {
+    if failures.is_empty() {
+        return Ok(());
+    }
+    eprintln!("Rules: {} bands failed", failures.len());
+    for failure in failures {
+        eprintln!("  {}", failure);
+    }
+    Err(anyhow!("{} bands failed the QAQC rules", failures.len()))
+}
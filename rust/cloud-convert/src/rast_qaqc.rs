@@ -1,26 +1,41 @@
+use crate::batch_convert::{BatchOptions, ErrorCategory, ProgressReporter, print_error_summary};
+use crate::open_limit;
+use crate::progress::status;
+use crate::qaqc_rules::{Rule, apply_rules, fail_on_rule_failures};
+use crate::stats_cache::{StatsCache, file_hash};
+use crate::tdigest::TDigest;
+use crate::tempdir::{TempFile, VALIDITY_TIF};
+use crate::tif2cog::{check_band_list, create_with_type};
+use crate::vsi::{is_vsi, to_vsi};
 use anyhow::{Error, Result, anyhow};
-use gdal::Dataset;
 use gdal::Metadata;
-use gdal::raster::{Buffer, GdalDataType, RasterBand};
+use gdal::raster::{Buffer, GdalDataType, RasterBand, RasterCreationOptions};
+use gdal::{Dataset, DriverManager};
 use num_traits::{Float, FromPrimitive, ToPrimitive};
 use polars::prelude::*;
-use rand::rng;
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::{SeedableRng, rng};
 use rayon::prelude::*;
-use std::fs::File;
-use std::ops::AddAssign;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
 use std::path::Path;
 use std::path::PathBuf;
 use std::str::FromStr;
-use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use walkdir::WalkDir;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RasterStats {
+    /// 1-based index of the band in its raster
+    #[serde(default)]
+    pub band: usize,
     pub name: String,
     pub dtype: String,
     pub mean: f64,
+    /// Sum of the valid values, e.g. total population; NaN for embedded statistics
+    pub sum: f64,
     pub min: f64,
     pub max: f64,
     pub variance: f64,
@@ -29,10 +44,25 @@ pub struct RasterStats {
     pub valid_count: u64,
     pub nodata_count: u64,
     pub nan_count: u64,
+    /// Valid pixels that are exactly zero; still included in the moments
+    pub zero_count: u64,
+    /// Which NoData rule(s) excluded pixels, e.g. "declared -9999" or "none"
+    pub effective_nodata: String,
     pub percent_valid: f64,
     pub q1: Option<f32>,
     pub median: Option<f32>,
     pub q3: Option<f32>,
+    /// `(percentile, value)` pairs for the `--percentiles` requested, percentiles in 0–100
+    #[serde(default)]
+    pub percentiles: Vec<(f32, f32)>,
+    /// Counts of valid values in `--histogram` equal-width bins spanning `min` to `max`
+    #[serde(default)]
+    pub histogram: Option<Vec<u64>>,
+    /// Number of distinct valid values, with `--distinct`
+    #[serde(default)]
+    pub distinct_count: Option<u64>,
+    /// Where the statistics came from: "scan" or "embedded" band metadata
+    pub stats_source: String,
 }
 impl RasterStats {
     /// Pretty print a single RasterStats to stdout
@@ -44,9 +74,16 @@ impl RasterStats {
     pub fn format_pretty(&self) -> String {
         let mut output = String::new();
 
-        output.push_str(&format!("┌─ Band: {} ({})\n", self.name, self.dtype));
+        output.push_str(&format!(
+            "┌─ Band {}: {} ({})\n",
+            self.band, self.name, self.dtype
+        ));
+        if self.stats_source != "scan" {
+            output.push_str(&format!("├─ Source:   {}\n", self.stats_source));
+        }
         output.push_str(&format!("├─ Statistics:\n"));
         output.push_str(&format!("│  • Mean:     {:>12.6}\n", self.mean));
+        output.push_str(&format!("│  • Sum:      {:>12.6}\n", self.sum));
         output.push_str(&format!("│  • Min:      {:>12.6}\n", self.min));
         output.push_str(&format!("│  • Max:      {:>12.6}\n", self.max));
         output.push_str(&format!("│  • Std Dev:  {:>12.6}\n", self.stdev));
@@ -60,14 +97,34 @@ impl RasterStats {
             output.push_str(&format!("│  • Median:   {:>12.6}\n", median));
             output.push_str(&format!("│  • Q3:       {:>12.6}\n", q3));
         }
+        if let Some(counts) = &self.histogram {
+            output.push_str(&format!(
+                "├─ Histogram ({} bins): {:?}\n",
+                counts.len(),
+                counts
+            ));
+        }
+        if !self.percentiles.is_empty() {
+            output.push_str("├─ Percentiles:\n");
+            for (p, value) in &self.percentiles {
+                output.push_str(&format!("│  • {:<9} {:>12.6}\n", format!("P{}:", p), value));
+            }
+        }
 
         output.push_str(&format!("└─ Data Info:\n"));
         output.push_str(&format!(
             "   • Valid:    {:>12} ({:>5.1}%)\n",
             self.valid_count, self.percent_valid
         ));
-        output.push_str(&format!("   • NoData:   {:>12}\n", self.nodata_count));
+        output.push_str(&format!(
+            "   • NoData:   {:>12} ({})\n",
+            self.nodata_count, self.effective_nodata
+        ));
         output.push_str(&format!("   • NaN:      {:>12}\n", self.nan_count));
+        output.push_str(&format!("   • Zero:     {:>12}\n", self.zero_count));
+        if let Some(distinct) = self.distinct_count {
+            output.push_str(&format!("   • Distinct: {:>12}\n", distinct));
+        }
 
         output
     }
@@ -90,21 +147,376 @@ pub fn print_all_bands(stats: &[RasterStats]) {
     }
 }
 
-fn percentile<T: Float + ToPrimitive>(sorted: &[T], p: f32) -> f32 {
+/// How quantiles fall between two sorted values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuantileMethod {
+    /// The value at the nearest rank
+    #[default]
+    Nearest,
+    /// Linear interpolation between the two bracketing values, as numpy and R (type 7) do
+    Linear,
+}
+
+impl QuantileMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            QuantileMethod::Nearest => "nearest",
+            QuantileMethod::Linear => "linear",
+        }
+    }
+}
+
+impl FromStr for QuantileMethod {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "nearest" => Ok(Self::Nearest),
+            "linear" => Ok(Self::Linear),
+            other => Err(anyhow!(
+                "Unsupported quantile method '{}'. Use 'nearest' or 'linear'.",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for QuantileMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+pub(crate) fn percentile<T: Float + ToPrimitive>(sorted: &[T], p: f32) -> f32 {
+    quantile(sorted, p, QuantileMethod::Nearest)
+}
+
+/// The `p` quantile (0 to 1) of `sorted` values, NaN when there are none
+pub fn quantile<T: Float + ToPrimitive>(sorted: &[T], p: f32, method: QuantileMethod) -> f32 {
     if sorted.is_empty() {
         return f32::NAN;
     }
-    let idx = ((sorted.len() - 1) as f32 * p).round() as usize;
-    sorted.get(idx).and_then(|v| v.to_f32()).unwrap_or(f32::NAN)
+    match method {
+        QuantileMethod::Nearest => {
+            let idx = ((sorted.len() - 1) as f32 * p).round() as usize;
+            sorted.get(idx).and_then(|v| v.to_f32()).unwrap_or(f32::NAN)
+        }
+        QuantileMethod::Linear => {
+            let value = |idx: usize| sorted.get(idx).and_then(|v| v.to_f64()).unwrap_or(f64::NAN);
+            let rank = (sorted.len() - 1) as f64 * p as f64;
+            let lower = rank.floor() as usize;
+            let lo = value(lower);
+            (lo + (value(rank.ceil() as usize) - lo) * (rank - lower as f64)) as f32
+        }
+    }
+}
+
+/// Options controlling how band statistics are computed
+#[derive(Debug, Clone, Default)]
+pub struct QaqcOptions {
+    /// Calculate quartiles, requires reading the full band into memory
+    pub quantiles: bool,
+    /// Estimate quartiles and percentiles with a [`TDigest`] while streaming blocks, instead of
+    /// reading and sorting the full band
+    pub approx_quantiles: bool,
+    /// Use STATISTICS_* band metadata when present instead of scanning pixels
+    pub use_embedded_stats: bool,
+    /// Always scan pixels, even when embedded statistics are available
+    pub force_scan: bool,
+    /// Stats cache file; unchanged files computed with the same options are not rescanned
+    pub cache: Option<PathBuf>,
+    /// How quartiles and percentiles are picked from the sorted values
+    pub quantile_method: QuantileMethod,
+    /// Extra percentiles (0–100) to report, e.g. `[1.0, 99.0]`; needs a full band read
+    pub percentiles: Vec<f32>,
+    /// Count valid values in this many equal-width bins between each band's min and max
+    pub histogram: Option<usize>,
+    /// Sentinel values counted as NoData in addition to each band's declared value
+    pub extra_nodata: Vec<f64>,
+    /// Finite values outside `(min, max)` are counted as NoData
+    pub valid_range: Option<(f64, f64)>,
+    /// Seed for picking the `pct_check` sample, so reruns check the same files; random when unset
+    pub seed: Option<u64>,
+    /// Only compute these 1-based bands; all bands when unset
+    pub bands: Option<Vec<usize>>,
+    /// Count distinct valid values; memory grows with the number of distinct values
+    pub distinct: bool,
 }
 
-pub fn compute_stats_generic<T: Float>(band: &RasterBand, quantiles: bool) -> Result<RasterStats>
+impl QaqcOptions {
+    /// The options that affect computed statistics, used to invalidate cached results
+    pub fn cache_key(&self) -> String {
+        format!(
+            "quantiles={};approx_quantiles={};use_embedded_stats={};force_scan={};quantile_method={};percentiles={:?};histogram={:?};extra_nodata={:?};valid_range={:?};bands={:?};distinct={}",
+            self.quantiles,
+            self.approx_quantiles,
+            self.use_embedded_stats,
+            self.force_scan,
+            self.quantile_method,
+            self.percentiles,
+            self.histogram,
+            self.extra_nodata,
+            self.valid_range,
+            self.bands,
+            self.distinct
+        )
+    }
+
+    /// The bands to compute for a raster with `band_count` bands, failing on an out-of-range
+    /// selection
+    pub fn band_indices(&self, band_count: usize) -> Result<Vec<usize>> {
+        match &self.bands {
+            Some(bands) => {
+                check_band_list(bands, band_count).map_err(|e| anyhow!(e))?;
+                Ok(bands.clone())
+            }
+            None => Ok((1..=band_count).collect()),
+        }
+    }
+
+    /// Fail on percentiles outside 0–100 or repeated, or an empty histogram, before any file
+    /// is read
+    pub fn check_percentiles(&self) -> Result<()> {
+        if self.histogram == Some(0) {
+            return Err(anyhow!("--histogram needs at least one bin"));
+        }
+        if let Some(p) = self
+            .percentiles
+            .iter()
+            .find(|p| !(0.0..=100.0).contains(*p))
+        {
+            return Err(anyhow!("Percentile {} is outside 0-100", p));
+        }
+        // Each percentile becomes a p<N> column, which must be unique
+        match self
+            .percentiles
+            .iter()
+            .enumerate()
+            .find(|(i, p)| self.percentiles[..*i].contains(p))
+        {
+            Some((_, p)) => Err(anyhow!("Percentile {} is given more than once", p)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Build stats from the STATISTICS_MINIMUM/MAXIMUM/MEAN/STDDEV band metadata, if all are present
+///
+/// `band_index` is the 1-based index `band` was opened with.
+pub fn embedded_stats(band: &RasterBand, band_index: usize) -> Result<Option<RasterStats>> {
+    let item = |key: &str| {
+        band.metadata_item(key, "")
+            .and_then(|v| v.trim().parse::<f64>().ok())
+    };
+    let (Some(min), Some(max), Some(mean), Some(stdev)) = (
+        item("STATISTICS_MINIMUM"),
+        item("STATISTICS_MAXIMUM"),
+        item("STATISTICS_MEAN"),
+        item("STATISTICS_STDDEV"),
+    ) else {
+        return Ok(None);
+    };
+
+    Ok(Some(RasterStats {
+        band: band_index,
+        name: band.description()?,
+        dtype: band.band_type().name(),
+        mean,
+        sum: f64::NAN,
+        min,
+        max,
+        variance: stdev.powi(2),
+        stdev,
+        cv: if mean != 0.0 { stdev / mean } else { 0.0 },
+        valid_count: 0,
+        nodata_count: 0,
+        nan_count: 0,
+        zero_count: 0,
+        effective_nodata: "n/a (embedded statistics)".to_string(),
+        percent_valid: item("STATISTICS_VALID_PERCENT").unwrap_or(f64::NAN),
+        q1: None,
+        median: None,
+        q3: None,
+        percentiles: Vec::new(),
+        histogram: None,
+        distinct_count: None,
+        stats_source: "embedded".to_string(),
+    }))
+}
+
+/// Describe the NoData rule applied while scanning a band
+fn describe_nodata(declared: Option<f64>, options: &QaqcOptions) -> String {
+    let mut rules: Vec<String> = declared
+        .map(|value| format!("declared {}", value))
+        .into_iter()
+        .collect();
+    if !options.extra_nodata.is_empty() {
+        let values: Vec<String> = options.extra_nodata.iter().map(f64::to_string).collect();
+        rules.push(format!("extra {}", values.join(" ")));
+    }
+    if let Some((min, max)) = options.valid_range {
+        rules.push(format!("outside [{}, {}]", min, max));
+    }
+    if rules.is_empty() {
+        "none".to_string()
+    } else {
+        rules.join("; ")
+    }
+}
+
+/// How a single pixel value counts towards the band statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelClass {
+    Valid,
+    /// NaN or infinite
+    NonFinite,
+    /// Matches a NoData value or falls outside the valid range
+    NoData,
+}
+
+/// Classify a pixel value against several NoData values and an optional `(min, max)` valid range
+///
+/// The 1e-6 tolerance only suits a `T` that holds the band's values exactly, which is why
+/// integer bands wider than 16 bits are scanned as f64 (see [`compute_stats`]).
+pub fn classify_pixel_with<T: Float>(
+    val: T,
+    nodata: &[T],
+    valid_range: Option<(T, T)>,
+) -> PixelClass {
+    let tolerance = T::from(1e-6).unwrap();
+    if !val.is_finite() {
+        PixelClass::NonFinite
+    } else if nodata
+        .iter()
+        .any(|&nodata| (val - nodata).abs() < tolerance)
+        || valid_range.is_some_and(|(min, max)| val < min || val > max)
+    {
+        PixelClass::NoData
+    } else {
+        PixelClass::Valid
+    }
+}
+
+/// Parse a `MIN,MAX` valid range
+pub fn parse_valid_range(s: &str) -> Result<(f64, f64), String> {
+    let invalid = || format!("Invalid valid range '{}'. Use MIN,MAX, e.g. 0,100.", s);
+    let (min, max) = s.split_once(',').ok_or_else(invalid)?;
+    let min: f64 = min.trim().parse().map_err(|_| invalid())?;
+    let max: f64 = max.trim().parse().map_err(|_| invalid())?;
+    if min > max {
+        return Err(format!(
+            "Valid range minimum {} is above the maximum {}",
+            min, max
+        ));
+    }
+    Ok((min, max))
+}
+
+/// Welford's online mean and sum of squared deviations, for a variance that stays accurate
+/// when values are large and their spread is small
+#[derive(Debug, Default)]
+struct Welford {
+    count: u64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Welford {
+    fn push(&mut self, val: f64) {
+        self.count += 1;
+        let delta = val - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (val - self.mean);
+    }
+
+    /// Running mean, NaN when no values were pushed
+    fn mean(&self) -> f64 {
+        if self.count == 0 { f64::NAN } else { self.mean }
+    }
+
+    /// Population variance, NaN when no values were pushed
+    fn variance(&self) -> f64 {
+        self.m2 / self.count as f64
+    }
+}
+
+/// Equal-width bins between a band's min and max; the max falls in the last bin
+#[derive(Debug)]
+struct Histogram {
+    min: f64,
+    width: f64,
+    counts: Vec<u64>,
+}
+
+impl Histogram {
+    fn new(min: Option<f64>, max: Option<f64>, bins: usize) -> Self {
+        let min = min.unwrap_or(0.0);
+        let max = max.unwrap_or(min);
+        Histogram {
+            min,
+            width: (max - min) / bins as f64,
+            counts: vec![0; bins],
+        }
+    }
+
+    fn push(&mut self, val: f64) {
+        let last = self.counts.len() - 1;
+        let idx = if self.width > 0.0 {
+            (((val - self.min) / self.width) as usize).min(last)
+        } else {
+            0
+        };
+        self.counts[idx] += 1;
+    }
+}
+
+/// Call `f` with every window of `band`: rows for striped rasters, blocks for tiled ones
+fn for_each_window<T>(band: &RasterBand, mut f: impl FnMut(&[T])) -> Result<()>
 where
-    T: Float + gdal::raster::GdalType + FromPrimitive + ToPrimitive + std::fmt::Debug + AddAssign,
+    T: gdal::raster::GdalType + Copy,
 {
-    let band_type = band.band_type();
     let (cols, rows) = (band.x_size(), band.y_size());
     let (block_x, block_y) = band.block_size();
+    if block_y == 1 {
+        // Row-wise read for non COG
+        for row in 0..rows {
+            let buf: Buffer<T> = band.read_as((0, row as isize), (cols, 1), (cols, 1), None)?;
+            f(buf.data());
+        }
+    } else {
+        // Tiled layout: block-wise read for COG
+        for y in (0..rows).step_by(block_y) {
+            for x in (0..cols).step_by(block_x) {
+                let win_width = (block_x).min(cols - x);
+                let win_height = (block_y).min(rows - y);
+                let buf: Buffer<T> = band.read_as(
+                    (x as isize, y as isize),
+                    (win_width, win_height),
+                    (win_width, win_height),
+                    None,
+                )?;
+                f(buf.data());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Bits identifying a value for `--distinct`, with -0.0 folded into 0.0
+fn distinct_key<T: Float>(val: T) -> u64 {
+    (val.to_f64().unwrap_or(f64::NAN) + 0.0).to_bits()
+}
+
+pub fn compute_stats_generic<T: Float>(
+    band: &RasterBand,
+    band_index: usize,
+    options: &QaqcOptions,
+) -> Result<RasterStats>
+where
+    T: Float + gdal::raster::GdalType + FromPrimitive + ToPrimitive + std::fmt::Debug,
+{
+    let band_type = band.band_type();
+    let (cols, rows) = (band.x_size(), band.y_size());
     let nodata = band.no_data_value();
     let name = band.description()?;
 
@@ -112,105 +524,180 @@ where
     let mut valid_count = 0u64;
     let mut nodata_count = 0u64;
     let mut nan_count = 0u64;
-    let mut sum = T::zero();
-    let mut sum_sq = T::zero();
+    let mut zero_count = 0u64;
+    // Summed in f64 whatever `T` is; an f32 sum stops counting whole units past 2^24.
+    // Integer bands are summed exactly, since an f64 running sum drifts once it passes 2^53.
+    let mut sum = 0.0f64;
+    let integer_band = band_type.is_integer();
+    let mut int_sum = 0i128;
+    let mut welford = Welford::default();
     let mut q1 = None;
     let mut median = None;
     let mut q3 = None;
+    let mut percentiles = Vec::new();
+    let mut histogram = options
+        .histogram
+        .map(|bins| Histogram::new(None, None, bins));
     let mut min = T::max_value();
     let mut max = T::min_value();
+    let mut distinct: Option<HashSet<u64>> = options.distinct.then(HashSet::new);
+    let wants_quantiles = options.quantiles || !options.percentiles.is_empty();
+    let mut digest = (wants_quantiles && options.approx_quantiles).then(TDigest::new);
 
-    let nodata_val = nodata.and_then(T::from_f64);
-    let epsilon = T::from_f64(1e-6).unwrap();
+    let nodata_vals: Vec<T> = nodata
+        .iter()
+        .chain(&options.extra_nodata)
+        .filter_map(|&v| T::from_f64(v))
+        .collect();
+    let valid_range = options
+        .valid_range
+        .and_then(|(min, max)| Some((T::from_f64(min)?, T::from_f64(max)?)));
 
-    let mut process_buffer = |data: &[T]| {
+    let process_buffer = |data: &[T]| {
         for &val in data {
-            if !val.is_finite() {
-                nan_count += 1;
-                continue;
-            }
-            if let Some(nodata_val) = nodata_val {
-                if (val - nodata_val).abs() < epsilon {
+            match classify_pixel_with(val, &nodata_vals, valid_range) {
+                PixelClass::NonFinite => {
+                    nan_count += 1;
+                    continue;
+                }
+                PixelClass::NoData => {
                     nodata_count += 1;
                     continue;
                 }
+                PixelClass::Valid => {}
             }
             valid_count += 1;
-            sum += val;
-            sum_sq += val * val;
+            if val == T::zero() {
+                zero_count += 1;
+            }
+            if integer_band {
+                int_sum += val.to_i128().unwrap_or(0);
+            } else {
+                sum += val.to_f64().unwrap_or(0.0);
+            }
+            welford.push(val.to_f64().unwrap_or(0.0));
             min = min.min(val);
             max = max.max(val);
+            if let Some(distinct) = &mut distinct {
+                distinct.insert(distinct_key(val));
+            }
+            if let Some(digest) = &mut digest {
+                digest.push(val.to_f64().unwrap_or(f64::NAN));
+            }
         }
     };
 
     // Hybrid reading
-    if quantiles {
-        // Full read as required to calcualte quartiles
+    if wants_quantiles && !options.approx_quantiles {
+        // Full read as required to calcualte quartiles and percentiles
         let buf: Buffer<T> = band.read_band_as()?;
         let mut valid_values: Vec<T> = Vec::with_capacity(buf.data().len());
 
         // Single pass to filter valid values and calculate sums
         for &val in buf.data() {
-            if !val.is_finite() {
-                nan_count += 1;
-                continue;
-            }
-            if let Some(nodata_val) = nodata.and_then(T::from_f64) {
-                if (val - nodata_val).abs() < T::from_f64(1e-6).unwrap() {
+            match classify_pixel_with(val, &nodata_vals, valid_range) {
+                PixelClass::NonFinite => {
+                    nan_count += 1;
+                    continue;
+                }
+                PixelClass::NoData => {
                     nodata_count += 1;
                     continue;
                 }
+                PixelClass::Valid => {}
             }
             valid_values.push(val);
-            sum += val;
-            sum_sq += val * val;
+            if val == T::zero() {
+                zero_count += 1;
+            }
+            if integer_band {
+                int_sum += val.to_i128().unwrap_or(0);
+            } else {
+                sum += val.to_f64().unwrap_or(0.0);
+            }
+            welford.push(val.to_f64().unwrap_or(0.0));
             min = min.min(val);
             max = max.max(val);
+            if let Some(distinct) = &mut distinct {
+                distinct.insert(distinct_key(val));
+            }
         }
         valid_count = valid_values.len() as u64;
 
-        // Calculate quartiles if we have valid data
-        if !valid_values.is_empty() {
-            // Sort the data in-place. `partial_cmp` is necessary for floats (f32/f64).
-            valid_values
-                .sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        // Sort the data in-place. `partial_cmp` is necessary for floats (f32/f64).
+        valid_values.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let method = options.quantile_method;
 
-            // Use our helper to calculate percentiles
-            q1 = Some(percentile(&valid_values, 0.25));
-            median = Some(percentile(&valid_values, 0.50));
-            q3 = Some(percentile(&valid_values, 0.75));
+        // Calculate quartiles if we have valid data
+        if options.quantiles && !valid_values.is_empty() {
+            q1 = Some(quantile(&valid_values, 0.25, method));
+            median = Some(quantile(&valid_values, 0.50, method));
+            q3 = Some(quantile(&valid_values, 0.75, method));
         }
-    } else if block_y == 1 {
-        // Row-wise read for non COG
-        for row in 0..rows {
-            let buf: Buffer<T> = band.read_as((0, row as isize), (cols, 1), (cols, 1), None)?;
-            process_buffer(buf.data());
+        // Requested percentiles are always reported, as NaN for bands without valid data
+        percentiles = options
+            .percentiles
+            .iter()
+            .map(|&p| (p, quantile(&valid_values, p / 100.0, method)))
+            .collect();
+        if let Some(hist) = &mut histogram {
+            *hist = Histogram::new(
+                valid_values.first().and_then(|v| v.to_f64()),
+                valid_values.last().and_then(|v| v.to_f64()),
+                hist.counts.len(),
+            );
+            valid_values
+                .iter()
+                .for_each(|v| hist.push(v.to_f64().unwrap_or(f64::NAN)));
         }
     } else {
-        // Tiled layout: block-wise read for COG
-        for y in (0..rows).step_by(block_y) {
-            for x in (0..cols).step_by(block_x) {
-                let win_width = (block_x).min(cols - x);
-                let win_height = (block_y).min(rows - y);
-                let buf: Buffer<T> = band.read_as(
-                    (x as isize, y as isize),
-                    (win_width, win_height),
-                    (win_width, win_height),
-                    None,
-                )?;
-                process_buffer(buf.data());
+        for_each_window(band, process_buffer)?;
+
+        // Estimated quantiles are interpolated, whatever the --quantile-method
+        if let Some(digest) = &mut digest {
+            let mut estimate = |p: f32| digest.quantile(p as f64) as f32;
+            if options.quantiles && valid_count > 0 {
+                q1 = Some(estimate(0.25));
+                median = Some(estimate(0.50));
+                q3 = Some(estimate(0.75));
             }
+            percentiles = options
+                .percentiles
+                .iter()
+                .map(|&p| (p, estimate(p / 100.0)))
+                .collect();
+        }
+
+        // Bin edges depend on the min and max, so the histogram needs a second pass
+        if let Some(hist) = &mut histogram
+            && valid_count > 0
+        {
+            *hist = Histogram::new(min.to_f64(), max.to_f64(), hist.counts.len());
+            for_each_window(band, |data: &[T]| {
+                data.iter()
+                    .filter(|&&val| {
+                        matches!(
+                            classify_pixel_with(val, &nodata_vals, valid_range),
+                            PixelClass::Valid
+                        )
+                    })
+                    .for_each(|val| hist.push(val.to_f64().unwrap_or(f64::NAN)));
+            })?;
         }
     }
 
     // Final calculations
     let valid_count_f64 = valid_count as f64;
-    let sum_f64 = sum.to_f64().unwrap_or(0.0);
-    let sum_sq_f64 = sum_sq.to_f64().unwrap_or(0.0);
+    let sum_f64 = if integer_band { int_sum as f64 } else { sum };
 
-    let mean = sum_f64 / valid_count_f64;
-    let variance = (sum_sq_f64 / valid_count_f64) - mean.powi(2);
-    let variance = if variance < 0.0 { 0.0 } else { variance };
+    // A large running sum cannot hold the fractions of each value, so float bands take the
+    // streaming mean; the exact integer sum already gives the closest f64 mean
+    let mean = if integer_band {
+        sum_f64 / valid_count_f64
+    } else {
+        welford.mean()
+    };
+    let variance = welford.variance();
     let stdev = variance.sqrt();
     let cv = if mean != 0.0 { stdev / mean } else { 0.0 };
     let percent_valid = valid_count_f64 / (cols * rows) as f64 * 100.0;
@@ -218,9 +705,11 @@ where
     let max = max.to_f64().unwrap_or(0.0);
 
     Ok(RasterStats {
+        band: band_index,
         name,
         dtype: band_type.name(),
         mean,
+        sum: sum_f64,
         min,
         max,
         variance,
@@ -229,40 +718,352 @@ where
         valid_count,
         nodata_count,
         nan_count,
+        zero_count,
+        effective_nodata: describe_nodata(nodata, options),
         percent_valid,
         q1,
         median,
         q3,
+        percentiles,
+        histogram: histogram.map(|h| h.counts),
+        distinct_count: distinct.map(|d| d.len() as u64),
+        stats_source: "scan".to_string(),
     })
 }
 
-pub fn compute_stats(band: &RasterBand, all_stats: bool) -> Result<RasterStats> {
+/// Stats of `band`, opened as band `band_index` (1-based) of its raster
+pub fn compute_stats(
+    band: &RasterBand,
+    band_index: usize,
+    options: &QaqcOptions,
+) -> Result<RasterStats> {
+    // Embedded statistics carry no percentiles, histogram or distinct count and ignore the
+    // extra NoData rules, so a scan is needed for those
+    if options.use_embedded_stats
+        && !options.force_scan
+        && options.percentiles.is_empty()
+        && options.histogram.is_none()
+        && options.extra_nodata.is_empty()
+        && options.valid_range.is_none()
+        && !options.distinct
+        && let Some(stats) = embedded_stats(band, band_index)?
+    {
+        return Ok(stats);
+    }
     match band.band_type() {
-        GdalDataType::Float64 => compute_stats_generic::<f64>(band, all_stats),
-        _ => compute_stats_generic::<f32>(band, all_stats),
+        GdalDataType::Float64 => compute_stats_generic::<f64>(band, band_index, options),
+        // f32 holds integers exactly only up to 2^24, so wider integer types are read as f64
+        ty if ty.is_integer() && ty.bits() > 16 => {
+            compute_stats_generic::<f64>(band, band_index, options)
+        }
+        _ => compute_stats_generic::<f32>(band, band_index, options),
     }
 }
 
-pub fn compute_all_bands(path: &Path, all_stats: bool) -> Result<Vec<RasterStats>> {
+/// Rasters with at least this many bands have their bands scanned in parallel
+const BAND_PARALLEL_MIN: usize = 4;
+
+/// Stats for every band of one raster, in band order
+///
+/// Stacks of [`BAND_PARALLEL_MIN`] or more bands are scanned in parallel, each rayon worker
+/// opening its own dataset handle as GDAL datasets cannot be shared across threads. Batches
+/// use [`compute_bands_flattened`] instead, which spreads bands of all files over the pool.
+pub fn compute_all_bands(path: &Path, options: &QaqcOptions) -> Result<Vec<RasterStats>> {
     // println!("Processing: {}", path.display());
-    let dataset = Dataset::open(path)?;
-    let band_count = dataset.raster_count();
-    let mut stats = Vec::with_capacity(band_count as usize);
+    let path = to_vsi(path);
+    let (permit, dataset) = open_limit::open(&path)?;
+    let bands = options.band_indices(dataset.raster_count())?;
 
-    for i in 1..=band_count {
-        let band = dataset.rasterband(i)?;
-        let results = compute_stats(&band, all_stats)?;
-        stats.push(results);
+    if bands.len() < BAND_PARALLEL_MIN {
+        let mut stats = Vec::with_capacity(bands.len());
+        for &i in &bands {
+            let band = dataset.rasterband(i)?;
+            let results = compute_stats(&band, i, options)?;
+            stats.push(results);
+        }
+        return Ok(stats);
     }
 
+    // Release this thread's permit so the workers can take it under a low --max-open
+    drop(dataset);
+    drop(permit);
+    bands
+        .into_par_iter()
+        .map_init(
+            // Swapped so the dataset drops before its permit
+            || open_limit::open(&path).map(|(permit, dataset)| (dataset, permit)),
+            |open, i| {
+                let (dataset, _) = open.as_ref().map_err(|e| anyhow!("{}", e))?;
+                compute_stats(&dataset.rasterband(i)?, i, options)
+                    .map_err(|e| anyhow!("band {}: {}", i, e))
+            },
+        )
+        .collect()
+}
+
+/// How a file enters the flattened scan in [`compute_bands_flattened`]
+enum PreparedFile {
+    Cached(Vec<RasterStats>),
+    Scan {
+        hash: Option<String>,
+        bands: Vec<usize>,
+    },
+}
+
+/// Error recorded for files and bands left unprocessed after a failure with `fail_fast`
+const SKIPPED_AFTER_FAILURE: &str = "skipped after an earlier failure";
+
+/// Stats for every band of every file, scheduling each (file, band) pair as its own rayon
+/// task so a directory of a few large multi-band files still uses every core
+///
+/// GDAL datasets cannot be shared across threads, so each rayon worker opens its own handle
+/// and keeps it while it scans bands of the same file. Bands are scheduled in file order,
+/// so an N-band file is opened about once per worker that touches it instead of once per
+/// band.
+/// Results come back in `files` order with bands in index order; a file fails if any of its
+/// bands does. Files with a cache hit are not rescanned, and fresh results are cached.
+///
+/// With `fail_fast`, files and bands not yet started when one fails are not processed
+/// and come back as errors.
+pub fn compute_bands_flattened(
+    files: &[PathBuf],
+    options: &QaqcOptions,
+    cache: Option<&StatsCache>,
+    progress: &ProgressReporter,
+    fail_fast: bool,
+) -> Vec<std::result::Result<Vec<RasterStats>, String>> {
+    // Set by the first failure when failing fast; work not yet started is then skipped
+    let stop = AtomicBool::new(false);
+    let check_stop = || -> Result<()> {
+        if stop.load(Ordering::SeqCst) {
+            return Err(anyhow!(SKIPPED_AFTER_FAILURE));
+        }
+        Ok(())
+    };
+    let note_failure = |failed: bool| {
+        if fail_fast && failed {
+            stop.store(true, Ordering::SeqCst);
+        }
+    };
+    let prepare = |path: &PathBuf| -> Result<PreparedFile> {
+        check_stop()?;
+        let hash = match cache {
+            Some(cache) => {
+                let hash = file_hash(path)?;
+                if let Some(stats) = cache.get(path, &hash, options) {
+                    return Ok(PreparedFile::Cached(stats));
+                }
+                Some(hash)
+            }
+            None => None,
+        };
+        let (_permit, dataset) = open_limit::open(path)?;
+        let bands = options.band_indices(dataset.raster_count())?;
+        Ok(PreparedFile::Scan { hash, bands })
+    };
+    let finish_file = |index: usize, ok: bool| {
+        let event = progress.finish(&files[index], ok);
+        if progress.shows_lines() {
+            eprintln!(
+                "Processed file {}/{}: {:?}",
+                event.index,
+                event.total,
+                files[index].file_name().unwrap_or_default()
+            );
+        }
+    };
+    let prepared: Vec<std::result::Result<PreparedFile, String>> = files
+        .par_iter()
+        .map(|path| {
+            let prepared = prepare(path).map_err(|e| e.to_string());
+            note_failure(prepared.is_err());
+            prepared
+        })
+        .collect();
+
+    let mut units = Vec::new();
+    let remaining: Vec<AtomicUsize> = prepared
+        .iter()
+        .enumerate()
+        .map(|(index, file)| match file {
+            Ok(PreparedFile::Scan { bands, .. }) if !bands.is_empty() => {
+                units.extend(bands.iter().map(|&band| (index, band)));
+                AtomicUsize::new(bands.len())
+            }
+            other => {
+                finish_file(index, other.is_ok());
+                AtomicUsize::new(0)
+            }
+        })
+        .collect();
+    let band_failed: Vec<AtomicBool> = files.iter().map(|_| AtomicBool::new(false)).collect();
+
+    let band_stats: Vec<std::result::Result<RasterStats, String>> = units
+        .par_iter()
+        .map_init(
+            || None,
+            |open: &mut Option<(usize, Dataset, open_limit::OpenPermit)>, &(index, band_index)| {
+                let path = &files[index];
+                let result = (|| {
+                    check_stop()?;
+                    // Keep the worker's dataset while it stays on one file; the tuple drops
+                    // the dataset before its permit
+                    if open
+                        .as_ref()
+                        .is_none_or(|(open_index, ..)| *open_index != index)
+                    {
+                        *open = None;
+                        let (permit, dataset) = open_limit::open(path)?;
+                        *open = Some((index, dataset, permit));
+                    }
+                    let (_, dataset, _) = open.as_ref().unwrap();
+                    let band = dataset.rasterband(band_index)?;
+                    compute_stats(&band, band_index, options)
+                })()
+                .map_err(|e| format!("band {}: {}", band_index, e));
+                if result.is_err() {
+                    band_failed[index].store(true, Ordering::SeqCst);
+                }
+                note_failure(result.is_err());
+                if remaining[index].fetch_sub(1, Ordering::SeqCst) == 1 {
+                    finish_file(index, !band_failed[index].load(Ordering::SeqCst));
+                }
+                result
+            },
+        )
+        .collect();
+
+    // Units are in (file, band) order, so each file's bands are a contiguous run
+    let mut band_stats = band_stats.into_iter();
+    prepared
+        .into_iter()
+        .zip(files)
+        .map(|(file, path)| match file? {
+            PreparedFile::Cached(stats) => Ok(stats),
+            PreparedFile::Scan { hash, bands } => {
+                let stats = band_stats
+                    .by_ref()
+                    .take(bands.len())
+                    .collect::<std::result::Result<Vec<_>, String>>()?;
+                if let (Some(cache), Some(hash)) = (cache, hash) {
+                    cache.insert(path, hash, options, &stats);
+                }
+                Ok(stats)
+            }
+        })
+        .collect()
+}
+
+/// Write a Byte COG on the raster's grid holding 1 where a pixel is valid in any band
+/// and 0 where every band is NoData or non-finite
+///
+/// Pixels are classified with [`classify_pixel_with`] under the same `options` as the band
+/// statistics: only the `bands` selected are read, and `extra_nodata` and `valid_range`
+/// apply on top of each band's own NoData value.
+pub fn write_validity_mask(path: &Path, output_path: &Path, options: &QaqcOptions) -> Result<()> {
+    let (_permit, dataset) = open_limit::open(to_vsi(path))?;
+    let (cols, rows) = dataset.raster_size();
+    let bands = options
+        .band_indices(dataset.raster_count())?
+        .into_iter()
+        .map(|i| dataset.rasterband(i))
+        .collect::<gdal::errors::Result<Vec<_>>>()?;
+    if bands.is_empty() {
+        return Err(anyhow!("{} has no bands", path.display()));
+    }
+    let nodata_vals: Vec<Vec<f64>> = bands
+        .iter()
+        .map(|band| {
+            band.no_data_value()
+                .iter()
+                .chain(&options.extra_nodata)
+                .copied()
+                .collect()
+        })
+        .collect();
+
+    let gtiff = DriverManager::get_driver_by_name("GTiff")?;
+    let temp_path = TempFile::new(output_path, VALIDITY_TIF);
+    let mut mask = create_with_type(
+        &gtiff,
+        temp_path.path(),
+        (cols, rows),
+        1,
+        GdalDataType::UInt8,
+    )?;
+    if let Ok(transform) = dataset.geo_transform() {
+        mask.set_geo_transform(&transform)?;
+    }
+    if let Ok(srs) = dataset.spatial_ref() {
+        mask.set_spatial_ref(&srs)?;
+    }
+    let mut mask_band = mask.rasterband(1)?;
+    mask_band.set_description("valid")?;
+
+    let chunk_rows = 256.min(rows.max(1));
+    let mut valid_pixels = 0u64;
+    for y in (0..rows).step_by(chunk_rows) {
+        let height = chunk_rows.min(rows - y);
+        let mut valid = vec![0u8; cols * height];
+        for (band, nodata) in bands.iter().zip(&nodata_vals) {
+            let buf: Buffer<f64> =
+                band.read_as((0, y as isize), (cols, height), (cols, height), None)?;
+            for (flag, &val) in valid.iter_mut().zip(buf.data()) {
+                if classify_pixel_with(val, nodata, options.valid_range) == PixelClass::Valid {
+                    *flag = 1;
+                }
+            }
+        }
+        valid_pixels += valid.iter().map(|&v| v as u64).sum::<u64>();
+        let mut buf = Buffer::new((cols, height), valid);
+        mask_band.write((0, y as isize), (cols, height), &mut buf)?;
+    }
+
+    let cog = DriverManager::get_driver_by_name("COG")?;
+    let copied = mask.create_copy(
+        &cog,
+        output_path,
+        &RasterCreationOptions::from_iter(["COMPRESS=DEFLATE"]),
+    );
+    drop(mask);
+    drop(temp_path);
+    copied?;
+
+    status!(
+        "Wrote validity mask to {} ({} of {} pixels valid)",
+        output_path.display(),
+        valid_pixels,
+        cols * rows
+    );
+    Ok(())
+}
+
+/// Like [`compute_all_bands`], but reuses and updates `cache` when given
+pub fn compute_all_bands_cached(
+    path: &Path,
+    options: &QaqcOptions,
+    cache: Option<&StatsCache>,
+) -> Result<Vec<RasterStats>> {
+    // Cache entries are keyed on a hash of the local file contents
+    let Some(cache) = cache.filter(|_| !is_vsi(path)) else {
+        return compute_all_bands(path, options);
+    };
+    let hash = file_hash(path)?;
+    if let Some(stats) = cache.get(path, &hash, options) {
+        return Ok(stats);
+    }
+    let stats = compute_all_bands(path, options)?;
+    cache.insert(path, hash, options, &stats);
     Ok(stats)
 }
 
-pub fn raster_stats_to_df(stats: Vec<RasterStats>, filename: &Path) -> LazyFrame {
+pub fn raster_stats_to_df(stats: Vec<RasterStats>, filename: &Path) -> Result<LazyFrame> {
     let stat_len = stats.len();
+    let mut band = Vec::with_capacity(stat_len);
     let mut name = Vec::with_capacity(stat_len);
     let mut dtype = Vec::with_capacity(stat_len);
     let mut mean = Vec::with_capacity(stat_len);
+    let mut sum = Vec::with_capacity(stat_len);
     let mut min = Vec::with_capacity(stat_len);
     let mut max = Vec::with_capacity(stat_len);
     let mut variance = Vec::with_capacity(stat_len);
@@ -271,15 +1072,22 @@ pub fn raster_stats_to_df(stats: Vec<RasterStats>, filename: &Path) -> LazyFrame
     let mut valid_count = Vec::with_capacity(stat_len);
     let mut nodata_count = Vec::with_capacity(stat_len);
     let mut nan_count = Vec::with_capacity(stat_len);
+    let mut zero_count = Vec::with_capacity(stat_len);
+    let mut distinct_count = Vec::with_capacity(stat_len);
+    let mut effective_nodata = Vec::with_capacity(stat_len);
     let mut percent_valid = Vec::with_capacity(stat_len);
     let mut q1 = Vec::with_capacity(stat_len);
     let mut median = Vec::with_capacity(stat_len);
     let mut q3 = Vec::with_capacity(stat_len);
+    let mut percentiles: Vec<(f32, Vec<f32>)> = Vec::new();
+    let mut stats_source = Vec::with_capacity(stat_len);
 
     for s in stats {
+        band.push(s.band as u32);
         name.push(s.name.clone());
         dtype.push(s.dtype.clone());
         mean.push(s.mean);
+        sum.push(s.sum);
         min.push(s.min);
         max.push(s.max);
         variance.push(s.variance);
@@ -288,19 +1096,38 @@ pub fn raster_stats_to_df(stats: Vec<RasterStats>, filename: &Path) -> LazyFrame
         valid_count.push(s.valid_count as u64);
         nodata_count.push(s.nodata_count as u64);
         nan_count.push(s.nan_count as u64);
+        zero_count.push(s.zero_count);
+        distinct_count.push(s.distinct_count);
+        effective_nodata.push(s.effective_nodata);
         percent_valid.push(s.percent_valid);
         q1.push(s.q1.unwrap_or(f32::NAN));
         median.push(s.median.unwrap_or(f32::NAN));
         q3.push(s.q3.unwrap_or(f32::NAN));
+        for (p, value) in &s.percentiles {
+            match percentiles.iter_mut().find(|(known, _)| known == p) {
+                Some((_, values)) => values.push(*value),
+                None => percentiles.push((*p, vec![*value])),
+            }
+        }
+        stats_source.push(s.stats_source);
     }
 
-    let file = vec![filename.file_name().unwrap().to_str().unwrap(); stat_len];
+    let file = vec![
+        filename
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        stat_len
+    ];
 
-    let result_df = DataFrame::new(vec![
+    let mut columns = vec![
         Column::new("file".into(), file),
+        Column::new("band".into(), band),
         Column::new("name".into(), name),
         Column::new("dtype".into(), dtype),
         Column::new("mean".into(), mean),
+        Column::new("sum".into(), sum),
         Column::new("min".into(), min),
         Column::new("max".into(), max),
         Column::new("variance".into(), variance),
@@ -309,13 +1136,24 @@ pub fn raster_stats_to_df(stats: Vec<RasterStats>, filename: &Path) -> LazyFrame
         Column::new("valid_count".into(), valid_count),
         Column::new("nodata_count".into(), nodata_count),
         Column::new("nan_count".into(), nan_count),
+        Column::new("zero_count".into(), zero_count),
+        Column::new("distinct_count".into(), distinct_count),
+        Column::new("effective_nodata".into(), effective_nodata),
         Column::new("percent_valid".into(), percent_valid),
         Column::new("q1".into(), q1),
         Column::new("median".into(), median),
         Column::new("q3".into(), q3),
-    ])
-    .unwrap();
-    return result_df.lazy();
+    ];
+    // One `p<N>` column per requested percentile, e.g. p5 and p99.9
+    columns.extend(
+        percentiles
+            .into_iter()
+            .map(|(p, values)| Column::new(format!("p{}", p).into(), values)),
+    );
+    columns.push(Column::new("stats_source".into(), stats_source));
+
+    let result_df = DataFrame::new(columns)?;
+    Ok(result_df.lazy())
 }
 
 const SUPPORTED_EXTENSIONS: &[&str] = &["tif", "tiff", "asc", "img", "vrt"];
@@ -341,23 +1179,459 @@ impl FromStr for OutputFormat {
     }
 }
 
-impl ToString for OutputFormat {
-    fn to_string(&self) -> String {
+impl OutputFormat {
+    pub fn name(&self) -> &'static str {
         match self {
-            OutputFormat::Parquet => "parquet".to_string(),
-            OutputFormat::Csv => "csv".to_string(),
+            OutputFormat::Parquet => "parquet",
+            OutputFormat::Csv => "csv",
+        }
+    }
+}
+
+impl std::fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Where and how QAQC results are written
+#[derive(Debug, Clone)]
+pub struct QaqcOutput {
+    pub format: OutputFormat,
+    /// Stream the table to stdout instead of a file (CSV only)
+    pub to_stdout: bool,
+    /// Write the table here instead of `qaqc.<ext>` next to the input; the extension follows `format`
+    pub out: Option<PathBuf>,
+    /// Build and concatenate per-file frames this many files at a time instead of all at once
+    pub concat_chunksize: Option<usize>,
+    /// Also write a batch rollup (see [`QaqcSummary`]) as JSON to this path
+    pub summary_json: Option<PathBuf>,
+    /// Fail after writing the report if [`strict_violations`] or [`crs_violation`] finds any
+    /// problem, or if the validity mask cannot be written
+    pub strict: bool,
+    /// Also write a validity mask (see [`write_validity_mask`]) here; single files only
+    pub write_mask: Option<PathBuf>,
+    /// Also write each file's band stats as JSON to `<input>.qaqc.json`
+    pub per_file_report: bool,
+    /// Put the per-file reports here instead of next to each input
+    pub report_dir: Option<PathBuf>,
+    /// Add `pass`/`flags` columns from these rules and fail if any band breaks one
+    pub rules: Vec<Rule>,
+    /// Also write the files that failed and their errors to `<table stem>_errors.<ext>`
+    pub write_errors: bool,
+}
+
+impl Default for QaqcOutput {
+    fn default() -> Self {
+        Self {
+            format: OutputFormat::Csv,
+            to_stdout: false,
+            out: None,
+            concat_chunksize: None,
+            summary_json: None,
+            strict: false,
+            write_mask: None,
+            per_file_report: false,
+            report_dir: None,
+            rules: Vec::new(),
+            write_errors: false,
+        }
+    }
+}
+
+/// Concatenate per-file frames into one table
+///
+/// By default every per-file frame is built and the frames are concatenated in one pass. With
+/// `chunksize`, frames are built that many files at a time and each batch is collected into one
+/// contiguous frame before the next is built. A per-file frame takes far more memory than the
+/// few rows it holds, so holding one batch at a time lowers peak memory on runs with tens of
+/// thousands of files, at the cost of a slower aggregation.
+fn concat_frames<T>(
+    items: &[T],
+    chunksize: Option<usize>,
+    to_frame: impl Fn(&T) -> Result<LazyFrame>,
+) -> Result<DataFrame> {
+    let Some(chunksize) = chunksize.filter(|&n| n > 0 && n < items.len()) else {
+        let frames = items.iter().map(to_frame).collect::<Result<Vec<_>>>()?;
+        return Ok(concat(frames, UnionArgs::default())?.collect()?);
+    };
+
+    let mut parts = Vec::with_capacity(items.len().div_ceil(chunksize));
+    for chunk in items.chunks(chunksize) {
+        let frames = chunk.iter().map(&to_frame).collect::<Result<Vec<_>>>()?;
+        let mut part = concat(frames, UnionArgs::default())?.collect()?;
+        part.rechunk_mut();
+        parts.push(part.lazy());
+    }
+    Ok(concat(parts, UnionArgs::default())?.collect()?)
+}
+
+/// Write the QAQC table to stdout, to `output.out`, or to `qaqc.<ext>` in `directory`
+///
+/// Returns the path written, or `None` for stdout.
+fn write_qaqc_table(
+    df: &mut DataFrame,
+    directory: &Path,
+    output: &QaqcOutput,
+) -> Result<Option<PathBuf>> {
+    if output.to_stdout {
+        return match output.format {
+            OutputFormat::Csv => {
+                CsvWriter::new(&mut std::io::stdout().lock()).finish(df)?;
+                Ok(None)
+            }
+            OutputFormat::Parquet => Err(anyhow!(
+                "Parquet output cannot be written to stdout, use --output-format csv"
+            )),
+        };
+    }
+
+    let path = table_path(directory, output);
+    write_frame(df, &path, output.format)?;
+    eprintln!("Wrote output to: {}", path.display());
+    Ok(Some(path))
+}
+
+/// `output.out` with the extension of the output format, or `qaqc.<ext>` in `directory`
+fn table_path(directory: &Path, output: &QaqcOutput) -> PathBuf {
+    let ext = output.format.to_string();
+    match &output.out {
+        Some(out) => out.with_extension(ext),
+        None => directory.join(format!("qaqc.{}", ext)),
+    }
+}
+
+/// Write the failed files of a batch, one row per file with its error and error category,
+/// next to the QAQC table as `<table stem>_errors.<ext>`
+///
+/// The table is written even when nothing failed, so it never lists files from an earlier run.
+fn write_error_table(
+    failed: &[(PathBuf, String)],
+    table_path: &Path,
+    format: OutputFormat,
+) -> Result<()> {
+    let stem = table_path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = table_path.extension().unwrap_or_default().to_string_lossy();
+    let path = table_path.with_file_name(format!("{}_errors.{}", stem, ext));
+    let mut df = df!(
+        "filename" => failed.iter().map(|(path, _)| path.display().to_string()).collect::<Vec<_>>(),
+        "error_type" => failed.iter().map(|(_, e)| ErrorCategory::classify(e).label()).collect::<Vec<_>>(),
+        "error" => failed.iter().map(|(_, e)| e.as_str()).collect::<Vec<_>>(),
+    )?;
+    write_frame(&mut df, &path, format)?;
+    eprintln!("Wrote {} errors to: {}", failed.len(), path.display());
+    Ok(())
+}
+
+pub(crate) fn write_frame(df: &mut DataFrame, path: &Path, format: OutputFormat) -> Result<()> {
+    let mut file = File::create(path)?;
+    match format {
+        OutputFormat::Csv => CsvWriter::new(&mut file).finish(df)?,
+        OutputFormat::Parquet => {
+            let _ = ParquetWriter::new(&mut file).finish(df)?; // _ bc pq writer returns size & csv doesn't
+        }
+    }
+    Ok(())
+}
+
+/// Concatenate QAQC Parquet tables from earlier runs into one table at `out`
+///
+/// Columns that an input lacks, e.g. one written before a column was added, are filled
+/// with nulls, and differing column types are widened to a common type. The output
+/// extension follows `format`. Returns the path written and its row count.
+pub fn merge_qaqc(
+    inputs: &[PathBuf],
+    out: &Path,
+    format: OutputFormat,
+) -> Result<(PathBuf, usize)> {
+    if inputs.is_empty() {
+        return Err(anyhow!("No QAQC tables to merge"));
+    }
+    let path = out.with_extension(format.to_string());
+    let mut frames = Vec::with_capacity(inputs.len());
+    for input in inputs {
+        if !input.is_file() {
+            return Err(anyhow!("QAQC table {} does not exist", input.display()));
+        }
+        // The inputs are still being read when the output is created
+        if fs::canonicalize(input).ok() == fs::canonicalize(&path).ok() {
+            return Err(anyhow!("Output {} is also an input", path.display()));
+        }
+        let frame = LazyFrame::scan_parquet(input, ScanArgsParquet::default())
+            .map_err(|e| anyhow!("Failed to read {}: {}", input.display(), e))?;
+        frames.push(frame);
+    }
+
+    let args = UnionArgs {
+        diagonal: true,
+        to_supertypes: true,
+        ..Default::default()
+    };
+    let mut df = concat(&frames, args)?.collect()?;
+    write_frame(&mut df, &path, format)?;
+    Ok((path, df.height()))
+}
+
+/// Histogram bins as a table with one row per file, band and bin
+///
+/// Bands without a histogram are left out.
+pub fn histogram_df(stats: &[RasterStats], filename: &Path) -> LazyFrame {
+    let file_name = filename.file_name().unwrap_or_default().to_string_lossy();
+    let mut name = Vec::new();
+    let mut band = Vec::new();
+    let mut bin = Vec::new();
+    let mut lower = Vec::new();
+    let mut upper = Vec::new();
+    let mut count = Vec::new();
+    for s in stats {
+        let Some(counts) = &s.histogram else {
+            continue;
+        };
+        let width = (s.max - s.min) / counts.len() as f64;
+        for (i, &c) in counts.iter().enumerate() {
+            name.push(s.name.clone());
+            band.push(s.band as u32);
+            bin.push(i as u32);
+            lower.push(s.min + width * i as f64);
+            upper.push(if i + 1 == counts.len() {
+                s.max
+            } else {
+                s.min + width * (i + 1) as f64
+            });
+            count.push(c);
         }
     }
+    let file = vec![file_name.to_string(); name.len()];
+    DataFrame::new(vec![
+        Column::new("file".into(), file),
+        Column::new("name".into(), name),
+        Column::new("band".into(), band),
+        Column::new("bin".into(), bin),
+        Column::new("lower".into(), lower),
+        Column::new("upper".into(), upper),
+        Column::new("count".into(), count),
+    ])
+    .unwrap()
+    .lazy()
+}
+
+/// Write histogram tables next to the QAQC table as `<table stem>_histogram.<ext>`
+fn write_histogram_table(
+    file_stats: &[(PathBuf, Vec<RasterStats>)],
+    table_path: Option<&Path>,
+    output: &QaqcOutput,
+) -> Result<()> {
+    let Some(table_path) = table_path else {
+        eprintln!("Histograms are not written with --stdout");
+        return Ok(());
+    };
+    if file_stats.is_empty() {
+        return Ok(());
+    }
+    let stem = table_path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = table_path.extension().unwrap_or_default().to_string_lossy();
+    let path = table_path.with_file_name(format!("{}_histogram.{}", stem, ext));
+    let mut df = concat_frames(file_stats, output.concat_chunksize, |(file, stats)| {
+        Ok(histogram_df(stats, file))
+    })?;
+    write_frame(&mut df, &path, output.format)?;
+    eprintln!("Wrote histograms to: {}", path.display());
+    Ok(())
+}
+
+/// Release-level rollup of a batch QAQC run
+///
+/// A band is flagged when it has no valid pixels; a file passes when it was read
+/// successfully and none of its bands are flagged.
+#[derive(Debug, Serialize)]
+pub struct QaqcSummary {
+    pub total_files: usize,
+    pub files_passed: usize,
+    pub files_failed: usize,
+    pub mean_percent_valid: Option<f64>,
+    pub bands_flagged: usize,
+}
+
+/// Summarize the combined QAQC table of `total_files` files, `errored_files` of which failed to read
+pub fn qaqc_summary(
+    df: &DataFrame,
+    total_files: usize,
+    errored_files: usize,
+) -> Result<QaqcSummary> {
+    let flagged = df
+        .clone()
+        .lazy()
+        .filter(col("valid_count").eq(lit(0u64)))
+        .collect()?;
+    let files_flagged = flagged.column("file")?.n_unique()?;
+    let files_failed = errored_files + files_flagged;
+
+    Ok(QaqcSummary {
+        total_files,
+        files_passed: total_files.saturating_sub(files_failed),
+        files_failed,
+        mean_percent_valid: df.column("percent_valid")?.f64()?.mean(),
+        bands_flagged: flagged.height(),
+    })
+}
+
+/// Values of a band type that mark saturation or an undeclared fill, by GDAL type name
+///
+/// Returns the lower limit, if any, and the upper limit. Zero is an ordinary value
+/// (class 0, dark pixels), so unsigned types are only checked against their maximum,
+/// and Byte is not checked at all because 8-bit imagery routinely spans 0-255.
+fn dtype_limits(dtype: &str) -> Option<(Option<f64>, f64)> {
+    let limits = match dtype {
+        "Int8" => (Some(i8::MIN as f64), i8::MAX as f64),
+        "UInt16" => (None, u16::MAX as f64),
+        "Int16" => (Some(i16::MIN as f64), i16::MAX as f64),
+        "UInt32" => (None, u32::MAX as f64),
+        "Int32" => (Some(i32::MIN as f64), i32::MAX as f64),
+        "Float32" => (Some(f32::MIN as f64), f32::MAX as f64),
+        _ => return None,
+    };
+    Some(limits)
+}
+
+/// Problems that fail a `--strict` run, one message per offending band
+///
+/// A band is fatal when it has no valid pixels, when it holds NaNs while also declaring
+/// a numeric NoData value (two conflicting NoData conventions), or when its valid values
+/// reach the smallest or largest value of its data type, which marks values saturated by
+/// an earlier conversion or an undeclared NoData fill such as -32768 or 3.4e38. Zero in
+/// an unsigned band and the full range of a Byte band are not flagged.
+pub fn strict_violations(df: &DataFrame) -> Result<Vec<String>> {
+    let valid = df.column("valid_count")?.u64()?;
+    let nan = df.column("nan_count")?.u64()?;
+    let nodata = df.column("effective_nodata")?.str()?;
+    let dtype = df.column("dtype")?.str()?;
+    let min = df.column("min")?.cast(&DataType::Float64)?;
+    let min = min.f64()?;
+    let max = df.column("max")?.cast(&DataType::Float64)?;
+    let max = max.f64()?;
+
+    let mut violations = Vec::new();
+    for (row, band) in band_labels(df)?.iter().enumerate() {
+        if valid.get(row) == Some(0) {
+            violations.push(format!("{}: no valid pixels", band));
+        }
+        if nan.get(row).unwrap_or(0) > 0
+            && nodata.get(row).is_some_and(|n| n.starts_with("declared"))
+        {
+            violations.push(format!(
+                "{}: NaN values alongside {} NoData",
+                band,
+                nodata.get(row).unwrap_or_default()
+            ));
+        }
+        if let (Some(dtype), Some(min), Some(max)) = (dtype.get(row), min.get(row), max.get(row))
+            && let Some((lo, hi)) = dtype_limits(dtype)
+            && (lo.is_some_and(|lo| min <= lo) || max >= hi)
+        {
+            violations.push(format!(
+                "{}: values [{}, {}] reach the limits of the {} range [{}, {}]",
+                band,
+                min,
+                max,
+                dtype,
+                lo.unwrap_or(0.0),
+                hi
+            ));
+        }
+    }
+    Ok(violations)
+}
+
+/// The `--strict` problem with a raster that has no coordinate reference system, if any
+pub fn crs_violation(path: &Path) -> Option<String> {
+    match open_limit::open(to_vsi(path)) {
+        Ok((_permit, dataset)) if dataset.spatial_ref().is_ok() => None,
+        Ok(_) => Some(format!("{}: no CRS", path.display())),
+        Err(e) => Some(format!("{}: failed to read CRS: {}", path.display(), e)),
+    }
+}
+
+/// `<file> band <n> (<name>)` for each row of a QAQC table
+///
+/// Bands are numbered from the `band` column, or by position within each file for tables
+/// without one.
+pub(crate) fn band_labels(df: &DataFrame) -> Result<Vec<String>> {
+    let files = df.column("file")?.str()?;
+    let names = df.column("name")?.str()?;
+    let bands = match df.column("band") {
+        Ok(column) => Some(column.cast(&DataType::UInt32)?),
+        Err(_) => None,
+    };
+    let bands = bands.as_ref().map(|c| c.u32()).transpose()?;
+
+    let mut band_index: std::collections::HashMap<&str, usize> = Default::default();
+    let mut labels = Vec::with_capacity(df.height());
+    for row in 0..df.height() {
+        let file = files.get(row).unwrap_or_default();
+        let position = band_index.entry(file).or_insert(0);
+        *position += 1;
+        let index = bands
+            .and_then(|bands| bands.get(row))
+            .map_or(*position, |band| band as usize);
+        labels.push(match names.get(row) {
+            Some(name) if !name.is_empty() => format!("{} band {} ({})", file, index, name),
+            _ => format!("{} band {}", file, index),
+        });
+    }
+    Ok(labels)
+}
+
+/// Print `violations` and turn them into an error when there are any
+fn fail_on_violations(violations: &[String]) -> Result<()> {
+    if violations.is_empty() {
+        return Ok(());
+    }
+    eprintln!("Strict mode: {} problems found", violations.len());
+    for violation in violations {
+        eprintln!("  {}", violation);
+    }
+    Err(anyhow!(
+        "Strict mode: {} QAQC problems found",
+        violations.len()
+    ))
+}
+
+/// Picks `pct` percent of `files` (rounded up) at random, returned sorted
+///
+/// With a seed the same files are picked on every run, whatever order they were listed in.
+pub fn sample_files(mut files: Vec<PathBuf>, pct: f32, seed: Option<u64>) -> Vec<PathBuf> {
+    let n_sample = ((pct.clamp(0.0, 100.0) / 100.0) * files.len() as f32).ceil() as usize;
+    match seed {
+        Some(seed) => {
+            // Directory listing order varies between file systems, so fix it before shuffling
+            files.sort();
+            files.shuffle(&mut StdRng::seed_from_u64(seed));
+        }
+        None => files.shuffle(&mut rng()),
+    }
+    files.truncate(n_sample);
+    // Sorted so rows come out in (file, band) order whatever the scheduling
+    files.sort();
+    files
 }
 
 pub fn batch_qaqc(
     directory: &Path,
     pct_check: f32,
-    quantiles: bool,
-    output_format: OutputFormat,
+    output: &QaqcOutput,
+    batch_options: &BatchOptions,
+    options: &QaqcOptions,
 ) -> Result<()> {
+    if output.to_stdout && matches!(output.format, OutputFormat::Parquet) {
+        return Err(anyhow!(
+            "Parquet output cannot be written to stdout, use --output-format csv"
+        ));
+    }
+
+    options.check_percentiles()?;
+
     let pct = pct_check.clamp(0.0, 100.0);
-    let mut files: Vec<PathBuf> = WalkDir::new(directory)
+    let files: Vec<PathBuf> = WalkDir::new(directory)
         .into_iter()
         .filter_map(|e| e.ok())
         .filter(|e| e.file_type().is_file())
@@ -373,59 +1647,198 @@ pub fn batch_qaqc(
     if n_total == 0 {
         return Err(anyhow!("No files found"));
     }
-    let n_sample = ((pct / 100.0) * n_total as f32).ceil() as usize;
-    files.shuffle(&mut rng());
-    let sample_files = &files[..n_sample];
+    let sample_files = sample_files(files, pct, options.seed);
 
     let total = sample_files.len();
-    let counter = Arc::new(AtomicUsize::new(1));
+    let cache = options.cache.as_deref().map(StatsCache::load);
+    let progress =
+        ProgressReporter::new(total, batch_options.progress_json).with_bar(batch_options.quiet);
 
-    let dfs: Vec<LazyFrame> = sample_files
-        .par_iter()
-        .filter_map(|path| {
-            let current = counter.fetch_add(1, Ordering::SeqCst);
-            eprintln!(
-                "Processing file {}/{}: {:?}",
-                current,
-                total,
-                path.file_name()?
-            );
-            match compute_all_bands(path, quantiles) {
-                Ok(df) => Some(raster_stats_to_df(df, path)),
-                Err(_) => None, // skip failed files
-            }
-        })
-        .collect();
+    let results: Vec<_> = compute_bands_flattened(
+        &sample_files,
+        options,
+        cache.as_ref(),
+        &progress,
+        batch_options.fail_fast,
+    )
+    .into_iter()
+    .zip(&sample_files)
+    .map(|(result, path)| {
+        result
+            .and_then(|stats| {
+                if output.per_file_report {
+                    write_per_file_report(path, &stats, output.report_dir.as_deref())
+                        .map_err(|e| e.to_string())?;
+                }
+                Ok((path.clone(), stats))
+            })
+            .map_err(|e| (path.clone(), e))
+    })
+    .collect();
 
-    assert!(!dfs.is_empty(), "No input dataframes to concatenate.");
-    let mut result = concat(&dfs, UnionArgs::default())
-        .unwrap()
-        .collect()
-        .unwrap();
+    // Statistics are kept as structs until the table is built, see `concat_frames`
+    let mut file_stats: Vec<(PathBuf, Vec<RasterStats>)> = Vec::with_capacity(results.len());
+    let mut failed: Vec<(PathBuf, String)> = Vec::new();
+    for result in results {
+        match result {
+            Ok(file) => file_stats.push(file),
+            Err(err) => failed.push(err),
+        }
+    }
 
-    let ext = match output_format {
-        OutputFormat::Csv => "csv",
-        OutputFormat::Parquet => "parquet",
-    };
+    if batch_options.summarize_errors && !failed.is_empty() {
+        print_error_summary(&failed);
+    } else if !failed.is_empty() {
+        eprintln!("{}/{} files failed:", failed.len(), total);
+        for (path, e) in &failed {
+            eprintln!("- {}: {}", path.display(), e);
+        }
+    }
 
-    let path = directory.join(format!("qaqc.{}", ext));
-    let mut file = File::create(&path)?;
+    if let Some(cache) = &cache {
+        eprintln!(
+            "Reused cached statistics for {}/{} files",
+            cache.hits(),
+            total
+        );
+        if let Err(e) = cache.save() {
+            eprintln!("Warning: {}", e);
+        }
+    }
 
-    match output_format {
-        OutputFormat::Csv => CsvWriter::new(&mut file).finish(&mut result)?,
-        OutputFormat::Parquet => {
-            let _ = ParquetWriter::new(&mut file).finish(&mut result)?; // _ bc pq writer returns size & csv doesn't
+    if output.write_errors {
+        if output.to_stdout {
+            eprintln!("Errors are not written to a table with --stdout");
+        } else {
+            write_error_table(&failed, &table_path(directory, output), output.format)?;
         }
     }
 
-    println!("Wrote output to: {}", path.display());
+    if batch_options.fail_fast
+        && let Some((path, e)) = failed
+            .iter()
+            .find(|(_, e)| !e.contains(SKIPPED_AFTER_FAILURE))
+    {
+        return Err(anyhow!(
+            "Stopped at the first failure, {}: {}",
+            path.display(),
+            e
+        ));
+    }
+    if file_stats.is_empty() {
+        return Err(anyhow!(
+            "All {} files failed, so there is no QAQC table to write",
+            total
+        ));
+    }
+    let mut result = concat_frames(&file_stats, output.concat_chunksize, |(path, stats)| {
+        raster_stats_to_df(stats.clone(), path)
+    })?;
+    let rule_failures = apply_rules(&mut result, &output.rules)?;
 
-    Ok(())
+    let table_path = write_qaqc_table(&mut result, directory, output)?;
+    if options.histogram.is_some() {
+        write_histogram_table(&file_stats, table_path.as_deref(), output)?;
+    }
+
+    if let Some(path) = &output.summary_json {
+        let summary = qaqc_summary(&result, total, failed.len())?;
+        fs::write(path, serde_json::to_string_pretty(&summary)?)?;
+        eprintln!("Wrote summary to: {}", path.display());
+    }
+
+    if output.strict {
+        let mut violations: Vec<String> = failed
+            .iter()
+            .map(|(path, e)| format!("{}: failed to read: {}", path.display(), e))
+            .collect();
+        violations.extend(strict_violations(&result)?);
+        let read: Vec<&PathBuf> = sample_files
+            .iter()
+            .filter(|path| !failed.iter().any(|(failed, _)| failed == *path))
+            .collect();
+        let crs: Vec<Option<String>> = read.par_iter().map(|path| crs_violation(path)).collect();
+        violations.extend(crs.into_iter().flatten());
+        if let Err(e) = fail_on_violations(&violations) {
+            fail_on_rule_failures(&rule_failures)?;
+            return Err(e);
+        }
+    }
+
+    fail_on_rule_failures(&rule_failures)
+}
+
+/// Write the band stats of one file as a JSON array to `<file name>.qaqc.json`, next to the
+/// input or in `report_dir`, returning the report path
+pub fn write_per_file_report(
+    path: &Path,
+    stats: &[RasterStats],
+    report_dir: Option<&Path>,
+) -> Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} has no file name", path.display()))?;
+    let dir = match report_dir {
+        Some(dir) => {
+            fs::create_dir_all(dir)?;
+            dir
+        }
+        None if is_vsi(path) => {
+            return Err(anyhow!(
+                "Cannot write a report next to remote input {}; pass --report-dir",
+                path.display()
+            ));
+        }
+        None => path.parent().unwrap_or(Path::new(".")),
+    };
+    let mut report_name = file_name.to_os_string();
+    report_name.push(".qaqc.json");
+    let report_path = dir.join(report_name);
+    fs::write(&report_path, serde_json::to_string_pretty(stats)?)?;
+    Ok(report_path)
 }
 
-pub fn single_qaqc(path: &Path, quantiles: bool) -> Result<()> {
-    let stats = compute_all_bands(path, quantiles)?;
+pub fn single_qaqc(path: &Path, output: &QaqcOutput, options: &QaqcOptions) -> Result<()> {
+    options.check_percentiles()?;
+    let cache = options.cache.as_deref().map(StatsCache::load);
+    let stats = compute_all_bands_cached(path, options, cache.as_ref())?;
+    if let Some(cache) = &cache {
+        cache.save()?;
+    }
     // println!("{:#?}", stats);
-    print_all_bands(&stats);
-    Ok(())
+    if !output.to_stdout {
+        print_all_bands(&stats);
+    }
+    if output.per_file_report {
+        write_per_file_report(path, &stats, output.report_dir.as_deref())?;
+    }
+    let mask_failure = output.write_mask.as_deref().and_then(|mask_path| {
+        let e = write_validity_mask(path, mask_path, options).err()?;
+        eprintln!("Writing validity mask failed: {}", e);
+        Some(format!(
+            "{}: writing validity mask failed: {}",
+            path.display(),
+            e
+        ))
+    });
+    let mut df = raster_stats_to_df(stats.clone(), path)?.collect()?;
+    let rule_failures = apply_rules(&mut df, &output.rules)?;
+    if output.to_stdout || output.out.is_some() {
+        let parent = path.parent().unwrap_or(Path::new("."));
+        let table_path = write_qaqc_table(&mut df, parent, output)?;
+        if options.histogram.is_some() {
+            let file_stats = [(path.to_path_buf(), stats.clone())];
+            write_histogram_table(&file_stats, table_path.as_deref(), output)?;
+        }
+    }
+    if output.strict {
+        let mut violations = strict_violations(&df)?;
+        violations.extend(crs_violation(path));
+        violations.extend(mask_failure);
+        if let Err(e) = fail_on_violations(&violations) {
+            fail_on_rule_failures(&rule_failures)?;
+            return Err(e);
+        }
+    }
+    fail_on_rule_failures(&rule_failures)
 }
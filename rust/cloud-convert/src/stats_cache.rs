@@ -0,0 +1,118 @@
+use crate::rast_qaqc::{QaqcOptions, RasterStats};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheEntry {
+    hash: String,
+    options: String,
+    stats: Vec<RasterStats>,
+}
+
+/// On-disk cache of per-file band statistics, keyed by path, content hash and options
+///
+/// Entries are reused only when both the file's SHA-256 and the statistics options match,
+/// so editing a raster or changing e.g. `--quantiles` recomputes it.
+pub struct StatsCache {
+    path: PathBuf,
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    dirty: Mutex<bool>,
+    hits: AtomicUsize,
+}
+
+/// SHA-256 of a file's contents as a hex string
+pub fn file_hash(path: &Path) -> Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+impl StatsCache {
+    /// Load the cache at `path`, starting empty if it is missing or unreadable
+    pub fn load(path: &Path) -> Self {
+        let entries = match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+                eprintln!(
+                    "Warning: ignoring unreadable stats cache {}: {}",
+                    path.display(),
+                    e
+                );
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+        StatsCache {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+            dirty: Mutex::new(false),
+            hits: AtomicUsize::new(0),
+        }
+    }
+
+    fn key(file: &Path) -> String {
+        file.canonicalize()
+            .unwrap_or_else(|_| file.to_path_buf())
+            .display()
+            .to_string()
+    }
+
+    /// Cached statistics for `file` if its contents and `options` are unchanged
+    pub fn get(&self, file: &Path, hash: &str, options: &QaqcOptions) -> Option<Vec<RasterStats>> {
+        let entries = self.entries.lock().unwrap();
+        let stats = entries
+            .get(&Self::key(file))
+            .filter(|entry| entry.hash == hash && entry.options == options.cache_key())
+            .map(|entry| entry.stats.clone());
+        if stats.is_some() {
+            self.hits.fetch_add(1, Ordering::SeqCst);
+        }
+        stats
+    }
+
+    /// Number of files served from the cache so far
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::SeqCst)
+    }
+
+    pub fn insert(&self, file: &Path, hash: String, options: &QaqcOptions, stats: &[RasterStats]) {
+        self.entries.lock().unwrap().insert(
+            Self::key(file),
+            CacheEntry {
+                hash,
+                options: options.cache_key(),
+                stats: stats.to_vec(),
+            },
+        );
+        *self.dirty.lock().unwrap() = true;
+    }
+
+    /// Write the cache back to disk if anything changed
+    pub fn save(&self) -> Result<()> {
+        if !*self.dirty.lock().unwrap() {
+            return Ok(());
+        }
+        let json = serde_json::to_string(&*self.entries.lock().unwrap())?;
+        fs::write(&self.path, json)
+            .with_context(|| format!("Failed to write stats cache {}", self.path.display()))?;
+        Ok(())
+    }
+}
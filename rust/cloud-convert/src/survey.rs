@@ -0,0 +1,332 @@
+use crate::datainfo::get_datainfo;
+use crate::open_limit;
+use crate::rast_qaqc::OutputFormat;
+use crate::tempdir::{INTERMEDIATE_SUFFIXES, PARTIAL, STDOUT};
+use anyhow::{Result, anyhow};
+use gdal::Metadata;
+use gdal::spatial_ref::{AxisMappingStrategy, CoordTransform, SpatialRef};
+use polars::prelude::*;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+// Plain JSON is left out: the stats cache, reports and manifests the tool writes into surveyed
+// trees use it. Parquet is surveyed only when it is GeoParquet (see `is_geoparquet`).
+const SURVEY_EXTENSIONS: &[&str] = &[
+    "tif", "tiff", "tff", "asc", "img", "vrt", "nc", "gpkg", "geojson", "fgb", "kml", "gpx", "shp",
+    "parquet",
+];
+
+/// Whether `path` is an intermediate file an interrupted conversion left behind (see
+/// `tempdir::intermediate_path`) rather than a dataset to survey
+fn is_intermediate(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+    let stem = path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_ascii_lowercase();
+    INTERMEDIATE_SUFFIXES
+        .iter()
+        .any(|suffix| name.ends_with(&format!(".{}", suffix)))
+        || [PARTIAL, STDOUT]
+            .iter()
+            .any(|suffix| stem.ends_with(&format!(".{}", suffix)))
+}
+
+/// Whether a Parquet file carries GeoParquet `geo` metadata
+///
+/// The QAQC and survey tables the tool writes are plain Parquet and are skipped. A file GDAL
+/// cannot open is kept, so the survey lists it as unreadable.
+fn is_geoparquet(path: &Path) -> bool {
+    let Ok((_permit, dataset)) = open_limit::open(path) else {
+        return true;
+    };
+    dataset
+        .layer(0)
+        .is_ok_and(|layer| layer.metadata_item("geo", "_PARQUET_METADATA_").is_some())
+}
+
+/// One raster, or one layer of a vector file, found by [`survey`]
+#[derive(Debug, Clone, Serialize)]
+pub struct SurveyItem {
+    pub path: PathBuf,
+    /// Layer name for vector files
+    pub layer: Option<String>,
+    /// CRS authority code, PROJ string or name; "Unknown" when missing
+    pub crs: String,
+    /// Pixel size in CRS units, rasters only
+    pub resolution: Option<(f64, f64)>,
+    /// `[xmin, ymin, xmax, ymax]` in the item's CRS
+    pub bounds: Option<[f64; 4]>,
+    /// `bounds` transformed to WGS 84 longitude/latitude, so extents can be compared
+    pub bounds_wgs84: Option<[f64; 4]>,
+}
+
+/// Items sharing a CRS and resolution
+#[derive(Debug, Clone, Serialize)]
+pub struct SurveyGroup {
+    pub crs: String,
+    pub resolution: Option<(f64, f64)>,
+    pub count: usize,
+    /// Union of the members' WGS 84 bounds
+    pub extent_wgs84: Option<[f64; 4]>,
+}
+
+/// Rollup of the CRSs, resolutions and extents across a directory tree
+#[derive(Debug, Clone, Serialize)]
+pub struct Survey {
+    pub items: Vec<SurveyItem>,
+    pub unreadable: Vec<(PathBuf, String)>,
+    /// Grouped by CRS and resolution, most common first
+    pub groups: Vec<SurveyGroup>,
+    pub distinct_crs: usize,
+    pub distinct_resolutions: usize,
+    /// Items whose WGS 84 extent overlaps at least one other item's
+    pub overlapping: usize,
+    /// Union of all WGS 84 extents
+    pub union_extent: Option<[f64; 4]>,
+}
+
+/// Round a resolution so float noise such as 0.008333333333 vs 0.0083333333333 groups together
+fn round_resolution((x, y): (f64, f64)) -> (f64, f64) {
+    let round = |v: f64| (v * 1e9).round() / 1e9;
+    (round(x), round(y))
+}
+
+fn union(a: Option<[f64; 4]>, b: Option<[f64; 4]>) -> Option<[f64; 4]> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some([
+            a[0].min(b[0]),
+            a[1].min(b[1]),
+            a[2].max(b[2]),
+            a[3].max(b[3]),
+        ]),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Whether two extents share some area; extents that only touch do not overlap
+fn overlaps(a: &[f64; 4], b: &[f64; 4]) -> bool {
+    a[0] < b[2] && b[0] < a[2] && a[1] < b[3] && b[1] < a[3]
+}
+
+fn to_wgs84(crs_id: Option<&str>, bounds: Option<[f64; 4]>) -> Option<[f64; 4]> {
+    let bounds = bounds?;
+    let mut source = SpatialRef::from_definition(crs_id?).ok()?;
+    source.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+    let mut target = SpatialRef::from_epsg(4326).ok()?;
+    target.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+    CoordTransform::new(&source, &target)
+        .and_then(|ct| ct.transform_bounds(&bounds, 21))
+        .ok()
+}
+
+/// Collect the CRS, resolution and bounds of every geospatial file under `directory`
+///
+/// Files are read in parallel with [`get_datainfo`]. Extents are compared in WGS 84, so
+/// items whose CRS cannot be transformed are left out of the overlap count and union.
+pub fn survey(directory: &Path) -> Result<Survey> {
+    let mut files: Vec<PathBuf> = WalkDir::new(directory)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.into_path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| SURVEY_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+                && !is_intermediate(path)
+        })
+        .collect();
+    if files.is_empty() {
+        return Err(anyhow!("No files found"));
+    }
+    files.sort();
+
+    let results: Vec<Result<Vec<SurveyItem>, (PathBuf, String)>> = files
+        .par_iter()
+        .filter(|path| {
+            !path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("parquet"))
+                || is_geoparquet(path)
+        })
+        .map(|path| {
+            let info = get_datainfo(path).map_err(|e| (path.clone(), e.to_string()))?;
+            let items = match info.layers {
+                Some(layers) => layers
+                    .into_iter()
+                    .map(|layer| SurveyItem {
+                        path: path.clone(),
+                        bounds_wgs84: to_wgs84(layer.crs_id.as_deref(), layer.bounds),
+                        layer: Some(layer.name),
+                        crs: layer.crs_id.or(layer.crs).unwrap_or("Unknown".to_string()),
+                        resolution: None,
+                        bounds: layer.bounds,
+                    })
+                    .collect(),
+                None => vec![SurveyItem {
+                    path: path.clone(),
+                    layer: None,
+                    bounds_wgs84: to_wgs84(info.crs_id.as_deref(), info.bounds),
+                    crs: info.crs_id.or(info.crs).unwrap_or("Unknown".to_string()),
+                    resolution: info.resolution.map(round_resolution),
+                    bounds: info.bounds,
+                }],
+            };
+            Ok(items)
+        })
+        .collect();
+
+    let mut items = Vec::new();
+    let mut unreadable = Vec::new();
+    for result in results {
+        match result {
+            Ok(file_items) => items.extend(file_items),
+            Err(e) => unreadable.push(e),
+        }
+    }
+
+    let mut grouped: BTreeMap<(String, Option<(u64, u64)>), SurveyGroup> = BTreeMap::new();
+    for item in &items {
+        let key = (
+            item.crs.clone(),
+            item.resolution.map(|(x, y)| (x.to_bits(), y.to_bits())),
+        );
+        let group = grouped.entry(key).or_insert_with(|| SurveyGroup {
+            crs: item.crs.clone(),
+            resolution: item.resolution,
+            count: 0,
+            extent_wgs84: None,
+        });
+        group.count += 1;
+        group.extent_wgs84 = union(group.extent_wgs84, item.bounds_wgs84);
+    }
+    let mut groups: Vec<SurveyGroup> = grouped.into_values().collect();
+    groups.sort_by_key(|g| std::cmp::Reverse(g.count));
+
+    let mut crs_names: Vec<&str> = items.iter().map(|i| i.crs.as_str()).collect();
+    crs_names.sort();
+    crs_names.dedup();
+    let mut resolutions: Vec<(u64, u64)> = items
+        .iter()
+        .filter_map(|i| i.resolution)
+        .map(|(x, y)| (x.to_bits(), y.to_bits()))
+        .collect();
+    resolutions.sort();
+    resolutions.dedup();
+
+    let extents: Vec<[f64; 4]> = items.iter().filter_map(|i| i.bounds_wgs84).collect();
+    let overlapping = (0..extents.len())
+        .into_par_iter()
+        .filter(|&i| {
+            extents
+                .iter()
+                .enumerate()
+                .any(|(j, other)| i != j && overlaps(&extents[i], other))
+        })
+        .count();
+    let union_extent = extents.iter().fold(None, |acc, e| union(acc, Some(*e)));
+
+    Ok(Survey {
+        distinct_crs: crs_names.len(),
+        distinct_resolutions: resolutions.len(),
+        items,
+        unreadable,
+        groups,
+        overlapping,
+        union_extent,
+    })
+}
+
+/// Survey groups as a table with one row per CRS and resolution
+pub fn survey_groups_df(survey: &Survey) -> Result<DataFrame> {
+    let groups = &survey.groups;
+    let extent = |i: usize| -> Vec<Option<f64>> {
+        groups
+            .iter()
+            .map(|g| g.extent_wgs84.map(|e| e[i]))
+            .collect()
+    };
+    let df = DataFrame::new(vec![
+        Column::new(
+            "crs".into(),
+            groups.iter().map(|g| g.crs.clone()).collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "resolution_x".into(),
+            groups
+                .iter()
+                .map(|g| g.resolution.map(|r| r.0))
+                .collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "resolution_y".into(),
+            groups
+                .iter()
+                .map(|g| g.resolution.map(|r| r.1))
+                .collect::<Vec<_>>(),
+        ),
+        Column::new(
+            "count".into(),
+            groups.iter().map(|g| g.count as u64).collect::<Vec<_>>(),
+        ),
+        Column::new("xmin_wgs84".into(), extent(0)),
+        Column::new("ymin_wgs84".into(), extent(1)),
+        Column::new("xmax_wgs84".into(), extent(2)),
+        Column::new("ymax_wgs84".into(), extent(3)),
+    ])?;
+    Ok(df)
+}
+
+/// Print the survey rollup and write the grouped table to `output_path`
+pub fn write_survey(survey: &Survey, output_path: &Path, format: OutputFormat) -> Result<()> {
+    println!(
+        "{} items, {} distinct CRSs, {} distinct resolutions",
+        survey.items.len(),
+        survey.distinct_crs,
+        survey.distinct_resolutions
+    );
+    println!("{} items overlap another item's extent", survey.overlapping);
+    match survey.union_extent {
+        Some([xmin, ymin, xmax, ymax]) => println!(
+            "Union extent (WGS 84): [{}, {}, {}, {}]",
+            xmin, ymin, xmax, ymax
+        ),
+        None => println!("Union extent (WGS 84): Unknown"),
+    }
+    println!("{:>6}  {:<24}  Resolution", "Items", "CRS");
+    for group in &survey.groups {
+        let resolution = match group.resolution {
+            Some((x, y)) => format!("{} x {}", x, y),
+            None => "-".to_string(),
+        };
+        println!("{:>6}  {:<24}  {}", group.count, group.crs, resolution);
+    }
+    if !survey.unreadable.is_empty() {
+        eprintln!("{} files could not be read:", survey.unreadable.len());
+        for (path, err) in &survey.unreadable {
+            eprintln!("- {}: {}", path.display(), err);
+        }
+    }
+
+    let mut df = survey_groups_df(survey)?;
+    let mut file = File::create(output_path)?;
+    match format {
+        OutputFormat::Csv => CsvWriter::new(&mut file).finish(&mut df)?,
+        OutputFormat::Parquet => {
+            ParquetWriter::new(&mut file).finish(&mut df)?;
+        }
+    }
+    eprintln!("Wrote survey to: {}", output_path.display());
+    Ok(())
+}
@@ -0,0 +1,145 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static TEMP_DIR: OnceLock<PathBuf> = OnceLock::new();
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// Suffixes passed to [`intermediate_path`], one per kind of intermediate file
+/// A finished output under its temporary name, before it is moved into place
+pub const PARTIAL: &str = "partial";
+/// A vector conversion streamed to stdout, before it is copied out
+pub const STDOUT: &str = "stdout";
+pub const PARTIAL_TIF: &str = "partial.tif";
+pub const WARP_TIF: &str = "warp.tif";
+pub const UNITS_TIF: &str = "units.tif";
+pub const RGB_TIF: &str = "rgb.tif";
+pub const MASK_TIF: &str = "mask.tif";
+pub const MATH_TIF: &str = "math.tif";
+pub const VALIDITY_TIF: &str = "validity.tif";
+pub const BAND_VRT: &str = "band.vrt";
+/// A Zarr store, a directory, under its temporary name
+pub const PARTIAL_ZARR: &str = "partial.zarr";
+
+/// Every fixed intermediate suffix; vector outputs use [`PARTIAL`] and [`STDOUT`] followed by
+/// their own extension instead
+pub const INTERMEDIATE_SUFFIXES: &[&str] = &[
+    PARTIAL_TIF,
+    WARP_TIF,
+    UNITS_TIF,
+    RGB_TIF,
+    MASK_TIF,
+    MATH_TIF,
+    VALIDITY_TIF,
+    BAND_VRT,
+    PARTIAL_ZARR,
+];
+
+/// Direct intermediate files to `dir` for the rest of the process
+pub fn set_temp_dir(dir: &Path) -> Result<(), String> {
+    fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create temp directory {}: {}", dir.display(), e))?;
+    TEMP_DIR
+        .set(dir.to_path_buf())
+        .map_err(|_| "Temp directory is already set".to_string())
+}
+
+/// The configured temp directory: `--temp-dir`, then `TMPDIR`, otherwise none
+pub fn temp_dir() -> Option<PathBuf> {
+    TEMP_DIR
+        .get()
+        .cloned()
+        .or_else(|| env::var_os("TMPDIR").map(PathBuf::from))
+}
+
+/// Path for an intermediate file derived from `target`
+///
+/// Without a temp directory the file sits next to `target` (as `<stem>.<suffix>`), so it
+/// shares the output's filesystem. With one, a unique name inside that directory is used.
+pub fn intermediate_path(target: &Path, suffix: &str) -> PathBuf {
+    match temp_dir() {
+        Some(dir) => {
+            let stem = target.file_stem().unwrap_or_default().to_string_lossy();
+            let id = COUNTER.fetch_add(1, Ordering::SeqCst);
+            dir.join(format!("{}.{}-{}.{}", stem, process::id(), id, suffix))
+        }
+        None => target.with_extension(suffix),
+    }
+}
+
+/// Move a finished file from `from` to `target`, replacing any file already there
+///
+/// A rename is atomic when both paths are on one filesystem. A temp directory on another
+/// filesystem cannot be renamed across, so the file is copied next to `target` first and that
+/// copy renamed over it; either way `target` never holds a partly written file.
+pub fn move_into_place(from: &Path, target: &Path) -> Result<(), String> {
+    let failed = |e: io::Error| {
+        format!(
+            "Failed to move {} to {}: {}",
+            from.display(),
+            target.display(),
+            e
+        )
+    };
+    match fs::rename(from, target) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            let mut staging = target.as_os_str().to_os_string();
+            staging.push(".");
+            staging.push(PARTIAL);
+            let staging = PathBuf::from(staging);
+            if let Err(e) = fs::copy(from, &staging).and_then(|_| fs::rename(&staging, target)) {
+                let _ = fs::remove_file(&staging);
+                return Err(failed(e));
+            }
+            let _ = fs::remove_file(from);
+            Ok(())
+        }
+        Err(e) => Err(failed(e)),
+    }
+}
+
+/// An intermediate file (or directory) at [`intermediate_path`] that is deleted, with any
+/// sidecars GDAL wrote next to it, when the guard is dropped
+///
+/// A conversion that fails part way returns early, and the guard still cleans up after it.
+/// Create the guard before opening a dataset on its path, so the dataset is closed first.
+#[derive(Debug)]
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    pub fn new(target: &Path, suffix: &str) -> Self {
+        TempFile(intermediate_path(target, suffix))
+    }
+
+    /// A guard for `<stem>.<suffix>` next to `target`, ignoring the temp directory
+    ///
+    /// For directories such as Zarr stores, which [`move_into_place`] cannot copy across
+    /// filesystems and so must be renamed within the output's own.
+    pub fn beside(target: &Path, suffix: &str) -> Self {
+        TempFile(target.with_extension(suffix))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+        let _ = fs::remove_file(&self.0);
+        for sidecar in ["aux.xml", "msk"] {
+            let mut path = self.0.clone().into_os_string();
+            path.push(".");
+            path.push(sidecar);
+            let _ = fs::remove_file(path);
+        }
+    }
+}
@@ -1,80 +1,2034 @@
-use gdal::Dataset;
-use gdal::DriverManager;
-use gdal::raster::RasterCreationOptions;
-use std::path::Path;
+use crate::crs_authority::{AuthorityCheck, check_output_authority, identify_epsg};
+use crate::gdal_options::merge_creation_options;
+use crate::open_limit;
+use crate::progress::status;
+use crate::rast_qaqc::percentile;
+use crate::tempdir::{
+    BAND_VRT, MASK_TIF, PARTIAL_TIF, RGB_TIF, TempFile, UNITS_TIF, WARP_TIF, move_into_place,
+};
+use crate::units::{UnitConversion, canonical_unit};
+use crate::vsi::{is_vsi, to_vsi};
+use gdal::programs::raster::{BuildVRTOptions, build_vrt};
+use gdal::raster::{
+    Buffer, ColorEntry, ColorInterpretation, ColorTable, GdalDataType, PaletteInterpretation,
+    RasterBand, RasterCreationOptions, ResampleAlg,
+};
+use gdal::spatial_ref::{AxisMappingStrategy, CoordTransform, SpatialRef};
+use gdal::{Dataset, GeoTransform, Metadata};
+use gdal::{Driver, DriverManager};
+use gdal_sys::{CPLErr, GDALResampleAlg};
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::str::FromStr;
 
-pub fn tif_to_cog(
-    input_path: &Path,
-    output_path: Option<&Path>,
-    overwrite: bool,
-) -> Result<String, String> {
-    // Check if the input file exists
-    if !input_path.exists() {
-        return Err(format!("Error: The file {:?} does not exist.", input_path));
+/// Output band data types supported for COG conversion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutDtype {
+    Byte,
+    UInt16,
+    Int16,
+    Float32,
+    Float64,
+}
+
+impl OutDtype {
+    pub fn gdal_type(&self) -> GdalDataType {
+        match self {
+            OutDtype::Byte => GdalDataType::UInt8,
+            OutDtype::UInt16 => GdalDataType::UInt16,
+            OutDtype::Int16 => GdalDataType::Int16,
+            OutDtype::Float32 => GdalDataType::Float32,
+            OutDtype::Float64 => GdalDataType::Float64,
+        }
     }
 
-    let out_path = match output_path {
-        Some(path) => {
-            // Warn if the output file exists and will be overwritten
-            if path.exists() && !overwrite {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutDtype::Byte => "Byte",
+            OutDtype::UInt16 => "UInt16",
+            OutDtype::Int16 => "Int16",
+            OutDtype::Float32 => "Float32",
+            OutDtype::Float64 => "Float64",
+        }
+    }
+
+    /// Representable value range of the type
+    pub fn range(&self) -> (f64, f64) {
+        match self {
+            OutDtype::Byte => (u8::MIN as f64, u8::MAX as f64),
+            OutDtype::UInt16 => (u16::MIN as f64, u16::MAX as f64),
+            OutDtype::Int16 => (i16::MIN as f64, i16::MAX as f64),
+            OutDtype::Float32 => (f32::MIN as f64, f32::MAX as f64),
+            OutDtype::Float64 => (f64::MIN, f64::MAX),
+        }
+    }
+}
+
+impl FromStr for OutDtype {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "byte" | "uint8" => Ok(OutDtype::Byte),
+            "uint16" => Ok(OutDtype::UInt16),
+            "int16" => Ok(OutDtype::Int16),
+            "float32" => Ok(OutDtype::Float32),
+            "float64" => Ok(OutDtype::Float64),
+            other => Err(format!(
+                "Unsupported output data type '{}'. Use Byte, UInt16, Int16, Float32 or Float64.",
+                other
+            )),
+        }
+    }
+}
+
+/// Band color interpretations that can be assigned with `--color-interp`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorInterp {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    Gray,
+    Undefined,
+}
+
+impl ColorInterp {
+    pub fn gdal_interp(&self) -> ColorInterpretation {
+        match self {
+            ColorInterp::Red => ColorInterpretation::RedBand,
+            ColorInterp::Green => ColorInterpretation::GreenBand,
+            ColorInterp::Blue => ColorInterpretation::BlueBand,
+            ColorInterp::Alpha => ColorInterpretation::AlphaBand,
+            ColorInterp::Gray => ColorInterpretation::GrayIndex,
+            ColorInterp::Undefined => ColorInterpretation::Undefined,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorInterp::Red => "red",
+            ColorInterp::Green => "green",
+            ColorInterp::Blue => "blue",
+            ColorInterp::Alpha => "alpha",
+            ColorInterp::Gray => "gray",
+            ColorInterp::Undefined => "undefined",
+        }
+    }
+}
+
+impl FromStr for ColorInterp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "red" => Ok(ColorInterp::Red),
+            "green" => Ok(ColorInterp::Green),
+            "blue" => Ok(ColorInterp::Blue),
+            "alpha" => Ok(ColorInterp::Alpha),
+            "gray" | "grey" => Ok(ColorInterp::Gray),
+            "undefined" => Ok(ColorInterp::Undefined),
+            other => Err(format!(
+                "Unsupported color interpretation '{}'. Use red, green, blue, alpha, gray or undefined.",
+                other
+            )),
+        }
+    }
+}
+
+/// Compression codec for COG output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    #[default]
+    Lzw,
+    Deflate,
+    Zstd,
+    /// Lossy WEBP, only for Byte rasters with 1, 3 or 4 bands
+    Webp,
+    /// LERC, lossless with GDAL's default MAX_Z_ERROR of 0
+    Lerc,
+    None,
+}
+
+impl Compression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Compression::Lzw => "LZW",
+            Compression::Deflate => "DEFLATE",
+            Compression::Zstd => "ZSTD",
+            Compression::Webp => "WEBP",
+            Compression::Lerc => "LERC",
+            Compression::None => "NONE",
+        }
+    }
+
+    /// Creation option that sets the level, with its valid range and GDAL's default
+    fn level_option(&self) -> Option<(&'static str, u8, u8, u8)> {
+        match self {
+            Compression::Lzw => None,
+            // 10-12 need GDAL built with libdeflate
+            Compression::Deflate => Some(("LEVEL", 1, 12, 6)),
+            Compression::Zstd => Some(("ZSTD_LEVEL", 1, 22, 9)),
+            Compression::Webp => Some(("QUALITY", 1, 100, 75)),
+            Compression::Lerc | Compression::None => None,
+        }
+    }
+
+    /// PREDICTOR creation option, for the codecs that support one
+    pub fn predictor_option(&self, predictor: Predictor) -> Result<String, String> {
+        match self {
+            Compression::Lzw | Compression::Deflate | Compression::Zstd => {
+                Ok(format!("PREDICTOR={}", predictor.name()))
+            }
+            _ => Err(format!(
+                "{} compression does not take a --predictor; use LZW, DEFLATE or ZSTD",
+                self.name()
+            )),
+        }
+    }
+
+    /// COMPRESS and level creation options, with `level` checked against the codec's range
+    ///
+    /// Returns the options and the effective level (GDAL's default when `level` is unset).
+    pub fn creation_options(&self, level: Option<u8>) -> Result<(Vec<String>, Option<u8>), String> {
+        let mut opts = vec![format!("COMPRESS={}", self.name())];
+        match (self.level_option(), level) {
+            (None, None) => Ok((opts, None)),
+            (None, Some(_)) => Err(format!(
+                "{} compression does not take a --compression-level",
+                self.name()
+            )),
+            (Some((key, min, max, default)), level) => {
+                let level = level.unwrap_or(default);
+                if !(min..=max).contains(&level) {
+                    return Err(format!(
+                        "{} level must be between {} and {}, got {}",
+                        self.name(),
+                        min,
+                        max,
+                        level
+                    ));
+                }
+                opts.push(format!("{}={}", key, level));
+                Ok((opts, Some(level)))
+            }
+        }
+    }
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "lzw" => Ok(Compression::Lzw),
+            "deflate" => Ok(Compression::Deflate),
+            "zstd" => Ok(Compression::Zstd),
+            "webp" => Ok(Compression::Webp),
+            "lerc" => Ok(Compression::Lerc),
+            "none" => Ok(Compression::None),
+            other => Err(format!(
+                "Unsupported compression '{}'. Use LZW, DEFLATE, ZSTD, LERC, WEBP or NONE.",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Compression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// TIFF predictor applied before compression
+///
+/// Horizontal differencing (`Standard`) helps integer rasters; `FloatingPoint` only applies
+/// to Float32/Float64 bands and usually compresses smooth climate or elevation data best.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Predictor {
+    None,
+    Standard,
+    FloatingPoint,
+}
+
+impl Predictor {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Predictor::None => "NO",
+            Predictor::Standard => "STANDARD",
+            Predictor::FloatingPoint => "FLOATING_POINT",
+        }
+    }
+}
+
+impl FromStr for Predictor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('_', "-").as_str() {
+            "1" | "no" | "none" => Ok(Predictor::None),
+            "2" | "standard" | "horizontal" => Ok(Predictor::Standard),
+            "3" | "floating-point" | "float" => Ok(Predictor::FloatingPoint),
+            other => Err(format!(
+                "Unsupported predictor '{}'. Use none (1), standard (2) or floating-point (3).",
+                other
+            )),
+        }
+    }
+}
+
+/// Resampling used to build COG overviews
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverviewResampling {
+    Nearest,
+    Bilinear,
+    Cubic,
+    Average,
+    /// Most frequent value, for categorical rasters
+    Mode,
+    Gauss,
+}
+
+impl OverviewResampling {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OverviewResampling::Nearest => "NEAREST",
+            OverviewResampling::Bilinear => "BILINEAR",
+            OverviewResampling::Cubic => "CUBIC",
+            OverviewResampling::Average => "AVERAGE",
+            OverviewResampling::Mode => "MODE",
+            OverviewResampling::Gauss => "GAUSS",
+        }
+    }
+}
+
+impl FromStr for OverviewResampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "nearest" => Ok(OverviewResampling::Nearest),
+            "bilinear" => Ok(OverviewResampling::Bilinear),
+            "cubic" => Ok(OverviewResampling::Cubic),
+            "average" => Ok(OverviewResampling::Average),
+            "mode" => Ok(OverviewResampling::Mode),
+            "gauss" => Ok(OverviewResampling::Gauss),
+            other => Err(format!(
+                "Unsupported overview resampling '{}'. Use nearest, bilinear, cubic, average, mode or gauss.",
+                other
+            )),
+        }
+    }
+}
+
+/// Resampling used when warping onto a `--reference` grid or a `--t-srs` CRS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarpResampling {
+    Nearest,
+    Bilinear,
+    Cubic,
+    CubicSpline,
+    Lanczos,
+    Average,
+    /// Most frequent value, for categorical rasters
+    Mode,
+}
+
+impl WarpResampling {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WarpResampling::Nearest => "nearest",
+            WarpResampling::Bilinear => "bilinear",
+            WarpResampling::Cubic => "cubic",
+            WarpResampling::CubicSpline => "cubicspline",
+            WarpResampling::Lanczos => "lanczos",
+            WarpResampling::Average => "average",
+            WarpResampling::Mode => "mode",
+        }
+    }
+
+    fn gdal_alg(&self) -> GDALResampleAlg::Type {
+        match self {
+            WarpResampling::Nearest => GDALResampleAlg::GRA_NearestNeighbour,
+            WarpResampling::Bilinear => GDALResampleAlg::GRA_Bilinear,
+            WarpResampling::Cubic => GDALResampleAlg::GRA_Cubic,
+            WarpResampling::CubicSpline => GDALResampleAlg::GRA_CubicSpline,
+            WarpResampling::Lanczos => GDALResampleAlg::GRA_Lanczos,
+            WarpResampling::Average => GDALResampleAlg::GRA_Average,
+            WarpResampling::Mode => GDALResampleAlg::GRA_Mode,
+        }
+    }
+
+    /// Bilinear for floating-point data and nearest otherwise, since integer bands are often
+    /// class codes that must not be blended
+    pub fn default_for(band_type: GdalDataType) -> Self {
+        if matches!(band_type, GdalDataType::Float32 | GdalDataType::Float64) {
+            WarpResampling::Bilinear
+        } else {
+            WarpResampling::Nearest
+        }
+    }
+}
+
+impl FromStr for WarpResampling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "nearest" | "near" => Ok(WarpResampling::Nearest),
+            "bilinear" => Ok(WarpResampling::Bilinear),
+            "cubic" => Ok(WarpResampling::Cubic),
+            "cubicspline" => Ok(WarpResampling::CubicSpline),
+            "lanczos" => Ok(WarpResampling::Lanczos),
+            "average" => Ok(WarpResampling::Average),
+            "mode" => Ok(WarpResampling::Mode),
+            other => Err(format!(
+                "Unsupported resampling '{}'. Use nearest, bilinear, cubic, cubicspline, lanczos, average or mode.",
+                other
+            )),
+        }
+    }
+}
+
+/// Whether a COG is written as BigTIFF, which lifts the 4 GiB limit of classic TIFF
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BigTiff {
+    Yes,
+    No,
+    /// BigTIFF when [`estimated_cog_bytes`] is above [`BIGTIFF_THRESHOLD`]
+    #[default]
+    IfNeeded,
+}
+
+impl BigTiff {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BigTiff::Yes => "YES",
+            BigTiff::No => "NO",
+            BigTiff::IfNeeded => "IF_NEEDED",
+        }
+    }
+}
+
+impl FromStr for BigTiff {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "yes" => Ok(BigTiff::Yes),
+            "no" => Ok(BigTiff::No),
+            "if_needed" => Ok(BigTiff::IfNeeded),
+            other => Err(format!(
+                "Unsupported BigTIFF setting '{}'. Use yes, no or if_needed.",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for BigTiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name().to_lowercase())
+    }
+}
+
+/// Estimated size above which `--bigtiff if_needed` writes BigTIFF
+///
+/// Below the 4 GiB classic TIFF limit, to leave room for headers and tile index tables.
+pub const BIGTIFF_THRESHOLD: u64 = 3_500_000_000;
+
+/// Uncompressed size of `dataset` written as a COG, counting a third extra for overviews
+///
+/// Compression usually makes the file smaller, so this errs towards BigTIFF.
+pub fn estimated_cog_bytes(dataset: &Dataset) -> u64 {
+    let (cols, rows) = dataset.raster_size();
+    let band_bytes: u64 = dataset
+        .rasterbands()
+        .filter_map(|band| band.ok())
+        .map(|band| band.band_type().bits() as u64 / 8)
+        .sum();
+    let full = cols as u64 * rows as u64 * band_bytes;
+    full + full / 3
+}
+
+/// Optional settings applied when writing a COG
+#[derive(Debug, Clone, Default)]
+pub struct CogOptions {
+    /// Convert the band data type on output
+    pub out_dtype: Option<OutDtype>,
+    /// Saturate values outside the output type range instead of failing
+    pub clamp: bool,
+    /// Stop building overviews once the smaller side would drop below this many pixels
+    pub min_overview_size: Option<usize>,
+    /// Overview resampling; the COG driver uses CUBIC (NEAREST for paletted data) when unset
+    pub overview_resampling: Option<OverviewResampling>,
+    /// Internal tile size in pixels, 512 when unset; see [`check_blocksize`]
+    pub blocksize: Option<usize>,
+    /// Write a `<output>.png` preview whose longer side is roughly this many pixels
+    pub thumbnail: Option<usize>,
+    /// Apply a 2-98% percentile stretch to the thumbnail instead of a min/max stretch
+    pub thumbnail_stretch: bool,
+    /// Warp the input onto this raster's CRS, origin, pixel size and dimensions
+    pub reference: Option<PathBuf>,
+    /// Replace the NoData value with an internal per-dataset mask
+    pub internal_mask: bool,
+    /// Compression codec, LZW by default
+    pub compression: Compression,
+    /// Codec level: ZSTD_LEVEL for ZSTD, LEVEL for DEFLATE, QUALITY for WEBP
+    pub compression_level: Option<u8>,
+    /// Predictor for LZW, DEFLATE and ZSTD
+    pub predictor: Option<Predictor>,
+    /// Palette file (`value R G B [A]` per line) to attach to a single-band input
+    pub color_table: Option<PathBuf>,
+    /// With `color_table`, write explicit RGB bands instead of a paletted band
+    pub force_rgb: bool,
+    /// Rescale values between units, e.g. K to degC
+    pub convert_units: Option<UnitConversion>,
+    /// Reproject to this CRS (e.g. EPSG:4326), keeping roughly the input pixel count
+    pub t_srs: Option<String>,
+    /// Resampling for `reference` and `t_srs`; see [`WarpResampling::default_for`] when unset
+    pub resampling: Option<WarpResampling>,
+    /// Output NoData value; pixels equal to the source NoData are rewritten to it
+    pub nodata: Option<f64>,
+    /// Remove the NoData flag from every output band, keeping the pixel values
+    pub clear_nodata: bool,
+    /// Extra COG driver creation options (`--co`), replacing any the tool sets with the same key
+    pub creation_options: Vec<(String, String)>,
+    /// Write BigTIFF always, never, or when the output is estimated to need it
+    pub bigtiff: BigTiff,
+    /// Leave blocks that are entirely NoData unwritten (SPARSE_OK=TRUE)
+    pub sparse: bool,
+    /// Stamp and/or require an authority code on the output CRS
+    pub crs_authority: AuthorityCheck,
+    /// Color interpretation of each output band, in band order
+    pub color_interp: Option<Vec<ColorInterp>>,
+    /// Reopen the output and check it is laid out as a COG; see [`validate_cog`]
+    pub verify: bool,
+    /// Only convert these 1-based bands, in this order; see [`parse_band_list`]
+    pub bands: Option<Vec<usize>>,
+}
+
+/// Parse a band selection such as `1-10,50` into 1-based band indices, in the order listed
+pub fn parse_band_list(spec: &str) -> Result<Vec<usize>, String> {
+    let invalid = |part: &str| {
+        format!(
+            "Invalid band '{}' in '{}'. Use 1-based indices or ranges, e.g. 1-10,50.",
+            part, spec
+        )
+    };
+    let mut bands = Vec::new();
+    for part in spec.split(',').map(str::trim) {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.trim(), last.trim()),
+            None => (part, part),
+        };
+        let first: usize = first.parse().map_err(|_| invalid(part))?;
+        let last: usize = last.parse().map_err(|_| invalid(part))?;
+        if first == 0 || last < first {
+            return Err(invalid(part));
+        }
+        for band in first..=last {
+            if bands.contains(&band) {
                 return Err(format!(
-                    "Error: The file {:?} already exists and overwrite is false.",
-                    path
+                    "Band {} is selected more than once in '{}'",
+                    band, spec
                 ));
             }
-            path.to_path_buf().with_extension("tif")
+            bands.push(band);
         }
-        None => {
-            let mut out_path = input_path.to_path_buf();
+    }
+    Ok(bands)
+}
 
-            if overwrite {
-                // If overwrite is true, use the input name as output name
-                out_path
-            } else {
-                // If overwrite is false, append "_cog" to the input name
-                let extension = out_path
-                    .extension()
-                    .unwrap_or_default()
-                    .to_str()
-                    .unwrap_or("");
-                out_path.set_file_name(format!(
-                    "{}_cog.{}",
-                    out_path
-                        .file_stem()
-                        .unwrap_or_default()
-                        .to_str()
-                        .unwrap_or(""),
-                    extension
+/// A `--bands` selection, parsed with [`parse_band_list`]
+///
+/// A newtype so clap takes the whole list as one value rather than one value per index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BandList(pub Vec<usize>);
+
+impl FromStr for BandList {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_band_list(s).map(BandList)
+    }
+}
+
+/// Check that every selected band exists in a raster with `band_count` bands
+pub fn check_band_list(bands: &[usize], band_count: usize) -> Result<(), String> {
+    match bands.iter().find(|&&band| band > band_count) {
+        Some(band) => Err(format!(
+            "Band {} is out of range; the raster has {} bands (1-{})",
+            band, band_count, band_count
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Check a COG tile size: TIFF tiles must be a positive multiple of 16 pixels
+pub fn check_blocksize(blocksize: usize) -> Result<(), String> {
+    if blocksize == 0 || !blocksize.is_multiple_of(16) {
+        return Err(format!(
+            "--blocksize must be a positive multiple of 16, got {}",
+            blocksize
+        ));
+    }
+    Ok(())
+}
+
+/// Number of 2x overview levels that keep the smaller raster side at or above `min_size`
+pub fn overview_count_for(size: (usize, usize), min_size: usize) -> usize {
+    let mut side = size.0.min(size.1);
+    let mut count = 0;
+    while min_size > 0 && side / 2 >= min_size {
+        side /= 2;
+        count += 1;
+    }
+    count
+}
+
+/// Check that every band fits in `dtype`, warning when the conversion narrows the type
+fn check_dtype_conversion(dataset: &Dataset, dtype: OutDtype, clamp: bool) -> Result<(), String> {
+    let (lo, hi) = dtype.range();
+
+    for band in dataset.rasterbands() {
+        let band = band.map_err(|e| format!("Failed to read band: {:?}", e))?;
+        let src_type = band.band_type();
+
+        if !src_type.is_conversion_lossy(dtype.gdal_type()) {
+            continue;
+        }
+        status!(
+            "Warning: converting {} to {} may lose data",
+            src_type.name(),
+            dtype.name()
+        );
+
+        if let Some(nodata) = band.no_data_value()
+            && (nodata < lo || nodata > hi)
+        {
+            status!(
+                "Warning: NoData value {} cannot be represented as {}",
+                nodata,
+                dtype.name()
+            );
+        }
+
+        if !clamp {
+            // An approximate range samples pixels or reads overviews and can miss a single
+            // out-of-range value, so every pixel is read
+            let range = band
+                .compute_raster_min_max(false)
+                .map_err(|e| format!("Failed to compute band range: {:?}", e))?;
+            if range.min < lo || range.max > hi {
+                return Err(format!(
+                    "Error: Values [{}, {}] fall outside the {} range [{}, {}], use --clamp to saturate them.",
+                    range.min,
+                    range.max,
+                    dtype.name(),
+                    lo,
+                    hi
                 ));
+            }
+        }
+    }
 
-                // If the output file exists, return an error
-                if out_path.exists() {
-                    return Err(format!(
-                        "Error: The file {:?} already exists and overwrite is false.",
-                        out_path
-                    ));
-                }
+    Ok(())
+}
+
+/// Rescale values to 0-255 between the min/max (or 2nd/98th percentile) of the valid pixels
+fn stretch_to_byte(data: &[f64], nodata: Option<f64>, percentile_stretch: bool) -> Vec<u8> {
+    let is_valid = |v: f64| v.is_finite() && nodata.is_none_or(|nd| v != nd);
+    let mut valid: Vec<f64> = data.iter().copied().filter(|v| is_valid(*v)).collect();
+    if valid.is_empty() {
+        return vec![0; data.len()];
+    }
+    valid.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+
+    let (lo, hi) = if percentile_stretch {
+        (
+            percentile(&valid, 0.02) as f64,
+            percentile(&valid, 0.98) as f64,
+        )
+    } else {
+        (valid[0], valid[valid.len() - 1])
+    };
+    let range = if hi > lo { hi - lo } else { 1.0 };
+
+    data.iter()
+        .map(|&v| {
+            if is_valid(v) {
+                ((v - lo) / range * 255.0).clamp(0.0, 255.0).round() as u8
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+/// Write a PNG preview next to `cog_path`, reading from the smallest overview that is
+/// still at least `size` pixels on its longer side
+pub fn write_thumbnail(
+    cog_path: &Path,
+    size: usize,
+    percentile_stretch: bool,
+) -> Result<PathBuf, String> {
+    let (_permit, dataset) = open_limit::open(cog_path)
+        .map_err(|e| format!("Failed to open COG for thumbnail: {:?}", e))?;
+    let (cols, rows) = dataset.raster_size();
+    let scale = size as f64 / cols.max(rows) as f64;
+    let thumb_size = (
+        ((cols as f64 * scale).round() as usize).max(1),
+        ((rows as f64 * scale).round() as usize).max(1),
+    );
+
+    // Grayscale for single band inputs, the first three bands as RGB otherwise
+    let bands: Vec<usize> = if dataset.raster_count() >= 3 {
+        vec![1, 2, 3]
+    } else {
+        vec![1]
+    };
+
+    let mem_driver = DriverManager::get_driver_by_name("MEM")
+        .map_err(|e| format!("Failed to get MEM driver: {:?}", e))?;
+    let thumb = mem_driver
+        .create("", thumb_size.0, thumb_size.1, bands.len())
+        .map_err(|e| format!("Failed to create thumbnail dataset: {:?}", e))?;
+
+    for (i, &band_index) in bands.iter().enumerate() {
+        let band = dataset
+            .rasterband(band_index)
+            .map_err(|e| format!("Failed to read band {}: {:?}", band_index, e))?;
+        let nodata = band.no_data_value();
+
+        let mut source = None;
+        for ov in 0..band.overview_count().unwrap_or(0) as usize {
+            if let Ok(overview) = band.overview(ov)
+                && overview.x_size().max(overview.y_size()) >= size
+            {
+                source = Some(overview);
+            }
+        }
+        let source = source.unwrap_or(band);
+
+        let buf: Buffer<f64> = source
+            .read_as(
+                (0, 0),
+                source.size(),
+                thumb_size,
+                Some(ResampleAlg::Average),
+            )
+            .map_err(|e| format!("Failed to read band {}: {:?}", band_index, e))?;
+        let mut out = Buffer::new(
+            thumb_size,
+            stretch_to_byte(buf.data(), nodata, percentile_stretch),
+        );
+        thumb
+            .rasterband(i + 1)
+            .and_then(|mut b| b.write((0, 0), thumb_size, &mut out))
+            .map_err(|e| format!("Failed to write thumbnail band: {:?}", e))?;
+    }
+
+    let png_path = cog_path.with_extension("png");
+    let png_driver = DriverManager::get_driver_by_name("PNG")
+        .map_err(|e| format!("Failed to get PNG driver: {:?}", e))?;
+    thumb
+        .create_copy(&png_driver, &png_path, &RasterCreationOptions::default())
+        .map_err(|e| format!("Failed to write thumbnail: {:?}", e))?;
+
+    status!(
+        "Thumbnail ({}x{}) saved to: {:?}",
+        thumb_size.0,
+        thumb_size.1,
+        png_path
+    );
+    Ok(png_path)
+}
+
+/// Create a dataset whose band type is only known at runtime
+pub(crate) fn create_with_type(
+    driver: &Driver,
+    path: &Path,
+    size: (usize, usize),
+    bands: usize,
+    dtype: GdalDataType,
+) -> gdal::errors::Result<Dataset> {
+    match dtype {
+        GdalDataType::UInt8 => driver.create_with_band_type::<u8, _>(path, size.0, size.1, bands),
+        GdalDataType::UInt16 => driver.create_with_band_type::<u16, _>(path, size.0, size.1, bands),
+        GdalDataType::Int16 => driver.create_with_band_type::<i16, _>(path, size.0, size.1, bands),
+        GdalDataType::UInt32 => driver.create_with_band_type::<u32, _>(path, size.0, size.1, bands),
+        GdalDataType::Int32 => driver.create_with_band_type::<i32, _>(path, size.0, size.1, bands),
+        GdalDataType::Float32 => {
+            driver.create_with_band_type::<f32, _>(path, size.0, size.1, bands)
+        }
+        _ => driver.create_with_band_type::<f64, _>(path, size.0, size.1, bands),
+    }
+}
+
+/// Assign `interps` to the bands of `dataset` in order, through an in-memory VRT
+fn apply_color_interp(dataset: Dataset, interps: &[ColorInterp]) -> Result<Dataset, String> {
+    if interps.len() != dataset.raster_count() {
+        return Err(format!(
+            "--color-interp lists {} interpretations but the output has {} bands",
+            interps.len(),
+            dataset.raster_count()
+        ));
+    }
+    let vrt =
+        build_vrt(None, &[dataset], None).map_err(|e| format!("Failed to build VRT: {:?}", e))?;
+    for (index, interp) in interps.iter().enumerate() {
+        vrt.rasterband(index + 1)
+            .and_then(|mut band| band.set_color_interpretation(interp.gdal_interp()))
+            .map_err(|e| {
+                format!(
+                    "Failed to set color interpretation of band {}: {:?}",
+                    index + 1,
+                    e
+                )
+            })?;
+    }
+    Ok(vrt)
+}
+
+/// Band descriptions, color interpretations and default-domain metadata read from an input
+///
+/// Warped and VRT intermediates can lose them, so [`restore_labels`] puts them back before the
+/// COG is written.
+struct SourceLabels {
+    bands: Vec<(String, ColorInterpretation)>,
+    metadata: Vec<(String, String)>,
+}
+
+impl SourceLabels {
+    /// Read the labels of the 1-based `bands` of `dataset`, or of every band when `None`
+    fn read(dataset: &Dataset, bands: Option<&[usize]>) -> Self {
+        let indices: Vec<usize> = match bands {
+            Some(bands) => bands.to_vec(),
+            None => (1..=dataset.raster_count()).collect(),
+        };
+        let bands = indices
+            .into_iter()
+            .map(|index| match dataset.rasterband(index) {
+                Ok(band) => (
+                    band.description().unwrap_or_default(),
+                    band.color_interpretation(),
+                ),
+                Err(_) => (String::new(), ColorInterpretation::Undefined),
+            })
+            .collect();
+        let metadata = dataset
+            .metadata_domain("")
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|item| item.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        SourceLabels { bands, metadata }
+    }
+}
+
+/// Put back the labels in `labels` that `dataset` has lost, through an in-memory VRT
+///
+/// A band gets the source description when it has none, and the source color interpretation
+/// when it only reports Undefined or Gray. Bands are matched by position, so band labels are
+/// left alone when a step changed the band count (`--force-rgb`). Metadata items the source has
+/// and `dataset` lacks are added.
+fn restore_labels(dataset: Dataset, labels: SourceLabels) -> Result<Dataset, String> {
+    let source_bands = if labels.bands.len() == dataset.raster_count() {
+        labels.bands
+    } else {
+        Vec::new()
+    };
+    let mut bands = Vec::with_capacity(source_bands.len());
+    let mut changed = false;
+    for (index, (description, interp)) in source_bands.into_iter().enumerate() {
+        let band = dataset
+            .rasterband(index + 1)
+            .map_err(|e| format!("Failed to read band {}: {:?}", index + 1, e))?;
+        let current_description = band.description().unwrap_or_default();
+        let current_interp = band.color_interpretation();
+        let description = if current_description.is_empty() && !description.is_empty() {
+            changed = true;
+            description
+        } else {
+            current_description
+        };
+        let lost_interp = matches!(
+            current_interp,
+            ColorInterpretation::Undefined | ColorInterpretation::GrayIndex
+        );
+        let interp = if lost_interp
+            && interp != current_interp
+            && interp != ColorInterpretation::Undefined
+        {
+            changed = true;
+            interp
+        } else {
+            current_interp
+        };
+        bands.push((description, interp));
+    }
+    let current_metadata = dataset.metadata_domain("").unwrap_or_default();
+    let missing: Vec<(String, String)> = labels
+        .metadata
+        .into_iter()
+        .filter(|(key, _)| dataset.metadata_item(key, "").is_none())
+        .collect();
+    if !changed && missing.is_empty() {
+        return Ok(dataset);
+    }
+
+    let mut vrt =
+        build_vrt(None, &[dataset], None).map_err(|e| format!("Failed to build VRT: {:?}", e))?;
+    for (index, (description, interp)) in bands.into_iter().enumerate() {
+        let mut band = vrt
+            .rasterband(index + 1)
+            .map_err(|e| format!("Failed to read band {}: {:?}", index + 1, e))?;
+        band.set_description(&description)
+            .and_then(|_| band.set_color_interpretation(interp))
+            .map_err(|e| format!("Failed to restore labels of band {}: {:?}", index + 1, e))?;
+    }
+    let current = current_metadata
+        .iter()
+        .filter_map(|item| item.split_once('='))
+        .map(|(key, value)| (key.to_string(), value.to_string()));
+    for (key, value) in current.chain(missing) {
+        vrt.set_metadata_item(&key, &value, "")
+            .map_err(|e| format!("Failed to restore metadata item {}: {:?}", key, e))?;
+    }
+    Ok(vrt)
+}
+
+/// Apply `conversion` to every band of `dataset`, writing a Float32 (Float64 for Float64
+/// inputs) GeoTIFF at `temp_path`
+///
+/// NoData pixels keep their value and the new unit is recorded in each band's `units`
+/// metadata item. A declared source unit that differs from `conversion.from` is an error.
+fn apply_unit_conversion(
+    dataset: &Dataset,
+    conversion: &UnitConversion,
+    temp_path: &Path,
+) -> Result<Dataset, String> {
+    let bands = dataset
+        .rasterbands()
+        .collect::<gdal::errors::Result<Vec<_>>>()
+        .map_err(|e| format!("Failed to read bands: {:?}", e))?;
+    for (index, band) in bands.iter().enumerate() {
+        let declared = band
+            .metadata_item("units", "")
+            .filter(|u| !u.is_empty())
+            .unwrap_or_else(|| band.unit());
+        if !declared.is_empty() && canonical_unit(&declared).is_some_and(|u| u != conversion.from) {
+            return Err(format!(
+                "Band {} declares units '{}', not {}",
+                index + 1,
+                declared,
+                conversion.from
+            ));
+        }
+    }
+
+    let dtype = if bands.iter().any(|b| b.band_type() == GdalDataType::Float64) {
+        GdalDataType::Float64
+    } else {
+        GdalDataType::Float32
+    };
+    let gtiff = DriverManager::get_driver_by_name("GTiff")
+        .map_err(|e| format!("Failed to get GTiff driver: {:?}", e))?;
+    let (cols, rows) = dataset.raster_size();
+    let mut converted = create_with_type(&gtiff, temp_path, (cols, rows), bands.len(), dtype)
+        .map_err(|e| format!("Failed to create converted dataset: {:?}", e))?;
+    if let Ok(transform) = dataset.geo_transform() {
+        converted
+            .set_geo_transform(&transform)
+            .map_err(|e| format!("Failed to set geotransform: {:?}", e))?;
+    }
+    if let Ok(srs) = dataset.spatial_ref() {
+        converted
+            .set_spatial_ref(&srs)
+            .map_err(|e| format!("Failed to set CRS: {:?}", e))?;
+    }
+
+    let chunk_rows = 256.min(rows.max(1));
+    for (index, source) in bands.iter().enumerate() {
+        let nodata = source.no_data_value();
+        let mut band = converted
+            .rasterband(index + 1)
+            .map_err(|e| format!("Failed to read band {}: {:?}", index + 1, e))?;
+        if let Some(nodata) = nodata {
+            band.set_no_data_value(Some(nodata))
+                .map_err(|e| format!("Failed to set NoData: {:?}", e))?;
+        }
+        band.set_metadata_item("units", conversion.to, "")
+            .map_err(|e| format!("Failed to set units: {:?}", e))?;
+        if let Ok(description) = source.description()
+            && !description.is_empty()
+        {
+            band.set_description(&description)
+                .map_err(|e| format!("Failed to set band description: {:?}", e))?;
+        }
+
+        for y in (0..rows).step_by(chunk_rows) {
+            let height = chunk_rows.min(rows - y);
+            let buf: Buffer<f64> = source
+                .read_as((0, y as isize), (cols, height), (cols, height), None)
+                .map_err(|e| format!("Failed to read band {}: {:?}", index + 1, e))?;
+            let data: Vec<f64> = buf
+                .data()
+                .iter()
+                .map(|&v| {
+                    if v.is_nan() || nodata == Some(v) {
+                        v
+                    } else {
+                        conversion.apply(v)
+                    }
+                })
+                .collect();
+            let mut buf = Buffer::new((cols, height), data);
+            band.write((0, y as isize), (cols, height), &mut buf)
+                .map_err(|e| format!("Failed to write band {}: {:?}", index + 1, e))?;
+        }
+    }
+    Ok(converted)
+}
+
+/// Read a palette file with one `value R G B [A]` entry per line
+///
+/// Fields may be separated by whitespace or commas; blank lines and `#` comments are
+/// skipped. Values must fit in UInt16 and colors in 0-255; alpha defaults to 255.
+pub fn read_color_table(path: &Path) -> Result<BTreeMap<u16, [u8; 4]>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read color table {}: {}", path.display(), e))?;
+    let mut colors = BTreeMap::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|f| !f.is_empty())
+            .collect();
+        let invalid = || {
+            format!(
+                "Invalid color table entry on line {} of {}: '{}' (expected 'value R G B [A]')",
+                number + 1,
+                path.display(),
+                line
+            )
+        };
+        if !(4..=5).contains(&fields.len()) {
+            return Err(invalid());
+        }
+        let value: u16 = fields[0].parse().map_err(|_| invalid())?;
+        let mut rgba = [0, 0, 0, 255];
+        for (channel, field) in rgba.iter_mut().zip(&fields[1..]) {
+            *channel = field.parse().map_err(|_| invalid())?;
+        }
+        colors.insert(value, rgba);
+    }
+    if colors.is_empty() {
+        return Err(format!("Color table {} has no entries", path.display()));
+    }
+    Ok(colors)
+}
+
+/// Check that `dataset` is a single Byte or UInt16 band that palette values can index
+fn check_palette_input(dataset: &Dataset) -> Result<(), String> {
+    if dataset.raster_count() != 1 {
+        return Err(format!(
+            "--color-table needs a single-band input, got {} bands",
+            dataset.raster_count()
+        ));
+    }
+    let band_type = dataset
+        .rasterband(1)
+        .map_err(|e| format!("Failed to read band 1: {:?}", e))?
+        .band_type();
+    if !matches!(band_type, GdalDataType::UInt8 | GdalDataType::UInt16) {
+        return Err(format!(
+            "--color-table needs a Byte or UInt16 band, got {} (use --out-dtype to convert)",
+            band_type.name()
+        ));
+    }
+    Ok(())
+}
+
+/// Wrap `dataset` in a VRT whose band carries `colors` as its palette
+fn apply_color_table(dataset: Dataset, colors: &BTreeMap<u16, [u8; 4]>) -> Result<Dataset, String> {
+    check_palette_input(&dataset)?;
+    let vrt =
+        build_vrt(None, &[dataset], None).map_err(|e| format!("Failed to build VRT: {:?}", e))?;
+    let mut table = ColorTable::new(PaletteInterpretation::Rgba);
+    for (&value, &[r, g, b, a]) in colors {
+        table.set_color_entry(
+            value,
+            &ColorEntry::rgba(r as i16, g as i16, b as i16, a as i16),
+        );
+    }
+    let mut band = vrt
+        .rasterband(1)
+        .map_err(|e| format!("Failed to read band 1: {:?}", e))?;
+    band.set_color_table(&table);
+    band.set_color_interpretation(ColorInterpretation::PaletteIndex)
+        .map_err(|e| format!("Failed to set color interpretation: {:?}", e))?;
+    Ok(vrt)
+}
+
+/// Expand the single band of `dataset` through `colors` into a Byte RGB GeoTIFF at
+/// `temp_path`; values missing from the table become black
+///
+/// When the band declares NoData, a fourth alpha band carries it: 0 at NoData pixels and the
+/// table's alpha elsewhere. Without NoData the output has three bands.
+fn expand_to_rgb(
+    dataset: &Dataset,
+    colors: &BTreeMap<u16, [u8; 4]>,
+    temp_path: &Path,
+) -> Result<Dataset, String> {
+    check_palette_input(dataset)?;
+    let source = dataset
+        .rasterband(1)
+        .map_err(|e| format!("Failed to read band 1: {:?}", e))?;
+    let nodata = source.no_data_value();
+    let channels = if nodata.is_some() { 4 } else { 3 };
+
+    let gtiff = DriverManager::get_driver_by_name("GTiff")
+        .map_err(|e| format!("Failed to get GTiff driver: {:?}", e))?;
+    let (cols, rows) = dataset.raster_size();
+    let mut rgb = create_with_type(
+        &gtiff,
+        temp_path,
+        (cols, rows),
+        channels,
+        GdalDataType::UInt8,
+    )
+    .map_err(|e| format!("Failed to create RGB dataset: {:?}", e))?;
+    if let Ok(transform) = dataset.geo_transform() {
+        rgb.set_geo_transform(&transform)
+            .map_err(|e| format!("Failed to set geotransform: {:?}", e))?;
+    }
+    if let Ok(srs) = dataset.spatial_ref() {
+        rgb.set_spatial_ref(&srs)
+            .map_err(|e| format!("Failed to set CRS: {:?}", e))?;
+    }
+
+    let interpretations = [
+        ColorInterpretation::RedBand,
+        ColorInterpretation::GreenBand,
+        ColorInterpretation::BlueBand,
+        ColorInterpretation::AlphaBand,
+    ];
+    for (index, interpretation) in interpretations.into_iter().take(channels).enumerate() {
+        rgb.rasterband(index + 1)
+            .and_then(|mut band| band.set_color_interpretation(interpretation))
+            .map_err(|e| format!("Failed to set color interpretation: {:?}", e))?;
+    }
+    let chunk_rows = 256.min(rows.max(1));
+    for y in (0..rows).step_by(chunk_rows) {
+        let height = chunk_rows.min(rows - y);
+        let values: Buffer<u16> = source
+            .read_as((0, y as isize), (cols, height), (cols, height), None)
+            .map_err(|e| format!("Failed to read band 1: {:?}", e))?;
+        for channel in 0..channels {
+            let data: Vec<u8> = values
+                .data()
+                .iter()
+                .map(|&v| match channel {
+                    3 if nodata == Some(v as f64) => 0,
+                    3 => colors.get(&v).map_or(255, |rgba| rgba[3]),
+                    _ => colors.get(&v).map_or(0, |rgba| rgba[channel]),
+                })
+                .collect();
+            let mut band = rgb
+                .rasterband(channel + 1)
+                .map_err(|e| format!("Failed to read RGB band: {:?}", e))?;
+            let mut buf = Buffer::new((cols, height), data);
+            band.write((0, y as isize), (cols, height), &mut buf)
+                .map_err(|e| format!("Failed to write RGB band: {:?}", e))?;
+        }
+    }
+    Ok(rgb)
+}
 
-                out_path.with_extension("tif")
+/// Copy `dataset` to a temporary GeoTIFF at `temp_path` whose NoData is replaced by a
+/// per-dataset mask; a pixel is masked only where every band holds its NoData value
+fn add_internal_mask(dataset: &Dataset, temp_path: &Path) -> Result<Dataset, String> {
+    let nodata = dataset
+        .rasterbands()
+        .map(|band| band.map(|b| b.no_data_value()))
+        .collect::<gdal::errors::Result<Vec<_>>>()
+        .map_err(|e| format!("Failed to read bands: {:?}", e))?;
+    if nodata.iter().all(Option::is_none) {
+        return Err(
+            "--internal-mask needs a source NoData value to build the mask from".to_string(),
+        );
+    }
+
+    let driver = DriverManager::get_driver_by_name("GTiff")
+        .map_err(|e| format!("Failed to get GTiff driver: {:?}", e))?;
+    let masked = dataset
+        .create_copy(
+            &driver,
+            temp_path,
+            &RasterCreationOptions::from_iter(["TILED=YES", "COMPRESS=LZW"]),
+        )
+        .map_err(|e| format!("Failed to create masked copy: {:?}", e))?;
+
+    let mut first_band = masked
+        .rasterband(1)
+        .map_err(|e| format!("Failed to read band 1: {:?}", e))?;
+    first_band
+        .create_mask_band(true)
+        .map_err(|e| format!("Failed to create mask band: {:?}", e))?;
+    let mut mask = first_band
+        .open_mask_band()
+        .map_err(|e| format!("Failed to open mask band: {:?}", e))?;
+
+    let (cols, rows) = masked.raster_size();
+    let chunk_rows = 256.min(rows.max(1));
+    for y in (0..rows).step_by(chunk_rows) {
+        let height = chunk_rows.min(rows - y);
+        let mut valid = vec![0u8; cols * height];
+        for (index, nodata) in nodata.iter().enumerate() {
+            let band = masked
+                .rasterband(index + 1)
+                .map_err(|e| format!("Failed to read band {}: {:?}", index + 1, e))?;
+            let buf: Buffer<f64> = band
+                .read_as((0, y as isize), (cols, height), (cols, height), None)
+                .map_err(|e| format!("Failed to read band {}: {:?}", index + 1, e))?;
+            for (v, &val) in valid.iter_mut().zip(buf.data()) {
+                if !val.is_nan() && nodata.is_none_or(|nd| val != nd) {
+                    *v = 255;
+                }
             }
         }
+        let mut buf = Buffer::new((cols, height), valid);
+        mask.write((0, y as isize), (cols, height), &mut buf)
+            .map_err(|e| format!("Failed to write mask: {:?}", e))?;
+    }
+
+    for index in 1..=masked.raster_count() {
+        masked
+            .rasterband(index)
+            .and_then(|mut band| band.set_no_data_value(None))
+            .map_err(|e| format!("Failed to clear NoData on band {}: {:?}", index, e))?;
+    }
+    Ok(masked)
+}
+
+/// Reproject `src` onto the grid of `dst` with `resampling`
+///
+/// gdal's `reproject` always resamples bilinearly, so this calls `GDALReprojectImage` itself.
+fn reproject_with(src: &Dataset, dst: &Dataset, resampling: WarpResampling) -> Result<(), String> {
+    let rv = unsafe {
+        gdal_sys::GDALReprojectImage(
+            src.c_dataset(),
+            ptr::null(),
+            dst.c_dataset(),
+            ptr::null(),
+            resampling.gdal_alg(),
+            0.0,
+            0.0,
+            None,
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
     };
-    println!("Output will be saved to: {:?}", out_path);
+    if rv != CPLErr::CE_None {
+        let message = unsafe { CStr::from_ptr(gdal_sys::CPLGetLastErrorMsg()) };
+        return Err(format!("Failed to warp: {}", message.to_string_lossy()));
+    }
+    Ok(())
+}
+
+/// Warp `dataset` onto a grid given by `srs`, `transform` and `size`, writing the result to
+/// a temporary GeoTIFF at `temp_path`
+fn warp_onto_grid(
+    dataset: &Dataset,
+    srs: &SpatialRef,
+    transform: &GeoTransform,
+    size: (usize, usize),
+    resampling: Option<WarpResampling>,
+    temp_path: &Path,
+) -> Result<Dataset, String> {
+    dataset
+        .spatial_ref()
+        .map_err(|e| format!("Input raster has no CRS to warp from: {:?}", e))?;
+
+    let band_type = dataset
+        .rasterband(1)
+        .map_err(|e| format!("Failed to read band 1: {:?}", e))?
+        .band_type();
+    let driver = DriverManager::get_driver_by_name("GTiff")
+        .map_err(|e| format!("Failed to get GTiff driver: {:?}", e))?;
+    let mut warped = create_with_type(&driver, temp_path, size, dataset.raster_count(), band_type)
+        .map_err(|e| format!("Failed to create warp output: {:?}", e))?;
+    warped
+        .set_geo_transform(transform)
+        .and_then(|_| warped.set_spatial_ref(srs))
+        .map_err(|e| format!("Failed to set warp output grid: {:?}", e))?;
+
+    for i in 1..=dataset.raster_count() {
+        let nodata = dataset
+            .rasterband(i)
+            .map_err(|e| format!("Failed to read band {}: {:?}", i, e))?
+            .no_data_value();
+        if let Some(nodata) = nodata {
+            let mut band = warped
+                .rasterband(i)
+                .map_err(|e| format!("Failed to read band {}: {:?}", i, e))?;
+            band.set_no_data_value(Some(nodata))
+                .and_then(|_| band.fill(nodata, None))
+                .map_err(|e| format!("Failed to initialise band {}: {:?}", i, e))?;
+        }
+    }
+
+    let resampling = resampling.unwrap_or_else(|| WarpResampling::default_for(band_type));
+    status!("Warp resampling: {}", resampling.name());
+    reproject_with(dataset, &warped, resampling)?;
+    Ok(warped)
+}
+
+/// Warp `dataset` onto the grid of `reference_path` (CRS, geotransform and size), writing the
+/// result to a temporary GeoTIFF at `temp_path`
+fn warp_to_reference(
+    dataset: &Dataset,
+    reference_path: &Path,
+    resampling: Option<WarpResampling>,
+    temp_path: &Path,
+) -> Result<Dataset, String> {
+    let (_permit, reference) = open_limit::open(reference_path).map_err(|e| {
+        format!(
+            "Failed to open reference raster {}: {:?}",
+            reference_path.display(),
+            e
+        )
+    })?;
+    let ref_transform = reference
+        .geo_transform()
+        .map_err(|e| format!("Reference raster has no geotransform: {:?}", e))?;
+    let ref_srs = reference
+        .spatial_ref()
+        .map_err(|e| format!("Reference raster has no CRS: {:?}", e))?;
+
+    let warped = warp_onto_grid(
+        dataset,
+        &ref_srs,
+        &ref_transform,
+        reference.raster_size(),
+        resampling,
+        temp_path,
+    )?;
+    status!(
+        "Warped to reference grid {} ({}x{})",
+        reference_path.display(),
+        reference.raster_size().0,
+        reference.raster_size().1
+    );
+    Ok(warped)
+}
+
+/// Warp `dataset` to the CRS `t_srs`, writing the result to a temporary GeoTIFF at `temp_path`
+///
+/// The output extent is the densified transform of the input bounds. Pixels are square and
+/// sized so the output diagonal spans as many pixels as the input diagonal, as gdalwarp does.
+fn warp_to_crs(
+    dataset: &Dataset,
+    t_srs: &str,
+    resampling: Option<WarpResampling>,
+    temp_path: &Path,
+) -> Result<Dataset, String> {
+    let mut target = SpatialRef::from_definition(t_srs)
+        .map_err(|e| format!("Invalid CRS '{}': {}", t_srs, e))?;
+    target.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+    let mut source = dataset
+        .spatial_ref()
+        .map_err(|e| format!("Input raster has no CRS to warp from: {:?}", e))?;
+    source.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+    let transform = dataset
+        .geo_transform()
+        .map_err(|e| format!("Input raster has no geotransform: {:?}", e))?;
+    if transform[2] != 0.0 || transform[4] != 0.0 {
+        return Err("--t-srs does not support rotated input grids".to_string());
+    }
+
+    let (cols, rows) = dataset.raster_size();
+    let x_edges = [transform[0], transform[0] + cols as f64 * transform[1]];
+    let y_edges = [transform[3], transform[3] + rows as f64 * transform[5]];
+    let bounds = [
+        x_edges[0].min(x_edges[1]),
+        y_edges[0].min(y_edges[1]),
+        x_edges[0].max(x_edges[1]),
+        y_edges[0].max(y_edges[1]),
+    ];
+    let [xmin, ymin, xmax, ymax] = CoordTransform::new(&source, &target)
+        .and_then(|ct| ct.transform_bounds(&bounds, 21))
+        .map_err(|e| format!("Failed to transform bounds to {}: {}", t_srs, e))?;
+
+    let pixel_diagonal = ((cols * cols + rows * rows) as f64).sqrt();
+    let res = (xmax - xmin).hypot(ymax - ymin) / pixel_diagonal;
+    let size = (
+        (((xmax - xmin) / res).ceil() as usize).max(1),
+        (((ymax - ymin) / res).ceil() as usize).max(1),
+    );
+    let out_transform = [xmin, res, 0.0, ymax, 0.0, -res];
+
+    let warped = warp_onto_grid(
+        dataset,
+        &target,
+        &out_transform,
+        size,
+        resampling,
+        temp_path,
+    )?;
+    status!("Reprojected to {} ({}x{})", t_srs, size.0, size.1);
+    Ok(warped)
+}
+
+/// Path [`tif_to_cog`] writes `input_path` to
+///
+/// `output_path` with a `.tif` extension when given; otherwise the input itself with
+/// `overwrite`, or the input name with a `_cog.tif` suffix.
+pub fn planned_cog_output(
+    input_path: &Path,
+    output_path: Option<&Path>,
+    overwrite: bool,
+) -> PathBuf {
+    match output_path {
+        Some(path) => path.with_extension("tif"),
+        None if overwrite => input_path.to_path_buf(),
+        None => input_path.with_file_name(format!(
+            "{}_cog.tif",
+            input_path.file_stem().unwrap_or_default().to_string_lossy()
+        )),
+    }
+}
+
+pub fn tif_to_cog(
+    input_path: &Path,
+    output_path: Option<&Path>,
+    overwrite: bool,
+    options: &CogOptions,
+) -> Result<String, String> {
+    // Cloud objects are read through GDAL, so only local inputs can be checked up front
+    let input_path = &to_vsi(input_path);
+    let remote = is_vsi(input_path);
+    if !remote && !input_path.exists() {
+        return Err(format!("Error: The file {:?} does not exist.", input_path));
+    }
+    if remote && output_path.is_none() {
+        return Err(format!(
+            "Error: {:?} is a remote object; pass --out to write the COG locally.",
+            input_path
+        ));
+    }
+    let (mut creation_opts, level) = options
+        .compression
+        .creation_options(options.compression_level)?;
+    if let Some(predictor) = options.predictor {
+        creation_opts.push(options.compression.predictor_option(predictor)?);
+    }
+    if let Some(blocksize) = options.blocksize {
+        check_blocksize(blocksize)?;
+        creation_opts.push(format!("BLOCKSIZE={}", blocksize));
+    }
+
+    // Checked after the extension is set, so e.g. a .nc input cannot replace a .tif
+    let out_path = planned_cog_output(input_path, output_path, overwrite);
+    if out_path.exists() && !overwrite {
+        return Err(format!(
+            "Error: The file {:?} already exists and overwrite is false.",
+            out_path
+        ));
+    }
+    status!("Output will be saved to: {:?}", out_path);
 
     // Open the dataset and handle errors
-    let dataset = Dataset::open(input_path.to_str().unwrap())
+    let (_permit, dataset) = open_limit::open(input_path.to_str().unwrap())
         .map_err(|e| format!("Failed to open dataset: {:?}", e))?;
+    let labels = SourceLabels::read(&dataset, options.bands.as_deref());
+
+    // Drop unselected bands first so no later step reads them
+    let dataset = match &options.bands {
+        Some(bands) => {
+            check_band_list(bands, dataset.raster_count())?;
+            status!(
+                "Selecting {} of {} bands",
+                bands.len(),
+                dataset.raster_count()
+            );
+            let args: Vec<String> = bands
+                .iter()
+                .flat_map(|band| ["-b".to_string(), band.to_string()])
+                .collect();
+            let vrt_options =
+                BuildVRTOptions::new(args).map_err(|e| format!("Invalid VRT options: {:?}", e))?;
+            build_vrt(None, &[dataset], Some(vrt_options))
+                .map_err(|e| format!("Failed to build VRT: {:?}", e))?
+        }
+        None => dataset,
+    };
 
     // Get the driver
     let driver = DriverManager::get_driver_by_name("COG")
         .expect("Failed to get COG driver, is GDAL up to date?");
 
-    let creation_options = RasterCreationOptions::from_iter(["COMPRESS=LZW"]);
+    // Warping happens first so later band transformations see the reference grid
+    // The guards delete the intermediate files however the conversion ends; each is
+    // created before the dataset written to it, so the dataset is closed first
+    let warp_path = TempFile::new(&out_path, WARP_TIF);
+    let resampling = options.resampling;
+    let dataset = match (&options.reference, &options.t_srs) {
+        (Some(_), Some(_)) => return Err("--reference and --t-srs are exclusive".to_string()),
+        (Some(reference), None) => {
+            warp_to_reference(&dataset, reference, resampling, warp_path.path())?
+        }
+        (None, Some(t_srs)) => warp_to_crs(&dataset, t_srs, resampling, warp_path.path())?,
+        (None, None) if resampling.is_some() => {
+            return Err("--resampling needs --reference or --t-srs".to_string());
+        }
+        (None, None) => dataset,
+    };
+
+    let units_path = TempFile::new(&out_path, UNITS_TIF);
+    let dataset = match &options.convert_units {
+        Some(conversion) => {
+            status!(
+                "Converting units {} -> {} (x * {} + {})",
+                conversion.from,
+                conversion.to,
+                conversion.scale,
+                conversion.offset
+            );
+            apply_unit_conversion(&dataset, conversion, units_path.path())?
+        }
+        None => dataset,
+    };
+
+    // Band transformations are applied through an in-memory VRT
+    let mut vrt_args: Vec<String> = Vec::new();
 
-    // Attempt to create the copy, handling any errors
+    if let Some(dtype) = options.out_dtype {
+        check_dtype_conversion(&dataset, dtype, options.clamp)?;
+        status!("Output data type: {}", dtype.name());
+        vrt_args.extend(["-ot".to_string(), dtype.name().to_string()]);
+    }
+    match (options.nodata, options.clear_nodata) {
+        (Some(_), true) => return Err("--nodata and --a-nodata-none are exclusive".to_string()),
+        (Some(nodata), false) => {
+            status!("Output NoData: {}", nodata);
+            vrt_args.extend(["-vrtnodata".to_string(), nodata.to_string()]);
+        }
+        (None, true) => {
+            status!("Output NoData: none");
+            // Without -srcnodata the VRT still skips source NoData pixels and they read as 0
+            vrt_args.extend([
+                "-srcnodata".to_string(),
+                "None".to_string(),
+                "-vrtnodata".to_string(),
+                "None".to_string(),
+            ]);
+        }
+        (None, false) => {}
+    }
+    if options.crs_authority.stamp
+        && let Some(code) = dataset
+            .spatial_ref()
+            .ok()
+            .as_ref()
+            .and_then(identify_epsg)
+            .and_then(|srs| srs.authority().ok())
+    {
+        status!("Stamping CRS as {}", code);
+        vrt_args.extend(["-a_srs".to_string(), code]);
+    }
+
+    let dataset = if vrt_args.is_empty() {
+        dataset
+    } else {
+        let vrt_options =
+            BuildVRTOptions::new(vrt_args).map_err(|e| format!("Invalid VRT options: {:?}", e))?;
+        build_vrt(None, &[dataset], Some(vrt_options))
+            .map_err(|e| format!("Failed to build VRT: {:?}", e))?
+    };
+
+    // Categorical palettes are applied after any data type conversion
+    let rgb_path = TempFile::new(&out_path, RGB_TIF);
+    let dataset = match &options.color_table {
+        Some(path) => {
+            let colors = read_color_table(path)?;
+            if options.force_rgb {
+                let rgb = expand_to_rgb(&dataset, &colors, rgb_path.path())?;
+                if rgb.raster_count() == 4 {
+                    status!(
+                        "Output bands: 4 (RGBA expanded from {} colors, NoData in alpha)",
+                        colors.len()
+                    );
+                } else {
+                    status!(
+                        "Output bands: 3 (RGB expanded from {} colors)",
+                        colors.len()
+                    );
+                }
+                rgb
+            } else {
+                let paletted = apply_color_table(dataset, &colors)?;
+                status!("Output bands: 1 (paletted, {} colors)", colors.len());
+                paletted
+            }
+        }
+        None if options.force_rgb => return Err("--force-rgb needs --color-table".to_string()),
+        None => dataset,
+    };
+
+    // The steps above can drop band labels; --color-interp still has the last word
+    let dataset = restore_labels(dataset, labels)?;
+
+    // Set before masking, since the masked GTiff copy keeps the interpretations
+    let dataset = match &options.color_interp {
+        Some(interps) => {
+            let names: Vec<&str> = interps.iter().map(ColorInterp::name).collect();
+            status!("Color interpretation: {}", names.join(", "));
+            apply_color_interp(dataset, interps)?
+        }
+        None => dataset,
+    };
+
+    // The COG driver carries a per-dataset mask into the output and its overviews
+    let mask_path = TempFile::new(&out_path, MASK_TIF);
+    let dataset = if options.internal_mask {
+        add_internal_mask(&dataset, mask_path.path())?
+    } else {
+        dataset
+    };
+
+    match level {
+        Some(level) => status!("Compression: {} (level {})", options.compression, level),
+        None => status!("Compression: {}", options.compression),
+    }
+    if let Some(predictor) = options.predictor {
+        let band_type = dataset
+            .rasterband(1)
+            .map_err(|e| format!("Failed to read band 1: {:?}", e))?
+            .band_type();
+        if predictor == Predictor::FloatingPoint
+            && !matches!(band_type, GdalDataType::Float32 | GdalDataType::Float64)
+        {
+            return Err(format!(
+                "The floating-point predictor needs Float32 or Float64 data, not {}",
+                band_type.name()
+            ));
+        }
+        status!("Predictor: {}", predictor.name());
+    }
+
+    if let Some(min_size) = options.min_overview_size {
+        let (cols, rows) = dataset.raster_size();
+        if cols.min(rows) < min_size {
+            status!(
+                "Warning: input is {}x{} pixels, already smaller than the minimum overview size of {}",
+                cols,
+                rows,
+                min_size
+            );
+        }
+        match overview_count_for((cols, rows), min_size) {
+            0 => creation_opts.push("OVERVIEWS=NONE".to_string()),
+            count => creation_opts.push(format!("OVERVIEW_COUNT={}", count)),
+        }
+    }
+
+    if let Some(resampling) = options.overview_resampling {
+        status!("Overview resampling: {}", resampling.name());
+        creation_opts.push(format!("OVERVIEW_RESAMPLING={}", resampling.name()));
+    }
+
+    if options.sparse {
+        let supported = driver
+            .metadata_item("DMD_CREATIONOPTIONLIST", "")
+            .is_some_and(|list| list.contains("SPARSE_OK"));
+        if !supported {
+            return Err("This GDAL build's COG driver does not support SPARSE_OK".to_string());
+        }
+        status!("Sparse output: blocks that are entirely NoData are not written");
+        creation_opts.push("SPARSE_OK=TRUE".to_string());
+    }
+
+    // GDAL's own IF_NEEDED guess ignores overviews, so decide here from the estimate
+    let estimate = estimated_cog_bytes(&dataset);
+    let too_big = estimate > BIGTIFF_THRESHOLD;
+    let gigabytes = estimate as f64 / 1e9;
+    let bigtiff = match options.bigtiff {
+        BigTiff::IfNeeded if too_big => {
+            status!("BigTIFF: yes (estimated {:.1} GB uncompressed)", gigabytes);
+            BigTiff::Yes
+        }
+        BigTiff::IfNeeded => BigTiff::No,
+        BigTiff::No if too_big => {
+            status!(
+                "Warning: the output is estimated at {:.1} GB uncompressed and may not fit in a classic TIFF",
+                gigabytes
+            );
+            BigTiff::No
+        }
+        other => other,
+    };
+    creation_opts.push(format!("BIGTIFF={}", bigtiff.name()));
+
+    merge_creation_options(&mut creation_opts, &options.creation_options);
+    let creation_options = RasterCreationOptions::from_iter(creation_opts);
+
+    // The COG is written under a temporary name and renamed into place once complete, so an
+    // interrupted run never leaves a partial file at the output path
+    let partial_path = TempFile::new(&out_path, PARTIAL_TIF);
     dataset
-        .create_copy(&driver, out_path.to_str().unwrap(), &creation_options)
-        .map_err(|e| format!("Failed to create COG: {:?}", e))?;
+        .create_copy(&driver, partial_path.path(), &creation_options)
+        .map_err(|e| {
+            if bigtiff == BigTiff::No && too_big {
+                format!(
+                    "Failed to create COG: {:?}. The output is estimated at {:.1} GB and needs BigTIFF; rerun without --bigtiff no",
+                    e, gigabytes
+                )
+            } else {
+                format!("Failed to create COG: {:?}", e)
+            }
+        })?;
+
+    drop(dataset);
+    // Checked before the rename, so a failing COG never replaces the input or an earlier output
+    if options.crs_authority.require {
+        check_output_authority(partial_path.path(), &options.crs_authority)?;
+    }
+    move_into_place(partial_path.path(), &out_path)?;
+    if options.verify {
+        validate_cog(
+            &out_path,
+            options.blocksize.unwrap_or(512),
+            options.min_overview_size,
+        )?;
+    }
+
+    if let Some(size) = options.thumbnail {
+        write_thumbnail(&out_path, size, options.thumbnail_stretch)?;
+    }
 
     Ok(out_path.file_name().unwrap().to_str().unwrap().to_string())
 }
+
+/// Check that a GeoTIFF is cloud-optimized, along the lines of GDAL's
+/// `validate_cloud_optimized_geotiff.py`
+///
+/// The main image and every overview must be tiled at `blocksize`, rasters larger than one tile
+/// must have overviews, and GDAL must report the COG layout (`LAYOUT=COG`). With
+/// `min_overview_size`, overviews are only required when [`overview_count_for`] asks for at
+/// least one, since `--min-overview-size` writes none for small rasters. The main IFD must
+/// come first in the file, followed by the overview IFDs, and tile data must run from the
+/// smallest overview to the full-resolution image so readers can fetch a preview in one request.
+pub fn validate_cog(
+    path: &Path,
+    blocksize: usize,
+    min_overview_size: Option<usize>,
+) -> Result<(), String> {
+    let fail = |problem: String| {
+        Err(format!(
+            "{} is not a valid COG: {}",
+            path.display(),
+            problem
+        ))
+    };
+    let (_permit, dataset) = open_limit::open(path)
+        .map_err(|e| format!("Cannot reopen {} to validate it: {}", path.display(), e))?;
+    if dataset.driver().short_name() != "GTiff" {
+        return fail(format!(
+            "opened with the {} driver",
+            dataset.driver().short_name()
+        ));
+    }
+    if dataset
+        .metadata_item("LAYOUT", "IMAGE_STRUCTURE")
+        .as_deref()
+        != Some("COG")
+    {
+        return fail("GDAL does not report LAYOUT=COG".to_string());
+    }
+    let band = dataset
+        .rasterband(1)
+        .map_err(|e| format!("Cannot read band 1 of {}: {}", path.display(), e))?;
+
+    if band.block_size() != (blocksize, blocksize) {
+        let (x, y) = band.block_size();
+        return fail(format!(
+            "blocks are {}x{}, expected {}x{} tiles",
+            x, y, blocksize, blocksize
+        ));
+    }
+    let overview_count = band.overview_count().unwrap_or(0).max(0) as usize;
+    let (cols, rows) = dataset.raster_size();
+    let overviews_expected = match min_overview_size {
+        Some(min_size) => overview_count_for((cols, rows), min_size) > 0,
+        None => cols > blocksize || rows > blocksize,
+    };
+    if overview_count == 0 && overviews_expected {
+        return fail(format!("{}x{} raster has no overviews", cols, rows));
+    }
+
+    // IFD and first-tile offsets of the main image, then each overview from largest to smallest
+    let tiff_offset = |band: &RasterBand, key: &str| {
+        band.metadata_item(key, "TIFF")
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(0)
+    };
+    let mut ifd_offsets = vec![tiff_offset(&band, "IFD_OFFSET")];
+    let mut data_offsets = vec![tiff_offset(&band, "BLOCK_OFFSET_0_0")];
+    for idx in 0..overview_count {
+        let overview = band
+            .overview(idx)
+            .map_err(|e| format!("Cannot read overview {} of {}: {}", idx, path.display(), e))?;
+        if overview.block_size() != (blocksize, blocksize) {
+            let (x, y) = overview.block_size();
+            return fail(format!(
+                "overview {} has {}x{} blocks, expected {}x{} tiles",
+                idx, x, y, blocksize, blocksize
+            ));
+        }
+        ifd_offsets.push(tiff_offset(&overview, "IFD_OFFSET"));
+        data_offsets.push(tiff_offset(&overview, "BLOCK_OFFSET_0_0"));
+    }
+
+    // Offset 8 for classic TIFF, 16 for BigTIFF
+    if !matches!(ifd_offsets[0], 8 | 16) {
+        return fail(format!(
+            "the main IFD is at offset {}, not at the start of the file",
+            ifd_offsets[0]
+        ));
+    }
+    if let Some(idx) = ifd_offsets.windows(2).position(|pair| pair[1] <= pair[0]) {
+        return fail(format!(
+            "the IFD of overview {} comes before the one of the level above it",
+            idx
+        ));
+    }
+    // Sparse files leave empty tiles unwritten (offset 0), so only written tiles are compared
+    let written: Vec<(usize, u64)> = data_offsets
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|&(_, offset)| offset > 0)
+        .collect();
+    if let Some(pair) = written.windows(2).find(|pair| pair[1].1 >= pair[0].1) {
+        let level = |idx: usize| {
+            if idx == 0 {
+                "the main image".to_string()
+            } else {
+                format!("overview {}", idx - 1)
+            }
+        };
+        return fail(format!(
+            "tile data of {} comes after {}; the smallest overview should come first",
+            level(pair[1].0),
+            level(pair[0].0)
+        ));
+    }
+    Ok(())
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let month = month as i64;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Proleptic Gregorian date for a number of days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Parse `YYYY-MM-DD` (optionally followed by a time) or `YYYYMMDD`
+fn parse_calendar_date(value: &str) -> Option<(i64, u32, u32)> {
+    let value = value.trim();
+    let digits: String = if value.len() >= 10 && value.as_bytes()[4] == b'-' {
+        if value.as_bytes()[7] != b'-' {
+            return None;
+        }
+        // get() rather than indexing: a multi-byte character would put a range off a
+        // char boundary
+        format!(
+            "{}{}{}",
+            value.get(0..4)?,
+            value.get(5..7)?,
+            value.get(8..10)?
+        )
+    } else if value.len() == 8 {
+        value.to_string()
+    } else {
+        return None;
+    };
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let year = digits[0..4].parse().ok()?;
+    let month = digits[4..6].parse().ok()?;
+    let day = digits[6..8].parse().ok()?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    // Round-trip through day numbers to reject dates such as 2021-02-30
+    let normalized = civil_from_days(days_from_civil(year, month, day));
+    (normalized == (year, month, day)).then_some(normalized)
+}
+
+/// Turn a band date value into `YYYY-MM-DD`
+///
+/// Accepts calendar dates directly, or numeric offsets when `units` reads like the
+/// netCDF convention `days since 1900-01-01` (also `hours`, `minutes`, `seconds`).
+pub fn parse_band_date(value: &str, units: Option<&str>) -> Result<String, String> {
+    if let Some((year, month, day)) = parse_calendar_date(value) {
+        return Ok(format!("{:04}-{:02}-{:02}", year, month, day));
+    }
+
+    let offset: f64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Cannot parse '{}' as a date", value))?;
+    let units = units.ok_or_else(|| {
+        format!(
+            "Cannot parse '{}' as a date: numeric value without time units",
+            value
+        )
+    })?;
+    let (unit, epoch) = units
+        .split_once(" since ")
+        .ok_or_else(|| format!("Unsupported time units '{}'", units))?;
+    let seconds_per_unit = match unit.trim().to_lowercase().as_str() {
+        "days" | "day" => 86400.0,
+        "hours" | "hour" => 3600.0,
+        "minutes" | "minute" => 60.0,
+        "seconds" | "second" => 1.0,
+        other => return Err(format!("Unsupported time unit '{}'", other)),
+    };
+    let (year, month, day) = parse_calendar_date(epoch)
+        .ok_or_else(|| format!("Unsupported time units epoch '{}'", epoch))?;
+    let days =
+        days_from_civil(year, month, day) + (offset * seconds_per_unit / 86400.0).floor() as i64;
+    let (year, month, day) = civil_from_days(days);
+    Ok(format!("{:04}-{:02}-{:02}", year, month, day))
+}
+
+/// Write each band of a raster to its own single-band COG
+///
+/// Outputs are named `<stem>_b<N>.tif`, or `<stem>_<YYYY-MM-DD>.tif` when `date_key`
+/// names a band metadata item holding the band's date (e.g. `NETCDF_DIM_time`).
+///
+/// # Arguments
+/// * `input_path` - Multi-band raster to split
+/// * `output_dir` - Directory for the per-band COGs, defaults to the input's directory
+/// * `overwrite` - Replace existing outputs
+/// * `options` - COG options applied to every band
+/// * `date_key` - Band metadata item to read dates from
+pub fn split_bands_to_cogs(
+    input_path: &Path,
+    output_dir: Option<&Path>,
+    overwrite: bool,
+    options: &CogOptions,
+    date_key: Option<&str>,
+) -> Result<Vec<PathBuf>, String> {
+    if !input_path.exists() {
+        return Err(format!("Error: The file {:?} does not exist.", input_path));
+    }
+    let (_permit, dataset) =
+        open_limit::open(input_path).map_err(|e| format!("Failed to open dataset: {:?}", e))?;
+    let stem = input_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+    let out_dir = match output_dir {
+        Some(dir) => dir.to_path_buf(),
+        None => input_path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+    };
+    std::fs::create_dir_all(&out_dir)
+        .map_err(|e| format!("Failed to create output directory: {}", e))?;
+
+    // netCDF stores the units of dimension `time` as the dataset item `time#units`
+    let units = date_key.and_then(|key| {
+        let dim = key.strip_prefix("NETCDF_DIM_").unwrap_or(key);
+        dataset.metadata_item(&format!("{}#units", dim), "")
+    });
+
+    let bands = match &options.bands {
+        Some(bands) => {
+            check_band_list(bands, dataset.raster_count())?;
+            bands.clone()
+        }
+        None => (1..=dataset.raster_count()).collect(),
+    };
+    // Each per-band VRT has a single band, so the selection is not applied again
+    let band_options = CogOptions {
+        bands: None,
+        ..options.clone()
+    };
+
+    // Resolve every label first so a bad date fails before anything is written
+    let mut labels = Vec::with_capacity(bands.len());
+    for &index in &bands {
+        let label = match date_key {
+            Some(key) => {
+                let band = dataset
+                    .rasterband(index)
+                    .map_err(|e| format!("Failed to read band {}: {:?}", index, e))?;
+                let value = band
+                    .metadata_item(key, "")
+                    .ok_or_else(|| format!("Band {} has no '{}' metadata item", index, key))?;
+                parse_band_date(&value, units.as_deref())
+                    .map_err(|e| format!("Band {}: {}", index, e))?
+            }
+            None => format!("b{}", index),
+        };
+        if labels.contains(&label) {
+            return Err(format!("Band {} repeats the date {}", index, label));
+        }
+        labels.push(label);
+    }
+
+    let mut outputs = Vec::with_capacity(labels.len());
+    for (&index, label) in bands.iter().zip(&labels) {
+        let out_path = out_dir.join(format!("{}_{}.tif", stem, label));
+        let vrt_path = TempFile::new(&out_path, BAND_VRT);
+        let vrt_options = BuildVRTOptions::new(vec!["-b".to_string(), index.to_string()])
+            .map_err(|e| format!("Invalid VRT options: {:?}", e))?;
+        drop(
+            build_vrt(Some(vrt_path.path()), &[&dataset], Some(vrt_options))
+                .map_err(|e| format!("Failed to build VRT for band {}: {:?}", index, e))?,
+        );
+
+        tif_to_cog(vrt_path.path(), Some(&out_path), overwrite, &band_options)?;
+        outputs.push(out_path);
+    }
+
+    status!(
+        "Split {} bands of {} into {}",
+        outputs.len(),
+        input_path.display(),
+        out_dir.display()
+    );
+    Ok(outputs)
+}
@@ -0,0 +1,194 @@
+use crate::gdal_options::merge_creation_options;
+use crate::open_limit;
+use crate::progress::status;
+use crate::tempdir::{PARTIAL_ZARR, TempFile};
+use crate::vsi::{is_vsi, to_vsi};
+use gdal::raster::RasterCreationOptions;
+use gdal::{DriverManager, Metadata};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Codec for Zarr chunks
+///
+/// ZLIB and GZIP are always available; the others depend on how GDAL was built.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ZarrCompression {
+    #[default]
+    Zlib,
+    Gzip,
+    Zstd,
+    Lz4,
+    Blosc,
+    None,
+}
+
+impl ZarrCompression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ZarrCompression::Zlib => "ZLIB",
+            ZarrCompression::Gzip => "GZIP",
+            ZarrCompression::Zstd => "ZSTD",
+            ZarrCompression::Lz4 => "LZ4",
+            ZarrCompression::Blosc => "BLOSC",
+            ZarrCompression::None => "NONE",
+        }
+    }
+}
+
+impl FromStr for ZarrCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "zlib" => Ok(ZarrCompression::Zlib),
+            "gzip" => Ok(ZarrCompression::Gzip),
+            "zstd" => Ok(ZarrCompression::Zstd),
+            "lz4" => Ok(ZarrCompression::Lz4),
+            "blosc" => Ok(ZarrCompression::Blosc),
+            "none" => Ok(ZarrCompression::None),
+            other => Err(format!(
+                "Unsupported Zarr compression '{}'. Use zlib, gzip, zstd, lz4, blosc or none.",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ZarrCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name().to_lowercase())
+    }
+}
+
+/// Parse a chunk size given as `SIZE` (square) or `XSIZE,YSIZE`
+pub fn parse_chunk(s: &str) -> Result<(usize, usize), String> {
+    let invalid = || {
+        format!(
+            "Invalid chunk size '{}'. Use SIZE or XSIZE,YSIZE, e.g. 512 or 1024,256.",
+            s
+        )
+    };
+    let parse = |v: &str| {
+        v.trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(invalid)
+    };
+    match s.split_once(',') {
+        Some((x, y)) => Ok((parse(x)?, parse(y)?)),
+        None => parse(s).map(|size| (size, size)),
+    }
+}
+
+/// Options for [`tif_to_zarr`]
+#[derive(Debug, Clone, Default)]
+pub struct ZarrOptions {
+    /// Chunk width and height in pixels (driver default when unset)
+    pub chunk: Option<(usize, usize)>,
+    /// Chunk codec
+    pub compression: ZarrCompression,
+    /// Extra creation options (`--co`), replacing any the tool sets with the same key
+    pub creation_options: Vec<(String, String)>,
+}
+
+/// Delete the store (or file) at `path`, if there is one
+fn remove_store(path: &Path) -> Result<(), String> {
+    let removed = if path.is_dir() {
+        fs::remove_dir_all(path)
+    } else if path.exists() {
+        fs::remove_file(path)
+    } else {
+        return Ok(());
+    };
+    removed.map_err(|e| format!("Failed to remove existing {:?}: {}", path, e))
+}
+
+/// Converts a raster to a Zarr store with GDAL's Zarr driver
+///
+/// Paths are handled as in [`crate::tif2cog::tif_to_cog`], except that the output is a
+/// `.zarr` directory next to the input when `output_path` is unset. The store is written as
+/// `<output>.partial.zarr` and renamed into place once complete. With `overwrite`, an existing
+/// store is only deleted then, since the driver cannot write over one, so a failed conversion
+/// leaves it untouched.
+pub fn tif_to_zarr(
+    input_path: &Path,
+    output_path: Option<&Path>,
+    overwrite: bool,
+    options: &ZarrOptions,
+) -> Result<String, String> {
+    let input_path = &to_vsi(input_path);
+    let remote = is_vsi(input_path);
+    if !remote && !input_path.exists() {
+        return Err(format!("Error: The file {:?} does not exist.", input_path));
+    }
+    if remote && output_path.is_none() {
+        return Err(format!(
+            "Error: {:?} is a remote object; pass --out to write the Zarr store locally.",
+            input_path
+        ));
+    }
+
+    let out_path = output_path
+        .unwrap_or(input_path)
+        .to_path_buf()
+        .with_extension("zarr");
+    if out_path == *input_path {
+        return Err(format!(
+            "Error: The output {:?} would replace the input.",
+            out_path
+        ));
+    }
+    if out_path.exists() && !overwrite {
+        return Err(format!(
+            "Error: The file {:?} already exists and overwrite is false.",
+            out_path
+        ));
+    }
+    status!("Output will be saved to: {:?}", out_path);
+
+    let driver = DriverManager::get_driver_by_name("Zarr").map_err(|e| {
+        format!(
+            "This GDAL build has no Zarr driver (GDAL 3.4 or newer): {:?}",
+            e
+        )
+    })?;
+    // Codecs other than ZLIB and GZIP are optional, and the driver only lists the ones it has
+    let creation_list = driver
+        .metadata_item("DMD_CREATIONOPTIONLIST", "")
+        .unwrap_or_default();
+    let codec = format!("<Value>{}</Value>", options.compression.name());
+    if options.compression != ZarrCompression::None && !creation_list.contains(&codec) {
+        return Err(format!(
+            "This GDAL build's Zarr driver does not support {} compression",
+            options.compression.name()
+        ));
+    }
+    let mut creation_opts = vec![format!("COMPRESS={}", options.compression.name())];
+    if let Some((x, y)) = options.chunk {
+        creation_opts.push(format!("BLOCKSIZE={},{}", x, y));
+    }
+    merge_creation_options(&mut creation_opts, &options.creation_options);
+    let creation_options = RasterCreationOptions::from_iter(creation_opts);
+
+    let (_permit, dataset) =
+        open_limit::open(input_path).map_err(|e| format!("Failed to open dataset: {:?}", e))?;
+    let partial_path = TempFile::beside(&out_path, PARTIAL_ZARR);
+    // A leftover from an interrupted run would make the driver refuse to create the store
+    remove_store(partial_path.path())?;
+    dataset
+        .create_copy(&driver, partial_path.path(), &creation_options)
+        .map_err(|e| format!("Failed to create Zarr store: {:?}", e))?;
+
+    remove_store(&out_path)?;
+    fs::rename(partial_path.path(), &out_path)
+        .map_err(|e| format!("Failed to move Zarr store to {:?}: {}", out_path, e))?;
+
+    Ok(out_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string())
+}
@@ -0,0 +1,193 @@
+use std::str::FromStr;
+
+/// A unit known to `--convert-units`, as `value_in_base = value * scale + offset`
+struct Unit {
+    /// Name written to band metadata
+    name: &'static str,
+    aliases: &'static [&'static str],
+    quantity: &'static str,
+    scale: f64,
+    offset: f64,
+}
+
+const DAY: f64 = 86_400.0;
+
+/// Base units: kelvin, metre, mm per second, metre per second
+const UNITS: &[Unit] = &[
+    Unit {
+        name: "K",
+        aliases: &["kelvin"],
+        quantity: "temperature",
+        scale: 1.0,
+        offset: 0.0,
+    },
+    Unit {
+        name: "degC",
+        aliases: &["c", "°c", "celsius", "deg_c"],
+        quantity: "temperature",
+        scale: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        name: "degF",
+        aliases: &["f", "°f", "fahrenheit", "deg_f"],
+        quantity: "temperature",
+        scale: 5.0 / 9.0,
+        offset: 273.15 - 32.0 * 5.0 / 9.0,
+    },
+    Unit {
+        name: "m",
+        aliases: &["metre", "meter", "metres", "meters"],
+        quantity: "length",
+        scale: 1.0,
+        offset: 0.0,
+    },
+    Unit {
+        name: "km",
+        aliases: &["kilometre", "kilometer"],
+        quantity: "length",
+        scale: 1000.0,
+        offset: 0.0,
+    },
+    Unit {
+        name: "cm",
+        aliases: &["centimetre", "centimeter"],
+        quantity: "length",
+        scale: 0.01,
+        offset: 0.0,
+    },
+    Unit {
+        name: "mm",
+        aliases: &["millimetre", "millimeter"],
+        quantity: "length",
+        scale: 0.001,
+        offset: 0.0,
+    },
+    Unit {
+        name: "ft",
+        aliases: &["foot", "feet"],
+        quantity: "length",
+        scale: 0.3048,
+        offset: 0.0,
+    },
+    Unit {
+        name: "in",
+        aliases: &["inch", "inches"],
+        quantity: "length",
+        scale: 0.0254,
+        offset: 0.0,
+    },
+    Unit {
+        name: "mi",
+        aliases: &["mile", "miles"],
+        quantity: "length",
+        scale: 1609.344,
+        offset: 0.0,
+    },
+    Unit {
+        name: "mm/s",
+        aliases: &["kg m-2 s-1", "kg/m2/s"],
+        quantity: "rate",
+        scale: 1.0,
+        offset: 0.0,
+    },
+    Unit {
+        name: "mm/hour",
+        aliases: &["mm/h", "mm/hr"],
+        quantity: "rate",
+        scale: 1.0 / 3600.0,
+        offset: 0.0,
+    },
+    Unit {
+        name: "mm/day",
+        aliases: &["mm/d", "mm day-1"],
+        quantity: "rate",
+        scale: 1.0 / DAY,
+        offset: 0.0,
+    },
+    Unit {
+        name: "mm/year",
+        aliases: &["mm/yr", "mm/a", "mm year-1"],
+        quantity: "rate",
+        scale: 1.0 / (365.0 * DAY),
+        offset: 0.0,
+    },
+    Unit {
+        name: "m/s",
+        aliases: &["m s-1"],
+        quantity: "speed",
+        scale: 1.0,
+        offset: 0.0,
+    },
+    Unit {
+        name: "km/h",
+        aliases: &["kph", "km h-1"],
+        quantity: "speed",
+        scale: 1.0 / 3.6,
+        offset: 0.0,
+    },
+];
+
+fn find_unit(name: &str) -> Option<&'static Unit> {
+    let name = name.trim();
+    UNITS.iter().find(|unit| {
+        unit.name.eq_ignore_ascii_case(name)
+            || unit
+                .aliases
+                .iter()
+                .any(|alias| alias.eq_ignore_ascii_case(name))
+    })
+}
+
+/// Canonical name of a known unit, e.g. "celsius" -> "degC"
+pub fn canonical_unit(name: &str) -> Option<&'static str> {
+    find_unit(name).map(|unit| unit.name)
+}
+
+/// A linear conversion `to = from * scale + offset` between two units of the same quantity
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitConversion {
+    pub from: &'static str,
+    pub to: &'static str,
+    pub scale: f64,
+    pub offset: f64,
+}
+
+impl UnitConversion {
+    pub fn new(from: &str, to: &str) -> Result<Self, String> {
+        let unknown = |name: &str| {
+            let known: Vec<&str> = UNITS.iter().map(|unit| unit.name).collect();
+            format!("Unknown unit '{}'. Supported: {}", name, known.join(", "))
+        };
+        let source = find_unit(from).ok_or_else(|| unknown(from))?;
+        let target = find_unit(to).ok_or_else(|| unknown(to))?;
+        if source.quantity != target.quantity {
+            return Err(format!(
+                "Cannot convert {} ({}) to {} ({})",
+                source.name, source.quantity, target.name, target.quantity
+            ));
+        }
+        Ok(UnitConversion {
+            from: source.name,
+            to: target.name,
+            scale: source.scale / target.scale,
+            offset: (source.offset - target.offset) / target.scale,
+        })
+    }
+
+    pub fn apply(&self, value: f64) -> f64 {
+        value * self.scale + self.offset
+    }
+}
+
+impl FromStr for UnitConversion {
+    type Err = String;
+
+    /// Parse `FROM:TO`, e.g. `K:degC` or `mm/day:mm/year`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (from, to) = s
+            .split_once(':')
+            .ok_or_else(|| format!("Expected FROM:TO units, got '{}'", s))?;
+        UnitConversion::new(from, to)
+    }
+}
@@ -1,16 +1,688 @@
-use gdal::Dataset;
-// use gdal::errors::Result;
-use gdal::{DriverManager, vector::*};
-use std::path::{Path, PathBuf};
+use crate::crs_authority::{AuthorityCheck, check_output_authority, identify_epsg};
+use crate::gdal_options::merge_creation_options;
+use crate::open_limit;
+use crate::progress::status;
+use crate::tempdir::{PARTIAL, STDOUT, TempFile, move_into_place};
 use gdal::config;
+use gdal::spatial_ref::{AxisMappingStrategy, CoordTransform, SpatialRef};
+use gdal::{Dataset, DatasetOptions, DriverManager, GdalOpenFlags, Metadata, vector::*};
+use serde::Serialize;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Broad geometry family used to filter features; multi-geometries match their single type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeometryKind {
+    Point,
+    Line,
+    Polygon,
+}
+
+impl GeometryKind {
+    /// Whether an OGR geometry type belongs to this family, ignoring Z/M and multi-ness
+    pub fn matches(&self, ty: OGRwkbGeometryType::Type) -> bool {
+        use OGRwkbGeometryType::*;
+        let flat = geometry_type_flatten(ty);
+        match self {
+            GeometryKind::Point => flat == wkbPoint || flat == wkbMultiPoint,
+            GeometryKind::Line => flat == wkbLineString || flat == wkbMultiLineString,
+            GeometryKind::Polygon => flat == wkbPolygon || flat == wkbMultiPolygon,
+        }
+    }
+}
+
+impl FromStr for GeometryKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "point" | "multipoint" => Ok(GeometryKind::Point),
+            "line" | "linestring" | "multilinestring" => Ok(GeometryKind::Line),
+            "polygon" | "multipolygon" => Ok(GeometryKind::Polygon),
+            other => Err(format!(
+                "Unsupported geometry type '{}'. Use point, line or polygon.",
+                other
+            )),
+        }
+    }
+}
+
+/// How geometries are stored in the GeoParquet geometry column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GeoEncoding {
+    /// Well-known binary, readable by every GeoParquet reader
+    #[default]
+    Wkb,
+    /// Native GeoArrow struct/list columns (GeoParquet 1.1), for layers of a single geometry type
+    Geoarrow,
+}
+
+impl GeoEncoding {
+    /// Value of the Parquet driver's `GEOMETRY_ENCODING` layer creation option
+    pub fn name(&self) -> &'static str {
+        match self {
+            GeoEncoding::Wkb => "WKB",
+            GeoEncoding::Geoarrow => "GEOARROW",
+        }
+    }
+}
+
+impl FromStr for GeoEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "wkb" => Ok(GeoEncoding::Wkb),
+            "geoarrow" => Ok(GeoEncoding::Geoarrow),
+            other => Err(format!(
+                "Unsupported geometry encoding '{}'. Use wkb or geoarrow.",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for GeoEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name().to_lowercase())
+    }
+}
+
+/// Codec for the Parquet column chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParquetCompression {
+    Snappy,
+    Zstd,
+    Gzip,
+    None,
+}
+
+impl ParquetCompression {
+    /// Value of the Parquet driver's `COMPRESSION` layer creation option
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParquetCompression::Snappy => "SNAPPY",
+            ParquetCompression::Zstd => "ZSTD",
+            ParquetCompression::Gzip => "GZIP",
+            ParquetCompression::None => "NONE",
+        }
+    }
+}
+
+impl FromStr for ParquetCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "snappy" => Ok(ParquetCompression::Snappy),
+            "zstd" => Ok(ParquetCompression::Zstd),
+            "gzip" => Ok(ParquetCompression::Gzip),
+            "none" => Ok(ParquetCompression::None),
+            other => Err(format!(
+                "Unsupported Parquet compression '{}'. Use snappy, zstd, gzip or none.",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for ParquetCompression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name().to_lowercase())
+    }
+}
+
+/// Vector format written by [`vector_convert`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VectorFormat {
+    /// GeoParquet, the cloud-native default
+    #[default]
+    Parquet,
+    /// GeoPackage, for desktop GIS users
+    Gpkg,
+    /// FlatGeobuf, streamable with a spatial index
+    FlatGeobuf,
+    /// GeoJSON, for small layers and web maps
+    GeoJson,
+}
+
+impl VectorFormat {
+    /// Short name of the GDAL driver that writes this format
+    pub fn driver(&self) -> &'static str {
+        match self {
+            VectorFormat::Parquet => "Parquet",
+            VectorFormat::Gpkg => "GPKG",
+            VectorFormat::FlatGeobuf => "FlatGeobuf",
+            VectorFormat::GeoJson => "GeoJSON",
+        }
+    }
+
+    /// File extension given to outputs in this format
+    pub fn extension(&self) -> &'static str {
+        match self {
+            VectorFormat::Parquet => "parquet",
+            VectorFormat::Gpkg => "gpkg",
+            VectorFormat::FlatGeobuf => "fgb",
+            VectorFormat::GeoJson => "geojson",
+        }
+    }
+
+    /// Name used in messages
+    pub fn name(&self) -> &'static str {
+        match self {
+            VectorFormat::Parquet => "GeoParquet",
+            VectorFormat::Gpkg => "GeoPackage",
+            VectorFormat::FlatGeobuf => "FlatGeobuf",
+            VectorFormat::GeoJson => "GeoJSON",
+        }
+    }
+}
+
+impl FromStr for VectorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "parquet" | "geoparquet" => Ok(VectorFormat::Parquet),
+            "gpkg" | "geopackage" => Ok(VectorFormat::Gpkg),
+            "fgb" | "flatgeobuf" => Ok(VectorFormat::FlatGeobuf),
+            "geojson" => Ok(VectorFormat::GeoJson),
+            other => Err(format!(
+                "Unsupported vector format '{}'. Use parquet, gpkg, flatgeobuf or geojson.",
+                other
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for VectorFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            VectorFormat::Parquet => "parquet",
+            VectorFormat::Gpkg => "gpkg",
+            VectorFormat::FlatGeobuf => "flatgeobuf",
+            VectorFormat::GeoJson => "geojson",
+        })
+    }
+}
+
+/// Options for GeoParquet conversion
+#[derive(Debug, Clone, Default)]
+pub struct GpqOptions {
+    /// Reopen the written file and check its feature count matches the source
+    pub verify: bool,
+    /// Only keep features of this geometry family, skipping the rest
+    pub geometry_type: Option<GeometryKind>,
+    /// Source CRS to assume instead of the one declared by the input (e.g. "EPSG:4326")
+    pub s_srs: Option<String>,
+    /// CRS to reproject features into
+    pub t_srs: Option<String>,
+    /// Wrap longitudes into -180..180 (geographic output CRS only)
+    pub normalize_longitude: bool,
+    /// With `normalize_longitude`, split geometries that cross the antimeridian
+    pub split_antimeridian: bool,
+    /// CSV column holding point x/longitude (default "lon")
+    pub x_field: Option<String>,
+    /// CSV column holding point y/latitude (default "lat")
+    pub y_field: Option<String>,
+    /// CRS of CSV coordinates (default "EPSG:4326")
+    pub csv_crs: Option<String>,
+    /// Only convert features whose FID is at least this value
+    pub fid_start: Option<u64>,
+    /// Only convert features whose FID is below this value
+    pub fid_end: Option<u64>,
+    /// OGR SQL `WHERE` clause selecting the features to convert
+    pub where_clause: Option<String>,
+    /// Only convert features intersecting `[minx, miny, maxx, maxy]`, in the source CRS
+    pub bbox: Option<[f64; 4]>,
+    /// Stamp and/or require an authority code on the output CRS
+    pub crs_authority: AuthorityCheck,
+    /// Geometry column encoding
+    pub geo_encoding: GeoEncoding,
+    /// Features per Parquet row group (driver default when unset)
+    pub row_group_size: Option<usize>,
+    /// Column chunk codec (driver default when unset)
+    pub compression: Option<ParquetCompression>,
+    /// Flush the writer every this many features, so large layers are not buffered whole
+    pub batch_size: Option<usize>,
+    /// Input layer to convert, by name or zero-based index (default: the first)
+    pub layer: Option<String>,
+    /// Convert every layer to `<output stem>_<layer>.<format extension>`
+    pub all_layers: bool,
+    /// Replace existing outputs instead of failing
+    pub overwrite: bool,
+    /// Output format for [`vector_convert`] and [`explode_to_geoparquet`] (default GeoParquet)
+    pub format: VectorFormat,
+    /// Extra layer creation options (`--co`), replacing any the tool sets with the same key
+    pub creation_options: Vec<(String, String)>,
+    /// Add an `area_m2` column with each polygon's equal-area projected area in square meters
+    pub add_area: bool,
+    /// Add a `length_m` column with each line's great-circle length in meters
+    pub add_length: bool,
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+}
+
+/// GeoJSON and FlatGeobuf, whose readers handle a declared non-WGS84 CRS inconsistently
+fn is_geojson_or_fgb(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            ["geojson", "json", "fgb"]
+                .iter()
+                .any(|e| ext.eq_ignore_ascii_case(e))
+        })
+}
+
+/// Whether `srs` is geographic WGS 84 (EPSG:4326 or OGC:CRS84)
+fn is_wgs84(srs: &SpatialRef) -> bool {
+    srs.to_proj4()
+        .is_ok_and(|proj4| proj4.contains("+proj=longlat") && proj4.contains("+datum=WGS84"))
+}
+
+/// Column written by `--add-area`, in square meters
+pub const AREA_FIELD: &str = "area_m2";
+/// Column written by `--add-length`, in meters
+pub const LENGTH_FIELD: &str = "length_m";
+
+/// Mean Earth radius (IUGG) used for great-circle lengths
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+/// Transforms from the output CRS used to measure features for `--add-area` and `--add-length`
+///
+/// Neither is an ellipsoidal geodesic measure. Areas are planar areas in an equal-area
+/// projection: the output CRS itself when it is an equal-area projection in meters, otherwise
+/// World Cylindrical Equal Area (EPSG:6933). Edges are straight in that projection rather than
+/// geodesics, which matters only for long edges. Lengths are great-circle distances on a sphere
+/// between vertices in geographic WGS 84, within about 0.5% of the ellipsoidal distance.
+struct Measures {
+    /// `Some(None)` measures areas in the output CRS as-is
+    area: Option<Option<CoordTransform>>,
+    /// `Some(None)` when the output CRS is already geographic WGS 84
+    length: Option<Option<CoordTransform>>,
+}
+
+impl Measures {
+    fn new(srs: Option<&SpatialRef>, options: &GpqOptions) -> Result<Self, String> {
+        if !options.add_area && !options.add_length {
+            return Ok(Measures {
+                area: None,
+                length: None,
+            });
+        }
+        let srs =
+            srs.ok_or("--add-area and --add-length need a CRS; pass --s-srs to assign one")?;
+        let to = |definition: &str| -> Result<CoordTransform, String> {
+            CoordTransform::new(srs, &parse_srs(definition)?).map_err(|e| {
+                format!(
+                    "Failed to create CRS transform for measuring features: {}",
+                    e
+                )
+            })
+        };
+        let area = if !options.add_area {
+            None
+        } else if is_equal_area_meters(srs) {
+            Some(None)
+        } else {
+            Some(Some(to("EPSG:6933")?))
+        };
+        let length = if !options.add_length {
+            None
+        } else if is_wgs84(srs) {
+            Some(None)
+        } else {
+            Some(Some(to("EPSG:4326")?))
+        };
+        Ok(Measures { area, length })
+    }
+
+    /// Equal-area projected area of a polygon geometry in square meters; `None` for other
+    /// geometry types
+    fn area(&self, geom: &Geometry) -> Result<Option<f64>, String> {
+        let Some(transform) = &self.area else {
+            return Ok(None);
+        };
+        if !GeometryKind::Polygon.matches(geom.geometry_type()) {
+            return Ok(None);
+        }
+        let area = match transform {
+            Some(ct) => geom
+                .transform(ct)
+                .map_err(|e| format!("Failed to project geometry to measure its area: {}", e))?
+                .area(),
+            None => geom.area(),
+        };
+        Ok(Some(area))
+    }
+
+    /// Great-circle length of a line geometry in meters; `None` for other geometry types
+    fn length(&self, geom: &Geometry) -> Result<Option<f64>, String> {
+        let Some(transform) = &self.length else {
+            return Ok(None);
+        };
+        if !GeometryKind::Line.matches(geom.geometry_type()) {
+            return Ok(None);
+        }
+        let length =
+            match transform {
+                Some(ct) => great_circle_length(&geom.transform(ct).map_err(|e| {
+                    format!("Failed to project geometry to measure its length: {}", e)
+                })?),
+                None => great_circle_length(geom),
+            };
+        Ok(Some(length))
+    }
+}
+
+/// Whether `srs` is an equal-area projection with meter units, so planar areas are true areas
+fn is_equal_area_meters(srs: &SpatialRef) -> bool {
+    srs.to_proj4().is_ok_and(|proj4| {
+        let equal_area = ["aea", "laea", "cea", "moll", "sinu", "eqearth"]
+            .iter()
+            .any(|proj| proj4.contains(&format!("+proj={} ", proj)));
+        equal_area && proj4.contains("+units=m ")
+    })
+}
+
+/// Sum of great-circle distances between consecutive lon/lat vertices, recursing into parts
+fn great_circle_length(geom: &Geometry) -> f64 {
+    let parts = geom.geometry_count();
+    if parts > 0 {
+        return (0..parts)
+            .map(|i| great_circle_length(&geom.get_geometry(i)))
+            .sum();
+    }
+    let points = (0..geom.point_count())
+        .map(|i| geom.get_point(i as i32))
+        .collect::<Vec<_>>();
+    points
+        .windows(2)
+        .map(|pair| haversine(pair[0].0, pair[0].1, pair[1].0, pair[1].1))
+        .sum()
+}
+
+/// Great-circle distance in meters between two lon/lat points in degrees
+fn haversine(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = phi2 - phi1;
+    let d_lambda = (lon2 - lon1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().min(1.0).asin()
+}
 
-/// Converts a vector file to GeoParquet format - simplified version
+/// Open a CSV of points with GDAL's CSV driver, building geometries from the x/y columns
+fn open_csv_points(
+    input_path: &Path,
+    options: &GpqOptions,
+) -> Result<(open_limit::OpenPermit, Dataset), String> {
+    let x_field = options.x_field.as_deref().unwrap_or("lon");
+    let y_field = options.y_field.as_deref().unwrap_or("lat");
+    let x_names = format!("X_POSSIBLE_NAMES={}", x_field);
+    let y_names = format!("Y_POSSIBLE_NAMES={}", y_field);
+    let open_options = [x_names.as_str(), y_names.as_str(), "AUTODETECT_TYPE=YES"];
+
+    let (permit, dataset) = open_limit::open_ex(
+        input_path,
+        DatasetOptions {
+            open_flags: GdalOpenFlags::GDAL_OF_VECTOR,
+            allowed_drivers: Some(&["CSV"]),
+            open_options: Some(&open_options),
+            ..Default::default()
+        },
+    )
+    .map_err(|e| format!("Failed to open CSV {}: {}", input_path.display(), e))?;
+
+    // The driver silently falls back to a geometry-less table when the columns are missing
+    let layer = dataset
+        .layer(0)
+        .map_err(|e| format!("Failed to access CSV layer: {}", e))?;
+    let fields: Vec<String> = layer.defn().fields().map(|f| f.name()).collect();
+    for column in [x_field, y_field] {
+        if !fields.iter().any(|f| f.eq_ignore_ascii_case(column)) {
+            return Err(format!(
+                "CSV {} has no '{}' column (columns: {})",
+                input_path.display(),
+                column,
+                fields.join(", ")
+            ));
+        }
+    }
+    Ok((permit, dataset))
+}
+
+/// Apply `f` to the x coordinate of every vertex, recursing into parts and rings
+fn map_longitudes(geom: &mut Geometry, f: &impl Fn(f64) -> f64) {
+    let parts = geom.geometry_count();
+    if parts > 0 {
+        for i in 0..parts {
+            map_longitudes(&mut geom.get_geometry(i), f);
+        }
+        return;
+    }
+    for i in 0..geom.point_count() {
+        let (x, y, z) = geom.get_point(i as i32);
+        geom.set_point(i, (f(x), y, z));
+    }
+}
+
+fn wrap_longitude(x: f64) -> f64 {
+    if (-180.0..=180.0).contains(&x) {
+        x
+    } else {
+        (x + 180.0).rem_euclid(360.0) - 180.0
+    }
+}
+
+/// Wrap a geographic geometry into -180..180, optionally splitting it at the antimeridian
+///
+/// A geometry is treated as crossing the antimeridian when its wrapped extent is wider
+/// than 180 degrees, so genuinely wide features (e.g. a global coastline) are split too.
+/// Splitting needs GDAL built with GEOS.
+fn normalize_longitude(geom: &Geometry, split: bool) -> Result<Geometry, String> {
+    let is_3d = geometry_type_has_z(geom.geometry_type());
+    let mut wrapped = geom.clone();
+    map_longitudes(&mut wrapped, &wrap_longitude);
+    if !is_3d {
+        // OGR_G_SetPoint promotes to 3D
+        wrapped.flatten_to_2d();
+    }
+
+    let envelope = wrapped.envelope();
+    if !split
+        || envelope.MaxX - envelope.MinX <= 180.0
+        || GeometryKind::Point.matches(wrapped.geometry_type())
+    {
+        return Ok(wrapped);
+    }
+
+    // Make the geometry continuous across 180, then cut it into the two hemispheres
+    let mut shifted = wrapped.clone();
+    map_longitudes(&mut shifted, &|x| if x < 0.0 { x + 360.0 } else { x });
+    if !is_3d {
+        shifted.flatten_to_2d();
+    }
+    let bbox_error = |e| format!("Failed to build split box: {}", e);
+    let east = Geometry::bbox(0.0, -90.0, 180.0, 90.0).map_err(bbox_error)?;
+    let west = Geometry::bbox(180.0, -90.0, 360.0, 90.0).map_err(bbox_error)?;
+    let split_error =
+        || "Failed to split geometry at the antimeridian (is GDAL built with GEOS?)".to_string();
+    let east_part = shifted.intersection(&east).ok_or_else(split_error)?;
+    let mut west_part = shifted.intersection(&west).ok_or_else(split_error)?;
+    map_longitudes(&mut west_part, &|x| x - 360.0);
+    if !is_3d {
+        west_part.flatten_to_2d();
+    }
+
+    match (east_part.is_empty(), west_part.is_empty()) {
+        (false, false) => east_part.union(&west_part).ok_or_else(split_error),
+        (false, true) => Ok(east_part),
+        _ => Ok(west_part),
+    }
+}
+
+fn parse_srs(definition: &str) -> Result<SpatialRef, String> {
+    let mut srs = SpatialRef::from_definition(definition)
+        .map_err(|e| format!("Invalid CRS '{}': {}", definition, e))?;
+    srs.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+    Ok(srs)
+}
+
+/// OGR SQL filter restricting a layer to the `[fid_start, fid_end)` range, if one is set
+fn fid_filter(options: &GpqOptions) -> Result<Option<String>, String> {
+    match (options.fid_start, options.fid_end) {
+        (Some(start), Some(end)) if start >= end => Err(format!(
+            "--fid-start ({}) must be less than --fid-end ({})",
+            start, end
+        )),
+        (Some(start), Some(end)) => Ok(Some(format!("FID >= {} AND FID < {}", start, end))),
+        (Some(start), None) => Ok(Some(format!("FID >= {}", start))),
+        (None, Some(end)) => Ok(Some(format!("FID < {}", end))),
+        (None, None) => Ok(None),
+    }
+}
+
+/// Combined OGR SQL filter for the FID range and `--where`, if either is set
+fn attribute_filter(options: &GpqOptions) -> Result<Option<String>, String> {
+    let clauses = fid_filter(options)?
+        .into_iter()
+        .chain(options.where_clause.clone())
+        .collect::<Vec<_>>();
+    Ok(match clauses.as_slice() {
+        [] => None,
+        [clause] => Some(clause.clone()),
+        _ => Some(
+            clauses
+                .iter()
+                .map(|clause| format!("({})", clause))
+                .collect::<Vec<_>>()
+                .join(" AND "),
+        ),
+    })
+}
+
+/// Parse a `MINX,MINY,MAXX,MAXY` bounding box
+pub fn parse_bbox(s: &str) -> Result<[f64; 4], String> {
+    let invalid = || {
+        format!(
+            "Invalid bbox '{}'. Use MINX,MINY,MAXX,MAXY, e.g. 33.9,-4.7,41.9,5.0.",
+            s
+        )
+    };
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f64>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let bbox: [f64; 4] = values.try_into().map_err(|_| invalid())?;
+    if bbox[0] > bbox[2] || bbox[1] > bbox[3] {
+        return Err(format!("Bbox '{}' has a minimum above its maximum", s));
+    }
+    Ok(bbox)
+}
+
+/// Resolve the output CRS and, when reprojecting, the transform from the (possibly overridden) source CRS
+fn resolve_crs(
+    layer_srs: Option<SpatialRef>,
+    options: &GpqOptions,
+) -> Result<(Option<SpatialRef>, Option<CoordTransform>), String> {
+    let source = match &options.s_srs {
+        Some(definition) => {
+            let srs = parse_srs(definition)?;
+            if let Some(declared) = &layer_srs
+                && *declared != srs
+            {
+                status!(
+                    "Warning: input declares CRS '{}', using --s-srs '{}' instead",
+                    declared.name().unwrap_or_default(),
+                    definition
+                );
+            }
+            Some(srs)
+        }
+        None => layer_srs.map(|mut srs| {
+            srs.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+            srs
+        }),
+    };
+
+    let Some(definition) = &options.t_srs else {
+        return Ok((source, None));
+    };
+    let target = parse_srs(definition)?;
+    let source = source.ok_or_else(|| {
+        format!(
+            "Input has no CRS to reproject to '{}' from, pass --s-srs to assign one",
+            definition
+        )
+    })?;
+    let transform = CoordTransform::new(&source, &target)
+        .map_err(|e| format!("Failed to create CRS transform: {}", e))?;
+    Ok((Some(target), Some(transform)))
+}
+
+/// Path [`vector_convert`] writes `input_path` to: `output_path`, or the input next to itself,
+/// with the extension of `format`
+pub fn planned_vector_output(
+    input_path: &Path,
+    output_path: Option<&Path>,
+    format: VectorFormat,
+) -> PathBuf {
+    let extension = format.extension();
+    match output_path {
+        Some(p) => p.with_extension(extension),
+        None => {
+            let out = input_path.with_extension(extension);
+            // fallback if input path has no file name
+            if out.file_name().is_none() {
+                PathBuf::from("output").with_extension(extension)
+            } else {
+                out
+            }
+        }
+    }
+}
+
+/// Converts a vector file to `options.format`, giving the output that format's extension
 ///
 /// # Arguments
 /// * `input_path` - Path to the input vector file (any GDAL-supported format)
-/// * `output_path` - Path where the GeoParquet file will be written
+/// * `output_path` - Path where the output will be written, `-` for stdout
+/// * `options` - Output format and post-write checks to run on the output
+pub fn vector_convert(
+    input_path: &Path,
+    output_path: Option<&Path>,
+    options: &GpqOptions,
+) -> Result<String, String> {
+    if output_path.is_some_and(is_stdout) {
+        return convert_to_stdout(input_path, options);
+    }
+    let written = vector_convert_to_files(input_path, output_path, options)?;
+    let names: Vec<String> = written
+        .iter()
+        .map(|path| {
+            path.file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect();
+    Ok(names.join(", "))
+}
 
-pub fn vector_to_geoparquet(input_path: &Path, output_path: Option<&Path>) -> Result<String, String> {
+/// [`vector_convert`] to files, returning the full path of every file written
+///
+/// That is one path, or one per layer with `--all-layers`. Stdout is not supported.
+pub fn vector_convert_to_files(
+    input_path: &Path,
+    output_path: Option<&Path>,
+    options: &GpqOptions,
+) -> Result<Vec<PathBuf>, String> {
     let _ = config::set_config_option("OGR_GEOJSON_MAX_OBJ_SIZE", "0");
     // Validate input path
     if !input_path.exists() {
@@ -20,117 +692,741 @@ pub fn vector_to_geoparquet(input_path: &Path, output_path: Option<&Path>) -> Re
         ));
     }
 
-    // Determine output path
-    let out_path = match output_path {
-        Some(p) => p.to_path_buf().with_extension("parquet"),
-        None => {
-            let mut out = input_path.with_extension("parquet");
-            // fallback if input path has no file name
-            if out.file_name().is_none() {
-                out = PathBuf::from("output.parquet");
-            }
-            out
-        }
-    };
+    let extension = options.format.extension();
+    let out_path = planned_vector_output(input_path, output_path, options.format);
+    // e.g. a GeoPackage converted to GeoPackage without --out
+    if out_path == input_path {
+        return Err(format!(
+            "Output {} would replace the input, pass --out",
+            out_path.display()
+        ));
+    }
 
     // Open the source dataset
-    let dataset_src = Dataset::open(input_path).map_err(|e| format!("Failed to open source dataset {}: {}", input_path.display(), e))?;
-    
-    
-    // .expect(&format!(
-    //     "Failed to open source dataset: {}",
-    //     input_path.display()
-    // ));
+    // CSV coordinates carry no CRS, so assign one unless --s-srs already does
+    let csv_options;
+    let (_permit, dataset_src, options) = if is_csv(input_path) {
+        csv_options = GpqOptions {
+            s_srs: options
+                .s_srs
+                .clone()
+                .or_else(|| options.csv_crs.clone())
+                .or_else(|| Some("EPSG:4326".to_string())),
+            ..options.clone()
+        };
+        let (permit, dataset) = open_csv_points(input_path, &csv_options)?;
+        (permit, dataset, &csv_options)
+    } else {
+        let (permit, dataset) = open_limit::open(input_path).map_err(|e| {
+            format!(
+                "Failed to open source dataset {}: {}",
+                input_path.display(),
+                e
+            )
+        })?;
+        (permit, dataset, options)
+    };
 
     // Ensure dataset has layers
     if dataset_src.layer_count() == 0 {
         return Err("Source dataset contains no layers".to_string());
     }
 
-    let mut layer_src = dataset_src
-        .layer(0)
-        .map_err(|e| format!("Failed to access first layer of dataset {}: {}", input_path.display(), e))?;
+    if options.all_layers {
+        if options.layer.is_some() {
+            return Err("--layer and --all-layers cannot be combined".to_string());
+        }
+        let stem = out_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        let mut written = Vec::with_capacity(dataset_src.layer_count());
+        for mut layer_src in dataset_src.layers() {
+            let name = layer_src.name();
+            let layer_path = out_path.with_file_name(format!(
+                "{}_{}.{}",
+                stem,
+                name.replace(['/', '\\', ':'], "_"),
+                extension
+            ));
+            convert_layer(input_path, &mut layer_src, &layer_path, options)
+                .map_err(|e| format!("Layer '{}': {}", name, e))?;
+            written.push(layer_path);
+        }
+        return Ok(written);
+    }
+
+    let mut layer_src = select_layer(&dataset_src, options.layer.as_deref())
+        .map_err(|e| format!("{}: {}", input_path.display(), e))?;
+    convert_layer(input_path, &mut layer_src, &out_path, options)?;
+    Ok(vec![out_path])
+}
 
+/// Whether `path` asks for output on stdout: `-` or `/dev/stdout`
+pub fn is_stdout(path: &Path) -> bool {
+    path == Path::new("-") || path == Path::new("/dev/stdout")
+}
 
-    let spatial_ref_src = layer_src.spatial_ref();
+/// Converts to a temporary file, then copies it to stdout once all checks have passed
+///
+/// Parquet puts its metadata in a footer and the checks reopen the output, so it cannot be
+/// streamed as it is written.
+fn convert_to_stdout(input_path: &Path, options: &GpqOptions) -> Result<String, String> {
+    if options.all_layers {
+        return Err(
+            "--all-layers writes one file per layer and cannot write to stdout".to_string(),
+        );
+    }
+    let extension = options.format.extension();
+    let stem = input_path.file_stem().unwrap_or_default().to_string_lossy();
+    let target =
+        std::env::temp_dir().join(format!("{}-{}.{}", stem, std::process::id(), extension));
+    let temp_path = TempFile::new(&target, &format!("{}.{}", STDOUT, extension));
+    // A leftover from an interrupted run is not a previous conversion worth keeping
+    let temp_options = GpqOptions {
+        overwrite: true,
+        ..options.clone()
+    };
+    let result = vector_convert(input_path, Some(temp_path.path()), &temp_options).and_then(|_| {
+        let mut file = File::open(temp_path.path())
+            .map_err(|e| format!("Failed to read {}: {}", temp_path.path().display(), e))?;
+        let mut stdout = io::stdout().lock();
+        io::copy(&mut file, &mut stdout)
+            .and_then(|_| stdout.flush())
+            .map_err(|e| format!("Failed to write {} to stdout: {}", options.format.name(), e))
+    });
+    result.map(|_| "-".to_string())
+}
+
+/// Finds the layer named `selector`, or else the one at that zero-based index; `None` picks the first
+///
+/// The error lists the available layer names.
+fn select_layer<'a>(dataset: &'a Dataset, selector: Option<&str>) -> Result<Layer<'a>, String> {
+    let Some(selector) = selector else {
+        return dataset
+            .layer(0)
+            .map_err(|e| format!("Failed to access first layer: {}", e));
+    };
+    if let Ok(layer) = dataset.layer_by_name(selector) {
+        return Ok(layer);
+    }
+    if let Ok(idx) = selector.parse::<usize>()
+        && idx < dataset.layer_count()
+    {
+        return dataset
+            .layer(idx)
+            .map_err(|e| format!("Failed to access layer {}: {}", idx, e));
+    }
+    let names = dataset
+        .layers()
+        .map(|layer| layer.name())
+        .collect::<Vec<_>>();
+    Err(format!(
+        "No layer '{}'; available layers: {}",
+        selector,
+        names.join(", ")
+    ))
+}
+
+/// Writes one source layer to `out_path` and runs the post-write checks, returning the output file name
+fn convert_layer(
+    input_path: &Path,
+    layer_src: &mut Layer,
+    out_path: &Path,
+    options: &GpqOptions,
+) -> Result<String, String> {
+    // Refuse to guess rather than write coordinates under the wrong CRS
+    if is_geojson_or_fgb(input_path)
+        && options.s_srs.is_none()
+        && let Some(srs) = layer_src.spatial_ref().filter(|srs| !is_wgs84(srs))
+    {
+        let name = srs.name().unwrap_or_else(|| "unknown".to_string());
+        let authority = srs.authority().unwrap_or_else(|_| "<CRS>".to_string());
+        return Err(format!(
+            "{} declares CRS '{}' ({}), not WGS 84; pass --s-srs {} to confirm it, and --t-srs EPSG:4326 to reproject",
+            input_path.display(),
+            name,
+            authority,
+            authority
+        ));
+    }
+
+    write_checked_layer(layer_src, out_path, options)?;
+
+    status!(
+        "Successfully converted {} to {}: {}",
+        input_path.display(),
+        options.format.name(),
+        out_path.display()
+    );
+
+    Ok(out_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string())
+}
+
+/// Writes a single layer to a temporary file next to `out_path`, runs the post-write checks on
+/// it and only then moves it into place, returning the number of features written
+///
+/// An output that is interrupted or fails a check never replaces an existing file.
+fn write_checked_layer(
+    layer_src: &mut Layer,
+    out_path: &Path,
+    options: &GpqOptions,
+) -> Result<u64, String> {
+    if out_path.exists() && !options.overwrite {
+        return Err(format!(
+            "Error: The file {:?} already exists and overwrite is false.",
+            out_path
+        ));
+    }
+    let partial_path = TempFile::new(
+        out_path,
+        &format!("{}.{}", PARTIAL, options.format.extension()),
+    );
+    // Drivers such as GeoPackage refuse to create over a leftover from an interrupted run
+    let _ = fs::remove_file(partial_path.path());
+
+    let feature_count = write_layer(layer_src, partial_path.path(), options)?;
+    if options.format == VectorFormat::Parquet {
+        check_geo_metadata(partial_path.path())?;
+    }
+    if options.verify {
+        verify_geoparquet(partial_path.path(), feature_count)?;
+    }
+    if options.crs_authority.require {
+        check_output_authority(partial_path.path(), &options.crs_authority)?;
+    }
+    move_into_place(partial_path.path(), out_path)?;
+    Ok(feature_count)
+}
+
+/// Copies a single layer into a new file in `options.format`, returning the number of features written
+///
+/// Features are filtered by FID range and geometry type and reprojected according to `options`.
+fn write_layer(
+    layer_src: &mut Layer,
+    out_path: &Path,
+    options: &GpqOptions,
+) -> Result<u64, String> {
+    let geometry_filter = options.geometry_type;
+    let (spatial_ref_out, transform) = resolve_crs(layer_src.spatial_ref(), options)?;
+    let spatial_ref_out = match spatial_ref_out {
+        Some(srs) if options.crs_authority.stamp => match identify_epsg(&srs) {
+            Some(stamped) => {
+                status!(
+                    "Stamping CRS as {}",
+                    stamped.authority().unwrap_or_default()
+                );
+                Some(stamped)
+            }
+            None => Some(srs),
+        },
+        other => other,
+    };
+    if options.normalize_longitude
+        && !spatial_ref_out
+            .as_ref()
+            .is_some_and(|srs| srs.is_geographic())
+    {
+        return Err("--normalize-longitude needs a geographic output CRS".to_string());
+    }
+    let measures = Measures::new(spatial_ref_out.as_ref(), options)?;
+    if let Some(filter) = attribute_filter(options)? {
+        layer_src
+            .set_attribute_filter(&filter)
+            .map_err(|e| format!("Failed to filter features with '{}': {}", filter, e))?;
+    }
+    if let Some([min_x, min_y, max_x, max_y]) = options.bbox {
+        layer_src.set_spatial_filter_rect(min_x, min_y, max_x, max_y);
+    }
 
     // Get field definitions from source layer
     let fields_defn = layer_src
         .defn()
         .fields()
-        .map(|field| (field.name(), field.field_type(), field.width()))
+        .map(|field| {
+            (
+                field.name(),
+                field.field_type(),
+                field.width(),
+                field.precision(),
+            )
+        })
         .collect::<Vec<_>>();
 
-    // Create output dataset with Parquet driver
-    let drv = DriverManager::get_driver_by_name("Parquet")
-    .map_err(|e| format!("Failed to get Parquet Driver: {}", e))?;
+    if options.batch_size == Some(0) {
+        return Err("--batch-size must be at least 1".to_string());
+    }
+    if options.format != VectorFormat::Parquet {
+        let parquet_only = [
+            (options.geo_encoding != GeoEncoding::Wkb, "--geo-encoding"),
+            (options.row_group_size.is_some(), "--row-group-size"),
+            (options.compression.is_some(), "--parquet-compression"),
+            (options.batch_size.is_some(), "--batch-size"),
+        ];
+        if let Some((_, flag)) = parquet_only.iter().find(|(set, _)| *set) {
+            return Err(format!(
+                "{} only applies to GeoParquet output, not {}",
+                flag,
+                options.format.name()
+            ));
+        }
+    }
 
-    let out_path_str = out_path
-        .to_str()
-        .expect("Output path contains invalid UTF-8 characters");
+    // Create output dataset with the driver for the requested format
+    let drv = DriverManager::get_driver_by_name(options.format.driver())
+        .map_err(|e| format!("Failed to get {} Driver: {}", options.format.driver(), e))?;
 
-    let mut ds_dest = drv.create_vector_only(out_path_str).expect(&format!(
-        "Failed to create destination dataset at {}",
-        out_path.display()
-    ));
+    // Ask for the geometry encoding and a bbox covering column explicitly, rather than relying on
+    // the defaults of whichever GDAL version is installed
+    let layer_creation_list = drv
+        .metadata_item("DS_LAYER_CREATIONOPTIONLIST", "")
+        .unwrap_or_default();
+    // Other formats get the driver defaults; the Parquet-only options were refused above
+    let is_parquet = options.format == VectorFormat::Parquet;
+    let mut layer_creation_opts = Vec::new();
+    if is_parquet && layer_creation_list.contains("GEOMETRY_ENCODING") {
+        layer_creation_opts.push(format!("GEOMETRY_ENCODING={}", options.geo_encoding.name()));
+    } else if options.geo_encoding != GeoEncoding::Wkb {
+        return Err(format!(
+            "This GDAL build's Parquet driver cannot write {} geometries",
+            options.geo_encoding.name()
+        ));
+    }
+    if is_parquet && layer_creation_list.contains("WRITE_COVERING_BBOX") {
+        layer_creation_opts.push("WRITE_COVERING_BBOX=YES".to_string());
+    }
+    if let Some(row_group_size) = options.row_group_size {
+        if row_group_size == 0 {
+            return Err("--row-group-size must be at least 1".to_string());
+        }
+        layer_creation_opts.push(format!("ROW_GROUP_SIZE={}", row_group_size));
+    }
+    if let Some(compression) = options.compression {
+        // Codecs are optional in the Arrow library GDAL links against, and the driver only lists
+        // the ones it was built with
+        let codec = format!("<Value>{}</Value>", compression.name());
+        if compression != ParquetCompression::None && !layer_creation_list.contains(&codec) {
+            return Err(format!(
+                "This GDAL build's Parquet driver does not support {} compression",
+                compression.name()
+            ));
+        }
+        layer_creation_opts.push(format!("COMPRESSION={}", compression.name()));
+    }
+    merge_creation_options(&mut layer_creation_opts, &options.creation_options);
+    let layer_creation_opts = layer_creation_opts
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+
+    // GeoArrow columns are typed, so the layer needs a concrete geometry type
+    let geometry_type = match options.geo_encoding {
+        GeoEncoding::Wkb => OGRwkbGeometryType::wkbUnknown,
+        GeoEncoding::Geoarrow => output_geometry_type(layer_src, options)?,
+    };
+
+    let out_path_str = out_path.to_str().ok_or_else(|| {
+        format!(
+            "Output path {} contains invalid UTF-8 characters",
+            out_path.display()
+        )
+    })?;
+
+    let mut ds_dest = drv.create_vector_only(out_path_str).map_err(|e| {
+        format!(
+            "Failed to create destination dataset at {}: {}",
+            out_path.display(),
+            e
+        )
+    })?;
 
     // Create layer in the destination dataset
-    let lyr_dest = ds_dest
+    let mut lyr_dest = ds_dest
         .create_layer(LayerOptions {
-            srs: spatial_ref_src.as_ref(),
-            ..Default::default()
+            // GeoPackage tables need a name; GeoParquet ignores it
+            name: &layer_src.name(),
+            srs: spatial_ref_out.as_ref(),
+            ty: geometry_type,
+            options: Some(&layer_creation_opts),
         })
-        .expect("Failed to create destination layer");
+        .map_err(|e| format!("Failed to create destination layer: {}", e))?;
 
     // Copy field schema from source to destination
-    for fd in &fields_defn {
-        let field_defn = FieldDefn::new(&fd.0, fd.1)
-            .expect(&format!("Failed to create field definition for '{}'", fd.0));
+    for (idx, fd) in fields_defn.iter().enumerate() {
+        add_field(&lyr_dest, &fd.0, fd.1, fd.2, fd.3)
+            .map_err(|e| format!("Failed to add field {} ('{}') to layer: {}", idx, fd.0, e))?;
+    }
 
-        field_defn.set_width(fd.2);
-        field_defn
-            .add_to_layer(&lyr_dest)
-            .expect(&format!("Failed to add field '{}' to layer", fd.0));
+    // Measure columns follow the source fields, area before length
+    for (wanted, name) in [
+        (options.add_area, AREA_FIELD),
+        (options.add_length, LENGTH_FIELD),
+    ] {
+        if !wanted {
+            continue;
+        }
+        if fields_defn.iter().any(|fd| fd.0.eq_ignore_ascii_case(name)) {
+            return Err(format!("Input already has a '{}' field", name));
+        }
+        add_field(&lyr_dest, name, OGRFieldType::OFTReal, 0, 0)
+            .map_err(|e| format!("Failed to add field '{}' to layer: {}", name, e))?;
     }
+    let area_idx = options.add_area.then_some(fields_defn.len());
+    let length_idx = options
+        .add_length
+        .then(|| fields_defn.len() + usize::from(options.add_area));
 
     // Get layer definition for creating features
     let defn = Defn::from_layer(&lyr_dest);
 
     // Copy all features from source to destination
+    let mut feature_count = 0u64;
+    let mut skipped = 0u64;
     for feature_src in layer_src.features() {
+        if let Some(kind) = geometry_filter {
+            let keep = feature_src
+                .geometry()
+                .is_some_and(|geom| kind.matches(geom.geometry_type()));
+            if !keep {
+                skipped += 1;
+                continue;
+            }
+        }
+
+        // Name the source feature in errors so bad input can be found
+        let fid = feature_src
+            .fid()
+            .map_or_else(|| "without FID".to_string(), |fid| fid.to_string());
+
         // Create new feature
-        let mut feature_dest = Feature::new(&defn).expect("Failed to create feature");
+        let mut feature_dest = Feature::new(&defn)
+            .map_err(|e| format!("Failed to create feature for source feature {}: {}", fid, e))?;
 
-        // Copy geometry directly without transformation
+        // Copy geometry, reprojecting when a target CRS is set
         if let Some(geom) = feature_src.geometry() {
+            let geom = match &transform {
+                Some(ct) => geom.transform(ct).map_err(|e| {
+                    format!("Failed to reproject geometry of feature {}: {}", fid, e)
+                })?,
+                None => geom.clone(),
+            };
+            let geom = if options.normalize_longitude {
+                normalize_longitude(&geom, options.split_antimeridian)
+                    .map_err(|e| format!("Feature {}: {}", fid, e))?
+            } else {
+                geom
+            };
+            let measured = [
+                (area_idx, measures.area(&geom)),
+                (length_idx, measures.length(&geom)),
+            ];
+            for (idx, value) in measured {
+                if let (Some(idx), Some(value)) =
+                    (idx, value.map_err(|e| format!("Feature {}: {}", fid, e))?)
+                {
+                    feature_dest.set_field_double(idx, value).map_err(|e| {
+                        format!("Failed to set field {} of feature {}: {}", idx, fid, e)
+                    })?;
+                }
+            }
             feature_dest
-                .set_geometry(geom.clone())
-                .expect("Failed to set geometry");
+                .set_geometry(geom)
+                .map_err(|e| format!("Failed to set geometry of feature {}: {}", fid, e))?;
         }
 
         // Copy field values
         for idx in 0..fields_defn.len() {
             if let Some(value) = feature_src
                 .field(idx)
-                .expect(&format!("Failed to read field {}", idx))
+                .map_err(|e| format!("Failed to read field {} of feature {}: {}", idx, fid, e))?
             {
-                feature_dest
-                    .set_field(idx, &value)
-                    .expect(&format!("Failed to set field {}", idx));
+                feature_dest.set_field(idx, &value).map_err(|e| {
+                    format!("Failed to set field {} of feature {}: {}", idx, fid, e)
+                })?;
             }
         }
 
         // Add feature to destination layer
         feature_dest
             .create(&lyr_dest)
-            .expect("Failed to create feature in destination");
+            .map_err(|e| format!("Failed to write feature {} to destination: {}", fid, e))?;
+        feature_count += 1;
+
+        // Flush the dataset so the writer emits what it holds, then pick the layer up again
+        if let Some(batch_size) = options.batch_size
+            && feature_count.is_multiple_of(batch_size as u64)
+        {
+            ds_dest.flush_cache().map_err(|e| {
+                format!(
+                    "Failed to flush {} after {} features: {}",
+                    out_path.display(),
+                    feature_count,
+                    e
+                )
+            })?;
+            lyr_dest = ds_dest
+                .layer(0)
+                .map_err(|e| format!("Failed to reopen destination layer after flushing: {}", e))?;
+        }
+    }
+
+    if let Some(kind) = geometry_filter {
+        status!(
+            "Kept {} {:?} features, skipped {} of other geometry types",
+            feature_count,
+            kind,
+            skipped
+        );
+    }
+
+    Ok(feature_count)
+}
+
+/// Adds a field to `layer` with the width and precision of the source field
+fn add_field(
+    layer: &Layer,
+    name: &str,
+    field_type: OGRFieldType::Type,
+    width: i32,
+    precision: i32,
+) -> Result<(), String> {
+    let field = FieldDefn::new(name, field_type).map_err(|e| e.to_string())?;
+    field.set_width(width);
+    field.set_precision(precision);
+    field.add_to_layer(layer).map_err(|e| e.to_string())
+}
+
+/// Geometry type to declare on a GeoArrow output layer
+///
+/// Uses the source layer's declared type, or the multi-type of `--geometry-type-filter` for mixed
+/// layers; splitting at the antimeridian can turn single parts into multi-parts, so it also
+/// promotes to the multi-type.
+fn output_geometry_type(
+    layer_src: &Layer,
+    options: &GpqOptions,
+) -> Result<OGRwkbGeometryType::Type, String> {
+    use OGRwkbGeometryType::*;
+    let declared = layer_src
+        .defn()
+        .geom_fields()
+        .next()
+        .map_or(wkbUnknown, |field| field.field_type());
+    let kind = [
+        GeometryKind::Point,
+        GeometryKind::Line,
+        GeometryKind::Polygon,
+    ]
+    .into_iter()
+    .find(|kind| declared != wkbUnknown && kind.matches(declared))
+    .or(options.geometry_type);
+    let Some(kind) = kind else {
+        return Err(format!(
+            "GeoArrow encoding needs a single geometry type, but layer '{}' is mixed; pass --geometry-type-filter or use WKB",
+            layer_src.name()
+        ));
+    };
+
+    let multi = match kind {
+        GeometryKind::Point => wkbMultiPoint,
+        GeometryKind::Line => wkbMultiLineString,
+        GeometryKind::Polygon => wkbMultiPolygon,
+    };
+    if declared == wkbUnknown || options.split_antimeridian {
+        Ok(multi)
+    } else {
+        Ok(geometry_type_flatten(declared))
     }
+}
 
-    println!(
-        "Successfully converted {} to GeoParquet: {}",
+/// Reopens a written GeoParquet file and checks its `geo` metadata records the CRS
+///
+/// GeoParquet readers take a column without `crs` to be OGC:CRS84, so the key may only be left
+/// out for WGS 84 output. GDAL builds that do not expose the Parquet key-value metadata are
+/// not checked.
+fn check_geo_metadata(out_path: &Path) -> Result<(), String> {
+    let (_permit, dataset) = open_limit::open(out_path).map_err(|e| {
+        format!(
+            "Cannot reopen {} to check its geo metadata: {}",
+            out_path.display(),
+            e
+        )
+    })?;
+    let layer = dataset
+        .layer(0)
+        .map_err(|e| format!("No layer in {}: {}", out_path.display(), e))?;
+    let Some(geo) = layer.metadata_item("geo", "_PARQUET_METADATA_") else {
+        return Ok(());
+    };
+    let geo: serde_json::Value = serde_json::from_str(&geo)
+        .map_err(|e| format!("{} has unreadable geo metadata: {}", out_path.display(), e))?;
+    let primary = geo["primary_column"].as_str().unwrap_or("geometry");
+    let column = &geo["columns"][primary];
+    if column.is_null() {
+        return Err(format!(
+            "{} geo metadata does not describe column '{}'",
+            out_path.display(),
+            primary
+        ));
+    }
+
+    let Some(srs) = layer.spatial_ref() else {
+        return Ok(());
+    };
+    let has_crs = column.get("crs").is_some_and(|crs| !crs.is_null());
+    if !has_crs && (column.get("crs").is_some() || !is_wgs84(&srs)) {
+        return Err(format!(
+            "{} geo metadata has no CRS for '{}', but the layer is in {}",
+            out_path.display(),
+            primary,
+            srs.name().unwrap_or_else(|| "an unnamed CRS".to_string())
+        ));
+    }
+    Ok(())
+}
+
+/// Reopens a written GeoParquet file and checks it holds `expected` features
+fn verify_geoparquet(out_path: &Path, expected: u64) -> Result<(), String> {
+    let (_permit, dataset) = open_limit::open(out_path).map_err(|e| {
+        format!(
+            "Verification failed: cannot reopen {}: {}",
+            out_path.display(),
+            e
+        )
+    })?;
+    let layer = dataset.layer(0).map_err(|e| {
+        format!(
+            "Verification failed: no layer in {}: {}",
+            out_path.display(),
+            e
+        )
+    })?;
+    let written = layer.feature_count();
+    if written != expected {
+        return Err(format!(
+            "Verification failed: {} has {} features, expected {}",
+            out_path.display(),
+            written,
+            expected
+        ));
+    }
+    Ok(())
+}
+
+/// A layer written by [`explode_to_geoparquet`]
+#[derive(Debug, Serialize)]
+pub struct ManifestLayer {
+    pub layer: String,
+    pub file: String,
+    pub feature_count: u64,
+    pub crs: Option<String>,
+}
+
+/// Contents of the `manifest.json` written alongside exploded layers
+#[derive(Debug, Serialize)]
+pub struct ExplodeManifest {
+    pub source: String,
+    pub layers: Vec<ManifestLayer>,
+}
+
+/// Converts every layer of a vector file to its own file in `options.format` (GeoParquet by default)
+///
+/// # Arguments
+/// * `input_path` - Path to the input vector file (typically a multi-layer GeoPackage)
+/// * `output_dir` - Directory for `<layer_name>.<extension>` files and `manifest.json`,
+///   defaults to a directory named after the input file
+/// * `options` - Post-write checks to run on each layer file
+pub fn explode_to_geoparquet(
+    input_path: &Path,
+    output_dir: Option<&Path>,
+    options: &GpqOptions,
+) -> Result<String, String> {
+    if !input_path.exists() {
+        return Err(format!(
+            "Input path '{}' does not exist",
+            input_path.display()
+        ));
+    }
+
+    let out_dir = match output_dir {
+        Some(dir) if is_stdout(dir) => {
+            return Err(
+                "--explode writes one file per layer and cannot write to stdout".to_string(),
+            );
+        }
+        Some(dir) => dir.to_path_buf(),
+        None => input_path.with_extension(""),
+    };
+    fs::create_dir_all(&out_dir).map_err(|e| {
+        format!(
+            "Failed to create output directory {}: {}",
+            out_dir.display(),
+            e
+        )
+    })?;
+
+    let (_permit, dataset_src) = open_limit::open(input_path).map_err(|e| {
+        format!(
+            "Failed to open source dataset {}: {}",
+            input_path.display(),
+            e
+        )
+    })?;
+    if dataset_src.layer_count() == 0 {
+        return Err("Source dataset contains no layers".to_string());
+    }
+
+    let mut layers = Vec::with_capacity(dataset_src.layer_count());
+    for mut layer_src in dataset_src.layers() {
+        let name = layer_src.name();
+        let file_name = format!(
+            "{}.{}",
+            name.replace(['/', '\\', ':'], "_"),
+            options.format.extension()
+        );
+        let crs = match options.t_srs.as_ref().or(options.s_srs.as_ref()) {
+            Some(definition) => parse_srs(definition)?.name(),
+            None => layer_src.spatial_ref().and_then(|r| r.name()),
+        };
+
+        let layer_path = out_dir.join(&file_name);
+        let feature_count = write_checked_layer(&mut layer_src, &layer_path, options)
+            .map_err(|e| format!("Layer '{}': {}", name, e))?;
+        status!(
+            "Wrote layer '{}' ({} features) to {}",
+            name,
+            feature_count,
+            file_name
+        );
+
+        layers.push(ManifestLayer {
+            layer: name,
+            file: file_name,
+            feature_count,
+            crs,
+        });
+    }
+
+    let manifest = ExplodeManifest {
+        source: input_path.display().to_string(),
+        layers,
+    };
+    let manifest_path = out_dir.join("manifest.json");
+    let json = serde_json::to_string_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+    fs::write(&manifest_path, json)
+        .map_err(|e| format!("Failed to write {}: {}", manifest_path.display(), e))?;
+
+    status!(
+        "Exploded {} layers from {} into {}",
+        manifest.layers.len(),
         input_path.display(),
-        out_path.display()
+        out_dir.display()
     );
 
-    Ok(out_path.file_name().unwrap().to_str().unwrap().to_string())
+    Ok(manifest_path.display().to_string())
 }
@@ -0,0 +1,30 @@
+use std::path::{Path, PathBuf};
+
+/// URL schemes GDAL can read through a virtual file system, and the prefix each maps to
+const SCHEMES: &[(&str, &str)] = &[
+    ("s3://", "/vsis3/"),
+    ("gs://", "/vsigs/"),
+    ("az://", "/vsiaz/"),
+    ("http://", "/vsicurl/http://"),
+    ("https://", "/vsicurl/https://"),
+];
+
+/// Rewrite a cloud URL such as `s3://bucket/key.tif` to its GDAL path (`/vsis3/bucket/key.tif`)
+///
+/// Other paths, including ones that already start with `/vsi`, are returned unchanged.
+pub fn to_vsi(path: &Path) -> PathBuf {
+    let text = path.to_string_lossy();
+    for (scheme, prefix) in SCHEMES {
+        if let Some(rest) = text.strip_prefix(scheme) {
+            return PathBuf::from(format!("{}{}", prefix, rest));
+        }
+    }
+    path.to_path_buf()
+}
+
+/// Whether `path` is read through a GDAL virtual file system rather than the local disk
+///
+/// `Path::exists` and other `std::fs` calls cannot see these files, so callers skip them.
+pub fn is_vsi(path: &Path) -> bool {
+    to_vsi(path).to_string_lossy().starts_with("/vsi")
+}
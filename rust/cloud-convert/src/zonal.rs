@@ -0,0 +1,259 @@
+use crate::open_limit;
+use crate::rast_qaqc::{OutputFormat, PixelClass, classify_pixel_with, write_frame};
+use crate::tif2cog::check_band_list;
+use anyhow::{Result, anyhow};
+use gdal::DriverManager;
+use gdal::raster::{Buffer, RasterizeOptions, rasterize};
+use gdal::spatial_ref::{AxisMappingStrategy, CoordTransform};
+use gdal::vector::{Geometry, LayerAccess};
+use polars::prelude::*;
+use std::path::{Path, PathBuf};
+
+/// Options for [`zonal_stats`]
+#[derive(Debug, Clone, Default)]
+pub struct ZonalOptions {
+    /// Zone field identifying each feature in the output; the feature FID when unset
+    pub id_field: Option<String>,
+    /// Only compute these 1-based bands; all bands when unset
+    pub bands: Option<Vec<usize>>,
+    /// Count every pixel a zone touches, not only those whose center falls inside it
+    pub all_touched: bool,
+}
+
+/// Statistics of one band within one zone
+#[derive(Debug, Clone, PartialEq)]
+pub struct ZoneStats {
+    /// Value of the id field, or the FID
+    pub id: String,
+    pub band: usize,
+    /// Valid pixels in the zone; the other statistics are NaN when there are none
+    pub count: u64,
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+/// Running count, sum, min and max of the valid pixels of one zone
+struct ZoneAccumulator {
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl ZoneAccumulator {
+    fn new() -> Self {
+        ZoneAccumulator {
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    fn push(&mut self, val: f64) {
+        self.count += 1;
+        self.sum += val;
+        self.min = self.min.min(val);
+        self.max = self.max.max(val);
+    }
+
+    fn finish(self, id: &str, band: usize) -> ZoneStats {
+        let empty = self.count == 0;
+        let or_nan = |v: f64| if empty { f64::NAN } else { v };
+        ZoneStats {
+            id: id.to_string(),
+            band,
+            count: self.count,
+            mean: self.sum / self.count as f64,
+            min: or_nan(self.min),
+            max: or_nan(self.max),
+        }
+    }
+}
+
+/// Pixel window `(col, row, cols, rows)` of `gt` covering `envelope`, clipped to the raster
+///
+/// Returns `None` when the envelope lies outside the raster.
+fn envelope_window(
+    envelope: &gdal::vector::Envelope,
+    gt: &[f64; 6],
+    size: (usize, usize),
+) -> Option<(usize, usize, usize, usize)> {
+    let cols = [
+        (envelope.MinX - gt[0]) / gt[1],
+        (envelope.MaxX - gt[0]) / gt[1],
+    ];
+    let rows = [
+        (envelope.MinY - gt[3]) / gt[5],
+        (envelope.MaxY - gt[3]) / gt[5],
+    ];
+    let span = |[a, b]: [f64; 2], len: usize| {
+        let start = a.min(b).floor().max(0.0) as usize;
+        let end = (a.max(b).ceil().max(0.0) as usize).min(len);
+        (start < end).then_some((start, end - start))
+    };
+    let (col, width) = span(cols, size.0)?;
+    let (row, height) = span(rows, size.1)?;
+    Some((col, row, width, height))
+}
+
+/// Burns `geom` into a Byte mask over the pixel window `(col, row, cols, rows)` of `gt`
+fn zone_mask(
+    geom: &Geometry,
+    gt: &[f64; 6],
+    (col, row, cols, rows): (usize, usize, usize, usize),
+    all_touched: bool,
+) -> Result<Vec<u8>> {
+    let driver = DriverManager::get_driver_by_name("MEM")?;
+    let mut mask = driver.create_with_band_type::<u8, _>("", cols, rows, 1)?;
+    mask.set_geo_transform(&[
+        gt[0] + col as f64 * gt[1],
+        gt[1],
+        0.0,
+        gt[3] + row as f64 * gt[5],
+        0.0,
+        gt[5],
+    ])?;
+    let options = RasterizeOptions {
+        all_touched,
+        ..Default::default()
+    };
+    rasterize(
+        &mut mask,
+        &[1],
+        std::slice::from_ref(geom),
+        &[1.0],
+        Some(options),
+    )?;
+    let buf: Buffer<u8> = mask
+        .rasterband(1)?
+        .read_as((0, 0), (cols, rows), (cols, rows), None)?;
+    Ok(buf.data().to_vec())
+}
+
+/// Statistics of each band of `raster` within each feature of the first layer of `zones`
+///
+/// Each zone is rasterized onto the raster grid over its bounding box, and the pixels it
+/// covers are classified like the QAQC scan, so NoData and NaN pixels are not counted.
+/// Zones are reprojected to the raster CRS when the two differ. Features without a geometry,
+/// or outside the raster, get a count of 0. Rows come out in feature order, then band order.
+pub fn zonal_stats(raster: &Path, zones: &Path, options: &ZonalOptions) -> Result<Vec<ZoneStats>> {
+    let (_permit, dataset) = open_limit::open(raster)
+        .map_err(|e| anyhow!("Failed to open raster {}: {}", raster.display(), e))?;
+    let gt = dataset
+        .geo_transform()
+        .map_err(|e| anyhow!("{} has no geotransform: {}", raster.display(), e))?;
+    if gt[2] != 0.0 || gt[4] != 0.0 {
+        return Err(anyhow!(
+            "{} has a rotated geotransform, which zonal statistics do not support",
+            raster.display()
+        ));
+    }
+    let size = dataset.raster_size();
+    let bands = match &options.bands {
+        Some(bands) => {
+            check_band_list(bands, dataset.raster_count()).map_err(|e| anyhow!(e))?;
+            bands.clone()
+        }
+        None => (1..=dataset.raster_count()).collect(),
+    };
+
+    let (_zones_permit, zones_ds) = open_limit::open(zones)
+        .map_err(|e| anyhow!("Failed to open zones {}: {}", zones.display(), e))?;
+    let mut layer = zones_ds.layer(0)?;
+    let id_idx = match &options.id_field {
+        Some(name) => Some(layer.defn().field_index(name).map_err(|_| {
+            let fields: Vec<String> = layer.defn().fields().map(|f| f.name()).collect();
+            anyhow!(
+                "Zones {} have no field '{}' (fields: {})",
+                zones.display(),
+                name,
+                fields.join(", ")
+            )
+        })?),
+        None => None,
+    };
+
+    // Without a CRS on either side, the zones are taken to be in the raster CRS
+    let transform = match (layer.spatial_ref(), dataset.spatial_ref().ok()) {
+        (Some(mut source), Some(mut target)) if source != target => {
+            source.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+            target.set_axis_mapping_strategy(AxisMappingStrategy::TraditionalGisOrder);
+            Some(CoordTransform::new(&source, &target)?)
+        }
+        _ => None,
+    };
+
+    let mut stats = Vec::new();
+    for feature in layer.features() {
+        let id = match id_idx {
+            Some(idx) => feature.field_as_string(idx)?.unwrap_or_default(),
+            None => feature.fid().map(|fid| fid.to_string()).unwrap_or_default(),
+        };
+        let mut zone: Vec<ZoneAccumulator> = bands.iter().map(|_| ZoneAccumulator::new()).collect();
+
+        let geom = match (feature.geometry(), &transform) {
+            (Some(geom), Some(ct)) => Some(
+                geom.transform(ct)
+                    .map_err(|e| anyhow!("Failed to reproject zone {}: {}", id, e))?,
+            ),
+            (geom, _) => geom.cloned(),
+        };
+        let window = geom
+            .as_ref()
+            .and_then(|geom| envelope_window(&geom.envelope(), &gt, size));
+        if let (Some(geom), Some(window)) = (&geom, window) {
+            let mask = zone_mask(geom, &gt, window, options.all_touched)
+                .map_err(|e| anyhow!("Failed to rasterize zone {}: {}", id, e))?;
+            let (col, row, cols, rows) = window;
+            for (acc, &band_index) in zone.iter_mut().zip(&bands) {
+                let band = dataset.rasterband(band_index)?;
+                let nodata: Vec<f64> = band.no_data_value().into_iter().collect();
+                let buf: Buffer<f64> = band.read_as(
+                    (col as isize, row as isize),
+                    (cols, rows),
+                    (cols, rows),
+                    None,
+                )?;
+                for (&val, _) in buf.data().iter().zip(&mask).filter(|(_, m)| **m != 0) {
+                    if classify_pixel_with(val, &nodata, None) == PixelClass::Valid {
+                        acc.push(val);
+                    }
+                }
+            }
+        }
+        stats.extend(
+            zone.into_iter()
+                .zip(&bands)
+                .map(|(acc, &band)| acc.finish(&id, band)),
+        );
+    }
+    Ok(stats)
+}
+
+/// Zonal statistics as a table, with the id column named after `id_column`
+pub fn zonal_stats_df(stats: &[ZoneStats], id_column: &str) -> Result<DataFrame> {
+    Ok(df!(
+        id_column => stats.iter().map(|s| s.id.as_str()).collect::<Vec<_>>(),
+        "band" => stats.iter().map(|s| s.band as u32).collect::<Vec<_>>(),
+        "count" => stats.iter().map(|s| s.count).collect::<Vec<_>>(),
+        "mean" => stats.iter().map(|s| s.mean).collect::<Vec<_>>(),
+        "min" => stats.iter().map(|s| s.min).collect::<Vec<_>>(),
+        "max" => stats.iter().map(|s| s.max).collect::<Vec<_>>(),
+    )?)
+}
+
+/// Writes the zonal statistics table to `out`, with the extension of `format`, returning the path
+pub fn write_zonal_stats(
+    stats: &[ZoneStats],
+    options: &ZonalOptions,
+    out: &Path,
+    format: OutputFormat,
+) -> Result<PathBuf> {
+    let id_column = options.id_field.as_deref().unwrap_or("fid");
+    let mut df = zonal_stats_df(stats, id_column)?;
+    let path = out.with_extension(format.to_string());
+    write_frame(&mut df, &path, format)?;
+    Ok(path)
+}
@@ -0,0 +1,68 @@
+use cloud_convert::band_math::{BAND_MATH_NODATA, band_math_to_cog, parse_expression};
+use gdal::raster::Buffer;
+use gdal::spatial_ref::SpatialRef;
+use gdal::{Dataset, DriverManager};
+
+mod common;
+use common::TestDir;
+
+#[test]
+fn test_parse_expression() {
+    let expr = parse_expression("(b4 - b3) / (b4 + b3)").unwrap();
+    assert_eq!(expr.max_band(), 4);
+    assert_eq!(expr.eval(&[0.0, 0.0, 1.0, 3.0]), Some(0.5));
+    assert_eq!(
+        parse_expression("-b1 * 2").unwrap().eval(&[1.5]),
+        Some(-3.0)
+    );
+    assert_eq!(parse_expression("b1 / 0").unwrap().eval(&[1.0]), None);
+
+    assert!(parse_expression("b0 + 1").is_err());
+    assert!(parse_expression("(b1 + b2").is_err());
+    assert!(parse_expression("b1 +").is_err());
+    assert!(parse_expression("b1 ^ 2").is_err());
+}
+
+#[test]
+fn test_band_math_ratio() {
+    let dir = TestDir::new("band_math");
+    let input = dir.join("two_band.tif");
+    let output = dir.join("ratio.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let mut ds = driver
+            .create_with_band_type::<f32, _>(&input, 2, 2, 2)
+            .unwrap();
+        ds.set_geo_transform(&[0.0, 1.0, 0.0, 2.0, 0.0, -1.0])
+            .unwrap();
+        ds.set_spatial_ref(&SpatialRef::from_epsg(4326).unwrap())
+            .unwrap();
+        let mut b1 = Buffer::new((2, 2), vec![2.0f32, 3.0, 4.0, 5.0]);
+        let mut b2 = Buffer::new((2, 2), vec![1.0f32, 2.0, 0.0, 5.0]);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (2, 2), &mut b1)
+            .unwrap();
+        ds.rasterband(2)
+            .unwrap()
+            .write((0, 0), (2, 2), &mut b2)
+            .unwrap();
+    }
+
+    band_math_to_cog(&input, &output, "b1 / b2", true).unwrap();
+    assert!(!dir.join("ratio.partial.tif").exists());
+
+    let ds = Dataset::open(&output).unwrap();
+    let band = ds.rasterband(1).unwrap();
+    assert_eq!(band.no_data_value(), Some(BAND_MATH_NODATA));
+    let values = band
+        .read_as::<f32>((0, 0), (2, 2), (2, 2), None)
+        .unwrap()
+        .data()
+        .to_vec();
+    assert_eq!(values, vec![2.0, 1.5, BAND_MATH_NODATA as f32, 1.0]);
+
+    // Out-of-range band references are rejected before any output is written
+    assert!(band_math_to_cog(&input, &output, "b3 - b1", true).is_err());
+}
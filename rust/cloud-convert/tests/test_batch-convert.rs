@@ -1,11 +1,34 @@
 use cloud_convert::batch_convert;
-use std::path::Path;
+use cloud_convert::batch_convert::{
+    BatchOptions, ChecksumRow, ErrorCategory, ProgressReporter, batch_convert_manifest,
+    convert_with_retries, read_failed_from_report, read_manifest, resolve_output, summarize_errors,
+};
+use cloud_convert::stats_cache::file_hash;
+use cloud_convert::tif2cog::{CogOptions, Compression, tif_to_cog};
+use cloud_convert::vect2gpq::GpqOptions;
+use gdal::raster::Buffer;
+use gdal::spatial_ref::SpatialRef;
+use gdal::{Dataset, DriverManager, Metadata};
+use rayon::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+mod common;
+use common::TestDir;
 
 #[test]
 fn test_batch_convert_cog() {
     let input = Path::new("tests/data/batch_data");
     let out_dir = Some(Path::new("tests/data/batch_data/out"));
-    let result = batch_convert::batch_convert_cog(&input, out_dir, true);
+    let result = batch_convert::batch_convert_cog(
+        &input,
+        out_dir,
+        true,
+        &CogOptions::default(),
+        &BatchOptions::default(),
+    );
     assert!(result.is_ok());
 }
 
@@ -13,7 +36,12 @@ fn test_batch_convert_cog() {
 fn test_batch_convert_gpq() {
     let input = Path::new("tests/data/batch_data");
     let out_dir = Some(Path::new("tests/data/batch_data/out"));
-    let result = batch_convert::batch_convert_gpq(&input, out_dir);
+    let gpq_options = GpqOptions {
+        overwrite: true,
+        ..Default::default()
+    };
+    let result =
+        batch_convert::batch_convert_gpq(&input, out_dir, &gpq_options, &BatchOptions::default());
     assert!(result.is_ok(), "Batch convert failed: {:?}", result.err());
     let summary = result.unwrap();
     assert_eq!(summary.successful.len(), 4); // 3 files converted
@@ -25,5 +53,632 @@ fn test_batch_convert_gpq() {
     // );
 }
 
+#[test]
+fn test_summarize_errors() {
+    let failed = vec![
+        (
+            PathBuf::from("a.tif"),
+            "Error: The file \"a_cog.tif\" already exists and overwrite is false.".to_string(),
+        ),
+        (
+            PathBuf::from("b.tif"),
+            "Error: The file \"b_cog.tif\" already exists and overwrite is false.".to_string(),
+        ),
+        (
+            PathBuf::from("c.kml"),
+            "Failed to get Parquet Driver: not available".to_string(),
+        ),
+    ];
+    let summary = summarize_errors(&failed);
+    assert_eq!(summary.len(), 2);
+    assert_eq!(summary[0].0, ErrorCategory::OutputExists);
+    assert_eq!(summary[0].1, 2);
+    assert_eq!(summary[1].0, ErrorCategory::UnsupportedDriver);
+    assert_eq!(summary[1].2.0, PathBuf::from("c.kml"));
+}
+
+#[test]
+fn test_classify_error() {
+    let cases = [
+        (
+            "Error: The file \"a_cog.tif\" already exists and overwrite is false.",
+            ErrorCategory::OutputExists,
+        ),
+        ("Failed to read band 1: CplError", ErrorCategory::Io),
+        ("Cannot write out.parquet: read error", ErrorCategory::Io),
+        ("Failed to build thread pool", ErrorCategory::Other),
+        ("Band 2 is already sorted", ErrorCategory::Other),
+        (
+            "Input raster has no CRS to warp from: CplError",
+            ErrorCategory::CrsMissing,
+        ),
+        (
+            "Source dataset contains no layers",
+            ErrorCategory::EmptyInput,
+        ),
+        ("I/O error on band 1", ErrorCategory::Io),
+    ];
+    for (msg, category) in cases {
+        assert_eq!(ErrorCategory::classify(msg), category, "{}", msg);
+    }
+}
+
+#[test]
+fn test_classify_error_ignores_paths() {
+    let cases = [
+        (
+            "Failed to open dataset \"/data/crs_tiles/a.tif\": CplError",
+            ErrorCategory::Other,
+        ),
+        (
+            "Failed to open dataset /data/driver_exports/b.tif: CplError",
+            ErrorCategory::Other,
+        ),
+        (
+            "/data/empty/c.tif: Failed to read band 1",
+            ErrorCategory::Io,
+        ),
+        (
+            "C:\\data\\crs\\empty.tif: Failed to write band 1",
+            ErrorCategory::Io,
+        ),
+        (
+            "/data/crs_tiles/d.partial.tif raster: CRS 'unnamed' has no authority code",
+            ErrorCategory::Other,
+        ),
+        (
+            "/data/crs_tiles/d.partial.tif raster: no CRS",
+            ErrorCategory::CrsMissing,
+        ),
+    ];
+    for (msg, category) in cases {
+        assert_eq!(ErrorCategory::classify(msg), category, "{}", msg);
+    }
+}
+
+#[test]
+fn test_report_dtypes() {
+    let report = batch_convert::report_dtypes(Path::new("tests/data/batch_data")).unwrap();
+    let total: usize = report.iter().map(|(_, count)| count).sum();
+    assert_eq!(total, 4);
+    assert!(report.iter().all(|(profile, _)| profile.band_count >= 1));
+    batch_convert::print_dtype_report(&report);
+}
+
+#[test]
+fn test_convert_with_retries() {
+    let calls = AtomicUsize::new(0);
+    let flaky = |_: &Path, _: Option<&Path>| {
+        if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+            Err("transient I/O error".to_string())
+        } else {
+            Ok("done".to_string())
+        }
+    };
+
+    let logged = Mutex::new(Vec::new());
+    let log = |text: &str| logged.lock().unwrap().push(text.to_string());
+    let result = convert_with_retries(Path::new("a.tif"), None, 1, Duration::ZERO, flaky, log);
+    assert_eq!(result, Ok("done".to_string()));
+    assert_eq!(calls.load(Ordering::SeqCst), 2);
+    assert_eq!(
+        logged.into_inner().unwrap(),
+        vec!["Retry 1/1 for a.tif: transient I/O error"]
+    );
+
+    calls.store(0, Ordering::SeqCst);
+    let result = convert_with_retries(Path::new("a.tif"), None, 0, Duration::ZERO, flaky, |_| {});
+    assert!(result.is_err());
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_quiet_progress_still_counts() {
+    let progress = ProgressReporter::new(2, false).with_bar(true);
+    assert!(!progress.shows_lines());
+    assert_eq!(progress.finish(Path::new("a.tif"), true).index, 1);
+    let event = progress.finish(Path::new("b.tif"), false);
+    assert_eq!((event.index, event.status), (2, "failed"));
+
+    // JSON events replace the per-file lines
+    assert!(!ProgressReporter::new(2, true).shows_lines());
+}
+
+#[test]
+fn test_progress_events_under_concurrency() {
+    let progress = ProgressReporter::new(200, false);
+    let paths: Vec<PathBuf> = (0..200)
+        .map(|i| PathBuf::from(format!("{}.tif", i)))
+        .collect();
+
+    let mut events: Vec<_> = paths
+        .par_iter()
+        .map(|path| progress.finish(path, !path.ends_with("7.tif")))
+        .collect();
+    events.sort_by_key(|e| e.index);
+
+    let indices: Vec<usize> = events.iter().map(|e| e.index).collect();
+    assert_eq!(indices, (1..=200).collect::<Vec<_>>());
+    assert!(events.iter().all(|e| e.total == 200));
+    assert!(
+        events
+            .windows(2)
+            .all(|w| w[0].elapsed_ms <= w[1].elapsed_ms)
+    );
+    assert_eq!(events.iter().filter(|e| e.status == "failed").count(), 1);
+
+    let line = serde_json::to_value(&events[0]).unwrap();
+    for key in ["index", "total", "path", "status", "elapsed_ms"] {
+        assert!(line.get(key).is_some(), "Missing key {}", key);
+    }
+}
+
+#[test]
+fn test_manifest_per_row_options() {
+    let dir = TestDir::new("manifest");
+    let input = dir.join("utm.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let mut ds = driver
+            .create_with_band_type::<f32, _>(&input, 8, 8, 1)
+            .unwrap();
+        ds.set_geo_transform(&[500000.0, 100.0, 0.0, 10000.0, 0.0, -100.0])
+            .unwrap();
+        ds.set_spatial_ref(&SpatialRef::from_epsg(32637).unwrap())
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(-1.0)).unwrap();
+        let mut data: Vec<f32> = (0..64).map(|v| v as f32).collect();
+        data[0] = -1.0;
+        let mut buf = Buffer::new((8, 8), data);
+        band.write((0, 0), (8, 8), &mut buf).unwrap();
+    }
+
+    let manifest = dir.join("manifest.csv");
+    std::fs::write(
+        &manifest,
+        "input,output,compression,nodata,t_srs\n\
+         utm.tif,out/deflate.tif,DEFLATE,-9999,\n\
+         utm.tif,out/wgs84.tif,,,EPSG:4326\n",
+    )
+    .unwrap();
+
+    let rows = read_manifest(&manifest).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert_eq!(rows[0].compression, Some(Compression::Deflate));
+    assert_eq!(rows[0].nodata, Some(-9999.0));
+    assert_eq!(rows[1].t_srs.as_deref(), Some("EPSG:4326"));
+    assert_eq!(rows[1].output, dir.join("out/wgs84.tif"));
+
+    let summary = batch_convert_manifest(
+        &manifest,
+        true,
+        &CogOptions::default(),
+        &BatchOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(summary.successful.len(), 2, "{:?}", summary.failed);
+
+    let deflate = Dataset::open(dir.join("out/deflate.tif")).unwrap();
+    assert_eq!(
+        deflate
+            .metadata_item("COMPRESSION", "IMAGE_STRUCTURE")
+            .as_deref(),
+        Some("DEFLATE")
+    );
+    let band = deflate.rasterband(1).unwrap();
+    assert_eq!(band.no_data_value(), Some(-9999.0));
+    assert_eq!(band.read_band_as::<f32>().unwrap().data()[0], -9999.0);
+    assert_eq!(deflate.spatial_ref().unwrap().auth_code().unwrap(), 32637);
+
+    let wgs84 = Dataset::open(dir.join("out/wgs84.tif")).unwrap();
+    assert_eq!(
+        wgs84
+            .metadata_item("COMPRESSION", "IMAGE_STRUCTURE")
+            .as_deref(),
+        Some("LZW")
+    );
+    assert_eq!(wgs84.spatial_ref().unwrap().auth_code().unwrap(), 4326);
+    assert_eq!(wgs84.rasterband(1).unwrap().no_data_value(), Some(-1.0));
+}
+
+#[test]
+fn test_manifest_schema_errors() {
+    let dir = TestDir::new("manifest_schema");
+    let manifest = dir.join("manifest.csv");
+    std::fs::write(dir.join("a.tif"), b"").unwrap();
+
+    let cases = [
+        (
+            "input,output,resampling\na.tif,b.tif,nearest\n",
+            "Unknown manifest column",
+        ),
+        ("input,nodata\na.tif,0\n", "missing the 'output' column"),
+        ("input,output\na.tif\n", "Line 2"),
+        ("input,output,nodata\na.tif,b.tif,none\n", "invalid nodata"),
+        ("input,output\nmissing.tif,b.tif\n", "does not exist"),
+        ("input,output\na.tif,b.tif\na.tif,b.tif\n", "more than once"),
+    ];
+    for (text, expected) in cases {
+        std::fs::write(&manifest, text).unwrap();
+        let err = read_manifest(&manifest).unwrap_err();
+        assert!(err.contains(expected), "{:?}: {}", text, err);
+    }
+}
+
+#[test]
+fn test_resume_from_report() {
+    let dir = TestDir::new("resume");
+    let input_dir = dir.join("in");
+    let out_dir = dir.join("out");
+    std::fs::create_dir_all(&input_dir).unwrap();
+
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    for name in ["a.tif", "b.tif", "c.tif"] {
+        driver
+            .create_with_band_type::<u8, _>(input_dir.join(name), 4, 4, 1)
+            .unwrap();
+    }
+
+    let report = dir.join("report.json");
+    let previous = serde_json::json!({
+        "successful": [[input_dir.join("a.tif"), "a.tif"]],
+        "failed": [
+            [input_dir.join("b.tif"), "Failed to create COG: disk full"],
+            [input_dir.join("c.tif"), "Failed to open dataset"]
+        ]
+    });
+    std::fs::write(&report, previous.to_string()).unwrap();
+
+    let new_report = dir.join("report_retry.json");
+    let options = BatchOptions {
+        resume_from: Some(report.clone()),
+        report: Some(new_report.clone()),
+        ..Default::default()
+    };
+    let summary = batch_convert::batch_convert_cog(
+        &input_dir,
+        Some(&out_dir),
+        true,
+        &CogOptions::default(),
+        &options,
+    )
+    .unwrap();
+
+    let mut retried: Vec<PathBuf> = summary.successful.iter().map(|(p, _)| p.clone()).collect();
+    retried.sort();
+    assert_eq!(
+        retried,
+        vec![input_dir.join("b.tif"), input_dir.join("c.tif")]
+    );
+    assert!(!out_dir.join("a.tif").exists());
+    assert!(out_dir.join("b.tif").exists());
+
+    // The new report can itself be resumed from, and has nothing left to retry
+    assert!(
+        read_failed_from_report(&new_report, &input_dir)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_resume_from_invalid_report() {
+    let dir = TestDir::new("resume_invalid");
+    let report = dir.join("report.json");
+
+    std::fs::write(&report, r#"{"failed": ["a.tif"]}"#).unwrap();
+    let err = read_failed_from_report(&report, &dir).unwrap_err();
+    assert!(err.contains("is not a batch report"), "{}", err);
+
+    std::fs::write(
+        &report,
+        r#"{"successful": [], "failed": [["/elsewhere/a.tif", "boom"]]}"#,
+    )
+    .unwrap();
+    let err = read_failed_from_report(&report, &dir).unwrap_err();
+    assert!(err.contains("not in"), "{}", err);
+}
+
+#[test]
+fn test_batch_dry_run() {
+    let dir = TestDir::new("dry_run");
+    let input_dir = dir.join("in");
+    let out_dir = dir.join("out");
+    std::fs::create_dir_all(&input_dir).unwrap();
+
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    for name in ["a.tif", "b.tif"] {
+        driver
+            .create_with_band_type::<u8, _>(input_dir.join(name), 4, 4, 1)
+            .unwrap();
+    }
+
+    let options = BatchOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+    let summary = batch_convert::batch_convert_cog(
+        &input_dir,
+        Some(&out_dir),
+        false,
+        &CogOptions::default(),
+        &options,
+    )
+    .unwrap();
+    let mut planned: Vec<&str> = summary.successful.iter().map(|(_, o)| o.as_str()).collect();
+    planned.sort();
+    let expected = [out_dir.join("a.tif"), out_dir.join("b.tif")];
+    assert_eq!(
+        planned,
+        expected
+            .iter()
+            .map(|p| p.to_str().unwrap())
+            .collect::<Vec<_>>()
+    );
+    assert!(summary.failed.is_empty());
+    assert!(!out_dir.exists());
+
+    // Without --out, COGs are planned next to the inputs with a _cog suffix
+    let summary =
+        batch_convert::batch_convert_cog(&input_dir, None, false, &CogOptions::default(), &options)
+            .unwrap();
+    assert!(
+        summary
+            .successful
+            .iter()
+            .all(|(_, o)| o.ends_with("_cog.tif"))
+    );
+    assert!(!input_dir.join("a_cog.tif").exists());
+
+    // The real run writes exactly the planned files
+    let converted = batch_convert::batch_convert_cog(
+        &input_dir,
+        None,
+        false,
+        &CogOptions::default(),
+        &BatchOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(converted.successful.len(), summary.successful.len());
+    for (_, planned) in &summary.successful {
+        assert!(Path::new(planned).exists(), "{}", planned);
+    }
+}
+
+#[test]
+fn test_batch_recursive() {
+    let dir = TestDir::new("recursive");
+    let input_dir = dir.join("in");
+    let out_dir = input_dir.join("cogs");
+    std::fs::create_dir_all(input_dir.join("2020/jan")).unwrap();
+    std::fs::create_dir_all(&out_dir).unwrap();
+
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    for name in ["a.tif", "2020/b.tif", "2020/jan/c.tif", "cogs/old.tif"] {
+        driver
+            .create_with_band_type::<u8, _>(input_dir.join(name), 4, 4, 1)
+            .unwrap();
+    }
+
+    // Without --recursive only the top level is converted
+    let summary = batch_convert::batch_convert_cog(
+        &input_dir,
+        Some(&out_dir),
+        false,
+        &CogOptions::default(),
+        &BatchOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(summary.successful.len(), 1);
+
+    let options = BatchOptions {
+        recursive: true,
+        ..Default::default()
+    };
+    let summary = batch_convert::batch_convert_cog(
+        &input_dir,
+        Some(&out_dir),
+        false,
+        &CogOptions::default(),
+        &options,
+    )
+    .unwrap();
+    assert!(summary.failed.is_empty());
+    // The output directory inside the input is not converted again
+    assert_eq!(summary.successful.len(), 3);
+    assert!(out_dir.join("2020/b.tif").exists());
+    assert!(out_dir.join("2020/jan/c.tif").exists());
+}
+
+#[test]
+fn test_batch_skips_existing_cogs() {
+    let dir = TestDir::new("skip_cogs");
+    let input_dir = dir.join("in");
+    let out_dir = dir.join("out");
+    std::fs::create_dir_all(&input_dir).unwrap();
+
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    let plain = dir.join("plain.tif");
+    driver
+        .create_with_band_type::<u8, _>(&plain, 4, 4, 1)
+        .unwrap();
+    driver
+        .create_with_band_type::<u8, _>(input_dir.join("raw.tif"), 4, 4, 1)
+        .unwrap();
+    tif_to_cog(
+        &plain,
+        Some(&input_dir.join("done.tif")),
+        true,
+        &CogOptions::default(),
+    )
+    .unwrap();
+
+    // An input that is already a COG is still converted, so options such as --bands apply
+    let cog_options = CogOptions {
+        bands: Some(vec![1]),
+        ..Default::default()
+    };
+    let summary = batch_convert::batch_convert_cog(
+        &input_dir,
+        Some(&out_dir),
+        false,
+        &cog_options,
+        &BatchOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(summary.successful.len(), 2);
+    assert!(summary.skipped.is_empty());
+    assert!(out_dir.join("done.tif").exists());
+
+    // A rerun skips the outputs it already wrote
+    let summary = batch_convert::batch_convert_cog(
+        &input_dir,
+        Some(&out_dir),
+        false,
+        &cog_options,
+        &BatchOptions::default(),
+    )
+    .unwrap();
+    assert!(summary.successful.is_empty());
+    assert_eq!(summary.skipped.len(), 2);
+
+    // --force reconverts them in place, without --overwrite
+    let options = BatchOptions {
+        force: true,
+        ..Default::default()
+    };
+    let summary =
+        batch_convert::batch_convert_cog(&input_dir, Some(&out_dir), false, &cog_options, &options)
+            .unwrap();
+    assert_eq!(summary.successful.len(), 2);
+    assert!(summary.skipped.is_empty());
+}
+
+#[test]
+fn test_batch_checksums() {
+    let dir = TestDir::new("checksums");
+    let input_dir = dir.join("in");
+    std::fs::create_dir_all(&input_dir).unwrap();
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    for name in ["b.tif", "a, c.tif"] {
+        driver
+            .create_with_band_type::<u8, _>(input_dir.join(name), 4, 4, 1)
+            .unwrap();
+    }
+    std::fs::write(input_dir.join("broken.tif"), "not a raster").unwrap();
+
+    let json = dir.join("checksums.json");
+    let options = BatchOptions {
+        checksums: Some(json.clone()),
+        ..Default::default()
+    };
+    let out_dir = dir.join("out");
+    let summary = batch_convert::batch_convert_cog(
+        &input_dir,
+        Some(&out_dir),
+        true,
+        &CogOptions::default(),
+        &options,
+    )
+    .unwrap();
+    assert_eq!(summary.failed.len(), 1);
+
+    // Only the successful outputs are listed, sorted by input
+    let rows: Vec<ChecksumRow> =
+        serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    assert_eq!(rows.len(), 2);
+    assert!(rows[0].input.ends_with("a, c.tif"));
+    for row in &rows {
+        assert!(row.output.starts_with(&out_dir));
+        assert_eq!(row.bytes, std::fs::metadata(&row.output).unwrap().len());
+        assert_eq!(row.sha256, file_hash(&row.output).unwrap());
+    }
+
+    // Any other extension writes CSV, quoting paths that hold a comma
+    let csv = dir.join("checksums.csv");
+    let options = BatchOptions {
+        checksums: Some(csv.clone()),
+        ..Default::default()
+    };
+    batch_convert::batch_convert_cog(
+        &input_dir,
+        Some(&out_dir),
+        true,
+        &CogOptions::default(),
+        &options,
+    )
+    .unwrap();
+    let text = std::fs::read_to_string(&csv).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "input,output,bytes,sha256");
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with('"'), "{}", lines[1]);
+    assert!(lines[2].ends_with(&file_hash(&rows[1].output).unwrap()));
+}
+
+#[test]
+fn test_manifest_checksums_follow_each_row() {
+    let dir = TestDir::new("manifest_checksums");
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    driver
+        .create_with_band_type::<u8, _>(dir.join("in.tif"), 4, 4, 1)
+        .unwrap();
+
+    // The same input written twice, to different directories and with different codecs
+    let manifest = dir.join("manifest.csv");
+    std::fs::write(
+        &manifest,
+        "input,output,compression
+         in.tif,lzw/in.tif,LZW
+         in.tif,deflate/in.tif,DEFLATE
+",
+    )
+    .unwrap();
+    let json = dir.join("checksums.json");
+    let options = BatchOptions {
+        checksums: Some(json.clone()),
+        ..Default::default()
+    };
+    let summary =
+        batch_convert_manifest(&manifest, true, &CogOptions::default(), &options).unwrap();
+    assert_eq!(summary.successful.len(), 2, "{:?}", summary.failed);
 
+    let rows: Vec<ChecksumRow> =
+        serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    let mut outputs: Vec<PathBuf> = rows.iter().map(|row| row.output.clone()).collect();
+    outputs.sort();
+    assert_eq!(
+        outputs,
+        vec![dir.join("deflate/in.tif"), dir.join("lzw/in.tif")]
+    );
+    for row in &rows {
+        assert_eq!(row.sha256, file_hash(&row.output).unwrap());
+    }
+}
 
+#[test]
+fn test_resolve_output_dir() {
+    let root = TestDir::new("output_dir");
+    let dir = root.join("out");
+    let input = Path::new("tests/data/test_input.tif");
+
+    // A single file lands in the directory under its own name, which is created
+    let out = resolve_output(input, None, Some(dir.clone()), false).unwrap();
+    assert_eq!(out, Some(dir.join("test_input.tif")));
+    assert!(dir.is_dir());
+
+    // Directory inputs and multi-file modes take the directory itself
+    let out = resolve_output(Path::new("tests/data"), None, Some(dir.clone()), false).unwrap();
+    assert_eq!(out, Some(dir.clone()));
+    let out = resolve_output(input, None, Some(dir.clone()), true).unwrap();
+    assert_eq!(out, Some(dir.clone()));
+
+    let out = PathBuf::from("elsewhere.tif");
+    assert_eq!(
+        resolve_output(input, Some(out.clone()), None, false).unwrap(),
+        Some(out.clone())
+    );
+    assert!(resolve_output(input, Some(out), Some(dir), false).is_err());
+}
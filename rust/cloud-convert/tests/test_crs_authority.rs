@@ -0,0 +1,119 @@
+use cloud_convert::crs_authority::{AuthorityCheck, identify_epsg};
+use cloud_convert::tif2cog::{CogOptions, tif_to_cog};
+use cloud_convert::vect2gpq::{GpqOptions, vector_convert};
+use gdal::spatial_ref::SpatialRef;
+use gdal::vector::LayerAccess;
+use gdal::{Dataset, DriverManager};
+
+mod common;
+use common::TestDir;
+
+// WGS 84 as bare WKT, without AUTHORITY nodes
+const WGS84_NO_CODE: &str = r#"GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563]],PRIMEM["Greenwich",0],UNIT["degree",0.0174532925199433]]"#;
+// Africa Albers Equal Area, which has no EPSG code
+const ALBERS_AFRICA: &str = "+proj=aea +lat_1=20 +lat_2=-23 +lat_0=0 +lon_0=25 +x_0=0 +y_0=0 +datum=WGS84 +units=m +no_defs";
+
+#[test]
+fn test_identify_epsg() {
+    let srs = SpatialRef::from_wkt(WGS84_NO_CODE).unwrap();
+    assert!(srs.authority().is_err());
+    let identified = identify_epsg(&srs).unwrap();
+    assert_eq!(identified.authority().unwrap(), "EPSG:4326");
+
+    let albers = SpatialRef::from_proj4(ALBERS_AFRICA).unwrap();
+    assert!(identify_epsg(&albers).is_none());
+}
+
+#[test]
+fn test_require_epsg_geoparquet() {
+    let dir = TestDir::new("require_epsg");
+    let input = dir.join("sites.csv");
+    let output = dir.join("sites.parquet");
+    std::fs::write(&input, "site,lon,lat\nA,36.8,-1.3\nB,38.7,9.0\n").unwrap();
+
+    let require = AuthorityCheck {
+        require: true,
+        ..Default::default()
+    };
+    let options = GpqOptions {
+        csv_crs: Some(ALBERS_AFRICA.to_string()),
+        crs_authority: require.clone(),
+        ..Default::default()
+    };
+    let err = vector_convert(&input, Some(&output), &options).unwrap_err();
+    assert!(err.contains("no authority code"), "{}", err);
+    // The failed output is never moved into place, so the next attempt needs no --overwrite
+    assert!(!output.exists());
+    assert!(!dir.join("sites.partial.parquet").exists());
+
+    // Bare WKT fails until the identified code is stamped
+    let options = GpqOptions {
+        csv_crs: Some(WGS84_NO_CODE.to_string()),
+        crs_authority: require.clone(),
+        ..Default::default()
+    };
+    let err = vector_convert(&input, Some(&output), &options).unwrap_err();
+    assert!(err.contains("--stamp-epsg"), "{}", err);
+    assert!(!output.exists());
+
+    let options = GpqOptions {
+        csv_crs: Some(WGS84_NO_CODE.to_string()),
+        crs_authority: AuthorityCheck {
+            stamp: true,
+            ..require.clone()
+        },
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+    let ds = Dataset::open(&output).unwrap();
+    let srs = ds.layer(0).unwrap().spatial_ref().unwrap();
+    assert_eq!(srs.authority().unwrap(), "EPSG:4326");
+    drop(ds);
+
+    // A failing rerun with --overwrite leaves the earlier good output alone
+    let before = std::fs::read(&output).unwrap();
+    let options = GpqOptions {
+        csv_crs: Some(ALBERS_AFRICA.to_string()),
+        crs_authority: require,
+        overwrite: true,
+        ..Default::default()
+    };
+    assert!(vector_convert(&input, Some(&output), &options).is_err());
+    assert_eq!(std::fs::read(&output).unwrap(), before);
+}
+
+#[test]
+fn test_require_epsg_cog() {
+    let dir = TestDir::new("require_epsg_cog");
+    let input = dir.join("albers.tif");
+    let output = dir.join("albers_cog.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let mut ds = driver
+            .create_with_band_type::<u8, _>(&input, 4, 4, 1)
+            .unwrap();
+        ds.set_geo_transform(&[0.0, 1000.0, 0.0, 0.0, 0.0, -1000.0])
+            .unwrap();
+        ds.set_spatial_ref(&SpatialRef::from_proj4(ALBERS_AFRICA).unwrap())
+            .unwrap();
+    }
+
+    let options = CogOptions {
+        crs_authority: AuthorityCheck {
+            require: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    assert!(tif_to_cog(&input, Some(&output), false, &options).is_err());
+    assert!(!output.exists());
+
+    // Without --require-epsg the same conversion succeeds
+    tif_to_cog(&input, Some(&output), false, &CogOptions::default()).unwrap();
+
+    // Converting in place with --overwrite keeps the input when the check fails
+    let before = std::fs::read(&input).unwrap();
+    assert!(tif_to_cog(&input, None, true, &options).is_err());
+    assert_eq!(std::fs::read(&input).unwrap(), before);
+}
@@ -1,6 +1,14 @@
-use cloud_convert::datainfo::{get_datainfo, print_datainfo};
+use cloud_convert::datainfo::{
+    DatasetType, TOOL_DRIVERS, extract_feature, get_datainfo, get_details, list_drivers,
+    print_datainfo, print_datainfo_json, print_details, print_drivers, subdatasets,
+};
+use gdal::spatial_ref::SpatialRef;
+use gdal::{DriverManager, Metadata};
 use std::path::Path;
 
+mod common;
+use common::TestDir;
+
 #[test]
 fn test_datainfo_get() {
     let datainfo = get_datainfo(&Path::new("tests/data/test_input.gpkg")).unwrap();
@@ -14,6 +22,7 @@ fn test_datainfo_get() {
         "The CRS of the first layer is incorrect. Expected 'WGS 84' but found '{}'.",
         lyr1_crs
     );
+    assert_eq!(lyr1.epsg.as_deref(), Some("EPSG:4326"));
     assert_eq!(
         lyr1.name.clone(),
         "atlas_gaul_a0_africa_verysimple",
@@ -34,3 +43,290 @@ fn test_datainfo_tif() {
     let rast_size = datainfo.size.unwrap();
     assert_eq!(rast_size, (828, 746), "Raster size is incorrect");
 }
+
+#[test]
+fn test_datainfo_json() {
+    let datainfo = get_datainfo(Path::new("tests/data/test_input.gpkg")).unwrap();
+    print_datainfo_json(&datainfo).unwrap();
+
+    let json = serde_json::to_value(&datainfo).unwrap();
+    for key in [
+        "dataset_type",
+        "driver",
+        "crs",
+        "crs_id",
+        "epsg",
+        "crs_units",
+        "size",
+        "resolution",
+        "bounds",
+        "rotated_transform",
+        "band_count",
+        "band_types",
+        "bands",
+        "overview_count",
+        "block_size",
+        "layers",
+        "layer_count",
+        "subdatasets",
+    ] {
+        assert!(json.get(key).is_some(), "Missing key {}", key);
+    }
+    assert_eq!(json["dataset_type"], "Vector");
+    assert!(json["size"].is_null());
+    let layer = &json["layers"][0];
+    assert_eq!(layer["name"], "atlas_gaul_a0_africa_verysimple");
+    assert!(layer["feature_count"].as_u64().unwrap() > 0);
+    assert!(layer["fields"][0][0].is_string());
+}
+
+#[test]
+fn test_datainfo_bounds_and_resolution() {
+    let dir = TestDir::new("datainfo_bounds");
+    let write = |name: &str, transform: [f64; 6]| {
+        let path = dir.join(name);
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let mut ds = driver
+            .create_with_band_type::<u8, _>(&path, 20, 10, 1)
+            .unwrap();
+        ds.set_geo_transform(&transform).unwrap();
+        path
+    };
+
+    let north_up = get_datainfo(&write("north_up.tif", [30.0, 0.5, 0.0, 5.0, 0.0, -0.25])).unwrap();
+    assert_eq!(north_up.resolution, Some((0.5, 0.25)));
+    assert_eq!(north_up.bounds, Some([30.0, 2.5, 40.0, 5.0]));
+    assert!(north_up.rotated_transform.is_none());
+
+    let rotated = [30.0, 0.5, 0.1, 5.0, 0.1, -0.25];
+    let info = get_datainfo(&write("rotated.tif", rotated)).unwrap();
+    assert_eq!(info.rotated_transform, Some(rotated));
+    print_datainfo(&info);
+}
+
+#[test]
+fn test_datainfo_bands() {
+    let dir = TestDir::new("datainfo_bands");
+    let path = dir.join("datainfo_bands.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<i16, _>(&path, 4, 4, 2)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(-32768.0)).unwrap();
+        band.set_description("elevation").unwrap();
+    }
+
+    let info = get_datainfo(&path).unwrap();
+    let bands = info.bands.as_ref().unwrap();
+    assert_eq!(bands.len(), 2);
+    assert_eq!(bands[0].dtype, "Int16");
+    assert_eq!(bands[0].no_data_value, Some(-32768.0));
+    assert_eq!(bands[0].description, "elevation");
+    assert_eq!(bands[1].no_data_value, None);
+    assert_eq!(bands[1].description, "");
+    print_datainfo(&info);
+}
+
+#[test]
+fn test_datainfo_subdatasets() {
+    // A GeoPackage with two raster tables exposes them as subdatasets, like NetCDF variables
+    let dir = TestDir::new("subdatasets");
+    let path = dir.join("subdatasets.gpkg");
+    let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+    for (table, append) in [("rain", "NO"), ("temp", "YES")] {
+        let options = gdal::raster::RasterCreationOptions::from_iter([
+            format!("RASTER_TABLE={}", table),
+            format!("APPEND_SUBDATASET={}", append),
+        ]);
+        let mut ds = driver
+            .create_with_band_type_with_options::<u8, _>(&path, 4, 4, 1, &options)
+            .unwrap();
+        ds.set_geo_transform(&[0.0, 1.0, 0.0, 4.0, 0.0, -1.0])
+            .unwrap();
+        ds.set_spatial_ref(&SpatialRef::from_epsg(4326).unwrap())
+            .unwrap();
+    }
+
+    let info = get_datainfo(&path).unwrap();
+    assert!(matches!(info.dataset_type, DatasetType::Raster));
+    let listed = info.subdatasets.as_ref().unwrap();
+    assert_eq!(listed.len(), 2);
+    assert!(listed[0].0.ends_with(":rain"), "{:?}", listed);
+    print_datainfo(&info);
+
+    // Each subdataset name opens as a regular raster
+    let rain = get_datainfo(std::path::Path::new(&listed[0].0)).unwrap();
+    assert_eq!(rain.band_count, Some(1));
+    assert!(
+        subdatasets(&gdal::Dataset::open(&path).unwrap())
+            .iter()
+            .all(|(_, desc)| !desc.is_empty())
+    );
+}
+
+#[test]
+fn test_list_drivers() {
+    let all = list_drivers(false);
+    assert!(all.iter().any(|d| d.short_name == "GTiff" && d.raster));
+
+    let used = list_drivers(true);
+    assert_eq!(used.len(), TOOL_DRIVERS.len());
+    let cog = used.iter().find(|d| d.short_name == "COG").unwrap();
+    assert!(cog.available && cog.create_copy);
+    print_drivers(&used);
+}
+
+#[test]
+fn test_datainfo_crs_units() {
+    let datainfo = get_datainfo(Path::new("tests/data/test_input.gpkg")).unwrap();
+    let units = datainfo.layers.unwrap()[0].crs_units.clone().unwrap();
+    assert_eq!(units.kind, "angular");
+    assert_eq!(units.name, "degree");
+
+    let dir = TestDir::new("crs_units");
+    let path = dir.join("crs_units.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let mut ds = driver
+            .create_with_band_type::<u8, _>(&path, 2, 2, 1)
+            .unwrap();
+        ds.set_spatial_ref(&SpatialRef::from_epsg(32637).unwrap())
+            .unwrap();
+    }
+    let datainfo = get_datainfo(&path).unwrap();
+    let units = datainfo.crs_units.clone().unwrap();
+    assert_eq!(units.kind, "linear");
+    assert_eq!(units.to_base, 1.0);
+
+    let json = serde_json::to_value(&datainfo).unwrap();
+    assert_eq!(json["crs_units"]["kind"], "linear");
+}
+
+#[test]
+fn test_datainfo_epsg() {
+    let dir = TestDir::new("datainfo_epsg");
+    let path = dir.join("datainfo_epsg.tif");
+    let write = |srs: SpatialRef| {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let mut ds = driver
+            .create_with_band_type::<u8, _>(&path, 2, 2, 1)
+            .unwrap();
+        ds.set_spatial_ref(&srs).unwrap();
+    };
+
+    write(SpatialRef::from_epsg(32637).unwrap());
+    let datainfo = get_datainfo(&path).unwrap();
+    assert_eq!(datainfo.epsg.as_deref(), Some("EPSG:32637"));
+
+    // A CRS written without a code is identified when GDAL is confident
+    let wgs84 = r#"GEOGCS["WGS 84",DATUM["WGS_1984",SPHEROID["WGS 84",6378137,298.257223563]],PRIMEM["Greenwich",0],UNIT["degree",0.0174532925199433]]"#;
+    write(SpatialRef::from_wkt(wgs84).unwrap());
+    let datainfo = get_datainfo(&path).unwrap();
+    assert_eq!(datainfo.epsg.as_deref(), Some("EPSG:4326"));
+
+    // Otherwise the WKT is reported
+    write(
+        SpatialRef::from_proj4(
+            "+proj=aea +lat_1=20 +lat_2=-23 +lat_0=0 +lon_0=25 +datum=WGS84 +units=m +no_defs",
+        )
+        .unwrap(),
+    );
+    let datainfo = get_datainfo(&path).unwrap();
+    let epsg = datainfo.epsg.unwrap();
+    assert!(epsg.contains("Albers"), "{}", epsg);
+}
+
+#[test]
+fn test_extract_feature() {
+    let path = Path::new("tests/data/test_input.gpkg");
+    let feature = extract_feature(path, None, Some(1), None).unwrap();
+    assert_eq!(feature["type"], "Feature");
+    assert_eq!(feature["id"], 1);
+    assert!(feature["properties"].is_object());
+    let geometry_type = feature["geometry"]["type"].as_str().unwrap();
+    assert!(geometry_type.ends_with("Polygon"), "{}", geometry_type);
+
+    let by_filter = extract_feature(
+        path,
+        Some("atlas_gaul_a0_africa_verysimple"),
+        None,
+        Some("FID = 1"),
+    )
+    .unwrap();
+    assert_eq!(by_filter, feature);
+
+    let err = extract_feature(path, None, Some(999_999), None).unwrap_err();
+    assert!(err.contains("No feature with FID 999999"), "{}", err);
+    assert!(extract_feature(path, Some("missing"), Some(1), None).is_err());
+}
+
+#[test]
+fn test_datainfo_overviews_and_block_size() {
+    let dir = TestDir::new("datainfo_tiling");
+    let path = dir.join("datainfo_tiling.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let options = gdal::raster::RasterCreationOptions::from_iter([
+            "TILED=YES",
+            "BLOCKXSIZE=32",
+            "BLOCKYSIZE=32",
+        ]);
+        let mut ds = driver
+            .create_with_band_type_with_options::<u8, _>(&path, 128, 128, 1, &options)
+            .unwrap();
+        ds.build_overviews("NEAREST", &[2, 4], &[]).unwrap();
+    }
+
+    let info = get_datainfo(&path).unwrap();
+    assert_eq!(info.overview_count, Some(2));
+    assert_eq!(info.block_size, Some((32, 32)));
+    print_datainfo(&info);
+
+    // Vector datasets have neither
+    let info = get_datainfo(Path::new("tests/data/test_input.gpkg")).unwrap();
+    assert!(info.overview_count.is_none() && info.block_size.is_none());
+}
+
+#[test]
+fn test_datainfo_details() {
+    let dir = TestDir::new("datainfo_details");
+    let path = dir.join("datainfo_details.tif");
+    let transform = [30.0, 0.5, 0.0, 5.0, 0.0, -0.5];
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let mut ds = driver
+            .create_with_band_type::<u8, _>(&path, 4, 4, 1)
+            .unwrap();
+        ds.set_geo_transform(&transform).unwrap();
+        ds.set_spatial_ref(&SpatialRef::from_epsg(4326).unwrap())
+            .unwrap();
+        ds.set_metadata_item("PROVIDER", "CHIRPS", "").unwrap();
+    }
+
+    let details = get_details(&path).unwrap();
+    assert_eq!(details.geo_transform, Some(transform));
+    assert!(
+        details
+            .metadata
+            .iter()
+            .any(|(domain, key, value)| domain.is_empty()
+                && key == "PROVIDER"
+                && value == "CHIRPS")
+    );
+    assert!(details.gcps.is_empty());
+    assert_eq!(details.wkt[0].0, "CRS");
+    assert!(details.wkt[0].1.contains("WGS 84"));
+    print_details(&details);
+
+    // Vector layers have no geotransform, but each layer CRS is listed
+    let details = get_details(Path::new("tests/data/test_input.gpkg")).unwrap();
+    assert!(details.geo_transform.is_none());
+    assert!(
+        details
+            .wkt
+            .iter()
+            .any(|(label, _)| label.starts_with("Layer '"))
+    );
+}
@@ -0,0 +1,41 @@
+use cloud_convert::gdal_options::{merge_creation_options, parse_key_value, set_config_options};
+
+#[test]
+fn test_parse_key_value() {
+    assert_eq!(
+        parse_key_value("NUM_THREADS=ALL_CPUS").unwrap(),
+        ("NUM_THREADS".to_string(), "ALL_CPUS".to_string())
+    );
+    // Only the first '=' separates, so values may contain one
+    assert_eq!(
+        parse_key_value("OPTIONS=a=b").unwrap(),
+        ("OPTIONS".to_string(), "a=b".to_string())
+    );
+    assert!(parse_key_value("BIGTIFF").is_err());
+    assert!(parse_key_value("=YES").is_err());
+}
+
+#[test]
+fn test_merge_creation_options() {
+    let mut options = vec!["COMPRESS=LZW".to_string(), "BLOCKSIZE=512".to_string()];
+    merge_creation_options(
+        &mut options,
+        &[
+            ("compress".to_string(), "ZSTD".to_string()),
+            ("BIGTIFF".to_string(), "YES".to_string()),
+        ],
+    );
+    assert_eq!(
+        options,
+        vec!["BLOCKSIZE=512", "compress=ZSTD", "BIGTIFF=YES"]
+    );
+}
+
+#[test]
+fn test_set_config_options() {
+    set_config_options(&[("CLOUD_CONVERT_TEST_OPTION".to_string(), "42".to_string())]).unwrap();
+    assert_eq!(
+        gdal::config::get_config_option("CLOUD_CONVERT_TEST_OPTION", "").unwrap(),
+        "42"
+    );
+}
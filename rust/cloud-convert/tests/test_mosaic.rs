@@ -0,0 +1,158 @@
+use cloud_convert::mosaic::{MergeStrategy, mosaic_to_cog, read_band_names, stack_to_cog};
+use gdal::raster::Buffer;
+use gdal::spatial_ref::SpatialRef;
+use gdal::{Dataset, DriverManager, Metadata};
+use std::path::Path;
+
+mod common;
+use common::TestDir;
+
+fn write_tile(path: &Path, origin_x: f64, value: f32) {
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    let mut ds = driver
+        .create_with_band_type::<f32, _>(path, 10, 10, 1)
+        .unwrap();
+    ds.set_geo_transform(&[origin_x, 1.0, 0.0, 10.0, 0.0, -1.0])
+        .unwrap();
+    ds.set_spatial_ref(&SpatialRef::from_epsg(32637).unwrap())
+        .unwrap();
+    let mut buf = Buffer::new((10, 10), vec![value; 100]);
+    ds.rasterband(1)
+        .unwrap()
+        .write((0, 0), (10, 10), &mut buf)
+        .unwrap();
+}
+
+#[test]
+fn test_mosaic_merge_strategies() {
+    let dir = TestDir::new("mosaic");
+    let left = dir.join("left.tif");
+    let right = dir.join("right.tif");
+    write_tile(&left, 0.0, 1.0);
+    write_tile(&right, 5.0, 3.0);
+    let inputs = vec![left, right];
+
+    let cases = [
+        (MergeStrategy::First, 1.0),
+        (MergeStrategy::Last, 3.0),
+        (MergeStrategy::Min, 1.0),
+        (MergeStrategy::Max, 3.0),
+        (MergeStrategy::Mean, 2.0),
+    ];
+    for (strategy, expected) in cases {
+        let output = dir.join(format!("mosaic_{}.tif", strategy));
+        mosaic_to_cog(&inputs, &output, strategy, None, true).unwrap();
+
+        let ds = Dataset::open(&output).unwrap();
+        assert_eq!(ds.raster_size(), (15, 10));
+        let buf = ds
+            .rasterband(1)
+            .unwrap()
+            .read_as::<f64>((7, 0), (1, 1), (1, 1), None)
+            .unwrap();
+        assert_eq!(buf.data()[0], expected, "strategy {}", strategy);
+        assert!(!output.with_extension("partial.tif").exists());
+    }
+}
+
+#[test]
+fn test_integer_mosaic_gaps_need_nodata() {
+    let dir = TestDir::new("mosaic_nodata");
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    // Byte tiles without NoData, 10 pixels apart
+    let inputs: Vec<_> = [(0.0, 1u8), (20.0, 3u8)]
+        .iter()
+        .map(|&(origin_x, value)| {
+            let path = dir.join(format!("tile_{}.tif", value));
+            let mut ds = driver
+                .create_with_band_type::<u8, _>(&path, 10, 10, 1)
+                .unwrap();
+            ds.set_geo_transform(&[origin_x, 1.0, 0.0, 10.0, 0.0, -1.0])
+                .unwrap();
+            ds.set_spatial_ref(&SpatialRef::from_epsg(32637).unwrap())
+                .unwrap();
+            let mut buf = Buffer::new((10, 10), vec![value; 100]);
+            ds.rasterband(1)
+                .unwrap()
+                .write((0, 0), (10, 10), &mut buf)
+                .unwrap();
+            path
+        })
+        .collect();
+
+    for strategy in [MergeStrategy::First, MergeStrategy::Max] {
+        let output = dir.join(format!("mosaic_{}.tif", strategy));
+        let err = mosaic_to_cog(&inputs, &output, strategy, None, true).unwrap_err();
+        assert!(err.contains("--nodata"), "{}", err);
+
+        mosaic_to_cog(&inputs, &output, strategy, Some(255.0), true).unwrap();
+        let ds = Dataset::open(&output).unwrap();
+        assert_eq!(ds.raster_size(), (30, 10));
+        let band = ds.rasterband(1).unwrap();
+        assert_eq!(band.no_data_value(), Some(255.0));
+        let row = band.read_as::<u8>((0, 0), (30, 1), (30, 1), None).unwrap();
+        assert_eq!(row.data()[5], 1, "strategy {}", strategy);
+        assert_eq!(row.data()[15], 255, "strategy {}", strategy);
+        assert_eq!(row.data()[25], 3, "strategy {}", strategy);
+    }
+}
+
+#[test]
+fn test_stack_band_names() {
+    let dir = TestDir::new("stack");
+    let inputs: Vec<_> = (0..3)
+        .map(|i| {
+            let path = dir.join(format!("band_{}.tif", i));
+            write_tile(&path, 0.0, i as f32);
+            path
+        })
+        .collect();
+    let names_file = dir.join("names.txt");
+    std::fs::write(&names_file, "ndvi\nevi\n\nlst\n").unwrap();
+    let names = read_band_names(&names_file).unwrap();
+
+    let output = dir.join("stack.tif");
+    stack_to_cog(&inputs, &output, Some(&names), true).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(ds.raster_count(), 3);
+    for (i, name) in ["ndvi", "evi", "lst"].iter().enumerate() {
+        assert_eq!(ds.rasterband(i + 1).unwrap().description().unwrap(), *name);
+    }
+    assert!(!dir.join("stack.partial.tif").exists());
+
+    assert!(stack_to_cog(&inputs, &output, Some(&names[..2]), true).is_err());
+}
+
+#[test]
+fn test_stack_counts_bands_of_multiband_inputs() {
+    let dir = TestDir::new("stack_multiband");
+    let single = dir.join("single.tif");
+    write_tile(&single, 0.0, 1.0);
+    let pair = dir.join("pair.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let mut ds = driver
+            .create_with_band_type::<f32, _>(&pair, 10, 10, 2)
+            .unwrap();
+        ds.set_geo_transform(&[0.0, 1.0, 0.0, 10.0, 0.0, -1.0])
+            .unwrap();
+        ds.set_spatial_ref(&SpatialRef::from_epsg(32637).unwrap())
+            .unwrap();
+    }
+    let inputs = vec![single, pair];
+    let output = dir.join("stack.tif");
+
+    // Three output bands from two files
+    let names = ["a", "b"].map(String::from);
+    let err = stack_to_cog(&inputs, &output, Some(&names), true).unwrap_err();
+    assert!(err.contains("2 band names for 3 bands"), "{}", err);
+
+    stack_to_cog(&inputs, &output, None, true).unwrap();
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(ds.raster_count(), 3);
+    let described: Vec<_> = (1..=3)
+        .map(|i| ds.rasterband(i).unwrap().description().unwrap())
+        .collect();
+    assert_eq!(described, ["single", "pair_1", "pair_2"]);
+}
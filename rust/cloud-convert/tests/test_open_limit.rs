@@ -0,0 +1,42 @@
+use cloud_convert::open_limit::{acquire, max_open, open, set_max_open};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_open_limit_blocks_other_threads() {
+    set_max_open(1).unwrap();
+    assert_eq!(max_open(), 1);
+    assert!(set_max_open(2).is_err());
+
+    let outer = acquire();
+    // Nested opens on the same thread share its slot instead of deadlocking
+    let nested = acquire();
+
+    let (tx, rx) = mpsc::channel();
+    let waiter = thread::spawn(move || {
+        let _permit = acquire();
+        tx.send(()).unwrap();
+    });
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+    drop(outer);
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+    drop(nested);
+    rx.recv_timeout(Duration::from_secs(5)).unwrap();
+    waiter.join().unwrap();
+
+    // A dataset opened through the helper holds the slot until its permit drops
+    let (permit, dataset) = open("tests/data/test_input.tif").unwrap();
+    let (tx, rx) = mpsc::channel();
+    let waiter = thread::spawn(move || {
+        let (_permit, dataset) = open("tests/data/test_input.tif").unwrap();
+        tx.send(dataset.raster_count()).unwrap();
+    });
+    assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+    let band_count = dataset.raster_count();
+    drop(dataset);
+    drop(permit);
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), band_count);
+    waiter.join().unwrap();
+}
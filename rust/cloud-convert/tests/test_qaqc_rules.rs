@@ -0,0 +1,108 @@
+use cloud_convert::qaqc_rules::{Comparison, Rule, apply_rules, evaluate_rules, load_rules};
+use polars::prelude::*;
+
+mod common;
+use common::TestDir;
+
+fn stats_table() -> DataFrame {
+    df!(
+        "file" => ["a.tif", "a.tif", "b.tif"],
+        "name" => ["red", "", ""],
+        "min" => [0.0, -1.0, 0.0],
+        "max" => [1.0, 1.0, f64::NAN],
+        "percent_valid" => [99.0, 97.5, 80.0],
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_parse_rule() {
+    let rule: Rule = "percent_valid >= 95".parse().unwrap();
+    assert_eq!(rule.column, "percent_valid");
+    assert_eq!(rule.comparison, Comparison::Ge);
+    assert_eq!(rule.value, 95.0);
+
+    let rule: Rule = "min>-1e3".parse().unwrap();
+    assert_eq!(rule.comparison, Comparison::Gt);
+    assert_eq!(rule.value, -1000.0);
+
+    for bad in ["percent_valid 95", ">= 95", "min = 0", "max <= one"] {
+        assert!(bad.parse::<Rule>().is_err(), "{}", bad);
+    }
+}
+
+#[test]
+fn test_evaluate_rules() {
+    let rules: Vec<Rule> = ["percent_valid >= 95", "min >= 0", "max <= 1"]
+        .iter()
+        .map(|r| r.parse().unwrap())
+        .collect();
+    let flags = evaluate_rules(&stats_table(), &rules).unwrap();
+    assert!(flags[0].is_empty());
+    assert_eq!(flags[1], vec!["min is -1, needs >= 0"]);
+    // NaN counts as missing and fails the rule
+    assert_eq!(
+        flags[2],
+        vec![
+            "percent_valid is 80, needs >= 95",
+            "max is missing, needs <= 1"
+        ]
+    );
+
+    let unknown = vec!["p99 < 10".parse().unwrap()];
+    let err = evaluate_rules(&stats_table(), &unknown).unwrap_err();
+    assert!(err.to_string().contains("'p99'"), "{}", err);
+}
+
+#[test]
+fn test_apply_rules_adds_pass_and_flags() {
+    let mut df = stats_table();
+    let rules = vec!["min >= 0".parse().unwrap()];
+    let failures = apply_rules(&mut df, &rules).unwrap();
+    assert_eq!(failures, vec!["a.tif band 2: min is -1, needs >= 0"]);
+
+    let pass: Vec<Option<bool>> = df
+        .column("pass")
+        .unwrap()
+        .bool()
+        .unwrap()
+        .into_iter()
+        .collect();
+    assert_eq!(pass, vec![Some(true), Some(false), Some(true)]);
+    assert_eq!(
+        df.column("flags").unwrap().str().unwrap().get(1),
+        Some("min is -1, needs >= 0")
+    );
+
+    // Without rules the table is left alone
+    let mut df = stats_table();
+    assert!(apply_rules(&mut df, &[]).unwrap().is_empty());
+    assert!(df.column("pass").is_err());
+}
+
+#[test]
+fn test_load_rules_toml() {
+    let dir = TestDir::new("rules");
+    let path = dir.join("rules.toml");
+    std::fs::write(
+        &path,
+        "# release gate\n[[rules]]\ncolumn = \"percent_valid\"\nop = \">=\"\nvalue = 95\n\n\
+         [[rules]]  # reflectance\ncolumn = \"max\"\nop = \"<=\"\nvalue = 1.0\n",
+    )
+    .unwrap();
+    let rules = load_rules(&path).unwrap();
+    assert_eq!(rules.len(), 2);
+    assert_eq!(rules[0].to_string(), "percent_valid >= 95");
+    assert_eq!(rules[1].to_string(), "max <= 1");
+
+    for bad in [
+        "[[rules]]\ncolumn = \"mean\"\nop = \"~\"\nvalue = 3\n",
+        "[[rules]]\ncolumn = \"mean\"\nop = \"<\"\nvalue = 3\nunit = \"m\"\n",
+        "[[rules]]\ncolumn = \"mean\"\nop = \"<\"\n",
+        "# no rules\n",
+    ] {
+        std::fs::write(&path, bad).unwrap();
+        let err = load_rules(&path).unwrap_err();
+        assert!(err.to_string().contains("rules"), "{}", err);
+    }
+}
@@ -0,0 +1,1262 @@
+use cloud_convert::batch_convert::{BatchOptions, ProgressReporter};
+use cloud_convert::rast_qaqc::{
+    OutputFormat, QaqcOptions, QaqcOutput, QuantileMethod, batch_qaqc, compute_all_bands,
+    compute_all_bands_cached, compute_bands_flattened, crs_violation, histogram_df, merge_qaqc,
+    parse_valid_range, quantile, raster_stats_to_df, sample_files, single_qaqc, strict_violations,
+    write_validity_mask,
+};
+use cloud_convert::stats_cache::StatsCache;
+use gdal::Metadata;
+use gdal::raster::Buffer;
+use gdal::spatial_ref::SpatialRef;
+use gdal::{Dataset, DriverManager};
+
+mod common;
+use common::TestDir;
+
+#[test]
+fn test_embedded_stats() {
+    let dir = TestDir::new("embedded_stats");
+    let path = dir.join("with_stats.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 4, 4, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        let mut buf = Buffer::new((4, 4), vec![1.0f32; 16]);
+        band.write((0, 0), (4, 4), &mut buf).unwrap();
+        // Deliberately different from the pixel values so the source is unambiguous
+        band.set_metadata_item("STATISTICS_MINIMUM", "10", "")
+            .unwrap();
+        band.set_metadata_item("STATISTICS_MAXIMUM", "20", "")
+            .unwrap();
+        band.set_metadata_item("STATISTICS_MEAN", "15", "").unwrap();
+        band.set_metadata_item("STATISTICS_STDDEV", "2", "")
+            .unwrap();
+    }
+
+    let options = QaqcOptions {
+        use_embedded_stats: true,
+        ..Default::default()
+    };
+    let stats = compute_all_bands(&path, &options).unwrap();
+    assert_eq!(stats[0].stats_source, "embedded");
+    assert_eq!(stats[0].effective_nodata, "n/a (embedded statistics)");
+    assert_eq!(stats[0].min, 10.0);
+    assert_eq!(stats[0].max, 20.0);
+    assert_eq!(stats[0].variance, 4.0);
+
+    let options = QaqcOptions {
+        use_embedded_stats: true,
+        force_scan: true,
+        ..Default::default()
+    };
+    let stats = compute_all_bands(&path, &options).unwrap();
+    assert_eq!(stats[0].stats_source, "scan");
+    assert_eq!(stats[0].max, 1.0);
+}
+
+#[test]
+fn test_variance_of_large_values() {
+    let dir = TestDir::new("large_variance");
+    let path = dir.join("large_values.tif");
+
+    // A large offset plus a small repeating pattern, whose variance is 8.25
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f64, _>(&path, 100, 100, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        let data: Vec<f64> = (0..10_000).map(|i| 1e9 + (i % 10) as f64).collect();
+        let mut buf = Buffer::new((100, 100), data);
+        band.write((0, 0), (100, 100), &mut buf).unwrap();
+    }
+
+    for quantiles in [false, true] {
+        let options = QaqcOptions {
+            quantiles,
+            ..Default::default()
+        };
+        let stats = compute_all_bands(&path, &options).unwrap();
+        assert!(
+            (stats[0].variance - 8.25).abs() < 1e-6,
+            "Variance {}",
+            stats[0].variance
+        );
+        assert!((stats[0].mean - (1e9 + 4.5)).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn test_float32_sum_of_population_counts() {
+    let dir = TestDir::new("float32_sum");
+    let path = dir.join("population.tif");
+
+    // A million cells of 250.5 people; a running f32 sum rounds each addition to a multiple of
+    // 16 once it passes 2^27, and ends about two million too high
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 1000, 1000, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        let mut buf = Buffer::new((1000, 1000), vec![250.5f32; 1_000_000]);
+        band.write((0, 0), (1000, 1000), &mut buf).unwrap();
+    }
+
+    for quantiles in [false, true] {
+        let options = QaqcOptions {
+            quantiles,
+            ..Default::default()
+        };
+        let stats = &compute_all_bands(&path, &options).unwrap()[0];
+        assert_eq!(stats.sum, 250_500_000.0);
+        assert_eq!(stats.mean, 250.5);
+    }
+}
+
+#[test]
+fn test_mean_of_large_float_values() {
+    let dir = TestDir::new("large_mean");
+    let path = dir.join("large_values.tif");
+
+    // 10^12 plus eighths; dividing the f64 sum by the count is about 0.4 too low here
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f64, _>(&path, 1000, 100, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        let data: Vec<f64> = (0..100_000)
+            .map(|i| 1e12 + (i % 8) as f64 * 0.125)
+            .collect();
+        let mut buf = Buffer::new((1000, 100), data);
+        band.write((0, 0), (1000, 100), &mut buf).unwrap();
+    }
+
+    for quantiles in [false, true] {
+        let options = QaqcOptions {
+            quantiles,
+            ..Default::default()
+        };
+        let stats = &compute_all_bands(&path, &options).unwrap()[0];
+        assert_eq!(stats.mean, 1e12 + 0.4375);
+    }
+}
+
+#[test]
+fn test_int32_values_near_1e8() {
+    let dir = TestDir::new("int32_stats");
+    let path = dir.join("population.tif");
+
+    // 10^8 plus 0, 3, 6 or 9, each 2500 times; f32 would round these to multiples of 8
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<i32, _>(&path, 100, 100, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        let data: Vec<i32> = (0..10_000).map(|i| 100_000_000 + (i % 4) * 3).collect();
+        let mut buf = Buffer::new((100, 100), data);
+        band.write((0, 0), (100, 100), &mut buf).unwrap();
+    }
+
+    for quantiles in [false, true] {
+        let options = QaqcOptions {
+            quantiles,
+            ..Default::default()
+        };
+        let stats = &compute_all_bands(&path, &options).unwrap()[0];
+        assert_eq!(stats.min, 100_000_000.0);
+        assert_eq!(stats.max, 100_000_009.0);
+        assert_eq!(stats.mean, 100_000_004.5);
+        assert!(
+            (stats.variance - 11.25).abs() < 1e-6,
+            "Variance {}",
+            stats.variance
+        );
+        assert_eq!(stats.zero_count, 0);
+    }
+}
+
+#[test]
+fn test_nodata_beyond_f32_precision() {
+    let dir = TestDir::new("wide_nodata");
+
+    // 2^24 + 1 has no f32 equivalent, so it must not be rounded onto its neighbours
+    let ids = dir.join("ids.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<u32, _>(&ids, 100, 100, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(16_777_217.0)).unwrap();
+        let data: Vec<u32> = (0..10_000).map(|i| 16_777_216 + i % 4).collect();
+        let mut buf = Buffer::new((100, 100), data);
+        band.write((0, 0), (100, 100), &mut buf).unwrap();
+    }
+    let stats = &compute_all_bands(&ids, &QaqcOptions::default()).unwrap()[0];
+    assert_eq!(stats.nodata_count, 2500);
+    assert_eq!(stats.valid_count, 7500);
+    assert_eq!(stats.percent_valid, 75.0);
+}
+
+#[test]
+fn test_quantile_methods() {
+    let values = [1.0f32, 2.0, 3.0, 4.0];
+    let nearest: Vec<f32> = [0.25, 0.5, 0.75]
+        .iter()
+        .map(|&p| quantile(&values, p, QuantileMethod::Nearest))
+        .collect();
+    assert_eq!(nearest, vec![2.0, 3.0, 3.0]);
+    // Matches numpy.quantile's default
+    let linear: Vec<f32> = [0.25, 0.5, 0.75]
+        .iter()
+        .map(|&p| quantile(&values, p, QuantileMethod::Linear))
+        .collect();
+    assert_eq!(linear, vec![1.75, 2.5, 3.25]);
+    assert!(quantile::<f32>(&[], 0.5, QuantileMethod::Linear).is_nan());
+
+    let dir = TestDir::new("quantile_method");
+    let path = dir.join("four_values.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 2, 2, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        let mut buf = Buffer::new((2, 2), values.to_vec());
+        band.write((0, 0), (2, 2), &mut buf).unwrap();
+    }
+    let options = QaqcOptions {
+        quantiles: true,
+        quantile_method: QuantileMethod::Linear,
+        ..Default::default()
+    };
+    let stats = compute_all_bands(&path, &options).unwrap();
+    assert_eq!(stats[0].q1, Some(1.75));
+    assert_eq!(stats[0].median, Some(2.5));
+}
+
+#[test]
+fn test_percentiles() {
+    let dir = TestDir::new("percentiles");
+    let path = dir.join("one_to_hundred.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 10, 10, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        let mut buf = Buffer::new((10, 10), (1..=100).map(|v| v as f32).collect());
+        band.write((0, 0), (10, 10), &mut buf).unwrap();
+    }
+
+    let options = QaqcOptions {
+        percentiles: vec![5.0, 95.0],
+        ..Default::default()
+    };
+    let stats = compute_all_bands(&path, &options).unwrap();
+    assert_eq!(stats[0].percentiles, vec![(5.0, 6.0), (95.0, 95.0)]);
+    // Quartiles are still only computed with --quantiles
+    assert_eq!(stats[0].q1, None);
+
+    let df = raster_stats_to_df(stats, &path).unwrap().collect().unwrap();
+    let p95 = df.column("p95").unwrap().f32().unwrap().get(0);
+    assert_eq!(p95, Some(95.0));
+    assert!(df.column("p5").is_ok());
+
+    let options = QaqcOptions {
+        percentiles: vec![150.0],
+        ..Default::default()
+    };
+    let err = single_qaqc(&path, &QaqcOutput::default(), &options).unwrap_err();
+    assert!(err.to_string().contains("outside 0-100"), "{}", err);
+    // Repeated percentiles would make two p5 columns
+    let options = QaqcOptions {
+        percentiles: vec![5.0, 95.0, 5.0],
+        ..Default::default()
+    };
+    let err = single_qaqc(&path, &QaqcOutput::default(), &options).unwrap_err();
+    assert!(err.to_string().contains("more than once"), "{}", err);
+    let mut stats = compute_all_bands(&path, &QaqcOptions::default()).unwrap();
+    stats[0].percentiles = vec![(5.0, 6.0), (5.0, 6.0)];
+    assert!(raster_stats_to_df(stats, &path).is_err());
+}
+
+#[test]
+fn test_approx_quantiles() {
+    let dir = TestDir::new("approx_quantiles");
+    let path = dir.join("ramp.tif");
+    let (cols, rows) = (600, 500);
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, cols, rows, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(-1.0)).unwrap();
+        // Squared ramp, so quantiles are not evenly spaced, with a NoData column
+        let data = (0..rows * cols)
+            .map(|i| {
+                if i % cols == 0 {
+                    -1.0
+                } else {
+                    (i as f32 / 1000.0).powi(2)
+                }
+            })
+            .collect();
+        let mut buf = Buffer::new((cols, rows), data);
+        band.write((0, 0), (cols, rows), &mut buf).unwrap();
+    }
+
+    let exact_options = QaqcOptions {
+        quantiles: true,
+        percentiles: vec![1.0, 99.0],
+        quantile_method: QuantileMethod::Linear,
+        ..Default::default()
+    };
+    let exact = &compute_all_bands(&path, &exact_options).unwrap()[0];
+    let approx_options = QaqcOptions {
+        approx_quantiles: true,
+        ..exact_options
+    };
+    let approx = &compute_all_bands(&path, &approx_options).unwrap()[0];
+
+    // The other statistics come from the same scan either way
+    assert_eq!(approx.valid_count, exact.valid_count);
+    assert_eq!(approx.nodata_count, exact.nodata_count);
+    assert_eq!(approx.min, exact.min);
+    assert_eq!(approx.max, exact.max);
+
+    // Estimates are interpolated, so compare within 1% of the exact values, even at p1
+    let close = |a: f32, b: f32| (a - b).abs() <= b.abs() * 0.01;
+    for (a, e) in [
+        (approx.q1, exact.q1),
+        (approx.median, exact.median),
+        (approx.q3, exact.q3),
+    ] {
+        assert!(close(a.unwrap(), e.unwrap()), "{:?} vs {:?}", a, e);
+    }
+    for ((p, a), (_, e)) in approx.percentiles.iter().zip(&exact.percentiles) {
+        assert!(close(*a, *e), "p{}: {} vs {}", p, a, e);
+    }
+}
+
+#[test]
+fn test_histogram() {
+    let dir = TestDir::new("histogram");
+    let path = dir.join("one_to_hundred.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 10, 10, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(0.0)).unwrap();
+        let mut data: Vec<f32> = (1..=100).map(|v| v as f32).collect();
+        data[0] = 0.0; // NoData is left out of the bins
+        let mut buf = Buffer::new((10, 10), data);
+        band.write((0, 0), (10, 10), &mut buf).unwrap();
+    }
+
+    // The streamed (two-pass) and full-read paths bin the same way
+    for quantiles in [false, true] {
+        let options = QaqcOptions {
+            quantiles,
+            histogram: Some(4),
+            ..Default::default()
+        };
+        let stats = compute_all_bands(&path, &options).unwrap();
+        assert_eq!(stats[0].histogram, Some(vec![25, 24, 25, 25]));
+    }
+
+    let options = QaqcOptions {
+        histogram: Some(4),
+        ..Default::default()
+    };
+    let stats = compute_all_bands(&path, &options).unwrap();
+    let bins = histogram_df(&stats, &path).collect().unwrap();
+    assert_eq!(bins.height(), 4);
+    let upper = bins.column("upper").unwrap().f64().unwrap();
+    assert_eq!(upper.get(3), Some(100.0));
+}
+
+#[test]
+fn test_single_qaqc_histogram_table() {
+    use polars::prelude::*;
+
+    let dir = TestDir::new("single_histogram");
+    let path = dir.join("ramp.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 10, 10, 1)
+            .unwrap();
+        let data: Vec<f32> = (1..=100).map(|v| v as f32).collect();
+        let mut buf = Buffer::new((10, 10), data);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (10, 10), &mut buf)
+            .unwrap();
+    }
+
+    // run-qaqc FILE --histogram 4 --out report.csv
+    let options = QaqcOptions {
+        histogram: Some(4),
+        ..Default::default()
+    };
+    let output = QaqcOutput {
+        out: Some(dir.join("report.csv")),
+        ..Default::default()
+    };
+    single_qaqc(&path, &output, &options).unwrap();
+
+    let bins = CsvReadOptions::default()
+        .try_into_reader_with_file_path(Some(dir.join("report_histogram.csv")))
+        .unwrap()
+        .finish()
+        .unwrap();
+    assert_eq!(bins.height(), 4);
+    let files = bins.column("file").unwrap().str().unwrap();
+    assert!(files.into_iter().all(|file| file == Some("ramp.tif")));
+    let count: u64 = bins
+        .column("count")
+        .unwrap()
+        .cast(&DataType::UInt64)
+        .unwrap()
+        .u64()
+        .unwrap()
+        .sum()
+        .unwrap();
+    assert_eq!(count, 100);
+}
+
+#[test]
+fn test_extra_nodata_and_valid_range() {
+    let dir = TestDir::new("extra_nodata");
+    let path = dir.join("sentinels.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 4, 2, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(-9999.0)).unwrap();
+        let data = vec![-9999.0, 255.0, 5.0, 50.0, 150.0, 10.0, 20.0, 30.0];
+        let mut buf = Buffer::new((4, 2), data);
+        band.write((0, 0), (4, 2), &mut buf).unwrap();
+    }
+
+    let options = QaqcOptions {
+        extra_nodata: vec![255.0],
+        valid_range: Some((0.0, 100.0)),
+        ..Default::default()
+    };
+    let stats = compute_all_bands(&path, &options).unwrap();
+    assert_eq!(stats[0].nodata_count, 3);
+    assert_eq!(stats[0].valid_count, 5);
+    assert_eq!(stats[0].max, 50.0);
+    assert_eq!(
+        stats[0].effective_nodata,
+        "declared -9999; extra 255; outside [0, 100]"
+    );
+
+    assert_eq!(parse_valid_range("-50, 60"), Ok((-50.0, 60.0)));
+    assert!(parse_valid_range("100,0").is_err());
+    assert!(parse_valid_range("100").is_err());
+}
+
+#[test]
+fn test_band_parallel_keeps_band_order() {
+    let dir = TestDir::new("band_parallel");
+    let path = dir.join("stack.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 4, 4, 12)
+            .unwrap();
+        for i in 1..=12 {
+            let mut band = ds.rasterband(i).unwrap();
+            let mut buf = Buffer::new((4, 4), vec![i as f32; 16]);
+            band.write((0, 0), (4, 4), &mut buf).unwrap();
+        }
+    }
+
+    let stats = compute_all_bands(&path, &QaqcOptions::default()).unwrap();
+    let means: Vec<f64> = stats.iter().map(|s| s.mean).collect();
+    assert_eq!(means, (1..=12).map(f64::from).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_band_selection() {
+    let dir = TestDir::new("qaqc_bands");
+    let path = dir.join("stack.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 4, 4, 8)
+            .unwrap();
+        for i in 1..=8 {
+            let mut band = ds.rasterband(i).unwrap();
+            let mut buf = Buffer::new((4, 4), vec![i as f32; 16]);
+            band.write((0, 0), (4, 4), &mut buf).unwrap();
+        }
+    }
+
+    let options = QaqcOptions {
+        bands: Some(vec![2, 5, 6, 7, 8]),
+        ..Default::default()
+    };
+    let stats = compute_all_bands(&path, &options).unwrap();
+    let bands: Vec<usize> = stats.iter().map(|s| s.band).collect();
+    assert_eq!(bands, vec![2, 5, 6, 7, 8]);
+    assert_eq!(stats[0].mean, 2.0);
+
+    // The table and batch scan keep the source band numbers
+    let df = raster_stats_to_df(stats, &path).unwrap().collect().unwrap();
+    assert_eq!(df.column("band").unwrap().u32().unwrap().get(1), Some(5));
+    let flattened = compute_bands_flattened(
+        std::slice::from_ref(&path),
+        &options,
+        None,
+        &ProgressReporter::new(1, false),
+        false,
+    );
+    assert_eq!(flattened[0].as_ref().unwrap()[4].band, 8);
+
+    let options = QaqcOptions {
+        bands: Some(vec![9]),
+        ..Default::default()
+    };
+    let err = compute_all_bands(&path, &options).unwrap_err();
+    assert!(err.to_string().contains("has 8 bands"), "{}", err);
+}
+
+#[test]
+fn test_qaqc_stdout_rejects_parquet() {
+    let output = QaqcOutput {
+        format: OutputFormat::Parquet,
+        to_stdout: true,
+        ..Default::default()
+    };
+    let result = batch_qaqc(
+        std::path::Path::new("tests/data/batch_data"),
+        100.0,
+        &output,
+        &BatchOptions::default(),
+        &QaqcOptions::default(),
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn test_zero_count_separate_from_nodata() {
+    let dir = TestDir::new("zero_count");
+    let path = dir.join("zeros_and_nodata.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 4, 2, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(-9999.0)).unwrap();
+        let data = vec![0.0, 0.0, 0.0, 2.0, 4.0, -9999.0, -9999.0, 0.0];
+        let mut buf = Buffer::new((4, 2), data);
+        band.write((0, 0), (4, 2), &mut buf).unwrap();
+    }
+
+    for quantiles in [false, true] {
+        let options = QaqcOptions {
+            quantiles,
+            ..Default::default()
+        };
+        let stats = compute_all_bands(&path, &options).unwrap();
+        assert_eq!(stats[0].zero_count, 4);
+        assert_eq!(stats[0].nodata_count, 2);
+        assert_eq!(stats[0].effective_nodata, "declared -9999");
+        assert_eq!(stats[0].valid_count, 6);
+        assert_eq!(stats[0].mean, 1.0);
+    }
+}
+
+#[test]
+fn test_sum_and_distinct_count() {
+    let dir = TestDir::new("distinct");
+    let path = dir.join("classes.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<i16, _>(&path, 4, 2, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(-1.0)).unwrap();
+        let data = vec![3, 3, 7, 7, 7, 12, -1, -1];
+        let mut buf = Buffer::new((4, 2), data);
+        band.write((0, 0), (4, 2), &mut buf).unwrap();
+    }
+
+    // The distinct count is off by default
+    let stats = compute_all_bands(&path, &QaqcOptions::default()).unwrap();
+    assert_eq!(stats[0].sum, 39.0);
+    assert_eq!(stats[0].distinct_count, None);
+
+    for quantiles in [false, true] {
+        let options = QaqcOptions {
+            quantiles,
+            distinct: true,
+            ..Default::default()
+        };
+        let stats = compute_all_bands(&path, &options).unwrap();
+        assert_eq!(stats[0].sum, 39.0);
+        // NoData is not counted as a value
+        assert_eq!(stats[0].distinct_count, Some(3));
+
+        let df = raster_stats_to_df(stats, &path).unwrap().collect().unwrap();
+        assert_eq!(df.column("sum").unwrap().f64().unwrap().get(0), Some(39.0));
+        assert_eq!(
+            df.column("distinct_count").unwrap().u64().unwrap().get(0),
+            Some(3)
+        );
+    }
+}
+
+#[test]
+fn test_batch_qaqc_concat_chunksize() {
+    let dir = TestDir::new("concat_chunksize");
+    let read_table = |chunksize: Option<usize>, name: &str| {
+        let out = dir.join(name);
+        let output = QaqcOutput {
+            out: Some(out.clone()),
+            concat_chunksize: chunksize,
+            ..Default::default()
+        };
+        batch_qaqc(
+            std::path::Path::new("tests/data/batch_data"),
+            100.0,
+            &output,
+            &BatchOptions::default(),
+            &QaqcOptions::default(),
+        )
+        .unwrap();
+        std::fs::read_to_string(&out).unwrap()
+    };
+
+    // Batches of one file build the same table as the single concat
+    let all_at_once = read_table(None, "all.csv");
+    assert_eq!(read_table(Some(1), "chunked.csv"), all_at_once);
+    assert!(all_at_once.lines().count() > 2);
+}
+
+#[test]
+fn test_stats_cache_reused_on_second_run() {
+    let dir = TestDir::new("stats_cache");
+    let raster = dir.join("cached.tif");
+    let cache_path = dir.join("cache.json");
+    std::fs::copy("tests/data/test_input.tif", &raster).unwrap();
+    let options = QaqcOptions::default();
+
+    let cache = StatsCache::load(&cache_path);
+    let first = compute_all_bands_cached(&raster, &options, Some(&cache)).unwrap();
+    assert_eq!(cache.hits(), 0);
+    cache.save().unwrap();
+
+    let cache = StatsCache::load(&cache_path);
+    let second = compute_all_bands_cached(&raster, &options, Some(&cache)).unwrap();
+    assert_eq!(cache.hits(), 1);
+    assert_eq!(first[0].mean, second[0].mean);
+
+    // Different options invalidate the entry
+    let quantile_options = QaqcOptions {
+        quantiles: true,
+        ..Default::default()
+    };
+    compute_all_bands_cached(&raster, &quantile_options, Some(&cache)).unwrap();
+    assert_eq!(cache.hits(), 1);
+}
+
+#[test]
+fn test_qaqc_out_path() {
+    let dir = TestDir::new("qaqc_out");
+
+    // The extension follows the output format
+    let output = QaqcOutput {
+        format: OutputFormat::Parquet,
+        out: Some(dir.join("results.csv")),
+        ..Default::default()
+    };
+    batch_qaqc(
+        std::path::Path::new("tests/data/batch_data"),
+        100.0,
+        &output,
+        &BatchOptions::default(),
+        &QaqcOptions::default(),
+    )
+    .unwrap();
+    assert!(dir.join("results.parquet").exists());
+    assert!(!dir.join("results.csv").exists());
+
+    let output = QaqcOutput {
+        out: Some(dir.join("single")),
+        ..Default::default()
+    };
+    single_qaqc(
+        std::path::Path::new("tests/data/test_input.tif"),
+        &output,
+        &QaqcOptions::default(),
+    )
+    .unwrap();
+    assert!(dir.join("single.csv").exists());
+}
+
+#[test]
+fn test_batch_qaqc_summary_json() {
+    let dir = TestDir::new("qaqc_summary");
+    let summary_path = dir.join("qaqc_summary.json");
+    let output = QaqcOutput {
+        summary_json: Some(summary_path.clone()),
+        ..Default::default()
+    };
+    batch_qaqc(
+        std::path::Path::new("tests/data/batch_data"),
+        100.0,
+        &output,
+        &BatchOptions::default(),
+        &QaqcOptions::default(),
+    )
+    .unwrap();
+
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&summary_path).unwrap()).unwrap();
+    for key in [
+        "total_files",
+        "files_passed",
+        "files_failed",
+        "mean_percent_valid",
+        "bands_flagged",
+    ] {
+        assert!(json.get(key).is_some(), "Missing key {}", key);
+    }
+    let total = json["total_files"].as_u64().unwrap();
+    let passed = json["files_passed"].as_u64().unwrap();
+    let failed = json["files_failed"].as_u64().unwrap();
+    assert_eq!(passed + failed, total);
+}
+
+#[test]
+fn test_write_validity_mask() {
+    let dir = TestDir::new("validity_mask");
+    let path = dir.join("gappy.tif");
+    let mask_path = dir.join("gappy_mask.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 3, 2, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(-9999.0)).unwrap();
+        let data = vec![1.0, -9999.0, 0.0, f32::NAN, 5.0, -9999.0];
+        let mut buf = Buffer::new((3, 2), data);
+        band.write((0, 0), (3, 2), &mut buf).unwrap();
+    }
+
+    write_validity_mask(&path, &mask_path, &QaqcOptions::default()).unwrap();
+
+    let mask = Dataset::open(&mask_path).unwrap();
+    assert_eq!(mask.raster_size(), (3, 2));
+    let band = mask.rasterband(1).unwrap();
+    assert_eq!(band.band_type(), gdal::raster::GdalDataType::UInt8);
+    let values = band.read_band_as::<u8>().unwrap().data().to_vec();
+    assert_eq!(values, vec![1, 0, 1, 0, 1, 0]);
+
+    // The mask follows the same options as the stats scan
+    let options = QaqcOptions {
+        extra_nodata: vec![5.0],
+        valid_range: Some((0.5, 10.0)),
+        ..Default::default()
+    };
+    let filtered_path = dir.join("gappy_filtered_mask.tif");
+    write_validity_mask(&path, &filtered_path, &options).unwrap();
+    let filtered = Dataset::open(&filtered_path).unwrap();
+    let values = filtered
+        .rasterband(1)
+        .unwrap()
+        .read_band_as::<u8>()
+        .unwrap()
+        .data()
+        .to_vec();
+    assert_eq!(values, vec![1, 0, 0, 0, 0, 0]);
+    let stats = compute_all_bands(&path, &options).unwrap();
+    assert_eq!(stats[0].valid_count, 1);
+}
+
+#[test]
+fn test_strict_mode_fails_on_empty_band() {
+    let dir = TestDir::new("strict");
+    let path = dir.join("empty_band.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 2, 2, 2)
+            .unwrap();
+        let mut good = Buffer::new((2, 2), vec![1.0f32; 4]);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (2, 2), &mut good)
+            .unwrap();
+        let mut empty_band = ds.rasterband(2).unwrap();
+        empty_band.set_no_data_value(Some(-1.0)).unwrap();
+        let mut empty = Buffer::new((2, 2), vec![-1.0f32; 4]);
+        empty_band.write((0, 0), (2, 2), &mut empty).unwrap();
+    }
+
+    let stats = compute_all_bands(&path, &QaqcOptions::default()).unwrap();
+    let df = raster_stats_to_df(stats, &path).unwrap().collect().unwrap();
+    let violations = strict_violations(&df).unwrap();
+    assert_eq!(violations.len(), 1);
+    assert!(violations[0].contains("band 2"));
+
+    let lenient = QaqcOutput::default();
+    single_qaqc(&path, &lenient, &QaqcOptions::default()).unwrap();
+    let strict = QaqcOutput {
+        strict: true,
+        ..Default::default()
+    };
+    assert!(single_qaqc(&path, &strict, &QaqcOptions::default()).is_err());
+
+    let status = std::process::Command::new(env!("CARGO_BIN_EXE_cloud-convert"))
+        .args(["run-qaqc", "--strict"])
+        .arg(&path)
+        .status()
+        .unwrap();
+    assert!(!status.success());
+}
+
+/// Write a 2x2 Float32 raster, in EPSG:4326 when `with_crs`
+fn write_small_raster(path: &std::path::Path, values: Vec<f32>, with_crs: bool) {
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    let mut ds = driver
+        .create_with_band_type::<f32, _>(path, 2, 2, 1)
+        .unwrap();
+    if with_crs {
+        ds.set_geo_transform(&[0.0, 1.0, 0.0, 2.0, 0.0, -1.0])
+            .unwrap();
+        ds.set_spatial_ref(&SpatialRef::from_epsg(4326).unwrap())
+            .unwrap();
+    }
+    let mut buf = Buffer::new((2, 2), values);
+    ds.rasterband(1)
+        .unwrap()
+        .write((0, 0), (2, 2), &mut buf)
+        .unwrap();
+}
+
+#[test]
+fn test_strict_mode_flags_values_at_dtype_limits() {
+    let dir = TestDir::new("strict_dtype_limits");
+    let path = dir.join("saturated.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<i16, _>(&path, 2, 2, 1)
+            .unwrap();
+        let mut buf = Buffer::new((2, 2), vec![i16::MIN, 1, 2, 3]);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (2, 2), &mut buf)
+            .unwrap();
+    }
+
+    let stats = compute_all_bands(&path, &QaqcOptions::default()).unwrap();
+    let df = raster_stats_to_df(stats, &path).unwrap().collect().unwrap();
+    let violations = strict_violations(&df).unwrap();
+    assert_eq!(violations.len(), 1);
+    assert!(violations[0].contains("Int16 range"));
+
+    let in_range = dir.join("in_range.tif");
+    write_small_raster(&in_range, vec![1.0, 2.0, 3.0, 4.0], true);
+    let stats = compute_all_bands(&in_range, &QaqcOptions::default()).unwrap();
+    let df = raster_stats_to_df(stats, &in_range)
+        .unwrap()
+        .collect()
+        .unwrap();
+    assert!(strict_violations(&df).unwrap().is_empty());
+}
+
+#[test]
+fn test_strict_mode_allows_zero_in_unsigned_bands() {
+    let dir = TestDir::new("strict_unsigned_zero");
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+
+    // Class-0 land cover and full-range 8-bit imagery
+    let byte = dir.join("landcover.tif");
+    {
+        let ds = driver
+            .create_with_band_type::<u8, _>(&byte, 2, 2, 1)
+            .unwrap();
+        let mut buf = Buffer::new((2, 2), vec![0u8, 1, 128, 255]);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (2, 2), &mut buf)
+            .unwrap();
+    }
+    let uint16 = dir.join("counts.tif");
+    {
+        let ds = driver
+            .create_with_band_type::<u16, _>(&uint16, 2, 2, 1)
+            .unwrap();
+        let mut buf = Buffer::new((2, 2), vec![0u16, 1, 2, 3]);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (2, 2), &mut buf)
+            .unwrap();
+    }
+    for path in [&byte, &uint16] {
+        let stats = compute_all_bands(path, &QaqcOptions::default()).unwrap();
+        let df = raster_stats_to_df(stats, path).unwrap().collect().unwrap();
+        assert!(strict_violations(&df).unwrap().is_empty(), "{:?}", path);
+    }
+
+    // The UInt16 maximum is still a saturation marker
+    let saturated = dir.join("saturated.tif");
+    {
+        let ds = driver
+            .create_with_band_type::<u16, _>(&saturated, 2, 2, 1)
+            .unwrap();
+        let mut buf = Buffer::new((2, 2), vec![0u16, 1, 2, u16::MAX]);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (2, 2), &mut buf)
+            .unwrap();
+    }
+    let stats = compute_all_bands(&saturated, &QaqcOptions::default()).unwrap();
+    let df = raster_stats_to_df(stats, &saturated)
+        .unwrap()
+        .collect()
+        .unwrap();
+    let violations = strict_violations(&df).unwrap();
+    assert_eq!(violations.len(), 1);
+    assert!(violations[0].contains("UInt16 range"));
+}
+
+#[test]
+fn test_strict_mode_fails_on_missing_crs() {
+    let dir = TestDir::new("strict_crs");
+    let with_crs = dir.join("with_crs.tif");
+    write_small_raster(&with_crs, vec![1.0, 2.0, 3.0, 4.0], true);
+    assert_eq!(crs_violation(&with_crs), None);
+
+    let strict = QaqcOutput {
+        strict: true,
+        ..Default::default()
+    };
+    batch_qaqc(
+        &dir,
+        100.0,
+        &strict,
+        &BatchOptions::default(),
+        &QaqcOptions::default(),
+    )
+    .unwrap();
+
+    let without_crs = dir.join("without_crs.tif");
+    write_small_raster(&without_crs, vec![1.0, 2.0, 3.0, 4.0], false);
+    assert!(crs_violation(&without_crs).unwrap().contains("no CRS"));
+    assert!(single_qaqc(&without_crs, &strict, &QaqcOptions::default()).is_err());
+    assert!(
+        batch_qaqc(
+            &dir,
+            100.0,
+            &strict,
+            &BatchOptions::default(),
+            &QaqcOptions::default(),
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_strict_mode_fails_when_mask_cannot_be_written() {
+    let dir = TestDir::new("strict_mask");
+    let path = dir.join("valid.tif");
+    write_small_raster(&path, vec![1.0, 2.0, 3.0, 4.0], true);
+    let mask_path = dir.join("missing").join("mask.tif");
+
+    let lenient = QaqcOutput {
+        write_mask: Some(mask_path.clone()),
+        ..Default::default()
+    };
+    single_qaqc(&path, &lenient, &QaqcOptions::default()).unwrap();
+    let strict = QaqcOutput {
+        strict: true,
+        write_mask: Some(mask_path.clone()),
+        ..Default::default()
+    };
+    assert!(single_qaqc(&path, &strict, &QaqcOptions::default()).is_err());
+    assert!(!mask_path.exists());
+
+    let written = QaqcOutput {
+        strict: true,
+        write_mask: Some(dir.join("mask.tif")),
+        ..Default::default()
+    };
+    single_qaqc(&path, &written, &QaqcOptions::default()).unwrap();
+}
+
+#[test]
+fn test_flattened_scan_matches_per_file() {
+    let dir = TestDir::new("flattened_qaqc");
+
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    let mut files = vec![];
+    for (name, bands) in [("a_multi.tif", 5), ("b_single.tif", 1)] {
+        let path = dir.join(name);
+        let ds = driver
+            .create_with_band_type::<f32, _>(&path, 8, 8, bands)
+            .unwrap();
+        for b in 1..=bands {
+            let mut buf = Buffer::new((8, 8), vec![b as f32 * 10.0; 64]);
+            ds.rasterband(b)
+                .unwrap()
+                .write((0, 0), (8, 8), &mut buf)
+                .unwrap();
+        }
+        files.push(path);
+    }
+    let broken = dir.join("c_broken.tif");
+    std::fs::write(&broken, b"not a raster").unwrap();
+    files.push(broken);
+
+    let options = QaqcOptions::default();
+    let progress = ProgressReporter::new(files.len(), false);
+    let results = compute_bands_flattened(&files, &options, None, &progress, false);
+
+    assert_eq!(results.len(), 3);
+    for (result, path) in results.iter().zip(&files[..2]) {
+        let expected = compute_all_bands(path, &options).unwrap();
+        let stats = result.as_ref().unwrap();
+        assert_eq!(stats.len(), expected.len());
+        for (got, want) in stats.iter().zip(&expected) {
+            assert_eq!(got.mean, want.mean);
+            assert_eq!(got.valid_count, want.valid_count);
+        }
+    }
+    let means: Vec<f64> = results[0]
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|s| s.mean)
+        .collect();
+    assert_eq!(means, vec![10.0, 20.0, 30.0, 40.0, 50.0]);
+    assert!(results[2].is_err());
+}
+
+#[test]
+fn test_seeded_sample_is_stable() {
+    let files: Vec<std::path::PathBuf> = (0..50)
+        .map(|i| std::path::PathBuf::from(format!("raster_{:02}.tif", i)))
+        .collect();
+    let mut reversed = files.clone();
+    reversed.reverse();
+
+    // Listing order must not matter for a fixed seed
+    let first = sample_files(files.clone(), 20.0, Some(42));
+    let second = sample_files(reversed, 20.0, Some(42));
+    assert_eq!(first.len(), 10);
+    assert_eq!(first, second);
+    assert!(first.windows(2).all(|pair| pair[0] < pair[1]));
+
+    let other_seed = sample_files(files.clone(), 20.0, Some(43));
+    assert_ne!(first, other_seed);
+    assert_eq!(sample_files(files, 100.0, None).len(), 50);
+}
+
+#[test]
+fn test_per_file_report() {
+    let dir = TestDir::new("per_file_report");
+
+    let output = QaqcOutput {
+        out: Some(dir.join("qaqc.csv")),
+        per_file_report: true,
+        report_dir: Some(dir.join("reports")),
+        ..Default::default()
+    };
+    batch_qaqc(
+        std::path::Path::new("tests/data/batch_data"),
+        100.0,
+        &output,
+        &BatchOptions::default(),
+        &QaqcOptions::default(),
+    )
+    .unwrap();
+
+    for name in [
+        "Cassava_Yield.tif",
+        "Soybean_Yield.tif",
+        "Wheat_Yield.tif",
+        "Yam_Yield.tif",
+    ] {
+        let report = dir.join("reports").join(format!("{}.qaqc.json", name));
+        let json = std::fs::read_to_string(&report).unwrap();
+        let stats: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let band_count = Dataset::open(format!("tests/data/batch_data/{}", name))
+            .unwrap()
+            .raster_count();
+        assert_eq!(
+            stats.as_array().unwrap().len(),
+            band_count,
+            "{}",
+            report.display()
+        );
+        assert!(stats[0]["mean"].is_number());
+    }
+}
+
+#[test]
+fn test_merge_qaqc() {
+    use polars::prelude::*;
+
+    let dir = TestDir::new("merge_qaqc");
+    let write = |name: &str, mut df: DataFrame| {
+        let path = dir.join(name);
+        ParquetWriter::new(std::fs::File::create(&path).unwrap())
+            .finish(&mut df)
+            .unwrap();
+        path
+    };
+    // The older table predates the sum column
+    let older = write(
+        "older.parquet",
+        df!("filename" => ["a.tif"], "band" => [1i64], "mean" => [0.5]).unwrap(),
+    );
+    let newer = write(
+        "newer.parquet",
+        df!("filename" => ["b.tif", "b.tif"], "band" => [1i64, 2], "mean" => [1.0, 2.0], "sum" => [10.0, 20.0])
+            .unwrap(),
+    );
+
+    let (path, rows) = merge_qaqc(
+        &[older.clone(), newer],
+        &dir.join("merged.csv"),
+        OutputFormat::Parquet,
+    )
+    .unwrap();
+    assert_eq!(path, dir.join("merged.parquet"));
+    assert_eq!(rows, 3);
+    let df = ParquetReader::new(std::fs::File::open(&path).unwrap())
+        .finish()
+        .unwrap();
+    let sum = df.column("sum").unwrap().f64().unwrap();
+    assert_eq!(sum.get(0), None);
+    assert_eq!(sum.get(2), Some(20.0));
+
+    assert!(
+        merge_qaqc(
+            &[older.clone(), dir.join("missing.parquet")],
+            &path,
+            OutputFormat::Parquet
+        )
+        .is_err()
+    );
+    assert!(merge_qaqc(std::slice::from_ref(&older), &older, OutputFormat::Parquet).is_err());
+}
+
+#[test]
+fn test_batch_qaqc_failures_are_errors() {
+    let dir = TestDir::new("qaqc_failures");
+    for name in ["a_broken.tif", "b_broken.tif"] {
+        std::fs::write(dir.join(name), b"not a raster").unwrap();
+    }
+    let output = QaqcOutput::default();
+
+    // Every file failing is an error, not a panic
+    let err = batch_qaqc(
+        &dir,
+        100.0,
+        &output,
+        &BatchOptions::default(),
+        &QaqcOptions::default(),
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("All 2 files failed"), "{}", err);
+
+    // One good file is enough for a table, unless the run fails fast
+    std::fs::copy("tests/data/test_input.tif", dir.join("c_good.tif")).unwrap();
+    batch_qaqc(
+        &dir,
+        100.0,
+        &output,
+        &BatchOptions::default(),
+        &QaqcOptions::default(),
+    )
+    .unwrap();
+    std::fs::remove_file(dir.join("qaqc.csv")).unwrap();
+
+    let fail_fast = BatchOptions {
+        fail_fast: true,
+        ..Default::default()
+    };
+    let err = batch_qaqc(&dir, 100.0, &output, &fail_fast, &QaqcOptions::default()).unwrap_err();
+    assert!(err.to_string().contains("_broken.tif"), "{}", err);
+    assert!(!dir.join("qaqc.csv").exists());
+}
+
+#[test]
+fn test_batch_qaqc_writes_error_table() {
+    use polars::prelude::*;
+
+    let dir = TestDir::new("qaqc_error_table");
+    std::fs::copy("tests/data/test_input.tif", dir.join("good.tif")).unwrap();
+    std::fs::write(dir.join("corrupt.tif"), b"not a raster").unwrap();
+
+    let output = QaqcOutput {
+        write_errors: true,
+        ..Default::default()
+    };
+    batch_qaqc(
+        &dir,
+        100.0,
+        &output,
+        &BatchOptions::default(),
+        &QaqcOptions::default(),
+    )
+    .unwrap();
+
+    let errors = CsvReadOptions::default()
+        .try_into_reader_with_file_path(Some(dir.join("qaqc_errors.csv")))
+        .unwrap()
+        .finish()
+        .unwrap();
+    assert_eq!(errors.height(), 1);
+    assert_eq!(
+        errors.get_column_names(),
+        ["filename", "error_type", "error"]
+    );
+    let filename = errors.column("filename").unwrap().str().unwrap().get(0);
+    assert!(filename.unwrap().ends_with("corrupt.tif"));
+
+    // A clean rerun replaces the table rather than leaving stale rows
+    std::fs::remove_file(dir.join("corrupt.tif")).unwrap();
+    batch_qaqc(
+        &dir,
+        100.0,
+        &output,
+        &BatchOptions::default(),
+        &QaqcOptions::default(),
+    )
+    .unwrap();
+    let errors = CsvReadOptions::default()
+        .try_into_reader_with_file_path(Some(dir.join("qaqc_errors.csv")))
+        .unwrap()
+        .finish()
+        .unwrap();
+    assert_eq!(errors.height(), 0);
+}
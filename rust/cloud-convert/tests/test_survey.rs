@@ -0,0 +1,86 @@
+use cloud_convert::rast_qaqc::OutputFormat;
+use cloud_convert::survey::{survey, write_survey};
+use cloud_convert::vect2gpq::{GpqOptions, vector_convert};
+use gdal::DriverManager;
+use gdal::spatial_ref::SpatialRef;
+use std::path::Path;
+
+mod common;
+use common::TestDir;
+
+fn write_raster(path: &Path, epsg: u32, transform: [f64; 6]) {
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+    let mut ds = driver
+        .create_with_band_type::<u8, _>(path, 10, 10, 1)
+        .unwrap();
+    ds.set_geo_transform(&transform).unwrap();
+    ds.set_spatial_ref(&SpatialRef::from_epsg(epsg).unwrap())
+        .unwrap();
+}
+
+#[test]
+fn test_survey_groups_and_extent() {
+    let dir = TestDir::new("survey");
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+
+    // Two overlapping 0.1 degree tiles, one adjacent tile in UTM, and a non-raster file
+    write_raster(&dir.join("a.tif"), 4326, [36.0, 0.1, 0.0, 1.0, 0.0, -0.1]);
+    write_raster(&dir.join("b.tif"), 4326, [36.5, 0.1, 0.0, 1.0, 0.0, -0.1]);
+    write_raster(
+        &dir.join("nested/c.tif"),
+        32637,
+        [500000.0, 1000.0, 0.0, -200000.0, 0.0, -1000.0],
+    );
+    std::fs::write(dir.join("notes.txt"), "not geospatial").unwrap();
+    std::fs::write(dir.join("broken.tif"), "not a tiff").unwrap();
+    // The tool's own stats cache, reports and leftover intermediates are not datasets
+    std::fs::write(dir.join(".qaqc_cache.json"), "{}").unwrap();
+    std::fs::write(dir.join("report.json"), "{}").unwrap();
+    std::fs::write(dir.join("a.warp.tif"), "partial").unwrap();
+
+    let result = survey(&dir).unwrap();
+    assert_eq!(result.items.len(), 3);
+    assert_eq!(result.unreadable.len(), 1);
+    assert_eq!(result.distinct_crs, 2);
+    assert_eq!(result.distinct_resolutions, 2);
+    assert_eq!(result.groups[0].crs, "EPSG:4326");
+    assert_eq!(result.groups[0].count, 2);
+    assert_eq!(result.overlapping, 2);
+
+    let [xmin, ymin, xmax, ymax] = result.union_extent.unwrap();
+    assert!((xmin - 36.0).abs() < 1e-9);
+    assert!((ymax - 1.0).abs() < 1e-9);
+    // The UTM tile sits south of the equator and east of 39E
+    assert!(ymin < -1.8 && xmax > 39.0, "{:?}", result.union_extent);
+
+    let out = dir.join("survey.csv");
+    write_survey(&result, &out, OutputFormat::Csv).unwrap();
+    let table = std::fs::read_to_string(&out).unwrap();
+    assert!(table.starts_with("crs,resolution_x,resolution_y,count"));
+    assert_eq!(table.lines().count(), 3);
+}
+
+#[test]
+fn test_survey_skips_own_parquet_tables() {
+    let dir = TestDir::new("survey_parquet");
+    write_raster(&dir.join("a.tif"), 4326, [36.0, 0.1, 0.0, 1.0, 0.0, -0.1]);
+    let sites = dir.join("sites.parquet");
+    vector_convert(
+        Path::new("tests/data/test_input.gpkg"),
+        Some(&sites),
+        &GpqOptions::default(),
+    )
+    .unwrap();
+
+    // A plain Parquet table from an earlier survey, and the leftover of an interrupted conversion
+    let first = survey(&dir).unwrap();
+    write_survey(&first, &dir.join("survey.parquet"), OutputFormat::Parquet).unwrap();
+    std::fs::copy(&sites, dir.join("roads.partial.parquet")).unwrap();
+
+    let result = survey(&dir).unwrap();
+    assert!(result.unreadable.is_empty(), "{:?}", result.unreadable);
+    assert_eq!(result.items.len(), first.items.len());
+    let paths: Vec<_> = result.items.iter().map(|item| item.path.clone()).collect();
+    assert!(paths.contains(&sites));
+    assert!(paths.contains(&dir.join("a.tif")));
+}
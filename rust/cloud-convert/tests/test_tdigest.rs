@@ -0,0 +1,56 @@
+use cloud_convert::rast_qaqc::{QuantileMethod, quantile};
+use cloud_convert::tdigest::TDigest;
+
+/// Deterministic values in [0, 1) (xorshift), so failures reproduce
+fn uniform(n: usize) -> Vec<f64> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    (0..n)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f64 / (1u64 << 53) as f64
+        })
+        .collect()
+}
+
+#[test]
+fn test_tdigest_matches_exact_quantiles() {
+    let n = 500_000;
+    let skewed: Vec<f64> = uniform(n).iter().map(|u| -u.ln() * 10.0).collect();
+    let spread: Vec<f64> = uniform(n).iter().map(|u| u.powi(3) * 1e6).collect();
+    for values in [skewed, spread] {
+        let mut digest = TDigest::new();
+        values.iter().for_each(|&v| digest.push(v));
+        let mut sorted = values.clone();
+        sorted.sort_unstable_by(f64::total_cmp);
+
+        for p in [0.001, 0.01, 0.05, 0.25, 0.5, 0.75, 0.95, 0.99, 0.999] {
+            let estimate = digest.quantile(p);
+            // The documented error is in rank, so compare where the estimate falls among the values
+            let rank = sorted.partition_point(|&v| v <= estimate) as f64 / n as f64;
+            assert!(
+                (rank - p).abs() < 0.005,
+                "p={} estimate {} at rank {}, exact {}",
+                p,
+                estimate,
+                rank,
+                quantile(&sorted, p as f32, QuantileMethod::Linear)
+            );
+        }
+        assert_eq!(digest.quantile(0.0), sorted[0]);
+        assert_eq!(digest.quantile(1.0), sorted[n - 1]);
+    }
+}
+
+#[test]
+fn test_tdigest_small_inputs_are_exact() {
+    let mut digest = TDigest::new();
+    assert!(digest.quantile(0.5).is_nan());
+    for v in [4.0, 1.0, 3.0, 2.0] {
+        digest.push(v);
+    }
+    assert_eq!(digest.quantile(0.25), 2.0);
+    assert_eq!(digest.quantile(0.5), 3.0);
+    assert_eq!(digest.quantile(1.0), 4.0);
+}
@@ -0,0 +1,53 @@
+use cloud_convert::tempdir::{
+    TempFile, intermediate_path, move_into_place, set_temp_dir, temp_dir,
+};
+use std::path::Path;
+
+mod common;
+use common::TestDir;
+
+#[test]
+fn test_temp_dir_override() {
+    let dir = TestDir::new("temp_override");
+    set_temp_dir(&dir).unwrap();
+    assert_eq!(temp_dir(), Some(dir.to_path_buf()));
+
+    let first = intermediate_path(Path::new("/data/out/result.tif"), "warp.tif");
+    let second = intermediate_path(Path::new("/data/out/result.tif"), "warp.tif");
+    assert!(first.starts_with(&dir));
+    assert!(first.to_string_lossy().ends_with(".warp.tif"));
+    assert_ne!(first, second);
+
+    assert!(set_temp_dir(&dir).is_err());
+
+    // The guard removes the file and GDAL's sidecars however the conversion ends
+    let temp = TempFile::new(Path::new("/data/out/result.tif"), "units.tif");
+    assert!(temp.path().starts_with(&dir));
+    let aux = dir.join(format!(
+        "{}.aux.xml",
+        temp.path().file_name().unwrap().to_string_lossy()
+    ));
+    std::fs::write(temp.path(), b"intermediate").unwrap();
+    std::fs::write(&aux, b"<PAMDataset/>").unwrap();
+    let path = temp.path().to_path_buf();
+    drop(temp);
+    assert!(!path.exists());
+    assert!(!aux.exists());
+}
+
+#[test]
+fn test_move_into_place_replaces_target() {
+    let dir = TestDir::new("move_into_place");
+    let from = dir.join("result.partial.tif");
+    let target = dir.join("result.tif");
+    std::fs::write(&target, b"old").unwrap();
+    std::fs::write(&from, b"new").unwrap();
+
+    move_into_place(&from, &target).unwrap();
+    assert_eq!(std::fs::read(&target).unwrap(), b"new");
+    assert!(!from.exists());
+
+    let err = move_into_place(&from, &target).unwrap_err();
+    assert!(err.contains("result.partial.tif"), "{}", err);
+    assert_eq!(std::fs::read(&target).unwrap(), b"new");
+}
@@ -0,0 +1,66 @@
+use cloud_convert::tif2zarr::{ZarrCompression, ZarrOptions, parse_chunk, tif_to_zarr};
+use gdal::Dataset;
+
+mod common;
+use common::TestDir;
+
+#[test]
+fn test_tif_to_zarr() {
+    let input = std::path::Path::new("tests/data/test_input.tif");
+    let dir = TestDir::new("zarr");
+    let output = dir.join("test_input.zarr");
+
+    let options = ZarrOptions {
+        chunk: Some((64, 32)),
+        ..Default::default()
+    };
+    let written = tif_to_zarr(input, Some(&dir.join("test_input")), false, &options).unwrap();
+    assert_eq!(written, "test_input.zarr");
+    assert!(output.is_dir());
+
+    let source = Dataset::open(input).unwrap();
+    let zarr = Dataset::open(&output).unwrap();
+    assert_eq!(zarr.raster_size(), source.raster_size());
+    assert_eq!(zarr.raster_count(), source.raster_count());
+    let band = zarr.rasterband(1).unwrap();
+    let (cols, rows) = source.raster_size();
+    assert_eq!(band.block_size(), (64, 32));
+    let read = |ds: &Dataset| {
+        ds.rasterband(1)
+            .unwrap()
+            .read_as::<f64>((0, 0), (cols, rows), (cols, rows), None)
+            .unwrap()
+            .data()
+            .to_vec()
+    };
+    assert_eq!(read(&zarr), read(&source));
+
+    // An existing store is only replaced with overwrite
+    let err = tif_to_zarr(input, Some(&output), false, &options).unwrap_err();
+    assert!(err.contains("already exists"), "{}", err);
+    let gzip = ZarrOptions {
+        compression: ZarrCompression::Gzip,
+        ..Default::default()
+    };
+    tif_to_zarr(input, Some(&output), true, &gzip).unwrap();
+    assert!(!dir.join("test_input.partial.zarr").exists());
+
+    // A conversion that fails leaves the existing store in place
+    let broken = dir.join("broken.tif");
+    std::fs::write(&broken, "not a tiff").unwrap();
+    assert!(tif_to_zarr(&broken, Some(&output), true, &gzip).is_err());
+    assert_eq!(read(&Dataset::open(&output).unwrap()), read(&source));
+}
+
+#[test]
+fn test_parse_chunk() {
+    assert_eq!(parse_chunk("512").unwrap(), (512, 512));
+    assert_eq!(parse_chunk("1024, 256").unwrap(), (1024, 256));
+    assert!(parse_chunk("0").is_err());
+    assert!(parse_chunk("512x512").is_err());
+    assert_eq!(
+        "zstd".parse::<ZarrCompression>().unwrap(),
+        ZarrCompression::Zstd
+    );
+    assert!("lzw".parse::<ZarrCompression>().is_err());
+}
@@ -0,0 +1,57 @@
+use cloud_convert::datainfo::get_datainfo;
+use cloud_convert::tif2cog::{CogOptions, tif_to_cog};
+use cloud_convert::vsi::{is_vsi, to_vsi};
+use gdal::DriverManager;
+use std::path::{Path, PathBuf};
+
+mod common;
+use common::TestDir;
+
+#[test]
+fn test_to_vsi() {
+    assert_eq!(
+        to_vsi(Path::new("s3://bucket/cogs/a.tif")),
+        PathBuf::from("/vsis3/bucket/cogs/a.tif")
+    );
+    assert_eq!(
+        to_vsi(Path::new("gs://bucket/a.tif")),
+        PathBuf::from("/vsigs/bucket/a.tif")
+    );
+    assert_eq!(
+        to_vsi(Path::new("https://example.com/a.tif")),
+        PathBuf::from("/vsicurl/https://example.com/a.tif")
+    );
+    assert_eq!(
+        to_vsi(Path::new("/vsis3/bucket/a.tif")),
+        PathBuf::from("/vsis3/bucket/a.tif")
+    );
+    assert_eq!(to_vsi(Path::new("data/a.tif")), PathBuf::from("data/a.tif"));
+
+    assert!(is_vsi(Path::new("s3://bucket/a.tif")));
+    assert!(is_vsi(Path::new("/vsimem/a.tif")));
+    assert!(!is_vsi(Path::new("/data/vsis3.tif")));
+}
+
+#[test]
+fn test_virtual_input() {
+    // /vsimem/ stands in for a cloud object: GDAL can read it but std::fs cannot
+    let input = Path::new("/vsimem/cloud_convert_vsi.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        driver
+            .create_with_band_type::<u8, _>(input, 8, 8, 1)
+            .unwrap();
+    }
+    assert!(!input.exists());
+
+    let info = get_datainfo(input).unwrap();
+    assert_eq!(info.size, Some((8, 8)));
+
+    let err = tif_to_cog(input, None, true, &CogOptions::default()).unwrap_err();
+    assert!(err.contains("--out"), "{}", err);
+
+    let dir = TestDir::new("vsi_cog");
+    let output = dir.join("vsi_cog.tif");
+    tif_to_cog(input, Some(&output), true, &CogOptions::default()).unwrap();
+    assert!(output.exists());
+}
@@ -0,0 +1,79 @@
+use cloud_convert::rast_qaqc::OutputFormat;
+use cloud_convert::zonal::{ZonalOptions, write_zonal_stats, zonal_stats};
+use gdal::DriverManager;
+use gdal::raster::Buffer;
+use gdal::spatial_ref::SpatialRef;
+
+mod common;
+use common::TestDir;
+
+#[test]
+fn test_zonal_stats() {
+    let dir = TestDir::new("zonal");
+    let raster = dir.join("values.tif");
+    let zones = dir.join("zones.geojson");
+
+    // 4x4 pixels of 1 degree holding 0..15 row by row, with 15 as NoData
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let mut ds = driver
+            .create_with_band_type::<f32, _>(&raster, 4, 4, 1)
+            .unwrap();
+        ds.set_geo_transform(&[0.0, 1.0, 0.0, 4.0, 0.0, -1.0])
+            .unwrap();
+        ds.set_spatial_ref(&SpatialRef::from_epsg(4326).unwrap())
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(15.0)).unwrap();
+        let mut buf = Buffer::new((4, 4), (0..16).map(|v| v as f32).collect());
+        band.write((0, 0), (4, 4), &mut buf).unwrap();
+    }
+    std::fs::write(
+        &zones,
+        r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {"name": "west"},
+             "geometry": {"type": "Polygon", "coordinates": [[[0, 0], [2, 0], [2, 4], [0, 4], [0, 0]]]}},
+            {"type": "Feature", "properties": {"name": "southeast"},
+             "geometry": {"type": "Polygon", "coordinates": [[[2, 0], [4, 0], [4, 2], [2, 2], [2, 0]]]}},
+            {"type": "Feature", "properties": {"name": "outside"},
+             "geometry": {"type": "Polygon", "coordinates": [[[10, 10], [11, 10], [11, 11], [10, 11], [10, 10]]]}}
+        ]}"#,
+    )
+    .unwrap();
+
+    let options = ZonalOptions {
+        id_field: Some("name".to_string()),
+        ..Default::default()
+    };
+    let stats = zonal_stats(&raster, &zones, &options).unwrap();
+    assert_eq!(stats.len(), 3);
+
+    assert_eq!(stats[0].id, "west");
+    assert_eq!(stats[0].count, 8);
+    assert_eq!(stats[0].mean, 6.5);
+    assert_eq!((stats[0].min, stats[0].max), (0.0, 13.0));
+
+    // The NoData pixel is left out
+    assert_eq!(stats[1].id, "southeast");
+    assert_eq!(stats[1].count, 3);
+    assert_eq!((stats[1].min, stats[1].max), (10.0, 14.0));
+
+    assert_eq!(stats[2].count, 0);
+    assert!(stats[2].mean.is_nan());
+
+    let path = write_zonal_stats(
+        &stats,
+        &options,
+        &dir.join("zonal.csv"),
+        OutputFormat::Parquet,
+    )
+    .unwrap();
+    assert_eq!(path, dir.join("zonal.parquet"));
+
+    let missing = ZonalOptions {
+        id_field: Some("iso3".to_string()),
+        ..Default::default()
+    };
+    let err = zonal_stats(&raster, &zones, &missing).unwrap_err();
+    assert!(err.to_string().contains("name"), "{}", err);
+}
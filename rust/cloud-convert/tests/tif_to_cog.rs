@@ -1,9 +1,1012 @@
-use cloud_convert::tif2cog::tif_to_cog;
+use cloud_convert::batch_convert::resolve_output;
+use cloud_convert::tif2cog::{
+    BigTiff, CogOptions, ColorInterp, Compression, OutDtype, OverviewResampling, Predictor,
+    WarpResampling, check_blocksize, estimated_cog_bytes, overview_count_for, parse_band_date,
+    parse_band_list, read_color_table, split_bands_to_cogs, tif_to_cog, validate_cog,
+};
+use gdal::raster::{Buffer, ColorInterpretation, GdalDataType};
+use gdal::spatial_ref::SpatialRef;
+use gdal::{Dataset, DriverManager, Metadata};
 use std::path::Path;
 
+mod common;
+use common::TestDir;
+
 #[test]
 fn test_tif_to_cog() {
     let input = Path::new("tests/data/test_input.tif");
     let output_path: Option<&Path> = None;
-    tif_to_cog(input, output_path, true).unwrap();
+    tif_to_cog(input, output_path, true, &CogOptions::default()).unwrap();
+}
+
+#[test]
+fn test_output_dir_keeps_existing_tif() {
+    let dir = TestDir::new("output_dir_exists");
+    let input = dir.join("in/scene.tiff");
+    std::fs::create_dir_all(input.parent().unwrap()).unwrap();
+    std::fs::copy("tests/data/test_input.tif", &input).unwrap();
+    let out_dir = dir.join("out");
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let existing = out_dir.join("scene.tif");
+    std::fs::write(&existing, "keep me").unwrap();
+
+    // --output-dir names the output after the input, scene.tiff, which is written as scene.tif
+    let out = resolve_output(&input, None, Some(out_dir), false).unwrap();
+    let err = tif_to_cog(&input, out.as_deref(), false, &CogOptions::default()).unwrap_err();
+    assert!(err.contains("already exists"), "{}", err);
+    assert_eq!(std::fs::read_to_string(&existing).unwrap(), "keep me");
+
+    tif_to_cog(&input, out.as_deref(), true, &CogOptions::default()).unwrap();
+    assert!(validate_cog(&existing, 512, None).is_ok());
+}
+
+#[test]
+fn test_tif_to_cog_overwrites_input_in_place() {
+    let dir = TestDir::new("overwrite_in_place");
+    let input = dir.join("scene.tif");
+    std::fs::copy("tests/data/test_input.tif", &input).unwrap();
+    let size = Dataset::open(&input).unwrap().raster_size();
+
+    // The COG is written under a temporary name, so the input is intact while it is read
+    tif_to_cog(&input, None, true, &CogOptions::default()).unwrap();
+    assert!(validate_cog(&input, 512, None).is_ok());
+    assert_eq!(Dataset::open(&input).unwrap().raster_size(), size);
+    assert!(!dir.join("scene.partial.tif").exists());
+}
+
+#[test]
+fn test_tif_to_cog_float32_to_byte() {
+    let dir = TestDir::new("out_dtype");
+    let input = dir.join("float_input.tif");
+    let output = dir.join("byte_output.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&input, 16, 16, 1)
+            .unwrap();
+        let data: Vec<f32> = (0..256).map(|v| v as f32 + 0.25).collect();
+        let mut buf = Buffer::new((16, 16), data);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (16, 16), &mut buf)
+            .unwrap();
+    }
+
+    let options = CogOptions {
+        out_dtype: Some(OutDtype::Byte),
+        clamp: true,
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(ds.rasterband(1).unwrap().band_type(), GdalDataType::UInt8);
+}
+
+#[test]
+fn test_out_dtype_range_check_sees_every_pixel() {
+    let dir = TestDir::new("out_dtype_range");
+    let input = dir.join("one_outlier.tif");
+    let output = dir.join("byte_output.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&input, 2048, 2048, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.fill(10.0, None).unwrap();
+        // A single pixel an approximate min/max is likely to skip
+        let mut buf = Buffer::new((1, 1), vec![300.0f32]);
+        band.write((1337, 777), (1, 1), &mut buf).unwrap();
+    }
+
+    let options = CogOptions {
+        out_dtype: Some(OutDtype::Byte),
+        ..Default::default()
+    };
+    let err = tif_to_cog(&input, Some(&output), true, &options).unwrap_err();
+    assert!(err.contains("[10, 300]"), "{}", err);
+    assert!(!output.exists());
+}
+
+#[test]
+fn test_overview_count_for() {
+    assert_eq!(overview_count_for((4096, 2048), 256), 3);
+    assert_eq!(overview_count_for((1000, 1000), 256), 1);
+    assert_eq!(overview_count_for((200, 300), 256), 0);
+}
+
+#[test]
+fn test_tif_to_cog_thumbnail() {
+    let input = Path::new("tests/data/test_input.tif");
+    let dir = TestDir::new("thumbnail");
+    let output = dir.join("thumb_cog.tif");
+
+    let options = CogOptions {
+        thumbnail: Some(64),
+        thumbnail_stretch: true,
+        ..Default::default()
+    };
+    tif_to_cog(input, Some(&output), true, &options).unwrap();
+
+    let png = Dataset::open(output.with_extension("png")).unwrap();
+    let (cols, rows) = png.raster_size();
+    assert!(
+        (62..=66).contains(&cols.max(rows)),
+        "Unexpected size {}x{}",
+        cols,
+        rows
+    );
+}
+
+#[test]
+fn test_tif_to_cog_reference_grid() {
+    let dir = TestDir::new("reference");
+    let input = dir.join("fine_input.tif");
+    let reference = dir.join("reference.tif");
+    let output = dir.join("aligned_cog.tif");
+    let wgs84 = SpatialRef::from_epsg(4326).unwrap();
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+
+    {
+        let mut ds = driver
+            .create_with_band_type::<f32, _>(&input, 40, 40, 1)
+            .unwrap();
+        ds.set_geo_transform(&[10.0, 0.25, 0.0, 20.0, 0.0, -0.25])
+            .unwrap();
+        ds.set_spatial_ref(&wgs84).unwrap();
+        let mut buf = Buffer::new((40, 40), vec![1.0f32; 1600]);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (40, 40), &mut buf)
+            .unwrap();
+    }
+    let ref_transform = [10.5, 0.5, 0.0, 19.5, 0.0, -0.5];
+    {
+        let mut ds = driver
+            .create_with_band_type::<f32, _>(&reference, 12, 10, 1)
+            .unwrap();
+        ds.set_geo_transform(&ref_transform).unwrap();
+        ds.set_spatial_ref(&wgs84).unwrap();
+    }
+
+    let options = CogOptions {
+        reference: Some(reference.clone()),
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(ds.raster_size(), (12, 10));
+    assert_eq!(ds.geo_transform().unwrap(), ref_transform);
+}
+
+#[test]
+fn test_reference_grid_resampling() {
+    let dir = TestDir::new("warp_resampling");
+    let input = dir.join("stripes.tif");
+    let reference = dir.join("shifted.tif");
+    let wgs84 = SpatialRef::from_epsg(4326).unwrap();
+    let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+
+    {
+        let mut ds = driver
+            .create_with_band_type::<f32, _>(&input, 4, 1, 1)
+            .unwrap();
+        ds.set_geo_transform(&[0.0, 1.0, 0.0, 1.0, 0.0, -1.0])
+            .unwrap();
+        ds.set_spatial_ref(&wgs84).unwrap();
+        let mut buf = Buffer::new((4, 1), vec![0.0f32, 10.0, 0.0, 10.0]);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (4, 1), &mut buf)
+            .unwrap();
+    }
+    // Reference pixel centres fall on the edges between input pixels
+    {
+        let mut ds = driver
+            .create_with_band_type::<f32, _>(&reference, 3, 1, 1)
+            .unwrap();
+        ds.set_geo_transform(&[0.5, 1.0, 0.0, 1.0, 0.0, -1.0])
+            .unwrap();
+        ds.set_spatial_ref(&wgs84).unwrap();
+    }
+
+    let warp = |resampling: Option<WarpResampling>, name: &str| -> Vec<f32> {
+        let output = dir.join(name);
+        let options = CogOptions {
+            reference: Some(reference.clone()),
+            resampling,
+            ..Default::default()
+        };
+        tif_to_cog(&input, Some(&output), true, &options).unwrap();
+        let ds = Dataset::open(&output).unwrap();
+        ds.rasterband(1)
+            .unwrap()
+            .read_band_as::<f32>()
+            .unwrap()
+            .data()
+            .to_vec()
+    };
+
+    // Float data is blended by default
+    assert_eq!(warp(None, "default.tif"), vec![5.0, 5.0, 5.0]);
+    let nearest = warp(Some(WarpResampling::Nearest), "nearest.tif");
+    assert!(
+        nearest.iter().all(|&v| v == 0.0 || v == 10.0),
+        "{:?}",
+        nearest
+    );
+
+    // The warp intermediate is removed
+    let leftovers: Vec<_> = std::fs::read_dir(&*dir)
+        .unwrap()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name().to_string_lossy().contains(".warp."))
+        .collect();
+    assert!(leftovers.is_empty());
+
+    let options = CogOptions {
+        resampling: Some(WarpResampling::Bilinear),
+        ..Default::default()
+    };
+    let err = tif_to_cog(&input, Some(&dir.join("unwarped.tif")), true, &options).unwrap_err();
+    assert!(err.contains("--resampling"), "{}", err);
+}
+
+#[test]
+fn test_split_bands_by_date() {
+    let dir = TestDir::new("split_dates");
+    let input = dir.join("series.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&input, 8, 8, 2)
+            .unwrap();
+        for (index, date) in [(1, "2021-03-01"), (2, "20210401")] {
+            let mut band = ds.rasterband(index).unwrap();
+            let mut buf = Buffer::new((8, 8), vec![index as f32; 64]);
+            band.write((0, 0), (8, 8), &mut buf).unwrap();
+            band.set_metadata_item("DATE", date, "").unwrap();
+        }
+    }
+
+    let out_dir = dir.join("out");
+    let outputs = split_bands_to_cogs(
+        &input,
+        Some(&out_dir),
+        true,
+        &CogOptions::default(),
+        Some("DATE"),
+    )
+    .unwrap();
+    assert_eq!(
+        outputs,
+        vec![
+            out_dir.join("series_2021-03-01.tif"),
+            out_dir.join("series_2021-04-01.tif")
+        ]
+    );
+    let ds = Dataset::open(&outputs[1]).unwrap();
+    assert_eq!(ds.raster_count(), 1);
+
+    let missing = split_bands_to_cogs(
+        &input,
+        Some(&out_dir),
+        true,
+        &CogOptions::default(),
+        Some("NETCDF_DIM_time"),
+    );
+    assert!(missing.is_err());
+}
+
+#[test]
+fn test_parse_band_date() {
+    assert_eq!(
+        parse_band_date("2021-03-01T00:00:00", None).unwrap(),
+        "2021-03-01"
+    );
+    assert_eq!(
+        parse_band_date("59", Some("days since 2021-01-01")).unwrap(),
+        "2021-03-01"
+    );
+    assert!(parse_band_date("2021-02-30", None).is_err());
+    assert!(parse_band_date("42", None).is_err());
+    // Multi-byte characters where the digits should be are an error, not a panic
+    for bad in ["2021-0é-01", "2021-01-0é"] {
+        assert!(parse_band_date(bad, None).is_err(), "{}", bad);
+    }
+    assert!(parse_band_date("3", Some("days since 2021-01-0é")).is_err());
+}
+
+#[test]
+fn test_tif_to_cog_internal_mask() {
+    let dir = TestDir::new("internal_mask");
+    let input = dir.join("nodata_input.tif");
+    let output = dir.join("masked_cog.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&input, 16, 16, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(-9999.0)).unwrap();
+        let mut data = vec![1.0f32; 256];
+        data[0] = -9999.0;
+        let mut buf = Buffer::new((16, 16), data);
+        band.write((0, 0), (16, 16), &mut buf).unwrap();
+    }
+
+    let options = CogOptions {
+        internal_mask: true,
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    let band = ds.rasterband(1).unwrap();
+    assert!(band.mask_flags().unwrap().is_per_dataset());
+    assert_eq!(band.no_data_value(), None);
+    let mask: Buffer<u8> = band
+        .open_mask_band()
+        .unwrap()
+        .read_as((0, 0), (2, 1), (2, 1), None)
+        .unwrap();
+    assert_eq!(mask.data(), &[0, 255]);
+}
+
+#[test]
+fn test_compression_level_range() {
+    assert!(Compression::Lzw.creation_options(Some(5)).is_err());
+    assert!(Compression::Zstd.creation_options(Some(23)).is_err());
+    assert!(Compression::Webp.creation_options(Some(0)).is_err());
+
+    let (opts, level) = Compression::Deflate.creation_options(None).unwrap();
+    assert_eq!(opts, vec!["COMPRESS=DEFLATE", "LEVEL=6"]);
+    assert_eq!(level, Some(6));
+}
+
+#[test]
+fn test_tif_to_cog_compression_levels() {
+    let dir = TestDir::new("compression_level");
+    let input = dir.join("compressible.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<u16, _>(&input, 512, 512, 1)
+            .unwrap();
+        let data: Vec<u16> = (0..512 * 512)
+            .map(|i| ((i % 512) * (i / 512) % 1009) as u16)
+            .collect();
+        let mut buf = Buffer::new((512, 512), data);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (512, 512), &mut buf)
+            .unwrap();
+    }
+
+    let mut sizes = vec![];
+    for level in [1, 19] {
+        let output = dir.join(format!("zstd_{}.tif", level));
+        let options = CogOptions {
+            compression: Compression::Zstd,
+            compression_level: Some(level),
+            ..Default::default()
+        };
+        tif_to_cog(&input, Some(&output), true, &options).unwrap();
+
+        let ds = Dataset::open(&output).unwrap();
+        assert_eq!(
+            ds.metadata_item("COMPRESSION", "IMAGE_STRUCTURE")
+                .as_deref(),
+            Some("ZSTD")
+        );
+        sizes.push(std::fs::metadata(&output).unwrap().len());
+    }
+    assert_ne!(sizes[0], sizes[1]);
+}
+
+#[test]
+fn test_tif_to_cog_color_table_force_rgb() {
+    let dir = TestDir::new("force_rgb");
+    let input = dir.join("classes.tif");
+    let colors = dir.join("classes.txt");
+    std::fs::write(
+        &colors,
+        "# class R G B\n1 255 0 0\n2, 0, 255, 0\n3 0 0 255 128\n",
+    )
+    .unwrap();
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<u8, _>(&input, 2, 2, 1)
+            .unwrap();
+        let mut buf = Buffer::new((2, 2), vec![1u8, 2, 3, 9]);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (2, 2), &mut buf)
+            .unwrap();
+    }
+
+    let table = read_color_table(&colors).unwrap();
+    assert_eq!(table[&3], [0, 0, 255, 128]);
+
+    let rgb_out = dir.join("classes_rgb.tif");
+    let options = CogOptions {
+        color_table: Some(colors.clone()),
+        force_rgb: true,
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&rgb_out), true, &options).unwrap();
+
+    let ds = Dataset::open(&rgb_out).unwrap();
+    assert_eq!(ds.raster_count(), 3);
+    let pixels: Vec<Vec<u8>> = (1..=3)
+        .map(|b| {
+            ds.rasterband(b)
+                .unwrap()
+                .read_band_as::<u8>()
+                .unwrap()
+                .data()
+                .to_vec()
+        })
+        .collect();
+    let pixel = |i: usize| [pixels[0][i], pixels[1][i], pixels[2][i]];
+    assert_eq!(pixel(0), [255, 0, 0]);
+    assert_eq!(pixel(1), [0, 255, 0]);
+    assert_eq!(pixel(2), [0, 0, 255]);
+    // Values missing from the table are black
+    assert_eq!(pixel(3), [0, 0, 0]);
+
+    // NoData is carried into an alpha band
+    let nodata_input = dir.join("classes_nodata.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<u8, _>(&nodata_input, 2, 2, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(0.0)).unwrap();
+        let mut buf = Buffer::new((2, 2), vec![1u8, 0, 3, 9]);
+        band.write((0, 0), (2, 2), &mut buf).unwrap();
+    }
+    let rgba_out = dir.join("classes_rgba.tif");
+    tif_to_cog(&nodata_input, Some(&rgba_out), true, &options).unwrap();
+    let ds = Dataset::open(&rgba_out).unwrap();
+    assert_eq!(ds.raster_count(), 4);
+    let alpha = ds.rasterband(4).unwrap();
+    assert_eq!(alpha.color_interpretation(), ColorInterpretation::AlphaBand);
+    let alpha = alpha.read_band_as::<u8>().unwrap().data().to_vec();
+    assert_eq!(alpha, vec![255, 0, 128, 255]);
+
+    let paletted_out = dir.join("classes_paletted.tif");
+    let options = CogOptions {
+        color_table: Some(colors),
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&paletted_out), true, &options).unwrap();
+    let ds = Dataset::open(&paletted_out).unwrap();
+    assert_eq!(ds.raster_count(), 1);
+    let entry = ds
+        .rasterband(1)
+        .unwrap()
+        .color_table()
+        .unwrap()
+        .entry_as_rgb(2)
+        .unwrap();
+    assert_eq!((entry.r, entry.g, entry.b), (0, 255, 0));
+}
+
+#[test]
+fn test_tif_to_cog_sparse() {
+    let dir = TestDir::new("sparse");
+    let input = dir.join("mostly_nodata.tif");
+    let dense_out = dir.join("dense.tif");
+    let sparse_out = dir.join("sparse.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&input, 2048, 2048, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(-9999.0)).unwrap();
+        band.fill(-9999.0, None).unwrap();
+        // A small valid patch in one corner, so most 512x512 blocks are empty
+        let patch: Vec<f32> = (0..64 * 64).map(|v| (v % 97) as f32 * 0.5).collect();
+        let mut buf = Buffer::new((64, 64), patch);
+        band.write((100, 100), (64, 64), &mut buf).unwrap();
+    }
+
+    tif_to_cog(&input, Some(&dense_out), true, &CogOptions::default()).unwrap();
+    let options = CogOptions {
+        sparse: true,
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&sparse_out), true, &options).unwrap();
+
+    let dense_size = std::fs::metadata(&dense_out).unwrap().len();
+    let sparse_size = std::fs::metadata(&sparse_out).unwrap().len();
+    assert!(
+        sparse_size < dense_size,
+        "sparse {} bytes, dense {} bytes",
+        sparse_size,
+        dense_size
+    );
+
+    let read = |path: &Path| {
+        let ds = Dataset::open(path).unwrap();
+        let band = ds.rasterband(1).unwrap();
+        assert_eq!(band.no_data_value(), Some(-9999.0));
+        band.read_band_as::<f32>().unwrap().data().to_vec()
+    };
+    assert_eq!(read(&dense_out), read(&sparse_out));
+}
+
+#[test]
+fn test_tif_to_cog_color_interp() {
+    let dir = TestDir::new("color_interp");
+    let input = dir.join("composite.tif");
+    let output = dir.join("composite_cog.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let options = gdal::raster::RasterCreationOptions::from_iter(["PHOTOMETRIC=MINISBLACK"]);
+        driver
+            .create_with_band_type_with_options::<u8, _>(&input, 8, 8, 4, &options)
+            .unwrap();
+    }
+
+    let interps: Vec<ColorInterp> = ["red", "green", "blue", "alpha"]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+    let options = CogOptions {
+        color_interp: Some(interps),
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    let read_back: Vec<ColorInterpretation> = (1..=4)
+        .map(|b| ds.rasterband(b).unwrap().color_interpretation())
+        .collect();
+    assert_eq!(
+        read_back,
+        vec![
+            ColorInterpretation::RedBand,
+            ColorInterpretation::GreenBand,
+            ColorInterpretation::BlueBand,
+            ColorInterpretation::AlphaBand,
+        ]
+    );
+
+    // The count must match the band count
+    let options = CogOptions {
+        color_interp: Some(vec![
+            ColorInterp::Red,
+            ColorInterp::Green,
+            ColorInterp::Blue,
+        ]),
+        ..Default::default()
+    };
+    let err = tif_to_cog(&input, Some(&output), true, &options).unwrap_err();
+    assert!(err.contains("4 bands"), "{}", err);
+    assert!("purple".parse::<ColorInterp>().is_err());
+}
+
+#[test]
+fn test_compression_codecs_and_predictor() {
+    assert_eq!("lerc".parse::<Compression>(), Ok(Compression::Lerc));
+    assert_eq!("NONE".parse::<Compression>(), Ok(Compression::None));
+    let err = "brotli".parse::<Compression>().unwrap_err();
+    assert!(err.contains("Unsupported compression 'brotli'"), "{}", err);
+    assert!(Compression::Lerc.creation_options(Some(3)).is_err());
+
+    assert_eq!("3".parse::<Predictor>(), Ok(Predictor::FloatingPoint));
+    assert_eq!(
+        Compression::Zstd.predictor_option(Predictor::Standard),
+        Ok("PREDICTOR=STANDARD".to_string())
+    );
+    assert!(
+        Compression::Webp
+            .predictor_option(Predictor::Standard)
+            .is_err()
+    );
+
+    let dir = TestDir::new("predictor");
+    let input = dir.join("elevation.tif");
+    let output = dir.join("elevation_cog.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&input, 64, 64, 1)
+            .unwrap();
+        let data: Vec<f32> = (0..64 * 64)
+            .map(|i| 1000.0 + (i % 64) as f32 * 0.25 + (i / 64) as f32 * 0.5)
+            .collect();
+        let mut buf = Buffer::new((64, 64), data);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (64, 64), &mut buf)
+            .unwrap();
+    }
+
+    let options = CogOptions {
+        compression: Compression::Zstd,
+        predictor: Some(Predictor::FloatingPoint),
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(
+        ds.metadata_item("COMPRESSION", "IMAGE_STRUCTURE")
+            .as_deref(),
+        Some("ZSTD")
+    );
+    assert_eq!(
+        ds.metadata_item("PREDICTOR", "IMAGE_STRUCTURE").as_deref(),
+        Some("3")
+    );
+
+    // The floating-point predictor is rejected for integer output
+    let options = CogOptions {
+        out_dtype: Some(OutDtype::Int16),
+        clamp: true,
+        ..options
+    };
+    let err = tif_to_cog(&input, Some(&output), true, &options).unwrap_err();
+    assert!(err.contains("Float32 or Float64"), "{}", err);
+}
+
+#[test]
+fn test_tif_to_cog_overview_resampling() {
+    let dir = TestDir::new("overview_resampling");
+    let input = dir.join("stripes.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<u8, _>(&input, 1024, 1024, 1)
+            .unwrap();
+        // Alternating class 0 and class 10 columns
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 2) as u8 * 10).collect();
+        let mut buf = Buffer::new((1024, 1024), data);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (1024, 1024), &mut buf)
+            .unwrap();
+    }
+
+    let overview_values = |resampling: &str| {
+        let output = dir.join(format!("stripes_{}.tif", resampling));
+        let options = CogOptions {
+            overview_resampling: Some(resampling.parse().unwrap()),
+            ..Default::default()
+        };
+        tif_to_cog(&input, Some(&output), true, &options).unwrap();
+        let ds = Dataset::open(&output).unwrap();
+        let mut values = ds
+            .rasterband(1)
+            .unwrap()
+            .overview(0)
+            .unwrap()
+            .read_band_as::<u8>()
+            .unwrap()
+            .data()
+            .to_vec();
+        values.sort();
+        values.dedup();
+        values
+    };
+
+    // Mode keeps overview pixels within the original classes; average blends them
+    assert!(overview_values("mode").iter().all(|v| *v == 0 || *v == 10));
+    assert_eq!(overview_values("average"), vec![5]);
+    assert!("lanczos".parse::<OverviewResampling>().is_err());
+}
+
+#[test]
+fn test_tif_to_cog_blocksize() {
+    assert!(check_blocksize(256).is_ok());
+    assert!(check_blocksize(0).is_err());
+    assert!(check_blocksize(100).is_err());
+
+    let input = Path::new("tests/data/test_input.tif");
+    let dir = TestDir::new("blocksize");
+    let output = dir.join("tiles_256.tif");
+
+    let options = CogOptions {
+        blocksize: Some(256),
+        ..Default::default()
+    };
+    tif_to_cog(input, Some(&output), true, &options).unwrap();
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(ds.rasterband(1).unwrap().block_size(), (256, 256));
+
+    // Rejected before the input is opened, so a missing output directory is never reached
+    let options = CogOptions {
+        blocksize: Some(500),
+        ..Default::default()
+    };
+    let err = tif_to_cog(input, Some(&dir.join("missing/out.tif")), true, &options).unwrap_err();
+    assert!(err.contains("multiple of 16"), "{}", err);
+}
+
+#[test]
+fn test_tif_to_cog_verify() {
+    let dir = TestDir::new("verify_cog");
+    let input = dir.join("striped.tif");
+    let output = dir.join("verified.tif");
+
+    // A plain striped GeoTIFF larger than one tile
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<u8, _>(&input, 1024, 1024, 1)
+            .unwrap();
+        let data: Vec<u8> = (0..1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let mut buf = Buffer::new((1024, 1024), data);
+        ds.rasterband(1)
+            .unwrap()
+            .write((0, 0), (1024, 1024), &mut buf)
+            .unwrap();
+    }
+    let err = validate_cog(&input, 512, None).unwrap_err();
+    assert!(err.contains("not a valid COG"), "{}", err);
+
+    let options = CogOptions {
+        blocksize: Some(256),
+        verify: true,
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+    validate_cog(&output, 256, None).unwrap();
+    // Tiles of another size are reported
+    let err = validate_cog(&output, 512, None).unwrap_err();
+    assert!(err.contains("256x256"), "{}", err);
+}
+
+#[test]
+fn test_tif_to_cog_verify_without_overviews() {
+    let dir = TestDir::new("verify_no_overviews");
+    let input = dir.join("small.tif");
+    let output = dir.join("small_cog.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        driver
+            .create_with_band_type::<u8, _>(&input, 1024, 1024, 1)
+            .unwrap();
+    }
+
+    // Larger than one tile, but halving once would drop below 1024 pixels
+    let options = CogOptions {
+        min_overview_size: Some(1024),
+        verify: true,
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(ds.rasterband(1).unwrap().overview_count().unwrap(), 0);
+    drop(ds);
+    validate_cog(&output, 512, Some(1024)).unwrap();
+    let err = validate_cog(&output, 512, None).unwrap_err();
+    assert!(err.contains("no overviews"), "{}", err);
+}
+
+#[test]
+fn test_parse_band_list() {
+    assert_eq!(parse_band_list("1-3,50").unwrap(), vec![1, 2, 3, 50]);
+    assert_eq!(parse_band_list(" 4 , 2 ").unwrap(), vec![4, 2]);
+    for bad in ["0", "3-1", "a", "1,,2", "1-"] {
+        assert!(parse_band_list(bad).is_err(), "{}", bad);
+    }
+    let err = parse_band_list("1-3,2").unwrap_err();
+    assert!(err.contains("more than once"), "{}", err);
+}
+
+#[test]
+fn test_tif_to_cog_band_selection() {
+    let dir = TestDir::new("cog_bands");
+    let input = dir.join("stack.tif");
+    let output = dir.join("selected.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<u8, _>(&input, 8, 8, 5)
+            .unwrap();
+        for i in 1..=5 {
+            let mut buf = Buffer::new((8, 8), vec![i as u8; 64]);
+            ds.rasterband(i)
+                .unwrap()
+                .write((0, 0), (8, 8), &mut buf)
+                .unwrap();
+        }
+    }
+
+    let options = CogOptions {
+        bands: Some(vec![4, 2]),
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(ds.raster_count(), 2);
+    let first = ds.rasterband(1).unwrap().read_band_as::<u8>().unwrap();
+    assert_eq!(first.data()[0], 4);
+
+    let options = CogOptions {
+        bands: Some(vec![6]),
+        ..Default::default()
+    };
+    let err = tif_to_cog(&input, Some(&output), true, &options).unwrap_err();
+    assert!(err.contains("has 5 bands"), "{}", err);
+}
+
+#[test]
+fn test_tif_to_cog_keeps_band_labels() {
+    let dir = TestDir::new("band_labels");
+    let input = dir.join("rgb.tif");
+    let output = dir.join("rgb_cog.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let options = gdal::raster::RasterCreationOptions::from_iter(["PHOTOMETRIC=RGB"]);
+        let mut ds = driver
+            .create_with_band_type_with_options::<u8, _>(&input, 8, 8, 3, &options)
+            .unwrap();
+        ds.set_geo_transform(&[36.0, 0.01, 0.0, -1.0, 0.0, -0.01])
+            .unwrap();
+        ds.set_spatial_ref(&SpatialRef::from_epsg(4326).unwrap())
+            .unwrap();
+        ds.set_metadata_item("SOURCE", "sentinel-2", "").unwrap();
+        for (index, name) in ["red", "green", "blue"].iter().enumerate() {
+            ds.rasterband(index + 1)
+                .unwrap()
+                .set_description(name)
+                .unwrap();
+        }
+    }
+
+    // Warping and the data type VRT both rebuild the bands
+    let options = CogOptions {
+        t_srs: Some("EPSG:3857".to_string()),
+        out_dtype: Some(OutDtype::UInt16),
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(
+        ds.metadata_item("SOURCE", "").as_deref(),
+        Some("sentinel-2")
+    );
+    let labels: Vec<(String, ColorInterpretation)> = (1..=3)
+        .map(|b| {
+            let band = ds.rasterband(b).unwrap();
+            (band.description().unwrap(), band.color_interpretation())
+        })
+        .collect();
+    assert_eq!(
+        labels,
+        vec![
+            ("red".to_string(), ColorInterpretation::RedBand),
+            ("green".to_string(), ColorInterpretation::GreenBand),
+            ("blue".to_string(), ColorInterpretation::BlueBand),
+        ]
+    );
+}
+
+#[test]
+fn test_tif_to_cog_nodata_set_and_cleared() {
+    let dir = TestDir::new("nodata_flag");
+    let input = dir.join("grid.tif");
+    let output = dir.join("grid_cog.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&input, 8, 8, 2)
+            .unwrap();
+        for b in 1..=2 {
+            let mut band = ds.rasterband(b).unwrap();
+            band.set_no_data_value(Some(-9999.0)).unwrap();
+            let mut data: Vec<f32> = (0..64).map(|v| v as f32).collect();
+            data[0] = -9999.0;
+            data[63] = -9999.0;
+            let mut buf = Buffer::new((8, 8), data);
+            band.write((0, 0), (8, 8), &mut buf).unwrap();
+        }
+    }
+    let nodata_of = |path: &Path| -> Vec<Option<f64>> {
+        let ds = Dataset::open(path).unwrap();
+        (1..=2)
+            .map(|b| ds.rasterband(b).unwrap().no_data_value())
+            .collect()
+    };
+
+    let options = CogOptions {
+        clear_nodata: true,
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+    assert_eq!(nodata_of(&output), vec![None, None]);
+    // The former NoData pixels keep their value instead of being filled
+    let ds = Dataset::open(&output).unwrap();
+    for b in 1..=2 {
+        let data = ds.rasterband(b).unwrap().read_band_as::<f32>().unwrap();
+        let data = data.data();
+        assert_eq!((data[0], data[1], data[63]), (-9999.0, 1.0, -9999.0));
+    }
+
+    let options = CogOptions {
+        nodata: Some(0.0),
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+    assert_eq!(nodata_of(&output), vec![Some(0.0), Some(0.0)]);
+
+    let options = CogOptions {
+        nodata: Some(0.0),
+        clear_nodata: true,
+        ..Default::default()
+    };
+    assert!(tif_to_cog(&input, Some(&output), true, &options).is_err());
+}
+
+#[test]
+fn test_tif_to_cog_creation_option_passthrough() {
+    let input = Path::new("tests/data/test_input.tif");
+    let dir = TestDir::new("co_passthrough");
+    let output = dir.join("co_passthrough.tif");
+
+    // --co overrides the codec chosen by --compression
+    let options = CogOptions {
+        creation_options: vec![("COMPRESS".to_string(), "DEFLATE".to_string())],
+        ..Default::default()
+    };
+    tif_to_cog(input, Some(&output), true, &options).unwrap();
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(
+        ds.metadata_item("COMPRESSION", "IMAGE_STRUCTURE")
+            .as_deref(),
+        Some("DEFLATE")
+    );
+}
+
+#[test]
+fn test_tif_to_cog_bigtiff() {
+    let dir = TestDir::new("bigtiff");
+    let input = dir.join("small.tif");
+    let output = dir.join("small_cog.tif");
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        driver
+            .create_with_band_type::<u16, _>(&input, 300, 150, 2)
+            .unwrap();
+    }
+    // 300 x 150 pixels x 2 bands x 2 bytes, plus a third for overviews
+    assert_eq!(
+        estimated_cog_bytes(&Dataset::open(&input).unwrap()),
+        240_000
+    );
+
+    // TIFF byte 2 is 42 for classic TIFF and 43 for BigTIFF
+    let version = |path: &Path| std::fs::read(path).unwrap()[2];
+    tif_to_cog(&input, Some(&output), true, &CogOptions::default()).unwrap();
+    assert_eq!(version(&output), 42);
+    let options = CogOptions {
+        bigtiff: BigTiff::Yes,
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+    assert_eq!(version(&output), 43);
+
+    assert_eq!("if-needed".parse::<BigTiff>().unwrap(), BigTiff::IfNeeded);
+    assert!("maybe".parse::<BigTiff>().is_err());
 }
@@ -0,0 +1,68 @@
+use cloud_convert::tif2cog::{CogOptions, tif_to_cog};
+use cloud_convert::units::UnitConversion;
+use gdal::raster::Buffer;
+use gdal::{Dataset, DriverManager, Metadata};
+
+mod common;
+use common::TestDir;
+
+#[test]
+fn test_kelvin_to_celsius() {
+    let conversion: UnitConversion = "K:degC".parse().unwrap();
+    assert_eq!(conversion.from, "K");
+    assert_eq!(conversion.to, "degC");
+    assert!((conversion.apply(273.15) - 0.0).abs() < 1e-9);
+    assert!((conversion.apply(300.0) - 26.85).abs() < 1e-9);
+
+    let conversion = UnitConversion::new("kelvin", "degF").unwrap();
+    assert!((conversion.apply(273.15) - 32.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_metres_to_feet() {
+    let conversion: UnitConversion = "m:ft".parse().unwrap();
+    assert!((conversion.apply(0.3048) - 1.0).abs() < 1e-12);
+    assert!((conversion.apply(1000.0) - 3_280.839_895).abs() < 1e-6);
+}
+
+#[test]
+fn test_unsupported_unit_pairs() {
+    let err = UnitConversion::new("K", "m").unwrap_err();
+    assert!(err.contains("temperature"), "{}", err);
+    assert!(UnitConversion::new("furlong", "m").is_err());
+    assert!("K-degC".parse::<UnitConversion>().is_err());
+}
+
+#[test]
+fn test_tif_to_cog_convert_units() {
+    let dir = TestDir::new("units");
+    let input = dir.join("kelvin.tif");
+    let output = dir.join("celsius.tif");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GTiff").unwrap();
+        let ds = driver
+            .create_with_band_type::<f32, _>(&input, 2, 2, 1)
+            .unwrap();
+        let mut band = ds.rasterband(1).unwrap();
+        band.set_no_data_value(Some(-9999.0)).unwrap();
+        let mut buf = Buffer::new((2, 2), vec![273.15f32, 300.0, 250.0, -9999.0]);
+        band.write((0, 0), (2, 2), &mut buf).unwrap();
+    }
+
+    let options = CogOptions {
+        convert_units: Some("K:degC".parse().unwrap()),
+        ..Default::default()
+    };
+    tif_to_cog(&input, Some(&output), true, &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    let band = ds.rasterband(1).unwrap();
+    assert_eq!(band.metadata_item("units", "").as_deref(), Some("degC"));
+    let data = band.read_band_as::<f32>().unwrap().data().to_vec();
+    assert!(data[0].abs() < 1e-3);
+    assert!((data[1] - 26.85).abs() < 1e-3);
+    assert!((data[2] + 23.15).abs() < 1e-3);
+    // NoData passes through unchanged
+    assert_eq!(data[3], -9999.0);
+}
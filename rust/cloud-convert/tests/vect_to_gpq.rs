@@ -1,9 +1,797 @@
-use cloud_convert::vect2gpq::vector_to_geoparquet;
+use cloud_convert::vect2gpq::{
+    GeoEncoding, GeometryKind, GpqOptions, ParquetCompression, VectorFormat, explode_to_geoparquet,
+    is_stdout, parse_bbox, vector_convert,
+};
+use gdal::spatial_ref::SpatialRef;
+use gdal::vector::{Geometry, LayerAccess, LayerOptions, OGRwkbGeometryType};
+use gdal::{Dataset, DriverManager, Metadata};
+
+mod common;
+use common::TestDir;
 
 #[test]
-fn test_vector_to_geoparquet() {
+fn test_vector_convert() {
     let input_path = std::path::Path::new("tests/data/test_input.gpkg");
     let output_path = std::path::Path::new("tests/data/test_output.parquet");
+    let options = GpqOptions {
+        overwrite: true,
+        ..Default::default()
+    };
+
+    vector_convert(input_path, Some(output_path), &options).unwrap();
+}
+
+#[test]
+fn test_existing_output_needs_overwrite() {
+    let input_path = std::path::Path::new("tests/data/test_input.gpkg");
+    let dir = TestDir::new("overwrite");
+    let output_path = dir.join("overwrite.parquet");
+    std::fs::write(&output_path, "previous conversion").unwrap();
+
+    let err = vector_convert(input_path, Some(&output_path), &GpqOptions::default()).unwrap_err();
+    assert!(err.contains("already exists"), "{}", err);
+    assert_eq!(
+        std::fs::read_to_string(&output_path).unwrap(),
+        "previous conversion"
+    );
+
+    let options = GpqOptions {
+        overwrite: true,
+        ..Default::default()
+    };
+    vector_convert(input_path, Some(&output_path), &options).unwrap();
+    assert!(Dataset::open(&output_path).is_ok());
+}
+
+#[test]
+fn test_vector_convert_verify() {
+    let input_path = std::path::Path::new("tests/data/test_input.gpkg");
+    let dir = TestDir::new("verify");
+    let output_path = dir.join("verify.parquet");
+
+    let options = GpqOptions {
+        verify: true,
+        overwrite: true,
+        ..Default::default()
+    };
+    vector_convert(input_path, Some(&output_path), &options).unwrap();
+}
+
+#[test]
+fn test_explode_to_geoparquet() {
+    let input_path = std::path::Path::new("tests/data/test_input.gpkg");
+    let dir = TestDir::new("explode");
+    let out_dir = dir.join("out");
+
+    explode_to_geoparquet(input_path, Some(&out_dir), &GpqOptions::default()).unwrap();
+
+    assert!(out_dir.join("manifest.json").exists());
+    let parquet_count = std::fs::read_dir(&out_dir)
+        .unwrap()
+        .filter_map(Result::ok)
+        .filter(|e| e.path().extension().is_some_and(|ext| ext == "parquet"))
+        .count();
+    assert!(parquet_count >= 1, "No parquet files were written");
+}
+
+#[test]
+fn test_geometry_type_filter() {
+    let dir = TestDir::new("geometry_filter");
+    let input = dir.join("mixed.gpkg");
+    let output = dir.join("polygons.parquet");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+        let mut ds = driver.create_vector_only(&input).unwrap();
+        let mut layer = ds
+            .create_layer(LayerOptions {
+                name: "mixed",
+                ty: OGRwkbGeometryType::wkbUnknown,
+                ..Default::default()
+            })
+            .unwrap();
+        for wkt in [
+            "POINT (1 1)",
+            "LINESTRING (0 0, 1 1)",
+            "POLYGON ((0 0, 1 0, 1 1, 0 0))",
+            "MULTIPOLYGON (((2 2, 3 2, 3 3, 2 2)))",
+        ] {
+            layer
+                .create_feature(Geometry::from_wkt(wkt).unwrap())
+                .unwrap();
+        }
+    }
+
+    let options = GpqOptions {
+        geometry_type: Some(GeometryKind::Polygon),
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(ds.layer(0).unwrap().feature_count(), 2);
+}
+
+#[test]
+fn test_s_srs_t_srs_reprojection() {
+    let dir = TestDir::new("s_srs");
+    let input = dir.join("no_crs.gpkg");
+    let output = dir.join("reprojected.parquet");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+        let mut ds = driver.create_vector_only(&input).unwrap();
+        let mut layer = ds
+            .create_layer(LayerOptions {
+                name: "no_crs",
+                ty: OGRwkbGeometryType::wkbPoint,
+                ..Default::default()
+            })
+            .unwrap();
+        layer
+            .create_feature(Geometry::from_wkt("POINT (500000 0)").unwrap())
+            .unwrap();
+    }
+
+    // Reprojecting needs a source CRS, which this input lacks
+    let options = GpqOptions {
+        t_srs: Some("EPSG:4326".to_string()),
+        ..Default::default()
+    };
+    let err = vector_convert(&input, Some(&output), &options).unwrap_err();
+    assert!(err.contains("no CRS to reproject"), "{}", err);
+
+    let options = GpqOptions {
+        s_srs: Some("EPSG:32637".to_string()),
+        t_srs: Some("EPSG:4326".to_string()),
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    let mut layer = ds.layer(0).unwrap();
+    assert_eq!(layer.spatial_ref().unwrap().auth_code().unwrap(), 4326);
+    let feature = layer.features().next().unwrap();
+    let (x, y, _) = feature.geometry().unwrap().get_point(0);
+    assert!((x - 39.0).abs() < 1e-6, "Unexpected longitude {}", x);
+    assert!(y.abs() < 1e-6, "Unexpected latitude {}", y);
+}
+
+#[test]
+fn test_normalize_longitude_antimeridian() {
+    let dir = TestDir::new("antimeridian");
+    let input = dir.join("pacific.gpkg");
+    let output = dir.join("pacific.parquet");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+        let mut ds = driver.create_vector_only(&input).unwrap();
+        let wgs84 = gdal::spatial_ref::SpatialRef::from_epsg(4326).unwrap();
+        let mut layer = ds
+            .create_layer(LayerOptions {
+                name: "pacific",
+                srs: Some(&wgs84),
+                ty: OGRwkbGeometryType::wkbUnknown,
+                ..Default::default()
+            })
+            .unwrap();
+        for wkt in [
+            // Straddles the antimeridian, spanning -179..179 when read naively
+            "POLYGON ((179 -1, -179 -1, -179 1, 179 1, 179 -1))",
+            // 0-360 longitude
+            "POINT (200 10)",
+        ] {
+            layer
+                .create_feature(Geometry::from_wkt(wkt).unwrap())
+                .unwrap();
+        }
+    }
+
+    let options = GpqOptions {
+        normalize_longitude: true,
+        split_antimeridian: true,
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    let mut layer = ds.layer(0).unwrap();
+    let geometries: Vec<Geometry> = layer
+        .features()
+        .map(|f| f.geometry().unwrap().clone())
+        .collect();
+
+    // Each part of the split polygon is narrow and inside -180..180
+    let polygon = &geometries[0];
+    assert_eq!(polygon.geometry_count(), 2);
+    for i in 0..polygon.geometry_count() {
+        let env = polygon.get_geometry(i).envelope();
+        assert!(env.MinX >= -180.0 && env.MaxX <= 180.0);
+        assert!(env.MaxX - env.MinX <= 1.0 + 1e-9, "Part {} too wide", i);
+    }
+    assert_eq!(geometries[1].get_point(0).0, -160.0);
+}
+
+#[test]
+fn test_csv_points_to_geoparquet() {
+    let dir = TestDir::new("csv_points");
+    let input = dir.join("sites.csv");
+    let output = dir.join("sites.parquet");
+    std::fs::write(
+        &input,
+        "site,longitude,latitude\nA,36.8,-1.3\nB,38.7,9.0\nC,3.4,6.5\n",
+    )
+    .unwrap();
+
+    // Default column names don't exist in this file
+    let result = vector_convert(&input, Some(&output), &GpqOptions::default());
+    assert!(result.unwrap_err().contains("'lon'"));
+
+    let options = GpqOptions {
+        x_field: Some("longitude".to_string()),
+        y_field: Some("latitude".to_string()),
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    let mut layer = ds.layer(0).unwrap();
+    assert_eq!(layer.feature_count(), 3);
+    assert_eq!(layer.spatial_ref().unwrap().auth_code().unwrap(), 4326);
+    let feature = layer.features().next().unwrap();
+    assert_eq!(feature.geometry().unwrap().get_point(0), (36.8, -1.3, 0.0));
+}
+
+#[test]
+fn test_fid_range() {
+    let dir = TestDir::new("fid_range");
+    let input = dir.join("points.gpkg");
+    let output = dir.join("shard.parquet");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+        let mut ds = driver.create_vector_only(&input).unwrap();
+        let mut layer = ds
+            .create_layer(LayerOptions {
+                name: "points",
+                ty: OGRwkbGeometryType::wkbPoint,
+                ..Default::default()
+            })
+            .unwrap();
+        // GPKG assigns FIDs 1..=5
+        for x in 0..5 {
+            layer
+                .create_feature(Geometry::from_wkt(&format!("POINT ({} 0)", x)).unwrap())
+                .unwrap();
+        }
+    }
+
+    let options = GpqOptions {
+        fid_start: Some(2),
+        fid_end: Some(4),
+        verify: true,
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(ds.layer(0).unwrap().feature_count(), 2);
+
+    let empty_range = GpqOptions {
+        fid_start: Some(4),
+        fid_end: Some(4),
+        overwrite: true,
+        ..Default::default()
+    };
+    assert!(vector_convert(&input, Some(&output), &empty_range).is_err());
+}
+
+#[test]
+fn test_geojson_non_wgs84_crs() {
+    let dir = TestDir::new("geojson_3857");
+    let input = dir.join("mercator.geojson");
+    let output = dir.join("mercator.parquet");
+    std::fs::write(
+        &input,
+        r#"{
+  "type": "FeatureCollection",
+  "crs": { "type": "name", "properties": { "name": "urn:ogc:def:crs:EPSG::3857" } },
+  "features": [
+    { "type": "Feature", "properties": { "id": 1 },
+      "geometry": { "type": "Point", "coordinates": [111319.49, 0.0] } }
+  ]
+}"#,
+    )
+    .unwrap();
+
+    let err = vector_convert(&input, Some(&output), &GpqOptions::default()).unwrap_err();
+    assert!(err.contains("EPSG:3857"), "{}", err);
+
+    let options = GpqOptions {
+        s_srs: Some("EPSG:3857".to_string()),
+        t_srs: Some("EPSG:4326".to_string()),
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    let mut layer = ds.layer(0).unwrap();
+    let feature = layer.features().next().unwrap();
+    let (x, y, _) = feature.geometry().unwrap().get_point(0);
+    assert!((x - 1.0).abs() < 1e-6, "x = {}", x);
+    assert!(y.abs() < 1e-6);
+}
+
+#[test]
+fn test_bad_feature_is_an_error_not_a_panic() {
+    let dir = TestDir::new("bad_feature");
+    let input = dir.join("bad_lat.gpkg");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+        let mut ds = driver.create_vector_only(&input).unwrap();
+        let mut layer = ds
+            .create_layer(LayerOptions {
+                name: "bad_lat",
+                ty: OGRwkbGeometryType::wkbPoint,
+                ..Default::default()
+            })
+            .unwrap();
+        // Latitude 95 cannot be projected to Web Mercator
+        layer
+            .create_feature(Geometry::from_wkt("POINT (10 95)").unwrap())
+            .unwrap();
+    }
+
+    let options = GpqOptions {
+        s_srs: Some("EPSG:4326".to_string()),
+        t_srs: Some("EPSG:3857".to_string()),
+        ..Default::default()
+    };
+    let err = vector_convert(&input, Some(&dir.join("bad_lat.parquet")), &options).unwrap_err();
+    assert!(err.contains("feature 1"), "{}", err);
+}
+
+#[test]
+fn test_geo_encoding_geoarrow() {
+    let dir = TestDir::new("geo_encoding");
+    let input = dir.join("points.gpkg");
+    let output = dir.join("points.parquet");
+
+    {
+        let srs = SpatialRef::from_epsg(32637).unwrap();
+        let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+        let mut ds = driver.create_vector_only(&input).unwrap();
+        let mut layer = ds
+            .create_layer(LayerOptions {
+                name: "points",
+                srs: Some(&srs),
+                ty: OGRwkbGeometryType::wkbPoint,
+                ..Default::default()
+            })
+            .unwrap();
+        for wkt in ["POINT (500000 0)", "POINT (510000 10000)"] {
+            layer
+                .create_feature(Geometry::from_wkt(wkt).unwrap())
+                .unwrap();
+        }
+    }
+
+    let options = GpqOptions {
+        geo_encoding: GeoEncoding::Geoarrow,
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    let layer = ds.layer(0).unwrap();
+    assert_eq!(layer.feature_count(), 2);
+    let geo = layer
+        .metadata_item("geo", "_PARQUET_METADATA_")
+        .expect("Parquet driver exposes no geo metadata");
+    let geo: serde_json::Value = serde_json::from_str(&geo).unwrap();
+    let column = &geo["columns"][geo["primary_column"].as_str().unwrap()];
+    assert_eq!(column["encoding"], "point");
+    assert!(column["crs"].is_object(), "No CRS in {}", column);
+    assert!(
+        column.get("covering").is_some(),
+        "No bbox covering in {}",
+        column
+    );
+}
+
+#[test]
+fn test_geo_encoding_geoarrow_rejects_mixed_layer() {
+    let dir = TestDir::new("geo_encoding_mixed");
+    let input = dir.join("mixed.gpkg");
+    let output = dir.join("mixed.parquet");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+        let mut ds = driver.create_vector_only(&input).unwrap();
+        let mut layer = ds
+            .create_layer(LayerOptions {
+                name: "mixed",
+                ty: OGRwkbGeometryType::wkbUnknown,
+                ..Default::default()
+            })
+            .unwrap();
+        for wkt in ["POINT (1 1)", "POLYGON ((0 0, 1 0, 1 1, 0 0))"] {
+            layer
+                .create_feature(Geometry::from_wkt(wkt).unwrap())
+                .unwrap();
+        }
+    }
+
+    let options = GpqOptions {
+        geo_encoding: GeoEncoding::Geoarrow,
+        ..Default::default()
+    };
+    let err = vector_convert(&input, Some(&output), &options).unwrap_err();
+    assert!(err.contains("single geometry type"), "{}", err);
+
+    // Keeping only the polygons gives the layer a single type again
+    let options = GpqOptions {
+        geo_encoding: GeoEncoding::Geoarrow,
+        geometry_type: Some(GeometryKind::Polygon),
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+}
+
+#[test]
+fn test_row_group_size_and_compression() {
+    let input_path = std::path::Path::new("tests/data/test_input.gpkg");
+    let dir = TestDir::new("zstd");
+    let output_path = dir.join("zstd.parquet");
+
+    let options = GpqOptions {
+        row_group_size: Some(1),
+        compression: Some(ParquetCompression::Zstd),
+        verify: true,
+        overwrite: true,
+        ..Default::default()
+    };
+    vector_convert(input_path, Some(&output_path), &options).unwrap();
+
+    let options = GpqOptions {
+        row_group_size: Some(0),
+        overwrite: true,
+        ..Default::default()
+    };
+    let err = vector_convert(input_path, Some(&output_path), &options).unwrap_err();
+    assert!(err.contains("--row-group-size"), "{}", err);
+}
+
+#[test]
+fn test_batch_size_flushes_large_layer() {
+    let dir = TestDir::new("batch_size");
+    let input = dir.join("many_points.gpkg");
+    let output = dir.join("many_points.parquet");
+
+    let total: u64 = 25_000;
+    {
+        let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+        let mut ds = driver.create_vector_only(&input).unwrap();
+        let mut txn = ds.start_transaction().unwrap();
+        let mut layer = txn
+            .create_layer(LayerOptions {
+                name: "many_points",
+                ty: OGRwkbGeometryType::wkbPoint,
+                ..Default::default()
+            })
+            .unwrap();
+        for i in 0..total as i64 {
+            let wkt = format!("POINT ({} {})", i % 360 - 180, i % 180 - 90);
+            layer
+                .create_feature(Geometry::from_wkt(&wkt).unwrap())
+                .unwrap();
+        }
+        txn.commit().unwrap();
+    }
+
+    // A batch size that does not divide the total leaves a partial last batch
+    let options = GpqOptions {
+        batch_size: Some(4_000),
+        verify: true,
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+
+    let ds = Dataset::open(&output).unwrap();
+    assert_eq!(ds.layer(0).unwrap().feature_count(), total);
+
+    // Each flush ends a row group: six full batches and the partial last one
+    use polars::prelude::*;
+    let mut reader = ParquetReader::new(std::fs::File::open(&output).unwrap());
+    let metadata = reader.get_metadata().unwrap();
+    let rows: Vec<usize> = metadata.row_groups.iter().map(|rg| rg.num_rows()).collect();
+    assert_eq!(rows, vec![4_000, 4_000, 4_000, 4_000, 4_000, 4_000, 1_000]);
+}
+
+#[test]
+fn test_layer_selection() {
+    let dir = TestDir::new("layer_selection");
+    let input = dir.join("admin.gpkg");
+    let output = dir.join("admin.parquet");
+
+    {
+        let driver = DriverManager::get_driver_by_name("GPKG").unwrap();
+        let mut ds = driver.create_vector_only(&input).unwrap();
+        for (name, count) in [("admin0", 1), ("admin1", 3)] {
+            let mut layer = ds
+                .create_layer(LayerOptions {
+                    name,
+                    ty: OGRwkbGeometryType::wkbPoint,
+                    ..Default::default()
+                })
+                .unwrap();
+            for i in 0..count {
+                layer
+                    .create_feature(Geometry::from_wkt(&format!("POINT ({} 0)", i)).unwrap())
+                    .unwrap();
+            }
+        }
+    }
+    let feature_count = |path: &std::path::Path| {
+        let ds = Dataset::open(path).unwrap();
+        ds.layer(0).unwrap().feature_count()
+    };
+
+    for selector in ["admin1", "1"] {
+        let options = GpqOptions {
+            layer: Some(selector.to_string()),
+            overwrite: true,
+            ..Default::default()
+        };
+        vector_convert(&input, Some(&output), &options).unwrap();
+        assert_eq!(feature_count(&output), 3, "--layer {}", selector);
+    }
+
+    let options = GpqOptions {
+        layer: Some("admin2".to_string()),
+        ..Default::default()
+    };
+    let err = vector_convert(&input, Some(&output), &options).unwrap_err();
+    assert!(err.contains("admin0, admin1"), "{}", err);
+
+    let options = GpqOptions {
+        all_layers: true,
+        ..Default::default()
+    };
+    vector_convert(&input, Some(&output), &options).unwrap();
+    assert_eq!(feature_count(&dir.join("admin_admin0.parquet")), 1);
+    assert_eq!(feature_count(&dir.join("admin_admin1.parquet")), 3);
+}
+
+#[test]
+fn test_date_fields_keep_their_types() {
+    use polars::prelude::*;
+
+    // Points with a BOOLEAN column and a DATE column, one row with both NULL
+    let input = std::path::Path::new("tests/data/typed_fields.gpkg");
+    let dir = TestDir::new("field_types");
+    let output = dir.join("typed.parquet");
+
+    vector_convert(input, Some(&output), &GpqOptions::default()).unwrap();
+
+    let df = ParquetReader::new(std::fs::File::open(&output).unwrap())
+        .finish()
+        .unwrap();
+    let days = df.column("day").unwrap();
+    assert_eq!(days.dtype(), &DataType::Date);
+    assert_eq!(days.null_count(), 1);
+    // The gdal crate does not expose OGR subtypes, so booleans arrive as 0/1 integers
+    let flags = df.column("flag").unwrap().i32().unwrap();
+    assert_eq!(flags.get(0), Some(1));
+    assert_eq!(flags.get(1), Some(0));
+    assert_eq!(flags.get(2), None);
+}
+
+#[test]
+fn test_stdout_output_needs_a_single_file() {
+    use std::path::Path;
+
+    assert!(is_stdout(Path::new("-")));
+    assert!(is_stdout(Path::new("/dev/stdout")));
+    assert!(!is_stdout(Path::new("out.parquet")));
+
+    let input_path = Path::new("tests/data/test_input.gpkg");
+    let err = explode_to_geoparquet(input_path, Some(Path::new("-")), &GpqOptions::default())
+        .unwrap_err();
+    assert!(err.contains("stdout"), "{}", err);
+
+    let options = GpqOptions {
+        all_layers: true,
+        ..Default::default()
+    };
+    let err = vector_convert(input_path, Some(Path::new("-")), &options).unwrap_err();
+    assert!(err.contains("stdout"), "{}", err);
+}
+
+#[test]
+fn test_vector_convert_other_formats() {
+    let input_path = std::path::Path::new("tests/data/test_input.gpkg");
+    let expected = Dataset::open(input_path)
+        .unwrap()
+        .layer(0)
+        .unwrap()
+        .feature_count();
+    let dir = TestDir::new("formats");
+
+    for (format, driver) in [
+        (VectorFormat::Gpkg, "GPKG"),
+        (VectorFormat::FlatGeobuf, "FlatGeobuf"),
+        (VectorFormat::GeoJson, "GeoJSON"),
+    ] {
+        let options = GpqOptions {
+            format,
+            overwrite: true,
+            verify: true,
+            ..Default::default()
+        };
+        // The extension follows the format, whatever --out says
+        let written =
+            vector_convert(input_path, Some(&dir.join("boundaries.out")), &options).unwrap();
+        assert_eq!(written, format!("boundaries.{}", format.extension()));
+
+        let ds = Dataset::open(dir.join(&written)).unwrap();
+        assert_eq!(ds.driver().short_name(), driver);
+        let layer = ds.layer(0).unwrap();
+        assert_eq!(layer.feature_count(), expected);
+        assert_eq!(
+            layer.spatial_ref().unwrap().authority().unwrap(),
+            "EPSG:4326"
+        );
+    }
+
+    // Parquet-only options are refused rather than ignored
+    let options = GpqOptions {
+        format: VectorFormat::Gpkg,
+        compression: Some(ParquetCompression::Zstd),
+        overwrite: true,
+        ..Default::default()
+    };
+    let err = vector_convert(input_path, Some(&dir.join("zstd.gpkg")), &options).unwrap_err();
+    assert!(err.contains("--parquet-compression"), "{}", err);
+
+    // Without --out a GeoPackage would be written over itself
+    let options = GpqOptions {
+        format: VectorFormat::Gpkg,
+        overwrite: true,
+        ..Default::default()
+    };
+    let err = vector_convert(input_path, None, &options).unwrap_err();
+    assert!(err.contains("replace the input"), "{}", err);
+    assert!("geopackage".parse::<VectorFormat>().unwrap() == VectorFormat::Gpkg);
+    assert!("shapefile".parse::<VectorFormat>().is_err());
+}
+
+#[test]
+fn test_add_area_and_length() {
+    let dir = TestDir::new("measures");
+    let input = dir.join("shapes.geojson");
+
+    // A one-degree square and a one-degree line on the equator, and a point that gets neither
+    std::fs::write(
+        &input,
+        r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {"name": "square"},
+             "geometry": {"type": "Polygon", "coordinates": [[[0, 0], [1, 0], [1, 1], [0, 1], [0, 0]]]}},
+            {"type": "Feature", "properties": {"name": "line"},
+             "geometry": {"type": "LineString", "coordinates": [[0, 0], [0.5, 0], [1, 0]]}},
+            {"type": "Feature", "properties": {"name": "point"},
+             "geometry": {"type": "Point", "coordinates": [0, 0]}}
+        ]}"#,
+    )
+    .unwrap();
+
+    // The measures should not depend on the output CRS
+    for t_srs in [None, Some("EPSG:3857".to_string())] {
+        let output = dir.join("shapes.parquet");
+        let options = GpqOptions {
+            add_area: true,
+            add_length: true,
+            t_srs,
+            overwrite: true,
+            ..Default::default()
+        };
+        vector_convert(&input, Some(&output), &options).unwrap();
+
+        let ds = Dataset::open(&output).unwrap();
+        let mut layer = ds.layer(0).unwrap();
+        let measure = |feature: &gdal::vector::Feature, name: &str| {
+            feature
+                .field_as_double(feature.field_index(name).unwrap())
+                .unwrap()
+        };
+        let features = layer.features().collect::<Vec<_>>();
+        // Ellipsoidal (WGS 84) values: the square's geodesic area, which the cylindrical
+        // equal-area projection preserves for a cell bounded by parallels and meridians, and
+        // the equator's geodesic length, which the great-circle length falls 0.11% short of
+        let area = measure(&features[0], "area_m2").unwrap();
+        assert!((area / 12_308_463_894.0 - 1.0).abs() < 1e-4, "{}", area);
+        assert!(measure(&features[0], "length_m").is_none());
+
+        let length = measure(&features[1], "length_m").unwrap();
+        assert!((length / 111_319.49 - 1.0).abs() < 0.005, "{}", length);
+        assert!((length / 111_195.08 - 1.0).abs() < 1e-4, "{}", length);
+        assert!(measure(&features[1], "area_m2").is_none());
+
+        assert!(measure(&features[2], "area_m2").is_none());
+        assert!(measure(&features[2], "length_m").is_none());
+    }
+
+    // An existing column is not overwritten
+    let measured = dir.join("shapes_measured.geojson");
+    std::fs::write(
+        &measured,
+        r#"{"type": "FeatureCollection", "features": [
+            {"type": "Feature", "properties": {"area_m2": 1.0},
+             "geometry": {"type": "Point", "coordinates": [0, 0]}}
+        ]}"#,
+    )
+    .unwrap();
+    let options = GpqOptions {
+        add_area: true,
+        overwrite: true,
+        ..Default::default()
+    };
+    let err = vector_convert(&measured, Some(&dir.join("measured.parquet")), &options).unwrap_err();
+    assert!(err.contains("area_m2"), "{}", err);
+}
+
+#[test]
+fn test_where_and_bbox_filters() {
+    let dir = TestDir::new("where_bbox");
+    let input = dir.join("sites.csv");
+    let output = dir.join("sites.parquet");
+    std::fs::write(
+        &input,
+        "iso3,lon,lat\nKEN,36.8,-1.3\nKEN,39.7,-4.0\nETH,38.7,9.0\nUGA,32.6,0.3\n",
+    )
+    .unwrap();
+
+    let count = |options: &GpqOptions| {
+        vector_convert(&input, Some(&output), options).unwrap();
+        Dataset::open(&output)
+            .unwrap()
+            .layer(0)
+            .unwrap()
+            .feature_count()
+    };
+    let by_country = GpqOptions {
+        where_clause: Some("iso3 = 'KEN'".to_string()),
+        overwrite: true,
+        verify: true,
+        ..Default::default()
+    };
+    assert_eq!(count(&by_country), 2);
+
+    let by_bbox = GpqOptions {
+        bbox: Some(parse_bbox("33.9,-4.7,41.9,10").unwrap()),
+        ..by_country.clone()
+    };
+    // The bbox keeps Kenya and Ethiopia, and --where narrows that to Kenya
+    assert_eq!(count(&by_bbox), 2);
+    let ethiopia = GpqOptions {
+        where_clause: Some("iso3 <> 'KEN'".to_string()),
+        ..by_bbox.clone()
+    };
+    assert_eq!(count(&ethiopia), 1);
+    // Combined with the FID range rather than replacing it; CSV rows are numbered from 1
+    let shard = GpqOptions {
+        bbox: None,
+        fid_start: Some(2),
+        ..by_country
+    };
+    assert_eq!(count(&shard), 1);
 
-    vector_to_geoparquet(input_path, Some(output_path)).unwrap();
+    let bad = GpqOptions {
+        where_clause: Some("no_such_column = 1".to_string()),
+        overwrite: true,
+        ..Default::default()
+    };
+    assert!(vector_convert(&input, Some(&output), &bad).is_err());
+    assert!(parse_bbox("1,2,3").is_err());
+    assert!(parse_bbox("3,0,1,2").is_err());
 }